@@ -0,0 +1,20 @@
+use alloc::vec::Vec;
+
+use axtest::ax_test;
+
+#[ax_test]
+fn test_alloc_free() {
+    let used = crate::used_bytes();
+    let v: Vec<u64> = (0..0x100).collect();
+    assert!(crate::used_bytes() > used);
+    assert_eq!(v.iter().sum::<u64>(), 0xff * 0x100 / 2);
+    drop(v);
+    assert_eq!(crate::used_bytes(), used);
+}
+
+#[ax_test]
+fn test_alloc_grows_heap() {
+    // much larger than the initial heap, so more pages are taken for it
+    let v: Vec<u8> = alloc::vec![0xa5; 0x10_0000];
+    assert!(v.iter().all(|&b| b == 0xa5));
+}
@@ -10,6 +10,9 @@ use spinlock::SpinNoIrq;
 extern crate log;
 extern crate alloc;
 
+#[cfg(ktest)]
+mod ktests;
+
 const PAGE_SIZE: usize = 4096;
 const MIN_HEAP_SIZE: usize = 0x8000; // 32 K
 
@@ -0,0 +1,191 @@
+//! Bounded multi-producer, single-consumer channels.
+
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use core::fmt;
+use core::future::poll_fn;
+use core::task::{Poll, Waker};
+
+use spinlock::SpinNoIrq;
+
+/// Creates a channel that buffers up to `capacity` messages, which must not
+/// be zero.
+pub fn channel<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    assert!(capacity > 0, "zero capacity");
+    let shared = Arc::new(SpinNoIrq::new(Shared {
+        queue: VecDeque::with_capacity(capacity),
+        capacity,
+        senders: 1,
+        receiver_alive: true,
+        recv_waker: None,
+        send_wakers: VecDeque::new(),
+    }));
+    (
+        Sender {
+            shared: shared.clone(),
+        },
+        Receiver { shared },
+    )
+}
+
+struct Shared<T> {
+    queue: VecDeque<T>,
+    capacity: usize,
+    senders: usize,
+    receiver_alive: bool,
+    recv_waker: Option<Waker>,
+    /// Senders waiting for room in the queue.
+    send_wakers: VecDeque<Waker>,
+}
+
+impl<T> Shared<T> {
+    fn wake_receiver(&mut self) {
+        if let Some(waker) = self.recv_waker.take() {
+            waker.wake();
+        }
+    }
+
+    fn wake_senders(&mut self) {
+        for waker in self.send_wakers.drain(..) {
+            waker.wake();
+        }
+    }
+}
+
+/// The sending half of a channel, which can be cloned.
+pub struct Sender<T> {
+    shared: Arc<SpinNoIrq<Shared<T>>>,
+}
+
+/// The receiving half of a channel.
+pub struct Receiver<T> {
+    shared: Arc<SpinNoIrq<Shared<T>>>,
+}
+
+/// The receiver has been dropped, and the message is given back.
+#[derive(PartialEq, Eq)]
+pub struct SendError<T>(pub T);
+
+/// Why [`Sender::try_send`] failed, with the message given back.
+#[derive(Debug, PartialEq, Eq)]
+pub enum TrySendError<T> {
+    /// The channel is full.
+    Full(T),
+    /// The receiver has been dropped.
+    Closed(T),
+}
+
+/// Why [`Receiver::try_recv`] failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TryRecvError {
+    /// The channel is empty.
+    Empty,
+    /// The channel is empty, and all senders have been dropped.
+    Closed,
+}
+
+impl<T> fmt::Debug for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SendError(..)")
+    }
+}
+
+impl<T> Sender<T> {
+    /// Sends `msg`, waiting for room in the channel if it is full.
+    pub async fn send(&self, msg: T) -> Result<(), SendError<T>> {
+        let mut msg = Some(msg);
+        poll_fn(|cx| {
+            let mut shared = self.shared.lock();
+            if !shared.receiver_alive {
+                return Poll::Ready(Err(SendError(msg.take().unwrap())));
+            }
+            if shared.queue.len() < shared.capacity {
+                shared.queue.push_back(msg.take().unwrap());
+                shared.wake_receiver();
+                return Poll::Ready(Ok(()));
+            }
+            shared.send_wakers.push_back(cx.waker().clone());
+            Poll::Pending
+        })
+        .await
+    }
+
+    /// Sends `msg` if there is room in the channel, without waiting.
+    pub fn try_send(&self, msg: T) -> Result<(), TrySendError<T>> {
+        let mut shared = self.shared.lock();
+        if !shared.receiver_alive {
+            return Err(TrySendError::Closed(msg));
+        }
+        if shared.queue.len() >= shared.capacity {
+            return Err(TrySendError::Full(msg));
+        }
+        shared.queue.push_back(msg);
+        shared.wake_receiver();
+        Ok(())
+    }
+
+    /// Returns whether the receiver has been dropped.
+    pub fn is_closed(&self) -> bool {
+        !self.shared.lock().receiver_alive
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.shared.lock().senders += 1;
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut shared = self.shared.lock();
+        shared.senders -= 1;
+        if shared.senders == 0 {
+            shared.wake_receiver();
+        }
+    }
+}
+
+impl<T> Receiver<T> {
+    /// Receives the next message, waiting for one if the channel is empty.
+    /// Returns `None` once the channel is empty and all senders are dropped.
+    pub async fn recv(&mut self) -> Option<T> {
+        poll_fn(|cx| {
+            let mut shared = self.shared.lock();
+            if let Some(msg) = shared.queue.pop_front() {
+                shared.wake_senders();
+                return Poll::Ready(Some(msg));
+            }
+            if shared.senders == 0 {
+                return Poll::Ready(None);
+            }
+            shared.recv_waker = Some(cx.waker().clone());
+            Poll::Pending
+        })
+        .await
+    }
+
+    /// Receives the next message if there is one, without waiting.
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        let mut shared = self.shared.lock();
+        match shared.queue.pop_front() {
+            Some(msg) => {
+                shared.wake_senders();
+                Ok(msg)
+            }
+            None if shared.senders == 0 => Err(TryRecvError::Closed),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let mut shared = self.shared.lock();
+        shared.receiver_alive = false;
+        shared.wake_senders();
+    }
+}
@@ -0,0 +1,220 @@
+//! Executors, and running futures on the caller.
+
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use alloc::task::Wake;
+use core::future::Future;
+use core::pin::{pin, Pin};
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::{Context, Poll, Waker};
+
+use spinlock::SpinNoIrq;
+
+use crate::park::Parker;
+use crate::timer;
+
+type BoxFuture = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
+
+/// Runs many futures on one thread.
+///
+/// Each spawned future is a task, which is polled again only after its waker
+/// is woken up. [`run`](Self::run) polls the woken tasks, and blocks while
+/// there are none.
+pub struct Executor {
+    ready_queue: SpinNoIrq<VecDeque<Arc<Task>>>,
+    parker: Arc<Parker>,
+}
+
+struct Task {
+    /// `None` once the future has completed.
+    future: SpinNoIrq<Option<BoxFuture>>,
+    executor: Arc<Executor>,
+    /// Whether the task is in the ready queue, so that it is queued once
+    /// however many times it is woken up.
+    queued: AtomicBool,
+}
+
+impl Wake for Task {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        if !self.queued.swap(true, Ordering::AcqRel) {
+            self.executor.ready_queue.lock().push_back(self.clone());
+            self.executor.parker.unpark();
+        }
+    }
+}
+
+impl Executor {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            ready_queue: SpinNoIrq::new(VecDeque::new()),
+            parker: Arc::new(Parker::new()),
+        })
+    }
+
+    /// Spawns `future` as a task, which is first polled by the next run.
+    pub fn spawn<F>(self: &Arc<Self>, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let (handle, output) = JoinHandle::new();
+        let future = async move { output.set(future.await) };
+        let task = Arc::new(Task {
+            future: SpinNoIrq::new(Some(Box::pin(future))),
+            executor: self.clone(),
+            queued: AtomicBool::new(false),
+        });
+        task.wake_by_ref();
+        handle
+    }
+
+    /// Polls the tasks that are ready, including the ones woken up while it
+    /// runs, and returns how many polls it took.
+    pub fn run_ready(&self) -> usize {
+        timer::fire_expired();
+        let mut polls = 0;
+        loop {
+            let task = self.ready_queue.lock().pop_front();
+            let Some(task) = task else {
+                break;
+            };
+            // Woken up again while being polled, it is queued again.
+            task.queued.store(false, Ordering::Release);
+            let waker = Waker::from(task.clone());
+            let mut future = task.future.lock();
+            if let Some(fut) = future.as_mut() {
+                if fut.as_mut().poll(&mut Context::from_waker(&waker)).is_ready() {
+                    *future = None;
+                }
+                polls += 1;
+            }
+        }
+        polls
+    }
+
+    /// Runs the tasks forever, blocking until one is woken up or a timer
+    /// expires whenever there is nothing to poll.
+    pub fn run(&self) -> ! {
+        loop {
+            self.run_ready();
+            if self.ready_queue.lock().is_empty() {
+                self.parker.park(timer::next_deadline());
+            }
+        }
+    }
+
+    /// Runs the tasks on the current thread until `future` completes, and
+    /// returns its output.
+    pub fn run_until<F: Future>(&self, future: F) -> F::Output {
+        let mut future = pin!(future);
+        let waker = Waker::from(self.parker.clone());
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            self.run_ready();
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+            if self.ready_queue.lock().is_empty() {
+                self.parker.park(timer::next_deadline());
+            }
+        }
+    }
+}
+
+/// Polls `future` on the current thread until it completes, blocking while
+/// it is pending, and returns its output.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let parker = Arc::new(Parker::new());
+    let waker = Waker::from(parker.clone());
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        timer::fire_expired();
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+        parker.park(timer::next_deadline());
+    }
+}
+
+/// Spawns `future` on the executor task, which is started on first use.
+#[cfg(feature = "multitask")]
+pub fn spawn<F>(future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    static EXECUTOR: SpinNoIrq<Option<Arc<Executor>>> = SpinNoIrq::new(None);
+
+    let executor = EXECUTOR
+        .lock()
+        .get_or_insert_with(|| {
+            let executor = Executor::new();
+            let runner = executor.clone();
+            axtask::spawn_raw(
+                move || runner.run(),
+                "async-executor".into(),
+                axtask::default_stack_size(),
+            );
+            executor
+        })
+        .clone();
+    executor.spawn(future)
+}
+
+/// A future for the output of a task spawned on an [`Executor`].
+pub struct JoinHandle<T> {
+    output: Arc<TaskOutput<T>>,
+}
+
+struct TaskOutput<T> {
+    /// The output, and the waker of the task awaiting it.
+    state: SpinNoIrq<(Option<T>, Option<Waker>)>,
+}
+
+impl<T> JoinHandle<T> {
+    fn new() -> (Self, Arc<TaskOutput<T>>) {
+        let output = Arc::new(TaskOutput {
+            state: SpinNoIrq::new((None, None)),
+        });
+        (
+            Self {
+                output: output.clone(),
+            },
+            output,
+        )
+    }
+}
+
+impl<T> TaskOutput<T> {
+    fn set(&self, output: T) {
+        let waker = {
+            let mut state = self.state.lock();
+            state.0 = Some(output);
+            state.1.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> Future for JoinHandle<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let mut state = self.output.state.lock();
+        match state.0.take() {
+            Some(output) => Poll::Ready(output),
+            None => {
+                state.1 = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
@@ -0,0 +1,29 @@
+//! A kernel-native async runtime.
+//!
+//! Futures run on an [`Executor`], which polls the tasks woken up since its
+//! last run, or directly on the caller with [`block_on`]. Both block on a
+//! wait queue while there is nothing to poll, until a waker or the nearest
+//! [`sleep`] deadline wakes them up, so many concurrent state machines share
+//! one thread instead of one stack each.
+//!
+//! With the `multitask` feature, [`spawn`] runs futures on a dedicated
+//! executor task. Without it, time is a fake clock for testing on the host,
+//! see [`time`].
+
+#![no_std]
+
+extern crate alloc;
+
+mod executor;
+mod park;
+mod timer;
+pub mod channel;
+pub mod time;
+
+#[cfg(test)]
+mod tests;
+
+pub use executor::{block_on, Executor, JoinHandle};
+#[cfg(feature = "multitask")]
+pub use executor::spawn;
+pub use timer::{sleep, sleep_until, Sleep};
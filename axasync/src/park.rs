@@ -0,0 +1,73 @@
+//! Blocking executors until they are woken up.
+
+use alloc::sync::Arc;
+use alloc::task::Wake;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::time::Duration;
+
+/// Blocks a thread until it is unparked, usually by a [`Waker`].
+///
+/// With the `multitask` feature, the thread blocks on a wait queue, so
+/// waking it up from another task or an IRQ handler unblocks it.
+///
+/// [`Waker`]: core::task::Waker
+pub(crate) struct Parker {
+    notified: AtomicBool,
+    #[cfg(feature = "multitask")]
+    wait_queue: axtask::WaitQueue,
+}
+
+impl Parker {
+    pub const fn new() -> Self {
+        Self {
+            notified: AtomicBool::new(false),
+            #[cfg(feature = "multitask")]
+            wait_queue: axtask::WaitQueue::new(),
+        }
+    }
+
+    /// Blocks until [`unpark`](Self::unpark) is called, or `deadline` passes.
+    /// It returns right away if `unpark` has been called since the last park.
+    pub fn park(&self, deadline: Option<Duration>) {
+        #[cfg(feature = "multitask")]
+        {
+            let notified = || self.notified.load(Ordering::Acquire);
+            match deadline {
+                Some(deadline) => {
+                    self.wait_queue.wait_until_timeout(deadline, notified);
+                }
+                None => self.wait_queue.wait_until(notified),
+            }
+        }
+        // Spins for wakers on other threads. With a timer pending, the fake
+        // clock jumps to its deadline instead, as if the executor had slept.
+        #[cfg(not(feature = "multitask"))]
+        if !self.notified.load(Ordering::Acquire) {
+            match deadline {
+                Some(deadline) => crate::time::advance_to(deadline),
+                None => {
+                    while !self.notified.load(Ordering::Acquire) {
+                        core::hint::spin_loop();
+                    }
+                }
+            }
+        }
+        self.notified.store(false, Ordering::Release);
+    }
+
+    pub fn unpark(&self) {
+        self.notified.store(true, Ordering::Release);
+        #[cfg(feature = "multitask")]
+        self.wait_queue.notify_one(true);
+    }
+}
+
+impl Wake for Parker {
+    fn wake(self: Arc<Self>) {
+        self.unpark();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.unpark();
+    }
+}
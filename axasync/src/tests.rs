@@ -0,0 +1,143 @@
+extern crate std;
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::time::Duration;
+
+use crate::channel::{channel, TryRecvError, TrySendError};
+use crate::{block_on, sleep, time, Executor};
+
+/// Locks disable preemption when another crate in the build enables the
+/// `preempt` feature of `kernel_guard`, and there is nothing to preempt here.
+struct KernelGuardIfImpl;
+
+#[crate_interface::impl_interface]
+impl kernel_guard::KernelGuardIf for KernelGuardIfImpl {
+    fn enable_preempt() {}
+    fn disable_preempt() {}
+}
+
+#[test]
+fn test_block_on_ready() {
+    assert_eq!(block_on(async { 1 + 2 }), 3);
+}
+
+#[test]
+fn test_sleep_advances_fake_clock() {
+    // Other tests move the clock too, but never backwards.
+    let start = time::now();
+    block_on(sleep(Duration::from_secs(3)));
+    assert!(time::now() >= start + Duration::from_secs(3));
+}
+
+#[test]
+fn test_sleep_until_past_deadline() {
+    block_on(crate::sleep_until(Duration::ZERO));
+}
+
+#[test]
+fn test_executor_runs_woken_tasks_only() {
+    let executor = Executor::new();
+    let (tx, mut rx) = channel::<u32>(4);
+    let received = Arc::new(AtomicUsize::new(0));
+    let counter = received.clone();
+    executor.spawn(async move {
+        while let Some(n) = rx.recv().await {
+            counter.fetch_add(n as usize, Ordering::Relaxed);
+        }
+    });
+
+    assert_eq!(executor.run_ready(), 1);
+    // Nothing has woken the receiver up.
+    assert_eq!(executor.run_ready(), 0);
+
+    tx.try_send(5).unwrap();
+    tx.try_send(7).unwrap();
+    assert_eq!(executor.run_ready(), 1);
+    assert_eq!(received.load(Ordering::Relaxed), 12);
+
+    drop(tx);
+    assert_eq!(executor.run_ready(), 1);
+    assert_eq!(executor.run_ready(), 0);
+}
+
+#[test]
+fn test_join_handle() {
+    let executor = Executor::new();
+    let a = executor.spawn(async { 20 });
+    let b = executor.spawn(async {
+        sleep(Duration::from_millis(10)).await;
+        22
+    });
+    let sum = executor.spawn(async move { a.await + b.await });
+    while executor.run_ready() > 0 {}
+    // `b` is still sleeping.
+    time::advance(Duration::from_millis(10));
+    executor.run_ready();
+    assert_eq!(block_on(sum), 42);
+}
+
+#[test]
+fn test_sleeps_wake_in_order() {
+    let executor = Executor::new();
+    let (tx, mut rx) = channel(8);
+    for ms in [30u64, 10, 20] {
+        let tx = tx.clone();
+        executor.spawn(async move {
+            sleep(Duration::from_millis(ms)).await;
+            tx.send(ms).await.unwrap();
+        });
+    }
+    drop(tx);
+    let collector = executor.spawn(async move {
+        let mut order = Vec::new();
+        while let Some(ms) = rx.recv().await {
+            order.push(ms);
+        }
+        order
+    });
+    assert_eq!(executor.run_until(collector), [10, 20, 30]);
+}
+
+#[test]
+fn test_channel_backpressure() {
+    let (tx, mut rx) = channel(2);
+    assert_eq!(tx.try_send(1), Ok(()));
+    assert_eq!(tx.try_send(2), Ok(()));
+    assert_eq!(tx.try_send(3), Err(TrySendError::Full(3)));
+
+    let executor = Executor::new();
+    let sender = tx.clone();
+    let sent = executor.spawn(async move { sender.send(3).await });
+    executor.run_ready();
+    // Blocked on the full channel until a message is received.
+    assert_eq!(rx.try_recv(), Ok(1));
+    assert_eq!(executor.run_ready(), 1);
+    assert_eq!(block_on(sent), Ok(()));
+    assert_eq!(rx.try_recv(), Ok(2));
+    assert_eq!(rx.try_recv(), Ok(3));
+    assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+    drop(tx);
+    assert_eq!(rx.try_recv(), Err(TryRecvError::Closed));
+}
+
+#[test]
+fn test_channel_receiver_dropped() {
+    let (tx, rx) = channel(1);
+    drop(rx);
+    assert!(tx.is_closed());
+    assert_eq!(block_on(tx.send(1)).unwrap_err().0, 1);
+    assert_eq!(tx.try_send(2), Err(TrySendError::Closed(2)));
+}
+
+#[test]
+fn test_waker_from_another_thread() {
+    let (tx, mut rx) = channel(1);
+    let thread = std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        tx.try_send(42).unwrap();
+    });
+    assert_eq!(block_on(rx.recv()), Some(42));
+    thread.join().unwrap();
+}
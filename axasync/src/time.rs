@@ -0,0 +1,41 @@
+//! The clock of timers.
+//!
+//! With the `multitask` feature, it is the system time. Otherwise it is a
+//! fake clock that starts at zero, and only moves when [`advance`] is called
+//! or an executor is idle with a timer pending, in which case it jumps to the
+//! deadline of that timer. So tests run instantly however long they sleep.
+
+/// Returns the current time.
+#[cfg(feature = "multitask")]
+pub fn now() -> core::time::Duration {
+    axhal::time::current_time()
+}
+
+#[cfg(not(feature = "multitask"))]
+pub use self::fake::{advance, now};
+#[cfg(not(feature = "multitask"))]
+pub(crate) use self::fake::advance_to;
+
+#[cfg(not(feature = "multitask"))]
+mod fake {
+    use core::sync::atomic::{AtomicU64, Ordering};
+    use core::time::Duration;
+
+    static NOW_NANOS: AtomicU64 = AtomicU64::new(0);
+
+    /// Returns the current time of the fake clock.
+    pub fn now() -> Duration {
+        Duration::from_nanos(NOW_NANOS.load(Ordering::Acquire))
+    }
+
+    /// Moves the fake clock forward by `dur`. Timers are fired by the next
+    /// executor run.
+    pub fn advance(dur: Duration) {
+        NOW_NANOS.fetch_add(dur.as_nanos() as u64, Ordering::AcqRel);
+    }
+
+    /// Moves the fake clock forward to `time`, if it is earlier.
+    pub(crate) fn advance_to(time: Duration) {
+        NOW_NANOS.fetch_max(time.as_nanos() as u64, Ordering::AcqRel);
+    }
+}
@@ -0,0 +1,96 @@
+//! Timers of [`sleep`] futures.
+//!
+//! The timers of all executors are in one list. An executor that is about to
+//! block waits no longer than the nearest deadline in it, and fires the
+//! expired timers when it wakes up, whichever executor they wake.
+
+use alloc::collections::BTreeMap;
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::task::{Context, Poll, Waker};
+use core::time::Duration;
+
+use spinlock::SpinNoIrq;
+
+use crate::time;
+
+/// Pending timers, ordered by deadline and then ID.
+static TIMERS: SpinNoIrq<BTreeMap<(Duration, u64), Waker>> = SpinNoIrq::new(BTreeMap::new());
+static NEXT_TIMER_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Wakes up the wakers of the timers whose deadlines have passed.
+pub(crate) fn fire_expired() {
+    let now = time::now();
+    loop {
+        // Woken up with the list unlocked, as wakers may take other locks.
+        let waker = {
+            let mut timers = TIMERS.lock();
+            match timers.first_entry() {
+                Some(entry) if entry.key().0 <= now => entry.remove(),
+                _ => break,
+            }
+        };
+        waker.wake();
+    }
+}
+
+/// Returns the nearest deadline of the pending timers.
+pub(crate) fn next_deadline() -> Option<Duration> {
+    TIMERS.lock().first_key_value().map(|(key, _)| key.0)
+}
+
+/// A future that completes at a deadline, returned by [`sleep`] and
+/// [`sleep_until`].
+pub struct Sleep {
+    deadline: Duration,
+    /// The ID of the timer, once the future is polled.
+    timer_id: Option<u64>,
+}
+
+impl Sleep {
+    /// Returns when the future completes.
+    pub fn deadline(&self) -> Duration {
+        self.deadline
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if time::now() >= self.deadline {
+            if let Some(id) = self.timer_id.take() {
+                TIMERS.lock().remove(&(self.deadline, id));
+            }
+            return Poll::Ready(());
+        }
+        let id = *self
+            .timer_id
+            .get_or_insert_with(|| NEXT_TIMER_ID.fetch_add(1, Ordering::Relaxed));
+        // The future may have moved to another task since the last poll.
+        TIMERS.lock().insert((self.deadline, id), cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        if let Some(id) = self.timer_id {
+            TIMERS.lock().remove(&(self.deadline, id));
+        }
+    }
+}
+
+/// Completes after `dur`.
+pub fn sleep(dur: Duration) -> Sleep {
+    sleep_until(time::now() + dur)
+}
+
+/// Completes at `deadline`, a time of [`time::now`].
+pub fn sleep_until(deadline: Duration) -> Sleep {
+    Sleep {
+        deadline,
+        timer_id: None,
+    }
+}
@@ -0,0 +1,215 @@
+//! Kernel command line of [ArceOS](https://github.com/rcore-os/arceos).
+//!
+//! The command line comes from `/chosen/bootargs` in the device tree, which
+//! QEMU sets from `-append`. It is a list of arguments separated by
+//! whitespace, and double quotes can be used to put spaces in an argument
+//! (`init="my app"`).
+//!
+//! Arguments before `--` of the form `name=value` or `name` are kernel
+//! parameters. Crates read them through a [`Param`], which falls back to a
+//! default value when the parameter is absent or invalid. Arguments that no
+//! crate reads, and all arguments after `--`, are left to the app (see
+//! [`app_args`]).
+
+#![no_std]
+
+#[macro_use]
+extern crate log;
+extern crate alloc;
+
+mod value;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use spin::Once;
+
+pub use self::value::FromParam;
+
+struct Arg {
+    /// The whole argument, without surrounding quotes.
+    text: &'static str,
+    name: &'static str,
+    value: Option<&'static str>,
+    /// Whether it is before `--`.
+    is_kernel: bool,
+    /// Whether it has been read by some crate.
+    used: AtomicBool,
+}
+
+/// A parsed command line.
+pub struct Cmdline {
+    raw: &'static str,
+    args: Vec<Arg>,
+}
+
+/// A typed kernel parameter with a default value.
+///
+/// ```ignore
+/// static TICKS_PER_SEC: Param<usize> = Param::new("ticks", 100);
+/// let ticks = TICKS_PER_SEC.get();
+/// ```
+pub struct Param<T: 'static> {
+    name: &'static str,
+    default: T,
+}
+
+static CMDLINE: Once<Cmdline> = Once::new();
+
+/// Removes the double quotes around `s`, if any.
+fn unquote(s: &str) -> &str {
+    s.strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .unwrap_or(s)
+}
+
+/// Splits `s` by whitespace outside double quotes.
+fn split_args(s: &str) -> impl Iterator<Item = &str> {
+    let mut rest = s;
+    core::iter::from_fn(move || {
+        rest = rest.trim_start();
+        if rest.is_empty() {
+            return None;
+        }
+        let mut in_quotes = false;
+        let end = rest
+            .char_indices()
+            .find(|&(_, c)| {
+                if c == '"' {
+                    in_quotes = !in_quotes;
+                }
+                c.is_whitespace() && !in_quotes
+            })
+            .map_or(rest.len(), |(i, _)| i);
+        let (arg, remaining) = rest.split_at(end);
+        rest = remaining;
+        Some(arg)
+    })
+}
+
+impl Cmdline {
+    /// Parses the command line.
+    pub fn new(raw: &'static str) -> Self {
+        let mut args = Vec::new();
+        let mut is_kernel = true;
+        for arg in split_args(raw) {
+            if is_kernel && arg == "--" {
+                is_kernel = false;
+                continue;
+            }
+            let (name, value) = match arg.split_once('=') {
+                Some((name, value)) if is_kernel => (name, Some(unquote(value))),
+                _ => (unquote(arg), None),
+            };
+            args.push(Arg {
+                text: unquote(arg),
+                name,
+                value,
+                is_kernel,
+                used: AtomicBool::new(false),
+            });
+        }
+        Self { raw, args }
+    }
+
+    /// Returns the command line as it is given.
+    pub fn raw(&self) -> &'static str {
+        self.raw
+    }
+
+    /// Returns the value of the kernel parameter `name`, and marks it as used.
+    ///
+    /// A parameter without `=` has an empty value. If the parameter is given
+    /// more than once, the last one wins.
+    pub fn get(&self, name: &str) -> Option<&'static str> {
+        let mut found = None;
+        for arg in self.args.iter().filter(|arg| arg.is_kernel && arg.name == name) {
+            arg.used.store(true, Ordering::Relaxed);
+            found = Some(arg.value.unwrap_or(""));
+        }
+        found
+    }
+
+    /// Returns the kernel parameters named `<prefix><name>` as `(name, value)`
+    /// pairs, and marks them as used.
+    pub fn with_prefix<'a>(
+        &'a self,
+        prefix: &'a str,
+    ) -> impl Iterator<Item = (&'static str, &'static str)> + 'a {
+        self.args.iter().filter(|arg| arg.is_kernel).filter_map(move |arg| {
+            let name = arg.name.strip_prefix(prefix)?;
+            arg.used.store(true, Ordering::Relaxed);
+            Some((name, arg.value.unwrap_or("")))
+        })
+    }
+
+    /// Returns the arguments left to the app.
+    pub fn app_args(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.args
+            .iter()
+            .filter(|arg| !arg.is_kernel || !arg.used.load(Ordering::Relaxed))
+            .map(|arg| arg.text)
+    }
+}
+
+impl<T: FromParam + Clone> Param<T> {
+    /// Creates a parameter named `name`.
+    pub const fn new(name: &'static str, default: T) -> Self {
+        Self { name, default }
+    }
+
+    /// Returns the name of the parameter.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Returns the default value of the parameter.
+    pub fn default_value(&self) -> T {
+        self.default.clone()
+    }
+
+    /// Reads the parameter from the kernel command line.
+    pub fn get(&self) -> T {
+        self.get_in(cmdline())
+    }
+
+    /// Reads the parameter from `cmdline`.
+    pub fn get_in(&self, cmdline: &Cmdline) -> T {
+        match cmdline.get(self.name) {
+            Some(value) => T::from_param(value).unwrap_or_else(|| {
+                warn!("invalid value of parameter {}: {:?}", self.name, value);
+                self.default.clone()
+            }),
+            None => self.default.clone(),
+        }
+    }
+}
+
+/// Initializes the kernel command line.
+///
+/// It should be called once the heap is ready, before any parameter is read.
+pub fn init(cmdline: &str) {
+    if CMDLINE.is_completed() {
+        warn!("kernel command line is already initialized");
+        return;
+    }
+    let raw = String::from(cmdline.trim()).leak();
+    CMDLINE.call_once(|| Cmdline::new(raw));
+}
+
+/// Returns the kernel command line, which is empty if it is not initialized.
+pub fn cmdline() -> &'static Cmdline {
+    CMDLINE.call_once(|| Cmdline::new(""))
+}
+
+/// Returns the arguments left to the app.
+///
+/// These are the arguments not read by any crate so far, and all arguments
+/// after `--`.
+pub fn app_args() -> impl Iterator<Item = &'static str> {
+    cmdline().app_args()
+}
+
+#[cfg(test)]
+mod tests;
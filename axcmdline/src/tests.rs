@@ -0,0 +1,85 @@
+use alloc::vec::Vec;
+
+use crate::{split_args, Cmdline, FromParam, Param};
+
+#[test]
+fn test_split_args() {
+    let args: Vec<_> = split_args("  a b=1\tc=\"x y\"  \"d e\" ").collect();
+    assert_eq!(args, ["a", "b=1", "c=\"x y\"", "\"d e\""]);
+    assert_eq!(split_args("").count(), 0);
+    assert_eq!(split_args("   ").count(), 0);
+    // an unclosed quote extends to the end
+    let args: Vec<_> = split_args("a=\"b c").collect();
+    assert_eq!(args, ["a=\"b c"]);
+}
+
+#[test]
+fn test_get() {
+    let cmdline = Cmdline::new("log=debug quiet init=\"my app\" ticks=100 ticks=1000");
+    assert_eq!(cmdline.get("log"), Some("debug"));
+    assert_eq!(cmdline.get("quiet"), Some(""));
+    assert_eq!(cmdline.get("init"), Some("my app"));
+    // the last one wins
+    assert_eq!(cmdline.get("ticks"), Some("1000"));
+    assert_eq!(cmdline.get("stack"), None);
+}
+
+#[test]
+fn test_with_prefix() {
+    let cmdline = Cmdline::new("log=warn log.axtask=trace log.axfs=debug logx=1");
+    let levels: Vec<_> = cmdline.with_prefix("log.").collect();
+    assert_eq!(levels, [("axtask", "trace"), ("axfs", "debug")]);
+    let args: Vec<_> = cmdline.app_args().collect();
+    assert_eq!(args, ["log=warn", "logx=1"]);
+}
+
+#[test]
+fn test_app_args() {
+    let cmdline = Cmdline::new("log=info foo -- log=debug \"hello world\" --");
+    assert_eq!(cmdline.app_args().count(), 5);
+    assert_eq!(cmdline.get("log"), Some("info"));
+    let args: Vec<_> = cmdline.app_args().collect();
+    assert_eq!(args, ["foo", "log=debug", "hello world", "--"]);
+
+    assert_eq!(Cmdline::new("").app_args().count(), 0);
+    assert_eq!(Cmdline::new("--").app_args().count(), 0);
+}
+
+#[test]
+fn test_param() {
+    static TICKS: Param<usize> = Param::new("ticks", 100);
+    static STACK: Param<usize> = Param::new("stack", 0x40000);
+    static QUIET: Param<bool> = Param::new("quiet", false);
+    static LOG: Param<&str> = Param::new("log", "warn");
+
+    let cmdline = Cmdline::new("ticks=1000 stack=oops quiet");
+    assert_eq!(TICKS.get_in(&cmdline), 1000);
+    assert_eq!(STACK.get_in(&cmdline), 0x40000); // invalid
+    assert!(QUIET.get_in(&cmdline));
+    assert_eq!(LOG.get_in(&cmdline), "warn"); // absent
+    assert_eq!(LOG.name(), "log");
+    assert_eq!(LOG.default_value(), "warn");
+    // invalid ones are used as well
+    assert_eq!(cmdline.app_args().count(), 0);
+}
+
+#[test]
+fn test_values() {
+    assert_eq!(usize::from_param("42"), Some(42));
+    assert_eq!(usize::from_param("0x1f"), Some(0x1f));
+    assert_eq!(usize::from_param("0b101"), Some(5));
+    assert_eq!(usize::from_param("0o17"), Some(0o17));
+    assert_eq!(usize::from_param("256K"), Some(256 * 1024));
+    assert_eq!(usize::from_param("0x10M"), Some(16 << 20));
+    assert_eq!(u64::from_param("2G"), Some(2 << 30));
+    assert_eq!(u8::from_param("1K"), None);
+    assert_eq!(u8::from_param("256"), None);
+    assert_eq!(u32::from_param(""), None);
+    assert_eq!(u32::from_param("-1"), None);
+    assert_eq!(i32::from_param("-1"), Some(-1));
+
+    assert_eq!(bool::from_param(""), Some(true));
+    assert_eq!(bool::from_param("on"), Some(true));
+    assert_eq!(bool::from_param("0"), Some(false));
+    assert_eq!(bool::from_param("maybe"), None);
+}
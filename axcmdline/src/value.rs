@@ -0,0 +1,71 @@
+//! Conversions from parameter values.
+
+/// A type that a parameter value can be converted to.
+pub trait FromParam: Sized {
+    /// Converts `value`, or returns `None` if it is invalid.
+    fn from_param(value: &'static str) -> Option<Self>;
+}
+
+impl FromParam for &'static str {
+    fn from_param(value: &'static str) -> Option<Self> {
+        Some(value)
+    }
+}
+
+/// A flag without a value (`quiet`) is true.
+impl FromParam for bool {
+    fn from_param(value: &'static str) -> Option<Self> {
+        match value {
+            "" | "1" | "y" | "yes" | "on" | "true" => Some(true),
+            "0" | "n" | "no" | "off" | "false" => Some(false),
+            _ => None,
+        }
+    }
+}
+
+/// Splits the radix prefix (`0x`, `0o`, `0b`) from an integer.
+fn split_radix(value: &str) -> (&str, u32) {
+    let prefixes = [("0x", 16), ("0X", 16), ("0o", 8), ("0b", 2)];
+    prefixes
+        .iter()
+        .find_map(|&(prefix, radix)| Some((value.strip_prefix(prefix)?, radix)))
+        .unwrap_or((value, 10))
+}
+
+/// Splits the size suffix (`K`, `M`, `G`) from an integer.
+fn split_unit(value: &str) -> (&str, u32) {
+    match value.as_bytes().last() {
+        Some(b'K' | b'k') => (&value[..value.len() - 1], 10),
+        Some(b'M' | b'm') => (&value[..value.len() - 1], 20),
+        Some(b'G' | b'g') => (&value[..value.len() - 1], 30),
+        _ => (value, 0),
+    }
+}
+
+macro_rules! impl_from_param_unsigned {
+    ($($t:ty),*) => {$(
+        /// Accepts `0x`, `0o` and `0b` prefixes, and `K`, `M` and `G` suffixes
+        /// for sizes in binary units (`256K`).
+        impl FromParam for $t {
+            fn from_param(value: &'static str) -> Option<Self> {
+                let (digits, radix) = split_radix(value);
+                let (digits, shift) = split_unit(digits);
+                let n = <$t>::from_str_radix(digits, radix).ok()?;
+                n.checked_mul((1 as $t).checked_shl(shift)?)
+            }
+        }
+    )*};
+}
+
+macro_rules! impl_from_param_signed {
+    ($($t:ty),*) => {$(
+        impl FromParam for $t {
+            fn from_param(value: &'static str) -> Option<Self> {
+                value.parse().ok()
+            }
+        }
+    )*};
+}
+
+impl_from_param_unsigned!(u8, u16, u32, u64, usize);
+impl_from_param_signed!(i8, i16, i32, i64, isize);
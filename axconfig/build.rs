@@ -0,0 +1,280 @@
+//! Generates the platform constants from `platforms/<AX_PLATFORM>.toml`.
+//!
+//! `AX_PLATFORM` is either the name of a file in `platforms/`, or the path
+//! of a `.toml` file elsewhere. All keys are required and checked here, so
+//! that a bad configuration fails the build rather than the boot.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+const DEFAULT_PLATFORM: &str = "qemu-virt-riscv64";
+
+/// CPU masks of tasks are 64-bit.
+const MAX_SMP: usize = 64;
+
+const PAGE_SIZE: usize = 0x1000;
+const SIZE_1M: usize = 0x10_0000;
+const SIZE_1G: usize = 0x4000_0000;
+const NANOS_PER_SEC: usize = 1_000_000_000;
+
+/// String keys and their doc comments.
+const STR_KEYS: &[(&str, &str)] = &[
+    ("arch", "Architecture identifier."),
+    ("platform", "Platform identifier."),
+];
+
+/// Integer keys and their doc comments.
+const INT_KEYS: &[(&str, &str)] = &[
+    (
+        "phys-memory-base",
+        "Base address of the whole physical memory.",
+    ),
+    ("phys-memory-size", "Size of the whole physical memory."),
+    (
+        "kernel-base-paddr",
+        "Base physical address of the kernel image.",
+    ),
+    (
+        "kernel-base-vaddr",
+        "Base virtual address of the kernel image.",
+    ),
+    (
+        "phys-virt-offset",
+        "Linear mapping offset, for quick conversions between physical and virtual addresses.",
+    ),
+    (
+        "aspace-bits",
+        "Number of bits of the virtual address space.",
+    ),
+    (
+        "plic-paddr",
+        "Base physical address of the PLIC, which routes the IRQs of devices.",
+    ),
+    ("timer-frequency", "Frequency of the hardware timer in Hz."),
+    ("ticks-per-sec", "Number of timer interrupts per second."),
+    ("task-stack-size", "Stack size of each task."),
+    (
+        "ksyms-size",
+        "Size of the space reserved for the kernel symbol table, with the `ksyms` feature.",
+    ),
+];
+
+struct Config {
+    strs: BTreeMap<&'static str, String>,
+    /// The values, and whether they are written in hex.
+    ints: BTreeMap<&'static str, (usize, bool)>,
+}
+
+impl Config {
+    fn int(&self, key: &str) -> usize {
+        self.ints[key].0
+    }
+}
+
+fn parse_int(key: &str, value: &toml::Value) -> Result<(usize, bool), String> {
+    let invalid = || format!("`{}` should be an integer, got {}", key, value);
+    match value {
+        toml::Value::Integer(n) => usize::try_from(*n)
+            .map(|n| (n, false))
+            .map_err(|_| invalid()),
+        toml::Value::String(s) => {
+            let s = s.replace('_', "");
+            match s.strip_prefix("0x") {
+                Some(hex) => usize::from_str_radix(hex, 16).map(|n| (n, true)),
+                None => s.parse().map(|n| (n, false)),
+            }
+            .map_err(|_| invalid())
+        }
+        _ => Err(invalid()),
+    }
+}
+
+fn load(path: &Path) -> Result<Config, String> {
+    let content = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let table: toml::Table = content
+        .parse()
+        .map_err(|e: toml::de::Error| e.to_string())?;
+
+    if let Some(key) = table
+        .keys()
+        .find(|key| !STR_KEYS.iter().chain(INT_KEYS).any(|(k, _)| k == key))
+    {
+        return Err(format!("unknown key `{}`", key));
+    }
+
+    let mut config = Config {
+        strs: BTreeMap::new(),
+        ints: BTreeMap::new(),
+    };
+    for &(key, _) in STR_KEYS {
+        match table.get(key) {
+            Some(toml::Value::String(s)) => config.strs.insert(key, s.clone()),
+            Some(_) => return Err(format!("`{}` should be a string", key)),
+            None => return Err(format!("missing key `{}`", key)),
+        };
+    }
+    for &(key, _) in INT_KEYS {
+        let value = table
+            .get(key)
+            .ok_or_else(|| format!("missing key `{}`", key))?;
+        config.ints.insert(key, parse_int(key, value)?);
+    }
+    Ok(config)
+}
+
+fn validate(config: &Config, path: &Path) -> Result<(), String> {
+    let check = |ok: bool, msg: &str| if ok { Ok(()) } else { Err(msg.to_string()) };
+
+    let stem = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or_default();
+    check(
+        config.strs["platform"] == stem,
+        "`platform` should be the same as the file name",
+    )?;
+    // Only check the architecture when building the kernel, not host tools
+    // and tests.
+    if std::env::var("CARGO_CFG_TARGET_OS").as_deref() == Ok("none") {
+        let target_arch = std::env::var("CARGO_CFG_TARGET_ARCH").unwrap_or_default();
+        check(
+            config.strs["arch"] == target_arch,
+            "`arch` does not match the target",
+        )?;
+    }
+
+    for key in [
+        "phys-memory-base",
+        "phys-memory-size",
+        "kernel-base-paddr",
+        "kernel-base-vaddr",
+        "phys-virt-offset",
+        "plic-paddr",
+        "task-stack-size",
+        "ksyms-size",
+    ] {
+        if !config.int(key).is_multiple_of(PAGE_SIZE) {
+            return Err(format!("`{}` should be aligned to {:#x}", key, PAGE_SIZE));
+        }
+    }
+
+    let aspace_bits = config.int("aspace-bits");
+    check(
+        aspace_bits == 39,
+        "`aspace-bits` should be 39, only Sv39 is supported",
+    )?;
+
+    let mem_base = config.int("phys-memory-base");
+    let mem_size = config.int("phys-memory-size");
+    let kernel_paddr = config.int("kernel-base-paddr");
+    let kernel_vaddr = config.int("kernel-base-vaddr");
+    let offset = config.int("phys-virt-offset");
+    check(
+        kernel_vaddr == kernel_paddr.wrapping_add(offset),
+        "`kernel-base-vaddr` should be `kernel-base-paddr` + `phys-virt-offset`",
+    )?;
+    check(
+        (kernel_vaddr as i64) >> (aspace_bits - 1) == -1,
+        "`kernel-base-vaddr` should be in the upper half of the address space",
+    )?;
+    // the early heap takes 1M below the kernel image
+    check(
+        kernel_paddr >= mem_base + SIZE_1M && kernel_paddr < mem_base + mem_size,
+        "`kernel-base-paddr` should be at least 1M above `phys-memory-base`, \
+         and within the physical memory",
+    )?;
+    // the boot page table maps the 1G region containing the memory base
+    check(
+        kernel_paddr - mem_base / SIZE_1G * SIZE_1G < SIZE_1G,
+        "`kernel-base-paddr` should be in the same 1G region as `phys-memory-base`",
+    )?;
+
+    let timer_freq = config.int("timer-frequency");
+    let ticks = config.int("ticks-per-sec");
+    check(timer_freq > 0, "`timer-frequency` should not be 0")?;
+    // time is converted with a whole number of nanoseconds per tick
+    check(
+        NANOS_PER_SEC % timer_freq == 0,
+        "`timer-frequency` should be at most 1GHz, and divide 1_000_000_000",
+    )?;
+    check(
+        ticks > 0 && ticks <= timer_freq,
+        "`ticks-per-sec` should be between 1 and `timer-frequency`",
+    )?;
+    check(
+        config.int("task-stack-size") > 0,
+        "`task-stack-size` should not be 0",
+    )?;
+    Ok(())
+}
+
+fn gen_config_rs(config: &Config, path: &Path) -> String {
+    let mut output = format!("// Generated from {} by build.rs\n", path.display());
+    let const_name = |key: &str| key.replace('-', "_").to_uppercase();
+    for &(key, doc) in STR_KEYS {
+        output += &format!(
+            "\n/// {}\npub const {}: &str = {:?};\n",
+            doc,
+            const_name(key),
+            config.strs[key]
+        );
+    }
+    for &(key, doc) in INT_KEYS {
+        let (value, is_hex) = config.ints[key];
+        let value = if is_hex {
+            format!("{:#x}", value)
+        } else {
+            value.to_string()
+        };
+        output += &format!(
+            "\n/// {}\npub const {}: usize = {};\n",
+            doc,
+            const_name(key),
+            value
+        );
+    }
+    output
+}
+
+/// Reads the number of CPUs from `AX_SMP`, 1 by default.
+fn smp() -> Result<usize, String> {
+    let smp = match std::env::var("AX_SMP") {
+        Ok(smp) if !smp.is_empty() => smp
+            .parse()
+            .map_err(|_| format!("`AX_SMP` should be an integer, got {:?}", smp))?,
+        _ => 1,
+    };
+    if smp == 0 || smp > MAX_SMP {
+        return Err(format!("`AX_SMP` should be between 1 and {}", MAX_SMP));
+    }
+    Ok(smp)
+}
+
+fn main() {
+    let platform = std::env::var("AX_PLATFORM")
+        .ok()
+        .filter(|p| !p.is_empty())
+        .unwrap_or_else(|| DEFAULT_PLATFORM.into());
+    let path = if platform.ends_with(".toml") {
+        PathBuf::from(platform)
+    } else {
+        let manifest_dir = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+        manifest_dir
+            .join("../platforms")
+            .join(format!("{}.toml", platform))
+    };
+    println!("cargo:rerun-if-env-changed=AX_PLATFORM");
+    println!("cargo:rerun-if-env-changed=AX_SMP");
+    println!("cargo:rerun-if-changed={}", path.display());
+
+    let config = load(&path)
+        .and_then(|config| validate(&config, &path).map(|_| config))
+        .unwrap_or_else(|e| panic!("invalid platform config {}: {}", path.display(), e));
+
+    let smp = smp().unwrap_or_else(|e| panic!("invalid SMP config: {}", e));
+
+    let mut output = gen_config_rs(&config, &path);
+    output += &format!("\n/// Number of CPUs, `SMP` in make.\npub const SMP: usize = {};\n", smp);
+    let out_dir = PathBuf::from(std::env::var("OUT_DIR").unwrap());
+    std::fs::write(out_dir.join("config.rs"), output).unwrap();
+}
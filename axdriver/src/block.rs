@@ -0,0 +1,125 @@
+//! Common traits and types for block storage device drivers (i.e. disk).
+
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::{DevError, DevResult};
+
+/// A boxed block device driver.
+pub type AxBlockDevice = Box<dyn BlockDriverOps>;
+
+/// Operations that require a block storage device driver to implement.
+pub trait BlockDriverOps: Send + Sync {
+    /// The name of the device.
+    fn device_name(&self) -> &str;
+
+    /// The number of blocks in this storage device.
+    ///
+    /// The total size of the device is `num_blocks() * block_size()`.
+    fn num_blocks(&self) -> u64;
+
+    /// The size of each block in bytes.
+    fn block_size(&self) -> usize;
+
+    /// Reads blocked data from the given block.
+    ///
+    /// The size of the buffer may exceed the block size, in which case multiple
+    /// contiguous blocks will be read.
+    fn read_block(&mut self, block_id: u64, buf: &mut [u8]) -> DevResult;
+
+    /// Writes blocked data to the given block.
+    ///
+    /// The size of the buffer may exceed the block size, in which case multiple
+    /// contiguous blocks will be written.
+    fn write_block(&mut self, block_id: u64, buf: &[u8]) -> DevResult;
+
+    /// Flushes the device to write all pending data to the storage.
+    fn flush(&mut self) -> DevResult;
+}
+
+const BLOCK_SIZE: usize = 512;
+
+/// A RAM disk that stores data in a vector.
+#[derive(Default)]
+pub struct RamDisk {
+    data: Vec<u8>,
+}
+
+impl RamDisk {
+    /// Creates a new RAM disk with the given size hint.
+    ///
+    /// The actual size of the RAM disk will be aligned upwards to the block
+    /// size (512 bytes).
+    pub fn new(size_hint: usize) -> Self {
+        let size = axconfig::align_up(size_hint, BLOCK_SIZE);
+        Self {
+            data: vec![0; size],
+        }
+    }
+
+    /// Creates a new RAM disk from the exiting data.
+    ///
+    /// The actual size of the RAM disk will be aligned upwards to the block
+    /// size (512 bytes).
+    pub fn from(buf: &[u8]) -> Self {
+        let size = axconfig::align_up(buf.len(), BLOCK_SIZE);
+        let mut data = vec![0; size];
+        data[..buf.len()].copy_from_slice(buf);
+        Self { data }
+    }
+
+    /// Returns the size of the RAM disk in bytes.
+    pub const fn size(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Returns the contents of the RAM disk.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+impl BlockDriverOps for RamDisk {
+    fn device_name(&self) -> &str {
+        "ramdisk"
+    }
+
+    #[inline]
+    fn num_blocks(&self) -> u64 {
+        (self.size() / BLOCK_SIZE) as u64
+    }
+
+    #[inline]
+    fn block_size(&self) -> usize {
+        BLOCK_SIZE
+    }
+
+    fn read_block(&mut self, block_id: u64, buf: &mut [u8]) -> DevResult {
+        let offset = block_id as usize * BLOCK_SIZE;
+        if offset + buf.len() > self.size() {
+            return Err(DevError::Io);
+        }
+        if !buf.len().is_multiple_of(BLOCK_SIZE) {
+            return Err(DevError::InvalidParam);
+        }
+        buf.copy_from_slice(&self.data[offset..offset + buf.len()]);
+        Ok(())
+    }
+
+    fn write_block(&mut self, block_id: u64, buf: &[u8]) -> DevResult {
+        let offset = block_id as usize * BLOCK_SIZE;
+        if offset + buf.len() > self.size() {
+            return Err(DevError::Io);
+        }
+        if !buf.len().is_multiple_of(BLOCK_SIZE) {
+            return Err(DevError::InvalidParam);
+        }
+        self.data[offset..offset + buf.len()].copy_from_slice(buf);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> DevResult {
+        Ok(())
+    }
+}
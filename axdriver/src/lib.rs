@@ -0,0 +1,84 @@
+//! Device drivers used by [ArceOS](https://github.com/rcore-os/arceos).
+//!
+//! Devices are probed from the `virtio,mmio` regions found in the device tree
+//! by [`init_drivers`], and handed out by class in [`AllDevices`].
+
+#![no_std]
+
+#[macro_use]
+extern crate log;
+extern crate alloc;
+
+#[cfg(feature = "block")]
+pub mod block;
+#[cfg(feature = "net")]
+pub mod net;
+
+mod virtio;
+
+use alloc::vec::Vec;
+
+#[cfg(feature = "block")]
+pub use block::{AxBlockDevice, BlockDriverOps};
+#[cfg(feature = "net")]
+pub use net::{AxNetDevice, EthernetAddress, NetDriverOps};
+
+/// The error type for device operation failures.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum DevError {
+    /// An entity already exists.
+    AlreadyExists,
+    /// Try again, for non-blocking APIs.
+    Again,
+    /// Bad internal state.
+    BadState,
+    /// Invalid parameter/argument.
+    InvalidParam,
+    /// Input/output error.
+    Io,
+    /// Not enough space/cannot allocate memory (DMA).
+    NoMemory,
+    /// Device or resource is busy.
+    ResourceBusy,
+    /// This operation is unsupported or unimplemented.
+    Unsupported,
+}
+
+/// A specialized `Result` type for device operations.
+pub type DevResult<T = ()> = Result<T, DevError>;
+
+/// All device drivers found during probing, grouped by device class.
+#[derive(Default)]
+pub struct AllDevices {
+    /// All block device drivers.
+    #[cfg(feature = "block")]
+    pub block: Vec<AxBlockDevice>,
+    /// All network device drivers.
+    #[cfg(feature = "net")]
+    pub net: Vec<AxNetDevice>,
+}
+
+/// Probes all `virtio,mmio` regions and initializes the drivers of the
+/// devices found there.
+///
+/// `mmio_regions` holds the physical `(address, size)` pairs of the regions.
+/// They must already be mapped at [`axconfig::phys_to_virt`].
+pub fn init_drivers(mmio_regions: &[(usize, usize)]) -> AllDevices {
+    info!("Initialize device drivers...");
+
+    #[allow(unused_mut)]
+    let mut all_devs = AllDevices::default();
+    for &(paddr, size) in mmio_regions {
+        virtio::probe_mmio_device(paddr, size, &mut all_devs);
+    }
+
+    #[cfg(feature = "block")]
+    for dev in &all_devs.block {
+        info!("  block device: {}, {} blocks", dev.device_name(), dev.num_blocks());
+    }
+    #[cfg(feature = "net")]
+    for dev in &all_devs.net {
+        info!("  net device: {}, mac {:02x?}", dev.device_name(), dev.mac_address().0);
+    }
+    all_devs
+}
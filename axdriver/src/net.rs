@@ -0,0 +1,37 @@
+//! Common traits and types for network device (NIC) drivers.
+
+use alloc::boxed::Box;
+
+use crate::DevResult;
+
+/// A boxed network device driver.
+pub type AxNetDevice = Box<dyn NetDriverOps>;
+
+/// The ethernet address of the NIC (MAC address).
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct EthernetAddress(pub [u8; 6]);
+
+/// Operations that require a network device (NIC) driver to implement.
+pub trait NetDriverOps: Send + Sync {
+    /// The name of the device.
+    fn device_name(&self) -> &str;
+
+    /// The ethernet address of the NIC.
+    fn mac_address(&self) -> EthernetAddress;
+
+    /// Whether can transmit packets.
+    fn can_transmit(&self) -> bool;
+
+    /// Whether can receive packets.
+    fn can_receive(&self) -> bool;
+
+    /// Receives a packet into `buf`, and returns the length of the packet.
+    ///
+    /// If currently no packet is received, returns [`DevError::Again`].
+    ///
+    /// [`DevError::Again`]: crate::DevError::Again
+    fn receive(&mut self, buf: &mut [u8]) -> DevResult<usize>;
+
+    /// Transmits a packet, and blocks until the device has taken it.
+    fn transmit(&mut self, packet: &[u8]) -> DevResult;
+}
@@ -0,0 +1,214 @@
+//! Drivers for VirtIO devices over the MMIO transport.
+
+use core::alloc::Layout;
+use core::ptr::NonNull;
+
+use axconfig::{phys_to_virt, virt_to_phys, PAGE_SIZE};
+use virtio_drivers::transport::mmio::{MmioTransport, VirtIOHeader};
+use virtio_drivers::transport::{DeviceType, Transport};
+use virtio_drivers::{BufferDirection, Hal, PhysAddr};
+
+use crate::{AllDevices, DevError};
+
+#[cfg_attr(not(any(feature = "block", feature = "net")), allow(unused_variables))]
+pub(crate) fn probe_mmio_device(paddr: usize, size: usize, all_devs: &mut AllDevices) {
+    let header = NonNull::new(phys_to_virt(paddr) as *mut VirtIOHeader).unwrap();
+    let transport = match unsafe { MmioTransport::new(header) } {
+        Ok(transport) => transport,
+        Err(_) => return, // empty slot
+    };
+    debug!(
+        "virtio-mmio [{:#x}, {:#x}): {:?}",
+        paddr,
+        paddr + size,
+        transport.device_type()
+    );
+
+    match transport.device_type() {
+        #[cfg(feature = "block")]
+        DeviceType::Block => match blk::VirtIoBlkDev::try_new(transport) {
+            Ok(dev) => all_devs.block.push(alloc::boxed::Box::new(dev)),
+            Err(e) => warn!("failed to initialize virtio-blk at {:#x}: {:?}", paddr, e),
+        },
+        #[cfg(feature = "net")]
+        DeviceType::Network => match net::VirtIoNetDev::try_new(transport) {
+            Ok(dev) => all_devs.net.push(alloc::boxed::Box::new(dev)),
+            Err(e) => warn!("failed to initialize virtio-net at {:#x}: {:?}", paddr, e),
+        },
+        ty => debug!("unsupported virtio device {:?} at {:#x}", ty, paddr),
+    }
+}
+
+#[allow(dead_code)]
+const fn as_dev_err(e: virtio_drivers::Error) -> DevError {
+    use virtio_drivers::Error::*;
+    match e {
+        QueueFull => DevError::BadState,
+        NotReady => DevError::Again,
+        WrongToken => DevError::BadState,
+        AlreadyUsed => DevError::AlreadyExists,
+        InvalidParam => DevError::InvalidParam,
+        DmaError => DevError::NoMemory,
+        IoError => DevError::Io,
+        Unsupported => DevError::Unsupported,
+        ConfigSpaceTooSmall => DevError::BadState,
+        ConfigSpaceMissing => DevError::BadState,
+        _ => DevError::BadState,
+    }
+}
+
+#[cfg(feature = "block")]
+mod blk {
+    use super::{as_dev_err, VirtIoHalImpl};
+    use crate::block::BlockDriverOps;
+    use crate::DevResult;
+    use virtio_drivers::device::blk::{VirtIOBlk, SECTOR_SIZE};
+    use virtio_drivers::transport::mmio::MmioTransport;
+
+    /// The VirtIO block device driver.
+    pub struct VirtIoBlkDev {
+        inner: VirtIOBlk<VirtIoHalImpl, MmioTransport>,
+    }
+
+    unsafe impl Send for VirtIoBlkDev {}
+    unsafe impl Sync for VirtIoBlkDev {}
+
+    impl VirtIoBlkDev {
+        pub fn try_new(transport: MmioTransport) -> DevResult<Self> {
+            Ok(Self {
+                inner: VirtIOBlk::new(transport).map_err(as_dev_err)?,
+            })
+        }
+    }
+
+    impl BlockDriverOps for VirtIoBlkDev {
+        fn device_name(&self) -> &str {
+            "virtio-blk"
+        }
+
+        #[inline]
+        fn num_blocks(&self) -> u64 {
+            self.inner.capacity()
+        }
+
+        #[inline]
+        fn block_size(&self) -> usize {
+            SECTOR_SIZE
+        }
+
+        fn read_block(&mut self, block_id: u64, buf: &mut [u8]) -> DevResult {
+            self.inner
+                .read_blocks(block_id as _, buf)
+                .map_err(as_dev_err)
+        }
+
+        fn write_block(&mut self, block_id: u64, buf: &[u8]) -> DevResult {
+            self.inner
+                .write_blocks(block_id as _, buf)
+                .map_err(as_dev_err)
+        }
+
+        fn flush(&mut self) -> DevResult {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(feature = "net")]
+mod net {
+    use super::{as_dev_err, VirtIoHalImpl};
+    use crate::net::{EthernetAddress, NetDriverOps};
+    use crate::{DevError, DevResult};
+    use virtio_drivers::device::net::VirtIONet;
+    use virtio_drivers::transport::mmio::MmioTransport;
+
+    const QUEUE_SIZE: usize = 64;
+    const NET_BUF_LEN: usize = 2048;
+
+    /// The VirtIO network device driver.
+    pub struct VirtIoNetDev {
+        inner: VirtIONet<VirtIoHalImpl, MmioTransport, QUEUE_SIZE>,
+    }
+
+    unsafe impl Send for VirtIoNetDev {}
+    unsafe impl Sync for VirtIoNetDev {}
+
+    impl VirtIoNetDev {
+        pub fn try_new(transport: MmioTransport) -> DevResult<Self> {
+            Ok(Self {
+                inner: VirtIONet::new(transport, NET_BUF_LEN).map_err(as_dev_err)?,
+            })
+        }
+    }
+
+    impl NetDriverOps for VirtIoNetDev {
+        fn device_name(&self) -> &str {
+            "virtio-net"
+        }
+
+        fn mac_address(&self) -> EthernetAddress {
+            EthernetAddress(self.inner.mac_address())
+        }
+
+        fn can_transmit(&self) -> bool {
+            self.inner.can_send()
+        }
+
+        fn can_receive(&self) -> bool {
+            self.inner.can_recv()
+        }
+
+        fn receive(&mut self, buf: &mut [u8]) -> DevResult<usize> {
+            let rx_buf = self.inner.receive().map_err(as_dev_err)?;
+            let packet = rx_buf.packet();
+            let len = packet.len();
+            let res = if len <= buf.len() {
+                buf[..len].copy_from_slice(packet);
+                Ok(len)
+            } else {
+                Err(DevError::InvalidParam)
+            };
+            self.inner.recycle_rx_buffer(rx_buf).map_err(as_dev_err)?;
+            res
+        }
+
+        fn transmit(&mut self, packet: &[u8]) -> DevResult {
+            let mut tx_buf = self.inner.new_tx_buffer(packet.len());
+            tx_buf.packet_mut().copy_from_slice(packet);
+            self.inner.send(tx_buf).map_err(as_dev_err)
+        }
+    }
+}
+
+/// DMA and address translation for VirtIO drivers.
+///
+/// DMA buffers are taken from the global allocator with page granularity, and
+/// the kernel maps all physical memory linearly at `PHYS_VIRT_OFFSET`.
+pub(crate) struct VirtIoHalImpl;
+
+unsafe impl Hal for VirtIoHalImpl {
+    fn dma_alloc(pages: usize, _direction: BufferDirection) -> (PhysAddr, NonNull<u8>) {
+        let layout = Layout::from_size_align(pages * PAGE_SIZE, PAGE_SIZE).unwrap();
+        let vaddr = unsafe { alloc::alloc::alloc_zeroed(layout) };
+        match NonNull::new(vaddr) {
+            Some(vaddr) => (virt_to_phys(vaddr.as_ptr() as usize), vaddr),
+            None => (0, NonNull::dangling()),
+        }
+    }
+
+    unsafe fn dma_dealloc(_paddr: PhysAddr, vaddr: NonNull<u8>, pages: usize) -> i32 {
+        let layout = Layout::from_size_align(pages * PAGE_SIZE, PAGE_SIZE).unwrap();
+        alloc::alloc::dealloc(vaddr.as_ptr(), layout);
+        0
+    }
+
+    unsafe fn mmio_phys_to_virt(paddr: PhysAddr, _size: usize) -> NonNull<u8> {
+        NonNull::new(phys_to_virt(paddr) as *mut u8).unwrap()
+    }
+
+    unsafe fn share(buffer: NonNull<[u8]>, _direction: BufferDirection) -> PhysAddr {
+        virt_to_phys(buffer.as_ptr() as *mut u8 as usize)
+    }
+
+    unsafe fn unshare(_paddr: PhysAddr, _buffer: NonNull<[u8]>, _direction: BufferDirection) {}
+}
@@ -5,6 +5,8 @@ extern crate axlog;
 extern crate alloc;
 
 pub mod util;
+mod node;
+mod walk;
 
 use core::str;
 use alloc::string::String;
@@ -12,11 +14,17 @@ use alloc::borrow::ToOwned;
 use alloc::vec::Vec;
 use util::{align, SliceRead, SliceReadError};
 
+pub use node::{FdtNode, FdtTree};
+pub use walk::{Chosen, DtNode, DtNodes, DtProp, DtProps, DtRegs, FdtReg, MemReservation};
+
 const MAGIC_NUMBER     : u32 = 0xd00dfeed;
 const SUPPORTED_VERSION: u32 = 17;
+const HEADER_SIZE      : usize = 40;
 const OF_DT_BEGIN_NODE : u32 = 0x00000001;
 const OF_DT_END_NODE   : u32 = 0x00000002;
 const OF_DT_PROP       : u32 = 0x00000003;
+const OF_DT_NOP        : u32 = 0x00000004;
+const OF_DT_END        : u32 = 0x00000009;
 
 #[derive(Debug)]
 pub enum DeviceTreeError {
@@ -28,11 +36,12 @@ pub enum DeviceTreeError {
     Utf8Error,
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
 pub enum PropError {
     NotFound,
     Utf8Error,
     Missing0,
+    InvalidLength,
     SliceReadError(SliceReadError),
 }
 
@@ -44,38 +53,61 @@ pub struct DeviceTree {
     totalsize: usize,
     pub off_struct: usize,
     off_strings: usize,
+    off_mem_rsvmap: usize,
 }
 
 impl DeviceTree {
     pub fn init(ptr: usize) -> DeviceTreeResult<Self> {
-        // Dtb head(total: 24bytes)
+        // Dtb head(total: 40bytes)
         // 0: magic_number(u32)
         // 4: totalsize(u32)
         // 8: off_dt_struct(u32)
         // 12: off_dt_strings(u32)
+        // 16: off_mem_rsvmap(u32)
         // 20: version(u32)
+        // 24: last_comp_version(u32)
+        // 28: boot_cpuid_phys(u32)
+        // 32: size_dt_strings(u32)
+        // 36: size_dt_struct(u32)
         let buf = unsafe {
-            core::slice::from_raw_parts(ptr as *const u8, 24)
+            core::slice::from_raw_parts(ptr as *const u8, HEADER_SIZE)
         };
 
         if buf.read_be_u32(0)? != MAGIC_NUMBER {
             return Err(DeviceTreeError::BadMagicNumber)
         }
 
-        // check total size
-        let totalsize = buf.read_be_u32(4)? as usize;
-
-        // check version
-        if buf.read_be_u32(20)? != SUPPORTED_VERSION {
+        // check version: newer blobs are fine as long as they are backwards
+        // compatible with the version we support.
+        let version = buf.read_be_u32(20)?;
+        let last_comp_version = buf.read_be_u32(24)?;
+        if version < SUPPORTED_VERSION || last_comp_version > SUPPORTED_VERSION {
             return Err(DeviceTreeError::VersionNotSupported);
         }
 
+        // check total size and all blocks are within it
+        let totalsize = buf.read_be_u32(4)? as usize;
         let off_struct = buf.read_be_u32(8)? as usize;
         let off_strings = buf.read_be_u32(12)? as usize;
+        let off_mem_rsvmap = buf.read_be_u32(16)? as usize;
+        let size_strings = buf.read_be_u32(32)? as usize;
+        let size_struct = buf.read_be_u32(36)? as usize;
+        if totalsize < HEADER_SIZE
+            || off_mem_rsvmap < HEADER_SIZE
+            || off_struct + size_struct > totalsize
+            || off_strings + size_strings > totalsize {
+            return Err(DeviceTreeError::SizeMismatch);
+        }
 
-        Ok(
-            Self {ptr, totalsize, off_struct, off_strings}
-        )
+        let dt = Self {ptr, totalsize, off_struct, off_strings, off_mem_rsvmap};
+        dt.validate()?;
+        Ok(dt)
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        unsafe {
+            core::slice::from_raw_parts(self.ptr as *const u8, self.totalsize)
+        }
     }
 
     pub fn parse(
@@ -89,9 +121,7 @@ impl DeviceTree {
                self.ptr, self.totalsize,
                self.off_struct, self.off_strings);
 
-        let buf = unsafe {
-            core::slice::from_raw_parts(self.ptr as *const u8, self.totalsize)
-        };
+        let buf = self.as_slice();
 
         // check for DT_BEGIN_NODE
         if buf.read_be_u32(pos)? != OF_DT_BEGIN_NODE {
@@ -153,6 +183,12 @@ impl From<SliceReadError> for DeviceTreeError {
     }
 }
 
+impl From<SliceReadError> for PropError {
+    fn from(e: SliceReadError) -> PropError {
+        PropError::SliceReadError(e)
+    }
+}
+
 impl From<str::Utf8Error> for PropError {
     fn from(_: str::Utf8Error) -> PropError {
         PropError::Utf8Error
@@ -164,3 +200,6 @@ impl From<str::Utf8Error> for DeviceTreeError {
         DeviceTreeError::Utf8Error
     }
 }
+
+#[cfg(test)]
+mod tests;
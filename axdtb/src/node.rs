@@ -0,0 +1,267 @@
+//! In-memory node tree of a device tree.
+
+use alloc::borrow::ToOwned;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use crate::util::SliceRead;
+use crate::walk::{DtProp, DtRegs, FdtReg, DEFAULT_ADDR_CELLS, DEFAULT_SIZE_CELLS};
+use crate::{DeviceTree, DeviceTreeResult, PropError};
+
+struct NodeData {
+    name: String,
+    props: Vec<(String, Vec<u8>)>,
+    parent: Option<usize>,
+    children: Vec<usize>,
+}
+
+/// All nodes of a device tree, parsed from the blob.
+///
+/// Nodes are stored in the order they appear in the blob, so the root
+/// is always the first one.
+pub struct FdtTree {
+    nodes: Vec<NodeData>,
+    phandles: BTreeMap<u32, usize>,
+}
+
+/// A node in a [`FdtTree`].
+#[derive(Clone, Copy)]
+pub struct FdtNode<'a> {
+    tree: &'a FdtTree,
+    idx: usize,
+}
+
+impl DeviceTree {
+    /// Parses the whole blob into a [`FdtTree`].
+    pub fn build_tree(&self) -> DeviceTreeResult<FdtTree> {
+        let mut tree = FdtTree {
+            nodes: Vec::new(),
+            phandles: BTreeMap::new(),
+        };
+        // indices of the ancestors of the current node
+        let mut ancestors: Vec<usize> = Vec::new();
+        for node in self.nodes() {
+            ancestors.truncate(node.depth());
+            let idx = tree.nodes.len();
+            let parent = ancestors.last().copied();
+            let mut props = Vec::new();
+            for prop in node.props() {
+                if prop.name == "phandle" || prop.name == "linux,phandle" {
+                    if let Ok(phandle) = prop.as_u32() {
+                        tree.phandles.insert(phandle, idx);
+                    }
+                }
+                props.push((prop.name.to_owned(), prop.value.to_owned()));
+            }
+            tree.nodes.push(NodeData {
+                name: node.name().to_owned(),
+                props,
+                parent,
+                children: Vec::new(),
+            });
+            if let Some(parent) = parent {
+                tree.nodes[parent].children.push(idx);
+            }
+            ancestors.push(idx);
+        }
+        Ok(tree)
+    }
+}
+
+impl FdtTree {
+    fn node(&self, idx: usize) -> FdtNode<'_> {
+        FdtNode { tree: self, idx }
+    }
+
+    /// Returns the root node.
+    pub fn root(&self) -> FdtNode<'_> {
+        self.node(0)
+    }
+
+    /// Returns all nodes in depth-first order.
+    pub fn nodes(&self) -> impl Iterator<Item = FdtNode<'_>> {
+        (0..self.nodes.len()).map(|idx| self.node(idx))
+    }
+
+    /// Finds a node by its full path, e.g. `/soc/serial@10000000`.
+    ///
+    /// A path component without a unit address (`/soc/serial`) matches
+    /// the first node of that name with any unit address.
+    pub fn find_node(&self, path: &str) -> Option<FdtNode<'_>> {
+        let path = path.strip_prefix('/')?;
+        let mut node = self.root();
+        for comp in path.split('/').filter(|c| !c.is_empty()) {
+            node = node.child(comp)?;
+        }
+        Some(node)
+    }
+
+    /// Finds the first node compatible with `compat`.
+    pub fn find_compatible(&self, compat: &str) -> Option<FdtNode<'_>> {
+        self.nodes().find(|node| node.is_compatible(compat))
+    }
+
+    /// Finds all nodes compatible with `compat`.
+    pub fn find_all_compatible<'a>(
+        &'a self, compat: &'a str
+    ) -> impl Iterator<Item = FdtNode<'a>> + 'a {
+        self.nodes().filter(move |node| node.is_compatible(compat))
+    }
+
+    /// Finds the node referred by `phandle`.
+    pub fn find_phandle(&self, phandle: u32) -> Option<FdtNode<'_>> {
+        self.phandles.get(&phandle).map(|&idx| self.node(idx))
+    }
+}
+
+impl<'a> FdtNode<'a> {
+    fn data(&self) -> &'a NodeData {
+        &self.tree.nodes[self.idx]
+    }
+
+    /// Returns the node name, including the unit address.
+    pub fn name(&self) -> &'a str {
+        &self.data().name
+    }
+
+    /// Returns the full path of the node.
+    pub fn path(&self) -> String {
+        match self.parent() {
+            None => "/".to_owned(),
+            Some(parent) => {
+                let mut path = parent.path();
+                if !path.ends_with('/') {
+                    path.push('/');
+                }
+                path.push_str(self.name());
+                path
+            }
+        }
+    }
+
+    /// Returns the parent node, or `None` for the root.
+    pub fn parent(&self) -> Option<FdtNode<'a>> {
+        self.data().parent.map(|idx| self.tree.node(idx))
+    }
+
+    /// Returns the direct children of the node.
+    pub fn children(&self) -> impl Iterator<Item = FdtNode<'a>> + 'a {
+        let tree = self.tree;
+        self.data().children.iter().map(move |&idx| tree.node(idx))
+    }
+
+    /// Finds a direct child by name, with or without the unit address.
+    pub fn child(&self, name: &str) -> Option<FdtNode<'a>> {
+        let with_unit = name.contains('@');
+        self.children().find(|child| {
+            let child_name = child.name();
+            child_name == name
+                || (!with_unit && child_name.split('@').next() == Some(name))
+        })
+    }
+
+    /// Returns all properties of the node.
+    pub fn props(&self) -> impl Iterator<Item = DtProp<'a>> + 'a {
+        self.data().props.iter().map(|(name, value)| DtProp { name, value })
+    }
+
+    /// Returns the raw value of a property.
+    pub fn prop(&self, name: &str) -> Option<&'a [u8]> {
+        self.get(name).ok().map(|p| p.value)
+    }
+
+    /// Returns whether the node has the property, e.g. `interrupt-controller`.
+    pub fn has_prop(&self, name: &str) -> bool {
+        self.prop(name).is_some()
+    }
+
+    fn get(&self, name: &str) -> Result<DtProp<'a>, PropError> {
+        self.props().find(|p| p.name == name).ok_or(PropError::NotFound)
+    }
+
+    /// Reads a property as a single big-endian `u32`.
+    pub fn prop_u32(&self, name: &str) -> Result<u32, PropError> {
+        self.get(name)?.as_u32()
+    }
+
+    /// Reads a property as a single big-endian `u64`.
+    pub fn prop_u64(&self, name: &str) -> Result<u64, PropError> {
+        self.get(name)?.as_u64()
+    }
+
+    /// Reads a property as a NUL-terminated string.
+    pub fn prop_str(&self, name: &str) -> Result<&'a str, PropError> {
+        self.get(name)?.as_str()
+    }
+
+    /// Reads a property as a list of NUL-terminated strings.
+    pub fn prop_str_list(&self, name: &str) -> Result<Vec<&'a str>, PropError> {
+        Ok(self.get(name)?.as_str_list()?.collect())
+    }
+
+    /// Returns the `compatible` strings, or an empty list if it is absent.
+    pub fn compatible(&self) -> Vec<&'a str> {
+        self.prop_str_list("compatible").unwrap_or_default()
+    }
+
+    /// Returns whether `compat` is one of the `compatible` strings.
+    pub fn is_compatible(&self, compat: &str) -> bool {
+        self.compatible().contains(&compat)
+    }
+
+    /// Returns the `phandle` of the node, if it is referred by others.
+    pub fn phandle(&self) -> Option<u32> {
+        self.prop_u32("phandle")
+            .or_else(|_| self.prop_u32("linux,phandle"))
+            .ok()
+    }
+
+    /// Returns `#address-cells` of the node, i.e. for its children.
+    pub fn address_cells(&self) -> usize {
+        self.prop_u32("#address-cells").map_or(DEFAULT_ADDR_CELLS, |v| v as usize)
+    }
+
+    /// Returns `#size-cells` of the node, i.e. for its children.
+    pub fn size_cells(&self) -> usize {
+        self.prop_u32("#size-cells").map_or(DEFAULT_SIZE_CELLS, |v| v as usize)
+    }
+
+    /// Decodes the `reg` property with the cells of the parent node.
+    pub fn reg(&self) -> Result<Vec<FdtReg>, PropError> {
+        let (addr_cells, size_cells) = match self.parent() {
+            Some(parent) => (parent.address_cells(), parent.size_cells()),
+            None => (DEFAULT_ADDR_CELLS, DEFAULT_SIZE_CELLS),
+        };
+        Ok(DtRegs::new(self.get("reg")?.value, addr_cells, size_cells)?.collect())
+    }
+
+    /// Returns the interrupt controller that the interrupts of this node are
+    /// routed to, following `interrupt-parent` up the tree.
+    pub fn interrupt_parent(&self) -> Option<FdtNode<'a>> {
+        let mut node = *self;
+        loop {
+            if let Ok(phandle) = node.prop_u32("interrupt-parent") {
+                return self.tree.find_phandle(phandle);
+            }
+            node = node.parent()?;
+            if node.has_prop("interrupt-controller") {
+                return Some(node);
+            }
+        }
+    }
+
+    /// Decodes the `interrupts` property, each interrupt specifier having
+    /// `#interrupt-cells` of the interrupt parent.
+    pub fn interrupts(&self) -> Result<Vec<Vec<u32>>, PropError> {
+        let val = self.get("interrupts")?.value;
+        let cells = self.interrupt_parent()
+            .ok_or(PropError::NotFound)?
+            .prop_u32("#interrupt-cells")? as usize;
+        if cells == 0 || val.len() % (cells * 4) != 0 {
+            return Err(PropError::InvalidLength);
+        }
+        val.chunks(cells * 4)
+            .map(|spec| (0..cells).map(|i| Ok(spec.read_be_u32(i * 4)?)).collect())
+            .collect()
+    }
+}
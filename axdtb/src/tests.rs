@@ -0,0 +1,230 @@
+// `testdata/qemu-virt.dtb` is compiled from `testdata/qemu-virt.dts` by
+// `dtc -I dts -O dtb -o qemu-virt.dtb qemu-virt.dts`.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::{Chosen, DeviceTree, DeviceTreeError, FdtReg, FdtTree, MemReservation, PropError};
+
+static QEMU_VIRT_DTB: &[u8] = include_bytes!("../testdata/qemu-virt.dtb");
+
+fn qemu_virt() -> FdtTree {
+    let dt = DeviceTree::init(QEMU_VIRT_DTB.as_ptr() as usize).unwrap();
+    dt.build_tree().unwrap()
+}
+
+#[test]
+fn test_paths() {
+    let tree = qemu_virt();
+    let root = tree.root();
+    assert_eq!(root.name(), "");
+    assert_eq!(root.path(), "/");
+    assert!(root.parent().is_none());
+
+    let names: Vec<_> = root.children().map(|n| n.name()).collect();
+    assert_eq!(names, ["chosen", "memory@80000000", "cpus", "soc"]);
+
+    let serial = tree.find_node("/soc/serial@10000000").unwrap();
+    assert_eq!(serial.path(), "/soc/serial@10000000");
+    assert_eq!(serial.parent().unwrap().name(), "soc");
+
+    // without the unit address
+    let intc = tree.find_node("/cpus/cpu/interrupt-controller").unwrap();
+    assert_eq!(intc.path(), "/cpus/cpu@0/interrupt-controller");
+    assert_eq!(tree.find_node("/soc/virtio_mmio").unwrap().name(), "virtio_mmio@10001000");
+
+    assert!(tree.find_node("/").is_some());
+    assert!(tree.find_node("/soc/serial@10000001").is_none());
+    assert!(tree.find_node("soc").is_none());
+}
+
+#[test]
+fn test_compatible() {
+    let tree = qemu_virt();
+    let serial = tree.find_compatible("ns16550a").unwrap();
+    assert_eq!(serial.name(), "serial@10000000");
+
+    let plic = tree.find_compatible("riscv,plic0").unwrap();
+    assert_eq!(plic.compatible(), ["sifive,plic-1.0.0", "riscv,plic0"]);
+    assert!(plic.is_compatible("sifive,plic-1.0.0"));
+
+    let virtio: Vec<_> = tree.find_all_compatible("virtio,mmio").map(|n| n.name()).collect();
+    assert_eq!(virtio, ["virtio_mmio@10001000", "virtio_mmio@10002000"]);
+
+    assert!(tree.find_compatible("virtio").is_none());
+    assert!(tree.find_node("/chosen").unwrap().compatible().is_empty());
+}
+
+#[test]
+fn test_props() {
+    let tree = qemu_virt();
+    let root = tree.root();
+    assert_eq!(root.prop_str("model"), Ok("riscv-virtio,qemu"));
+    assert_eq!(root.address_cells(), 2);
+    assert_eq!(root.size_cells(), 2);
+
+    let chosen = tree.find_node("/chosen").unwrap();
+    assert_eq!(chosen.prop_str("bootargs"), Ok("console=ttyS0 loglevel=debug"));
+
+    let cpus = tree.find_node("/cpus").unwrap();
+    assert_eq!(cpus.prop_u32("timebase-frequency"), Ok(10_000_000));
+    assert_eq!(cpus.prop_u32("clock-frequency"), Err(PropError::NotFound));
+    // not NUL-terminated
+    assert_eq!(cpus.prop_str("timebase-frequency"), Err(PropError::Missing0));
+
+    let memory = tree.find_node("/memory").unwrap();
+    assert_eq!(memory.prop_str("device_type"), Ok("memory"));
+    assert_eq!(memory.prop_u64("reg"), Ok(0x8000_0000));
+
+    let intc = tree.find_node("/cpus/cpu@0/interrupt-controller").unwrap();
+    assert!(intc.has_prop("interrupt-controller"));
+    assert_eq!(intc.prop("interrupt-controller"), Some(&[][..]));
+    assert!(matches!(intc.prop_u64("#interrupt-cells"), Err(PropError::SliceReadError(_))));
+}
+
+#[test]
+fn test_reg() {
+    let tree = qemu_virt();
+    let memory = tree.find_node("/memory@80000000").unwrap();
+    assert_eq!(
+        memory.reg(),
+        Ok(vec![FdtReg { address: 0x8000_0000, size: Some(0x800_0000) }])
+    );
+
+    let serial = tree.find_node("/soc/serial@10000000").unwrap();
+    assert_eq!(
+        serial.reg(),
+        Ok(vec![FdtReg { address: 0x1000_0000, size: Some(0x100) }])
+    );
+
+    // `/cpus` has `#address-cells = <1>` and `#size-cells = <0>`
+    let cpu = tree.find_node("/cpus/cpu@0").unwrap();
+    assert_eq!(cpu.reg(), Ok(vec![FdtReg { address: 0, size: None }]));
+
+    assert_eq!(tree.find_node("/soc").unwrap().reg(), Err(PropError::NotFound));
+}
+
+#[test]
+fn test_phandle_and_interrupts() {
+    let tree = qemu_virt();
+    let plic = tree.find_node("/soc/plic@c000000").unwrap();
+    let phandle = plic.phandle().unwrap();
+    assert_eq!(tree.find_phandle(phandle).unwrap().path(), plic.path());
+    assert!(tree.find_node("/soc").unwrap().phandle().is_none());
+    assert!(tree.find_phandle(0xdead).is_none());
+
+    let serial = tree.find_node("/soc/serial@10000000").unwrap();
+    assert_eq!(serial.interrupt_parent().unwrap().path(), plic.path());
+    assert_eq!(serial.interrupts(), Ok(vec![vec![10]]));
+
+    // inherited from `/soc`
+    let virtio = tree.find_node("/soc/virtio_mmio@10002000").unwrap();
+    assert_eq!(virtio.interrupt_parent().unwrap().path(), plic.path());
+    assert_eq!(virtio.interrupts(), Ok(vec![vec![2]]));
+
+    // the PLIC is wired to the CPU interrupt controller
+    let extended = plic.prop("interrupts-extended").unwrap();
+    let intc = tree.find_node("/cpus/cpu@0/interrupt-controller").unwrap();
+    assert_eq!(extended[..4], intc.phandle().unwrap().to_be_bytes());
+    assert_eq!(plic.interrupts(), Err(PropError::NotFound));
+}
+
+fn init_patched(patch: impl FnOnce(&mut Vec<u8>)) -> Result<DeviceTree, DeviceTreeError> {
+    // leaked, since `DeviceTree` refers to the blob by address
+    let mut blob = QEMU_VIRT_DTB.to_vec();
+    patch(&mut blob);
+    DeviceTree::init(blob.leak().as_ptr() as usize)
+}
+
+#[test]
+fn test_header() {
+    assert!(init_patched(|_| {}).is_ok());
+    assert!(matches!(
+        init_patched(|b| b[0] = 0),
+        Err(DeviceTreeError::BadMagicNumber)
+    ));
+    // a newer version that is backwards compatible
+    assert!(init_patched(|b| b[20..24].copy_from_slice(&18u32.to_be_bytes())).is_ok());
+    assert!(matches!(
+        init_patched(|b| b[24..28].copy_from_slice(&18u32.to_be_bytes())),
+        Err(DeviceTreeError::VersionNotSupported)
+    ));
+    assert!(matches!(
+        init_patched(|b| b[20..24].copy_from_slice(&16u32.to_be_bytes())),
+        Err(DeviceTreeError::VersionNotSupported)
+    ));
+    // blocks out of `totalsize`
+    assert!(matches!(
+        init_patched(|b| b[4..8].copy_from_slice(&0x100u32.to_be_bytes())),
+        Err(DeviceTreeError::SizeMismatch)
+    ));
+    // the root node is not closed
+    assert!(matches!(
+        init_patched(|b| {
+            let end = u32::from_be_bytes(b[8..12].try_into().unwrap()) as usize
+                + u32::from_be_bytes(b[36..40].try_into().unwrap()) as usize;
+            b[end - 8..end - 4].copy_from_slice(&4u32.to_be_bytes()); // NOP
+        }),
+        Err(DeviceTreeError::ParseError(_))
+    ));
+}
+
+#[test]
+fn test_walk() {
+    let dt = DeviceTree::init(QEMU_VIRT_DTB.as_ptr() as usize).unwrap();
+    let nodes: Vec<_> = dt.nodes().map(|n| (n.depth(), n.name())).collect();
+    assert_eq!(nodes, [
+        (0, ""),
+        (1, "chosen"),
+        (1, "memory@80000000"),
+        (1, "cpus"),
+        (2, "cpu@0"),
+        (3, "interrupt-controller"),
+        (1, "soc"),
+        (2, "serial@10000000"),
+        (2, "virtio_mmio@10001000"),
+        (2, "virtio_mmio@10002000"),
+        (2, "plic@c000000"),
+    ]);
+
+    let serial = dt.nodes().find(|n| n.is_compatible("ns16550a")).unwrap();
+    let names: Vec<_> = serial.props().map(|p| p.name).collect();
+    assert_eq!(names, ["interrupts", "interrupt-parent", "clock-frequency", "reg", "compatible"]);
+    assert_eq!(serial.prop("clock-frequency").unwrap().as_u32(), Ok(0x384000));
+    assert_eq!(serial.prop("compatible").unwrap().as_str(), Ok("ns16550a"));
+    assert!(serial.prop("status").is_none());
+    let reg: Vec<_> = serial.reg().unwrap().collect();
+    assert_eq!(reg, [FdtReg { address: 0x1000_0000, size: Some(0x100) }]);
+
+    let cpu = dt.nodes().find(|n| n.name() == "cpu@0").unwrap();
+    let reg: Vec<_> = cpu.reg().unwrap().collect();
+    assert_eq!(reg, [FdtReg { address: 0, size: None }]);
+
+    let plic = dt.nodes().find(|n| n.name() == "plic@c000000").unwrap();
+    let compat: Vec<_> = plic.prop("compatible").unwrap().as_str_list().unwrap().collect();
+    assert_eq!(compat, ["sifive,plic-1.0.0", "riscv,plic0"]);
+    assert!(plic.prop("interrupt-controller").unwrap().as_str_list().unwrap().next().is_none());
+    assert!(plic.prop("riscv,ndev").unwrap().as_str_list().is_err());
+    assert_eq!(plic.prop("riscv,ndev").unwrap().as_int(), Ok(0x35));
+    assert_eq!(plic.prop("interrupts-extended").unwrap().as_int(), Err(PropError::InvalidLength));
+}
+
+#[test]
+fn test_memory_reservations() {
+    let dt = DeviceTree::init(QEMU_VIRT_DTB.as_ptr() as usize).unwrap();
+    let rsv: Vec<_> = dt.memory_reservations().collect();
+    assert_eq!(rsv, [
+        MemReservation { address: 0x8000_0000, size: 0x20_0000 },
+        MemReservation { address: 0x87f0_0000, size: 0x10_0000 },
+    ]);
+}
+
+#[test]
+fn test_chosen() {
+    let dt = DeviceTree::init(QEMU_VIRT_DTB.as_ptr() as usize).unwrap();
+    assert_eq!(dt.chosen(), Some(Chosen {
+        bootargs: Some("console=ttyS0 loglevel=debug"),
+        stdout_path: Some("/soc/serial@10000000"),
+        initrd: Some(0x8800_0000..0x8810_0000),
+    }));
+}
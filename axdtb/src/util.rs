@@ -5,7 +5,7 @@ pub fn align(val: usize, to: usize) -> usize {
     val + (to - (val % to)) % to
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
 pub enum SliceReadError {
     UnexpectedEndOfInput,
 }
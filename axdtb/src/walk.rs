@@ -0,0 +1,381 @@
+//! Zero-copy walking over the blob.
+//!
+//! Nothing here allocates, so it can be used before the heap is ready.
+//! Names and values are borrowed from the blob.
+
+use core::ops::Range;
+use core::str;
+use crate::util::{align, SliceRead, SliceReadError};
+use crate::{DeviceTree, DeviceTreeError, DeviceTreeResult, PropError};
+use crate::{OF_DT_BEGIN_NODE, OF_DT_END, OF_DT_END_NODE, OF_DT_NOP, OF_DT_PROP};
+
+// Default values of `#address-cells` and `#size-cells` by the spec.
+pub(crate) const DEFAULT_ADDR_CELLS: usize = 2;
+pub(crate) const DEFAULT_SIZE_CELLS: usize = 1;
+
+/// Maximum depth of nested nodes.
+const MAX_DEPTH: usize = 16;
+
+#[derive(Clone, Copy)]
+struct Blob<'a> {
+    buf: &'a [u8],
+    off_strings: usize,
+}
+
+enum Token<'a> {
+    BeginNode(&'a str),
+    EndNode,
+    Prop(DtProp<'a>),
+    End,
+}
+
+/// A property of a node.
+#[derive(Debug, Clone, Copy)]
+pub struct DtProp<'a> {
+    pub name: &'a str,
+    pub value: &'a [u8],
+}
+
+/// Properties of a node, in the order they appear in the blob.
+pub struct DtProps<'a> {
+    blob: Blob<'a>,
+    pos: usize,
+}
+
+/// A node visited by [`DtNodes`].
+#[derive(Clone, Copy)]
+pub struct DtNode<'a> {
+    blob: Blob<'a>,
+    name: &'a str,
+    depth: usize,
+    /// `#address-cells` and `#size-cells` of the parent node.
+    parent_cells: (usize, usize),
+    props_pos: usize,
+}
+
+/// All nodes of the blob in depth-first order.
+pub struct DtNodes<'a> {
+    blob: Blob<'a>,
+    pos: usize,
+    depth: usize,
+    cells: [(usize, usize); MAX_DEPTH + 1],
+}
+
+/// An entry of the `reg` property: a region in the address space of the
+/// parent node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FdtReg {
+    pub address: u64,
+    /// `None` if the parent has `#size-cells = <0>`.
+    pub size: Option<u64>,
+}
+
+/// Entries of a `reg` property.
+pub struct DtRegs<'a> {
+    value: &'a [u8],
+    addr_cells: usize,
+    size_cells: usize,
+}
+
+/// An entry of the memory reservation block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemReservation {
+    pub address: u64,
+    pub size: u64,
+}
+
+/// Boot parameters in the `/chosen` node.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Chosen<'a> {
+    /// The kernel command line.
+    pub bootargs: Option<&'a str>,
+    /// Path or alias of the console device, optionally followed by
+    /// `:<options>`.
+    pub stdout_path: Option<&'a str>,
+    /// Physical address range of the initial ramdisk.
+    pub initrd: Option<Range<u64>>,
+}
+
+impl<'a> Blob<'a> {
+    /// Reads the token at `pos`, skipping NOPs, and returns it with the
+    /// position of the next one.
+    fn token(&self, mut pos: usize) -> DeviceTreeResult<(Token<'a>, usize)> {
+        loop {
+            let tag = self.buf.read_be_u32(pos)?;
+            pos += 4;
+            match tag {
+                OF_DT_BEGIN_NODE => {
+                    let name = bstring0(self.buf, pos)?;
+                    let next = align(pos + name.len() + 1, 4);
+                    return Ok((Token::BeginNode(str::from_utf8(name)?), next));
+                }
+                OF_DT_END_NODE => return Ok((Token::EndNode, pos)),
+                OF_DT_PROP => {
+                    let val_size = self.buf.read_be_u32(pos)? as usize;
+                    let name_offset = self.buf.read_be_u32(pos+4)? as usize;
+                    let val_end = pos + 8 + val_size;
+                    let value = self.buf.get(pos+8..val_end)
+                        .ok_or(SliceReadError::UnexpectedEndOfInput)?;
+                    let name = bstring0(self.buf, self.off_strings + name_offset)?;
+                    let prop = DtProp { name: str::from_utf8(name)?, value };
+                    return Ok((Token::Prop(prop), align(val_end, 4)));
+                }
+                OF_DT_NOP => {}
+                OF_DT_END => return Ok((Token::End, pos)),
+                _ => return Err(DeviceTreeError::ParseError(pos - 4)),
+            }
+        }
+    }
+}
+
+impl DeviceTree {
+    fn blob(&self) -> Blob<'_> {
+        Blob { buf: self.as_slice(), off_strings: self.off_strings }
+    }
+
+    /// Checks the structure block once, so that walking it later never
+    /// fails.
+    pub(crate) fn validate(&self) -> DeviceTreeResult<()> {
+        let blob = self.blob();
+        let mut pos = self.off_struct;
+        let mut depth = 0;
+        let mut has_root = false;
+        loop {
+            let (token, next) = blob.token(pos)?;
+            match token {
+                Token::BeginNode(_) if depth == 0 && has_root => {
+                    return Err(DeviceTreeError::ParseError(pos));
+                }
+                Token::BeginNode(_) if depth == MAX_DEPTH => {
+                    return Err(DeviceTreeError::ParseError(pos));
+                }
+                Token::BeginNode(_) => {
+                    depth += 1;
+                    has_root = true;
+                }
+                Token::EndNode | Token::Prop(_) if depth == 0 => {
+                    return Err(DeviceTreeError::ParseError(pos));
+                }
+                Token::EndNode => depth -= 1,
+                Token::Prop(_) => {}
+                Token::End if depth == 0 && has_root => return Ok(()),
+                Token::End => return Err(DeviceTreeError::ParseError(pos)),
+            }
+            pos = next;
+        }
+    }
+
+    /// Returns all nodes in depth-first order.
+    pub fn nodes(&self) -> DtNodes<'_> {
+        DtNodes {
+            blob: self.blob(),
+            pos: self.off_struct,
+            depth: 0,
+            cells: [(DEFAULT_ADDR_CELLS, DEFAULT_SIZE_CELLS); MAX_DEPTH + 1],
+        }
+    }
+
+    /// Returns the entries of the memory reservation block.
+    pub fn memory_reservations(&self) -> impl Iterator<Item = MemReservation> + '_ {
+        let buf = self.as_slice();
+        let mut pos = self.off_mem_rsvmap;
+        core::iter::from_fn(move || {
+            let address = buf.read_be_u64(pos).ok()?;
+            let size = buf.read_be_u64(pos + 8).ok()?;
+            pos += 16;
+            // the block is terminated by an entry of zeros
+            (address != 0 || size != 0).then_some(MemReservation { address, size })
+        })
+    }
+
+    /// Returns the boot parameters in `/chosen`, if the node exists.
+    pub fn chosen(&self) -> Option<Chosen<'_>> {
+        let node = self.nodes().find(|n| n.depth() == 1 && n.name() == "chosen")?;
+        let str_prop = |name| node.prop(name).and_then(|p| p.as_str().ok());
+        let int_prop = |name| node.prop(name).and_then(|p| p.as_int().ok());
+        let initrd = match (int_prop("linux,initrd-start"), int_prop("linux,initrd-end")) {
+            (Some(start), Some(end)) => Some(start..end),
+            _ => None,
+        };
+        Some(Chosen {
+            bootargs: str_prop("bootargs"),
+            stdout_path: str_prop("stdout-path"),
+            initrd,
+        })
+    }
+}
+
+impl<'a> DtProp<'a> {
+    /// Reads the value as a single big-endian `u32`.
+    pub fn as_u32(&self) -> Result<u32, PropError> {
+        Ok(self.value.read_be_u32(0)?)
+    }
+
+    /// Reads the value as a single big-endian `u64`.
+    pub fn as_u64(&self) -> Result<u64, PropError> {
+        Ok(self.value.read_be_u64(0)?)
+    }
+
+    /// Reads the value as an integer of one or two cells.
+    pub fn as_int(&self) -> Result<u64, PropError> {
+        match self.value.len() {
+            4 => self.as_u32().map(u64::from),
+            8 => self.as_u64(),
+            _ => Err(PropError::InvalidLength),
+        }
+    }
+
+    /// Reads the value as a NUL-terminated string.
+    pub fn as_str(&self) -> Result<&'a str, PropError> {
+        match self.value.split_last() {
+            Some((0, s)) => Ok(str::from_utf8(s)?),
+            _ => Err(PropError::Missing0),
+        }
+    }
+
+    /// Reads the value as a list of NUL-terminated strings.
+    pub fn as_str_list(&self) -> Result<impl Iterator<Item = &'a str>, PropError> {
+        if self.value.last().is_some_and(|&b| b != 0) {
+            return Err(PropError::Missing0);
+        }
+        Ok(str::from_utf8(self.value)?.split_terminator('\0'))
+    }
+}
+
+impl<'a> Iterator for DtProps<'a> {
+    type Item = DtProp<'a>;
+
+    fn next(&mut self) -> Option<DtProp<'a>> {
+        match self.blob.token(self.pos).ok()? {
+            (Token::Prop(prop), next) => {
+                self.pos = next;
+                Some(prop)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl<'a> DtNode<'a> {
+    /// Returns the node name, including the unit address.
+    pub fn name(&self) -> &'a str {
+        self.name
+    }
+
+    /// Returns the depth of the node, 0 for the root.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Returns the properties of the node.
+    pub fn props(&self) -> DtProps<'a> {
+        DtProps { blob: self.blob, pos: self.props_pos }
+    }
+
+    /// Finds a property by name.
+    pub fn prop(&self, name: &str) -> Option<DtProp<'a>> {
+        self.props().find(|p| p.name == name)
+    }
+
+    /// Returns `#address-cells` of the node, i.e. for its children.
+    pub fn address_cells(&self) -> usize {
+        self.prop("#address-cells")
+            .and_then(|p| p.as_u32().ok())
+            .map_or(DEFAULT_ADDR_CELLS, |v| v as usize)
+    }
+
+    /// Returns `#size-cells` of the node, i.e. for its children.
+    pub fn size_cells(&self) -> usize {
+        self.prop("#size-cells")
+            .and_then(|p| p.as_u32().ok())
+            .map_or(DEFAULT_SIZE_CELLS, |v| v as usize)
+    }
+
+    /// Returns whether `compat` is one of the `compatible` strings.
+    pub fn is_compatible(&self, compat: &str) -> bool {
+        self.prop("compatible")
+            .and_then(|p| p.as_str_list().ok())
+            .is_some_and(|mut list| list.any(|s| s == compat))
+    }
+
+    /// Decodes the `reg` property with the cells of the parent node.
+    pub fn reg(&self) -> Result<DtRegs<'a>, PropError> {
+        let prop = self.prop("reg").ok_or(PropError::NotFound)?;
+        DtRegs::new(prop.value, self.parent_cells.0, self.parent_cells.1)
+    }
+}
+
+impl<'a> Iterator for DtNodes<'a> {
+    type Item = DtNode<'a>;
+
+    fn next(&mut self) -> Option<DtNode<'a>> {
+        loop {
+            let (token, next) = self.blob.token(self.pos).ok()?;
+            self.pos = next;
+            match token {
+                Token::BeginNode(name) => {
+                    let node = DtNode {
+                        blob: self.blob,
+                        name,
+                        depth: self.depth,
+                        parent_cells: self.cells[self.depth],
+                        props_pos: next,
+                    };
+                    self.depth += 1;
+                    self.cells[self.depth] = (node.address_cells(), node.size_cells());
+                    return Some(node);
+                }
+                Token::EndNode => self.depth -= 1,
+                Token::Prop(_) => {}
+                Token::End => return None,
+            }
+        }
+    }
+}
+
+impl<'a> DtRegs<'a> {
+    pub(crate) fn new(
+        value: &'a [u8], addr_cells: usize, size_cells: usize
+    ) -> Result<Self, PropError> {
+        let entry_len = (addr_cells + size_cells) * 4;
+        if addr_cells == 0 || addr_cells > 2 || size_cells > 2
+            || !value.len().is_multiple_of(entry_len) {
+            return Err(PropError::InvalidLength);
+        }
+        Ok(Self { value, addr_cells, size_cells })
+    }
+}
+
+impl Iterator for DtRegs<'_> {
+    type Item = FdtReg;
+
+    fn next(&mut self) -> Option<FdtReg> {
+        if self.value.is_empty() {
+            return None;
+        }
+        let address = read_cells(self.value, 0, self.addr_cells).ok()?;
+        let size = match self.size_cells {
+            0 => None,
+            n => Some(read_cells(self.value, self.addr_cells * 4, n).ok()?),
+        };
+        self.value = &self.value[(self.addr_cells + self.size_cells) * 4..];
+        Some(FdtReg { address, size })
+    }
+}
+
+/// Same as [`SliceRead::read_bstring0`], but borrows from the blob rather than
+/// the reference to it.
+fn bstring0(buf: &[u8], pos: usize) -> Result<&[u8], SliceReadError> {
+    let rest = buf.get(pos..).ok_or(SliceReadError::UnexpectedEndOfInput)?;
+    let len = rest.iter().position(|&b| b == 0)
+        .ok_or(SliceReadError::UnexpectedEndOfInput)?;
+    Ok(&rest[..len])
+}
+
+fn read_cells(buf: &[u8], pos: usize, cells: usize) -> Result<u64, PropError> {
+    match cells {
+        1 => Ok(buf.read_be_u32(pos)? as u64),
+        2 => Ok(buf.read_be_u64(pos)?),
+        _ => Err(PropError::InvalidLength),
+    }
+}
@@ -0,0 +1,25 @@
+//! High-level filesystem operations by path.
+
+use axfs_vfs::{VfsNodeAttr, VfsResult};
+
+use crate::root;
+
+/// Returns the attributes of the file or directory at `path`.
+pub fn metadata(path: &str) -> VfsResult<VfsNodeAttr> {
+    root::lookup(path)?.get_attr()
+}
+
+/// Creates a new, empty directory at `path`.
+pub fn create_dir(path: &str) -> VfsResult {
+    root::create_dir(path)
+}
+
+/// Removes an empty directory.
+pub fn remove_dir(path: &str) -> VfsResult {
+    root::remove_dir(path)
+}
+
+/// Removes a file.
+pub fn remove_file(path: &str) -> VfsResult {
+    root::remove_file(path)
+}
@@ -0,0 +1,124 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+use axdriver::{AxBlockDevice, DevError};
+use axfs_vfs::{VfsError, VfsResult};
+
+/// A disk device with byte-granularity access on top of a block device.
+pub struct Disk {
+    dev: AxBlockDevice,
+    block_size: usize,
+    block_buf: Vec<u8>,
+}
+
+impl Disk {
+    /// Create a new disk.
+    pub fn new(dev: AxBlockDevice) -> Self {
+        let block_size = dev.block_size();
+        Self {
+            dev,
+            block_size,
+            block_buf: vec![0; block_size],
+        }
+    }
+
+    /// Get the size of the disk.
+    pub fn size(&self) -> u64 {
+        self.dev.num_blocks() * self.block_size as u64
+    }
+
+    /// Read data from the disk at the given byte offset.
+    pub fn read_at(&mut self, pos: u64, buf: &mut [u8]) -> VfsResult {
+        if pos + buf.len() as u64 > self.size() {
+            return Err(VfsError::UnexpectedEof);
+        }
+        let bs = self.block_size as u64;
+        let mut done = 0;
+        while done < buf.len() {
+            let cur = pos + done as u64;
+            let (block_id, offset) = (cur / bs, (cur % bs) as usize);
+            let remain = buf.len() - done;
+            if offset == 0 && remain >= self.block_size {
+                // read whole blocks directly into `buf`
+                let len = remain - remain % self.block_size;
+                self.dev
+                    .read_block(block_id, &mut buf[done..done + len])
+                    .map_err(as_vfs_err)?;
+                done += len;
+            } else {
+                let len = remain.min(self.block_size - offset);
+                self.dev
+                    .read_block(block_id, &mut self.block_buf)
+                    .map_err(as_vfs_err)?;
+                buf[done..done + len].copy_from_slice(&self.block_buf[offset..offset + len]);
+                done += len;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write data to the disk at the given byte offset.
+    pub fn write_at(&mut self, pos: u64, buf: &[u8]) -> VfsResult {
+        if pos + buf.len() as u64 > self.size() {
+            return Err(VfsError::StorageFull);
+        }
+        let bs = self.block_size as u64;
+        let mut done = 0;
+        while done < buf.len() {
+            let cur = pos + done as u64;
+            let (block_id, offset) = (cur / bs, (cur % bs) as usize);
+            let remain = buf.len() - done;
+            if offset == 0 && remain >= self.block_size {
+                let len = remain - remain % self.block_size;
+                self.dev
+                    .write_block(block_id, &buf[done..done + len])
+                    .map_err(as_vfs_err)?;
+                done += len;
+            } else {
+                // read-modify-write a partial block
+                let len = remain.min(self.block_size - offset);
+                self.dev
+                    .read_block(block_id, &mut self.block_buf)
+                    .map_err(as_vfs_err)?;
+                self.block_buf[offset..offset + len].copy_from_slice(&buf[done..done + len]);
+                self.dev
+                    .write_block(block_id, &self.block_buf)
+                    .map_err(as_vfs_err)?;
+                done += len;
+            }
+        }
+        Ok(())
+    }
+
+    /// Fill `len` bytes of the disk with zeros, starting at the given offset.
+    pub fn zero_at(&mut self, pos: u64, len: usize) -> VfsResult {
+        const ZEROS: [u8; 512] = [0; 512];
+        let mut done = 0;
+        while done < len {
+            let n = (len - done).min(ZEROS.len());
+            self.write_at(pos + done as u64, &ZEROS[..n])?;
+            done += n;
+        }
+        Ok(())
+    }
+
+    /// Flush all pending writes to the device.
+    pub fn flush(&mut self) -> VfsResult {
+        self.dev.flush().map_err(as_vfs_err)
+    }
+
+    /// Returns the underlying block device.
+    #[cfg(test)]
+    pub fn into_inner(self) -> AxBlockDevice {
+        self.dev
+    }
+}
+
+const fn as_vfs_err(e: DevError) -> VfsError {
+    match e {
+        DevError::InvalidParam => VfsError::InvalidInput,
+        DevError::NoMemory => VfsError::StorageFull,
+        DevError::Unsupported => VfsError::Unsupported,
+        _ => VfsError::Io,
+    }
+}
@@ -0,0 +1,251 @@
+//! Low-level file and directory operations on opened nodes.
+
+use axfs_vfs::{VfsDirEntry, VfsError, VfsNodeAttr, VfsNodeRef, VfsResult};
+
+use crate::root;
+
+/// Enumeration of possible methods to seek within a file.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SeekFrom {
+    /// Sets the offset to the provided number of bytes.
+    Start(u64),
+    /// Sets the offset to the size of this file plus the specified number of
+    /// bytes.
+    End(i64),
+    /// Sets the offset to the current position plus the specified number of
+    /// bytes.
+    Current(i64),
+}
+
+/// Options and flags which can be used to configure how a file is opened.
+///
+/// The rules follow those of `std::fs::OpenOptions`.
+#[derive(Debug, Clone, Default)]
+pub struct OpenOptions {
+    read: bool,
+    write: bool,
+    append: bool,
+    truncate: bool,
+    create: bool,
+    create_new: bool,
+}
+
+impl OpenOptions {
+    /// Creates a blank new set of options ready for configuration.
+    pub const fn new() -> Self {
+        Self {
+            read: false,
+            write: false,
+            append: false,
+            truncate: false,
+            create: false,
+            create_new: false,
+        }
+    }
+
+    /// Sets the option for read access.
+    pub fn read(&mut self, read: bool) {
+        self.read = read;
+    }
+
+    /// Sets the option for write access.
+    pub fn write(&mut self, write: bool) {
+        self.write = write;
+    }
+
+    /// Sets the option for the append mode.
+    pub fn append(&mut self, append: bool) {
+        self.append = append;
+    }
+
+    /// Sets the option for truncating a previous file.
+    pub fn truncate(&mut self, truncate: bool) {
+        self.truncate = truncate;
+    }
+
+    /// Sets the option to create a new file, or open it if it already exists.
+    pub fn create(&mut self, create: bool) {
+        self.create = create;
+    }
+
+    /// Sets the option to create a new file, failing if it already exists.
+    pub fn create_new(&mut self, create_new: bool) {
+        self.create_new = create_new;
+    }
+
+    const fn is_valid(&self) -> bool {
+        if !self.read && !self.write && !self.append {
+            return false;
+        }
+        match (self.write, self.append) {
+            (true, false) => {}
+            (false, false) => {
+                if self.truncate || self.create || self.create_new {
+                    return false;
+                }
+            }
+            (_, true) => {
+                if self.truncate && !self.create_new {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+}
+
+/// An opened file.
+pub struct File {
+    node: VfsNodeRef,
+    readable: bool,
+    writable: bool,
+    append: bool,
+    offset: u64,
+}
+
+impl File {
+    /// Opens a file at `path` with the given options.
+    pub fn open(path: &str, opts: &OpenOptions) -> VfsResult<Self> {
+        debug!("open file: {} {:?}", path, opts);
+        if !opts.is_valid() {
+            return Err(VfsError::InvalidInput);
+        }
+
+        let node = match root::lookup(path) {
+            Ok(_) if opts.create_new => return Err(VfsError::AlreadyExists),
+            Ok(node) => node,
+            Err(VfsError::NotFound) if opts.create || opts.create_new => {
+                root::create_file(path)?
+            }
+            Err(e) => return Err(e),
+        };
+        if node.get_attr()?.is_dir() {
+            return Err(VfsError::IsADirectory);
+        }
+
+        node.open()?;
+        if opts.truncate {
+            node.truncate(0)?;
+        }
+        Ok(Self {
+            node,
+            readable: opts.read,
+            writable: opts.write || opts.append,
+            append: opts.append,
+            offset: 0,
+        })
+    }
+
+    /// Truncates the file to the specified size.
+    pub fn truncate(&self, size: u64) -> VfsResult {
+        if !self.writable {
+            return Err(VfsError::PermissionDenied);
+        }
+        self.node.truncate(size)
+    }
+
+    /// Reads the file at the current position, and advances the position by
+    /// the number of bytes read.
+    pub fn read(&mut self, buf: &mut [u8]) -> VfsResult<usize> {
+        let n = self.read_at(self.offset, buf)?;
+        self.offset += n as u64;
+        Ok(n)
+    }
+
+    /// Reads the file at the given position, the current position is not
+    /// changed.
+    pub fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        if !self.readable {
+            return Err(VfsError::PermissionDenied);
+        }
+        self.node.read_at(offset, buf)
+    }
+
+    /// Writes the file at the current position (or the end of the file in the
+    /// append mode), and advances the position by the number of bytes
+    /// written.
+    pub fn write(&mut self, buf: &[u8]) -> VfsResult<usize> {
+        if self.append {
+            self.offset = self.get_attr()?.size();
+        }
+        let n = self.write_at(self.offset, buf)?;
+        self.offset += n as u64;
+        Ok(n)
+    }
+
+    /// Writes the file at the given position, the current position is not
+    /// changed.
+    pub fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        if !self.writable {
+            return Err(VfsError::PermissionDenied);
+        }
+        self.node.write_at(offset, buf)
+    }
+
+    /// Flushes the file, writes all buffered data to the underlying device.
+    pub fn flush(&self) -> VfsResult {
+        if !self.writable {
+            return Err(VfsError::PermissionDenied);
+        }
+        self.node.fsync()
+    }
+
+    /// Sets the position of the file, and returns the new position.
+    pub fn seek(&mut self, pos: SeekFrom) -> VfsResult<u64> {
+        let new_offset = match pos {
+            SeekFrom::Start(pos) => Some(pos),
+            SeekFrom::Current(off) => self.offset.checked_add_signed(off),
+            SeekFrom::End(off) => self.get_attr()?.size().checked_add_signed(off),
+        }
+        .ok_or(VfsError::InvalidInput)?;
+        self.offset = new_offset;
+        Ok(new_offset)
+    }
+
+    /// Gets the file attributes.
+    pub fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        self.node.get_attr()
+    }
+}
+
+impl Drop for File {
+    fn drop(&mut self) {
+        self.node.release().ok();
+    }
+}
+
+/// An opened directory.
+pub struct Directory {
+    node: VfsNodeRef,
+    entry_idx: usize,
+}
+
+impl Directory {
+    /// Opens a directory at `path`.
+    pub fn open_dir(path: &str) -> VfsResult<Self> {
+        debug!("open dir: {}", path);
+        let node = root::lookup(path)?;
+        if !node.get_attr()?.is_dir() {
+            return Err(VfsError::NotADirectory);
+        }
+        node.open()?;
+        Ok(Self { node, entry_idx: 0 })
+    }
+
+    /// Reads directory entries starts from the current position into the
+    /// given buffer, returns the number of entries read.
+    ///
+    /// After the read, the cursor will be advanced by the number of entries
+    /// read.
+    pub fn read_dir(&mut self, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+        let n = self.node.read_dir(self.entry_idx, dirents)?;
+        self.entry_idx += n;
+        Ok(n)
+    }
+}
+
+impl Drop for Directory {
+    fn drop(&mut self) {
+        self.node.release().ok();
+    }
+}
@@ -0,0 +1,320 @@
+//! On-disk directory entries, including VFAT long file names.
+
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use axfs_vfs::{VfsError, VfsResult};
+
+pub const DIR_ENTRY_SIZE: usize = 32;
+
+pub const ATTR_VOLUME_ID: u8 = 0x08;
+pub const ATTR_DIRECTORY: u8 = 0x10;
+pub const ATTR_ARCHIVE: u8 = 0x20;
+const ATTR_LFN: u8 = 0x0F;
+
+const SLOT_END: u8 = 0x00;
+const SLOT_DELETED: u8 = 0xE5;
+/// A leading `0xE5` of a name is stored as `0x05` on disk.
+const SLOT_KANJI_E5: u8 = 0x05;
+
+const CASE_LOWER_BASE: u8 = 0x08;
+const CASE_LOWER_EXT: u8 = 0x10;
+
+const LFN_LAST: u8 = 0x40;
+const LFN_ORD_MASK: u8 = 0x1F;
+const LFN_CHARS: usize = 13;
+const LFN_MAX_LEN: usize = 255;
+
+/// 1980-01-01, the earliest date FAT can represent.
+const DEFAULT_DATE: u16 = (1 << 5) | 1;
+
+/// Kind of a 32-byte directory slot.
+pub enum SlotKind {
+    /// This slot and all following slots are free.
+    End,
+    /// A deleted entry.
+    Deleted,
+    /// Part of a long file name.
+    LongName,
+    /// A short (8.3) entry, which describes a file or a directory.
+    Short,
+}
+
+impl SlotKind {
+    pub fn of(raw: &[u8]) -> Self {
+        match raw[0] {
+            SLOT_END => Self::End,
+            SLOT_DELETED => Self::Deleted,
+            _ if raw[11] & 0x3F == ATTR_LFN => Self::LongName,
+            _ => Self::Short,
+        }
+    }
+}
+
+/// Marks the slot as deleted.
+pub fn mark_deleted(raw: &mut [u8; DIR_ENTRY_SIZE]) {
+    raw[0] = SLOT_DELETED;
+}
+
+/// A short (8.3) directory entry.
+#[derive(Clone)]
+pub struct ShortEntry([u8; DIR_ENTRY_SIZE]);
+
+impl ShortEntry {
+    pub fn new(name: [u8; 11], case: u8, attr: u8, cluster: u32) -> Self {
+        let mut raw = [0; DIR_ENTRY_SIZE];
+        raw[..11].copy_from_slice(&name);
+        raw[11] = attr;
+        raw[12] = case;
+        for off in [16, 18, 24] {
+            raw[off..off + 2].copy_from_slice(&DEFAULT_DATE.to_le_bytes());
+        }
+        let mut ent = Self(raw);
+        ent.set_cluster(cluster);
+        ent
+    }
+
+    pub fn from_bytes(raw: [u8; DIR_ENTRY_SIZE]) -> Self {
+        Self(raw)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; DIR_ENTRY_SIZE] {
+        &self.0
+    }
+
+    pub fn short_name(&self) -> &[u8] {
+        &self.0[..11]
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.0[11] & ATTR_DIRECTORY != 0
+    }
+
+    pub fn is_volume_label(&self) -> bool {
+        self.0[11] & ATTR_VOLUME_ID != 0
+    }
+
+    pub fn is_dot(&self) -> bool {
+        self.0[0] == b'.'
+    }
+
+    pub fn cluster(&self) -> u32 {
+        let hi = u16::from_le_bytes([self.0[20], self.0[21]]) as u32;
+        let lo = u16::from_le_bytes([self.0[26], self.0[27]]) as u32;
+        (hi << 16) | lo
+    }
+
+    pub fn set_cluster(&mut self, cluster: u32) {
+        self.0[20..22].copy_from_slice(&((cluster >> 16) as u16).to_le_bytes());
+        self.0[26..28].copy_from_slice(&(cluster as u16).to_le_bytes());
+    }
+
+    pub fn size(&self) -> u32 {
+        u32::from_le_bytes(self.0[28..32].try_into().unwrap())
+    }
+
+    pub fn set_size(&mut self, size: u32) {
+        self.0[28..32].copy_from_slice(&size.to_le_bytes());
+    }
+
+    /// The checksum of the short name, stored in each of its long name slots.
+    pub fn checksum(&self) -> u8 {
+        self.0[..11]
+            .iter()
+            .fold(0u8, |sum, &c| sum.rotate_right(1).wrapping_add(c))
+    }
+
+    /// Returns the name in the `base.ext` form, with the lowercase flags of
+    /// Windows NT applied.
+    pub fn name(&self) -> String {
+        let mut name = String::new();
+        let lower_base = self.0[12] & CASE_LOWER_BASE != 0;
+        let lower_ext = self.0[12] & CASE_LOWER_EXT != 0;
+        for (i, &c) in self.0[..8].iter().enumerate() {
+            let c = if i == 0 && c == SLOT_KANJI_E5 { SLOT_DELETED } else { c };
+            push_short_char(&mut name, c, lower_base);
+        }
+        let name_len = name.trim_end().len();
+        name.truncate(name_len);
+        if self.0[8] != b' ' {
+            name.push('.');
+            for &c in &self.0[8..11] {
+                push_short_char(&mut name, c, lower_ext);
+            }
+            let name_len = name.trim_end().len();
+            name.truncate(name_len);
+        }
+        name
+    }
+}
+
+fn push_short_char(name: &mut String, c: u8, lower: bool) {
+    let c = if lower { c.to_ascii_lowercase() } else { c };
+    // characters of OEM code pages are not supported
+    name.push(if c.is_ascii() { c as char } else { '?' });
+}
+
+/// Collects long name slots that precede a short entry.
+#[derive(Default)]
+pub struct LongNameBuf {
+    chars: Vec<u16>,
+    next_ord: u8,
+    checksum: u8,
+    valid: bool,
+}
+
+impl LongNameBuf {
+    pub fn push(&mut self, raw: &[u8]) {
+        let ord = raw[0] & LFN_ORD_MASK;
+        if raw[0] & LFN_LAST != 0 {
+            self.valid = ord != 0;
+            self.chars = vec![0xFFFF; ord as usize * LFN_CHARS];
+            self.checksum = raw[13];
+        } else if !self.valid || ord == 0 || ord != self.next_ord || raw[13] != self.checksum {
+            self.valid = false;
+        }
+        if !self.valid {
+            return;
+        }
+        let start = (ord as usize - 1) * LFN_CHARS;
+        for (i, off) in LFN_OFFSETS.iter().enumerate() {
+            self.chars[start + i] = u16::from_le_bytes([raw[*off], raw[*off + 1]]);
+        }
+        self.next_ord = ord - 1;
+    }
+
+    /// Returns the long name that belongs to `short`, if any.
+    ///
+    /// The buffer is reset afterwards.
+    pub fn take(&mut self, short: &ShortEntry) -> Option<String> {
+        let valid = self.valid && self.next_ord == 0 && self.checksum == short.checksum();
+        self.valid = false;
+        if !valid {
+            return None;
+        }
+        let len = self.chars.iter().position(|&c| c == 0).unwrap_or(self.chars.len());
+        Some(
+            char::decode_utf16(self.chars[..len].iter().copied())
+                .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+                .collect(),
+        )
+    }
+
+    pub fn reset(&mut self) {
+        self.valid = false;
+    }
+}
+
+/// Byte offsets of the 13 UCS-2 characters in a long name slot.
+const LFN_OFFSETS: [usize; LFN_CHARS] = [1, 3, 5, 7, 9, 14, 16, 18, 20, 22, 24, 28, 30];
+
+/// Builds the long name slots for `name`, in the on-disk order.
+pub fn long_name_slots(name: &str, checksum: u8) -> Vec<[u8; DIR_ENTRY_SIZE]> {
+    let chars: Vec<u16> = name.encode_utf16().collect();
+    let count = chars.len().div_ceil(LFN_CHARS);
+    (1..=count)
+        .rev()
+        .map(|ord| {
+            let mut raw = [0; DIR_ENTRY_SIZE];
+            raw[0] = ord as u8 | if ord == count { LFN_LAST } else { 0 };
+            raw[11] = ATTR_LFN;
+            raw[13] = checksum;
+            let start = (ord - 1) * LFN_CHARS;
+            for (i, off) in LFN_OFFSETS.iter().enumerate() {
+                // terminated with a NUL, then padded with 0xFFFF
+                let c = match (start + i).cmp(&chars.len()) {
+                    core::cmp::Ordering::Less => chars[start + i],
+                    core::cmp::Ordering::Equal => 0,
+                    core::cmp::Ordering::Greater => 0xFFFF,
+                };
+                raw[*off..*off + 2].copy_from_slice(&c.to_le_bytes());
+            }
+            raw
+        })
+        .collect()
+}
+
+/// Checks whether `name` can be used as a file name.
+pub fn check_name(name: &str) -> VfsResult {
+    if name.is_empty()
+        || name == "."
+        || name == ".."
+        || name.encode_utf16().count() > LFN_MAX_LEN
+        || name
+            .chars()
+            .any(|c| (c as u32) < 0x20 || "\"*/:<>?\\|".contains(c))
+    {
+        Err(VfsError::InvalidInput)
+    } else {
+        Ok(())
+    }
+}
+
+fn is_short_char(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || b"!#$%&'()-@^_`{}~".contains(&c)
+}
+
+/// Returns the 8.3 name and the case flags if `name` can be stored in a
+/// short entry alone.
+pub fn to_short_name(name: &str) -> Option<([u8; 11], u8)> {
+    let (base, ext) = match name.rfind('.') {
+        Some(i) => (&name[..i], &name[i + 1..]),
+        None => (name, ""),
+    };
+    if base.is_empty() || base.len() > 8 || ext.len() > 3 {
+        return None;
+    }
+    let mut short = [b' '; 11];
+    let mut case = 0;
+    for (part, range, lower_flag) in [
+        (base, 0..8, CASE_LOWER_BASE),
+        (ext, 8..11, CASE_LOWER_EXT),
+    ] {
+        let bytes = part.as_bytes();
+        if !bytes.iter().all(|&c| is_short_char(c)) {
+            return None;
+        }
+        let has_lower = bytes.iter().any(u8::is_ascii_lowercase);
+        let has_upper = bytes.iter().any(u8::is_ascii_uppercase);
+        if has_lower && has_upper {
+            return None;
+        } else if has_lower {
+            case |= lower_flag;
+        }
+        for (i, c) in bytes.iter().enumerate() {
+            short[range.start + i] = c.to_ascii_uppercase();
+        }
+    }
+    if short[0] == SLOT_DELETED {
+        short[0] = SLOT_KANJI_E5;
+    }
+    Some((short, case))
+}
+
+/// Generates the `BASE~N.EXT` alias of a long name.
+pub fn short_alias(name: &str, n: u32) -> [u8; 11] {
+    let (base, ext) = match name.rfind('.') {
+        Some(i) if i > 0 => (&name[..i], &name[i + 1..]),
+        _ => (name, ""),
+    };
+    let convert = |part: &str, max: usize| -> Vec<u8> {
+        part.bytes()
+            .filter(|&c| c != b' ' && c != b'.')
+            .map(|c| if is_short_char(c) { c.to_ascii_uppercase() } else { b'_' })
+            .take(max)
+            .collect()
+    };
+    let tail = alloc::format!("~{}", n);
+    let mut base = convert(base, 8 - tail.len());
+    if base.is_empty() {
+        base.push(b'_');
+    }
+    base.extend_from_slice(tail.as_bytes());
+
+    let mut short = [b' '; 11];
+    short[..base.len()].copy_from_slice(&base);
+    let ext = convert(ext, 3);
+    short[8..8 + ext.len()].copy_from_slice(&ext);
+    short
+}
@@ -0,0 +1,494 @@
+//! A FAT32 filesystem, with VFAT long file names.
+//!
+//! All metadata is kept on the disk: nodes only remember where their
+//! directory entry (for files) or their first cluster (for directories) is,
+//! and every operation goes through the disk under a single lock.
+
+mod dirent;
+mod node;
+
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::vec;
+use alloc::vec::Vec;
+
+use axfs_vfs::{VfsError, VfsNodeRef, VfsOps, VfsResult};
+use spin::{Mutex, Once};
+
+use self::dirent::{LongNameBuf, ShortEntry, SlotKind, DIR_ENTRY_SIZE};
+use self::node::{FatDirNode, FatFileNode};
+use crate::dev::Disk;
+
+const FAT_ENTRY_MASK: u32 = 0x0FFF_FFFF;
+const FAT_EOC: u32 = 0x0FFF_FFFF;
+const FAT_EOC_MIN: u32 = 0x0FFF_FFF8;
+
+const FSINFO_FREE_COUNT: u64 = 488;
+const FSINFO_NEXT_FREE: u64 = 492;
+const FSINFO_UNKNOWN: u32 = 0xFFFF_FFFF;
+
+/// A FAT32 filesystem that implements [`axfs_vfs::VfsOps`].
+pub struct FatFileSystem {
+    this: Weak<FatFileSystem>,
+    inner: Mutex<FatInner>,
+    root_cluster: u32,
+    parent: Once<VfsNodeRef>,
+}
+
+/// A directory entry read from the disk.
+struct DirEntry {
+    name: String,
+    entry: ShortEntry,
+    /// Indices of the first (long name) and the last (short entry) slot.
+    first_slot: usize,
+    last_slot: usize,
+}
+
+/// All slots of a directory.
+struct DirScan {
+    /// Disk offsets of the slots.
+    slots: Vec<u64>,
+    /// Whether each slot is free.
+    free: Vec<bool>,
+    entries: Vec<DirEntry>,
+}
+
+impl DirScan {
+    fn find(&self, name: &str) -> Option<&DirEntry> {
+        self.entries
+            .iter()
+            .find(|e| !e.entry.is_dot() && e.name.eq_ignore_ascii_case(name))
+    }
+}
+
+struct FatInner {
+    disk: Disk,
+    cluster_size: usize,
+    fat_start: u64,
+    fat_size: u64,
+    num_fats: u32,
+    data_start: u64,
+    max_cluster: u32,
+    /// Offset of the FSInfo sector, until its hints have been invalidated.
+    fsinfo: Option<u64>,
+    next_free: u32,
+}
+
+impl FatFileSystem {
+    /// Opens the FAT32 filesystem on `disk`.
+    pub fn new(mut disk: Disk) -> VfsResult<Arc<Self>> {
+        let mut bpb = [0u8; 512];
+        disk.read_at(0, &mut bpb)?;
+        let u16_at = |off: usize| u16::from_le_bytes([bpb[off], bpb[off + 1]]) as u32;
+        let u32_at = |off: usize| u32::from_le_bytes(bpb[off..off + 4].try_into().unwrap());
+
+        let bytes_per_sector = u16_at(11);
+        let sectors_per_cluster = bpb[13] as u32;
+        let reserved_sectors = u16_at(14);
+        let num_fats = bpb[16] as u32;
+        let total_sectors = match u16_at(19) {
+            0 => u32_at(32),
+            n => n,
+        };
+        let fat_sectors = u32_at(36);
+        if bpb[510..512] != [0x55, 0xAA]
+            || !matches!(bytes_per_sector, 512 | 1024 | 2048 | 4096)
+            || !sectors_per_cluster.is_power_of_two()
+            || num_fats == 0
+            || u16_at(17) != 0 // root entry count
+            || u16_at(22) != 0 // FAT12/16 sectors per FAT
+            || fat_sectors == 0
+        {
+            warn!("not a FAT32 filesystem");
+            return Err(VfsError::InvalidData);
+        }
+
+        let bps = bytes_per_sector as u64;
+        let data_sectors = total_sectors
+            .checked_sub(reserved_sectors + num_fats * fat_sectors)
+            .ok_or(VfsError::InvalidData)?;
+        let cluster_count = (data_sectors / sectors_per_cluster)
+            .min((fat_sectors * bytes_per_sector / 4).saturating_sub(2));
+        let root_cluster = u32_at(44);
+        if root_cluster < 2 || root_cluster > cluster_count + 1 {
+            return Err(VfsError::InvalidData);
+        }
+        let fsinfo = match u16_at(48) {
+            0 | 0xFFFF => None,
+            sector => Some(sector as u64 * bps),
+        };
+
+        let inner = FatInner {
+            disk,
+            cluster_size: (bytes_per_sector * sectors_per_cluster) as usize,
+            fat_start: reserved_sectors as u64 * bps,
+            fat_size: fat_sectors as u64 * bps,
+            num_fats,
+            data_start: (reserved_sectors + num_fats * fat_sectors) as u64 * bps,
+            max_cluster: cluster_count + 1,
+            fsinfo,
+            next_free: 2,
+        };
+        info!(
+            "FAT32: {} clusters of {} bytes",
+            cluster_count, inner.cluster_size
+        );
+        Ok(Arc::new_cyclic(|this| Self {
+            this: this.clone(),
+            inner: Mutex::new(inner),
+            root_cluster,
+            parent: Once::new(),
+        }))
+    }
+
+    /// Returns the underlying disk.
+    #[cfg(test)]
+    fn into_disk(self: Arc<Self>) -> Disk {
+        Arc::into_inner(self)
+            .map(|fs| fs.inner.into_inner().disk)
+            .expect("filesystem is still in use")
+    }
+
+    fn arc(&self) -> Arc<Self> {
+        self.this.upgrade().unwrap()
+    }
+
+    fn dir_node(&self, cluster: u32) -> VfsNodeRef {
+        // `..` entries refer to the root directory as cluster 0
+        let cluster = if cluster == 0 { self.root_cluster } else { cluster };
+        Arc::new(FatDirNode::new(self.arc(), cluster))
+    }
+
+    fn file_node(&self, entry_pos: u64) -> VfsNodeRef {
+        Arc::new(FatFileNode::new(self.arc(), entry_pos))
+    }
+}
+
+impl VfsOps for FatFileSystem {
+    fn mount(&self, _path: &str, mount_point: VfsNodeRef) -> VfsResult {
+        if let Some(parent) = mount_point.parent() {
+            self.parent.call_once(|| parent);
+        }
+        Ok(())
+    }
+
+    fn umount(&self) -> VfsResult {
+        self.inner.lock().disk.flush()
+    }
+
+    fn root_dir(&self) -> VfsNodeRef {
+        self.dir_node(self.root_cluster)
+    }
+}
+
+impl FatInner {
+    fn read_u32(&mut self, pos: u64) -> VfsResult<u32> {
+        let mut buf = [0; 4];
+        self.disk.read_at(pos, &mut buf)?;
+        Ok(u32::from_le_bytes(buf))
+    }
+
+    fn write_u32(&mut self, pos: u64, val: u32) -> VfsResult {
+        self.disk.write_at(pos, &val.to_le_bytes())
+    }
+
+    fn check_cluster(&self, cluster: u32) -> VfsResult {
+        if cluster < 2 || cluster > self.max_cluster {
+            warn!("invalid cluster number {:#x}", cluster);
+            return Err(VfsError::InvalidData);
+        }
+        Ok(())
+    }
+
+    fn cluster_pos(&self, cluster: u32) -> u64 {
+        self.data_start + (cluster - 2) as u64 * self.cluster_size as u64
+    }
+
+    fn get_fat(&mut self, cluster: u32) -> VfsResult<u32> {
+        Ok(self.read_u32(self.fat_start + cluster as u64 * 4)? & FAT_ENTRY_MASK)
+    }
+
+    /// Updates the FAT entry of `cluster` in all copies of the FAT.
+    fn set_fat(&mut self, cluster: u32, val: u32) -> VfsResult {
+        for i in 0..self.num_fats as u64 {
+            let pos = self.fat_start + i * self.fat_size + cluster as u64 * 4;
+            // the high 4 bits are reserved
+            let old = self.read_u32(pos)?;
+            self.write_u32(pos, (old & !FAT_ENTRY_MASK) | (val & FAT_ENTRY_MASK))?;
+        }
+        Ok(())
+    }
+
+    /// Marks the free cluster hints of FSInfo as unknown, as they are not
+    /// maintained.
+    fn invalidate_fsinfo(&mut self) -> VfsResult {
+        if let Some(pos) = self.fsinfo.take() {
+            self.write_u32(pos + FSINFO_FREE_COUNT, FSINFO_UNKNOWN)?;
+            self.write_u32(pos + FSINFO_NEXT_FREE, FSINFO_UNKNOWN)?;
+        }
+        Ok(())
+    }
+
+    /// Returns all clusters of the chain that starts at `first`.
+    fn cluster_chain(&mut self, first: u32) -> VfsResult<Vec<u32>> {
+        let mut chain = Vec::new();
+        let mut cluster = first;
+        while cluster != 0 {
+            self.check_cluster(cluster)?;
+            if chain.len() > self.max_cluster as usize {
+                warn!("cluster chain loops at {:#x}", first);
+                return Err(VfsError::InvalidData);
+            }
+            chain.push(cluster);
+            cluster = match self.get_fat(cluster)? {
+                next if next >= FAT_EOC_MIN => 0,
+                next => next,
+            };
+        }
+        Ok(chain)
+    }
+
+    /// Allocates a zero-filled cluster and links it after `prev`.
+    fn alloc_cluster(&mut self, prev: Option<u32>) -> VfsResult<u32> {
+        self.invalidate_fsinfo()?;
+        let count = self.max_cluster - 1;
+        for i in 0..count {
+            let cluster = (self.next_free - 2 + i) % count + 2;
+            if self.get_fat(cluster)? == 0 {
+                self.set_fat(cluster, FAT_EOC)?;
+                if let Some(prev) = prev {
+                    self.set_fat(prev, cluster)?;
+                }
+                let pos = self.cluster_pos(cluster);
+                self.disk.zero_at(pos, self.cluster_size)?;
+                self.next_free = cluster;
+                return Ok(cluster);
+            }
+        }
+        Err(VfsError::StorageFull)
+    }
+
+    fn free_chain(&mut self, first: u32) -> VfsResult {
+        self.invalidate_fsinfo()?;
+        for cluster in self.cluster_chain(first)? {
+            self.set_fat(cluster, 0)?;
+        }
+        Ok(())
+    }
+
+    /// Grows or shrinks the chain that starts at `first` to `len` clusters.
+    fn resize_chain(&mut self, first: u32, len: usize) -> VfsResult<Vec<u32>> {
+        let mut chain = self.cluster_chain(first)?;
+        if chain.len() > len {
+            if len == 0 {
+                self.free_chain(first)?;
+            } else {
+                self.set_fat(chain[len - 1], FAT_EOC)?;
+                self.free_chain(chain[len])?;
+            }
+            chain.truncate(len);
+        }
+        let old_len = chain.len();
+        while chain.len() < len {
+            match self.alloc_cluster(chain.last().copied()) {
+                Ok(cluster) => chain.push(cluster),
+                Err(e) => {
+                    // give back what has been allocated
+                    if chain.len() > old_len {
+                        if old_len > 0 {
+                            self.set_fat(chain[old_len - 1], FAT_EOC)?;
+                        }
+                        self.free_chain(chain[old_len])?;
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        Ok(chain)
+    }
+
+    /// Calls `f` with the disk offset and length of each piece of the byte
+    /// range `[offset, offset + len)` in the chain.
+    fn for_each_piece(
+        &mut self,
+        chain: &[u32],
+        offset: u64,
+        len: usize,
+        mut f: impl FnMut(&mut Disk, u64, usize, usize) -> VfsResult,
+    ) -> VfsResult {
+        let cs = self.cluster_size;
+        let mut done = 0;
+        while done < len {
+            let cur = offset + done as u64;
+            let idx = (cur / cs as u64) as usize;
+            let off = (cur % cs as u64) as usize;
+            let cluster = *chain.get(idx).ok_or(VfsError::InvalidData)?;
+            let n = (len - done).min(cs - off);
+            let pos = self.cluster_pos(cluster) + off as u64;
+            f(&mut self.disk, pos, done, n)?;
+            done += n;
+        }
+        Ok(())
+    }
+
+    fn read_chain(&mut self, chain: &[u32], offset: u64, buf: &mut [u8]) -> VfsResult {
+        self.for_each_piece(chain, offset, buf.len(), |disk, pos, done, n| {
+            disk.read_at(pos, &mut buf[done..done + n])
+        })
+    }
+
+    fn write_chain(&mut self, chain: &[u32], offset: u64, buf: &[u8]) -> VfsResult {
+        self.for_each_piece(chain, offset, buf.len(), |disk, pos, done, n| {
+            disk.write_at(pos, &buf[done..done + n])
+        })
+    }
+
+    fn zero_chain(&mut self, chain: &[u32], offset: u64, len: usize) -> VfsResult {
+        self.for_each_piece(chain, offset, len, |disk, pos, _, n| disk.zero_at(pos, n))
+    }
+
+    fn read_slot(&mut self, pos: u64) -> VfsResult<[u8; DIR_ENTRY_SIZE]> {
+        let mut raw = [0; DIR_ENTRY_SIZE];
+        self.disk.read_at(pos, &mut raw)?;
+        Ok(raw)
+    }
+
+    /// Reads the short entry of an existing file or directory.
+    fn read_entry(&mut self, pos: u64) -> VfsResult<ShortEntry> {
+        let raw = self.read_slot(pos)?;
+        match SlotKind::of(&raw) {
+            SlotKind::Short => Ok(ShortEntry::from_bytes(raw)),
+            _ => Err(VfsError::NotFound), // removed
+        }
+    }
+
+    fn write_entry(&mut self, pos: u64, entry: &ShortEntry) -> VfsResult {
+        self.disk.write_at(pos, entry.as_bytes())
+    }
+
+    /// Reads all slots and entries of the directory.
+    fn scan_dir(&mut self, dir_cluster: u32) -> VfsResult<DirScan> {
+        let chain = self.cluster_chain(dir_cluster)?;
+        let mut scan = DirScan {
+            slots: Vec::new(),
+            free: Vec::new(),
+            entries: Vec::new(),
+        };
+        let mut long_name = LongNameBuf::default();
+        let mut first_slot = 0;
+        let mut end = false;
+        let mut buf = vec![0; self.cluster_size];
+        for cluster in chain {
+            let pos = self.cluster_pos(cluster);
+            self.disk.read_at(pos, &mut buf)?;
+            for (i, raw) in buf.as_chunks::<DIR_ENTRY_SIZE>().0.iter().enumerate() {
+                let idx = scan.slots.len();
+                scan.slots.push(pos + (i * DIR_ENTRY_SIZE) as u64);
+                let kind = if end { SlotKind::End } else { SlotKind::of(raw) };
+                scan.free.push(matches!(kind, SlotKind::End | SlotKind::Deleted));
+                match kind {
+                    SlotKind::End => end = true,
+                    SlotKind::Deleted => long_name.reset(),
+                    SlotKind::LongName => {
+                        if raw[0] & 0x40 != 0 {
+                            first_slot = idx;
+                        }
+                        long_name.push(raw);
+                    }
+                    SlotKind::Short => {
+                        let entry = ShortEntry::from_bytes(*raw);
+                        let (name, first_slot) = match long_name.take(&entry) {
+                            Some(name) => (name, first_slot),
+                            None => (entry.name(), idx),
+                        };
+                        if !entry.is_volume_label() {
+                            scan.entries.push(DirEntry {
+                                name,
+                                entry,
+                                first_slot,
+                                last_slot: idx,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        Ok(scan)
+    }
+
+    /// Adds a new entry to the directory, and returns the disk offset of its
+    /// short entry.
+    fn add_entry(
+        &mut self,
+        dir_cluster: u32,
+        name: &str,
+        attr: u8,
+        cluster: u32,
+    ) -> VfsResult<u64> {
+        dirent::check_name(name)?;
+        let mut scan = self.scan_dir(dir_cluster)?;
+        if scan.find(name).is_some() {
+            return Err(VfsError::AlreadyExists);
+        }
+
+        let (entry, long_slots) = match dirent::to_short_name(name) {
+            Some((short, case)) => (ShortEntry::new(short, case, attr, cluster), Vec::new()),
+            None => {
+                let short = (1..1_000_000)
+                    .map(|n| dirent::short_alias(name, n))
+                    .find(|s| scan.entries.iter().all(|e| e.entry.short_name() != s))
+                    .ok_or(VfsError::AlreadyExists)?;
+                let entry = ShortEntry::new(short, 0, attr, cluster);
+                let long_slots = dirent::long_name_slots(name, entry.checksum());
+                (entry, long_slots)
+            }
+        };
+
+        // find enough consecutive free slots, or grow the directory
+        let needed = long_slots.len() + 1;
+        let mut run = 0;
+        let mut start = None;
+        for (i, &free) in scan.free.iter().enumerate() {
+            run = if free { run + 1 } else { 0 };
+            if run == needed {
+                start = Some(i + 1 - needed);
+                break;
+            }
+        }
+        let start = match start {
+            Some(start) => start,
+            None => {
+                let mut last = *self.cluster_chain(dir_cluster)?.last().unwrap();
+                while run < needed {
+                    last = self.alloc_cluster(Some(last))?;
+                    let pos = self.cluster_pos(last);
+                    for i in 0..self.cluster_size / DIR_ENTRY_SIZE {
+                        scan.slots.push(pos + (i * DIR_ENTRY_SIZE) as u64);
+                    }
+                    run += self.cluster_size / DIR_ENTRY_SIZE;
+                }
+                scan.slots.len() - run
+            }
+        };
+
+        for (i, raw) in long_slots.iter().enumerate() {
+            self.disk.write_at(scan.slots[start + i], raw)?;
+        }
+        let pos = scan.slots[start + long_slots.len()];
+        self.write_entry(pos, &entry)?;
+        Ok(pos)
+    }
+
+    /// Marks all slots of the entry as deleted.
+    fn remove_entry(&mut self, scan: &DirScan, entry: &DirEntry) -> VfsResult {
+        for &pos in &scan.slots[entry.first_slot..=entry.last_slot] {
+            let mut raw = self.read_slot(pos)?;
+            dirent::mark_deleted(&mut raw);
+            self.disk.write_at(pos, &raw)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,255 @@
+use alloc::sync::Arc;
+
+use axfs_vfs::path::split_path;
+use axfs_vfs::{VfsDirEntry, VfsError, VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType, VfsResult};
+
+use super::dirent::{ShortEntry, ATTR_ARCHIVE, ATTR_DIRECTORY, DIR_ENTRY_SIZE};
+use super::{FatFileSystem, FatInner};
+
+/// A directory in the FAT filesystem, identified by its first cluster.
+pub struct FatDirNode {
+    fs: Arc<FatFileSystem>,
+    cluster: u32,
+}
+
+/// A file in the FAT filesystem, identified by the disk offset of its
+/// directory entry.
+pub struct FatFileNode {
+    fs: Arc<FatFileSystem>,
+    entry_pos: u64,
+}
+
+impl FatDirNode {
+    pub(super) fn new(fs: Arc<FatFileSystem>, cluster: u32) -> Self {
+        Self { fs, cluster }
+    }
+
+    fn is_root(&self) -> bool {
+        self.cluster == self.fs.root_cluster
+    }
+
+    /// Looks up a direct child of this directory.
+    fn lookup_child(&self, name: &str) -> VfsResult<VfsNodeRef> {
+        let mut inner = self.fs.inner.lock();
+        let scan = inner.scan_dir(self.cluster)?;
+        let ent = scan.find(name).ok_or(VfsError::NotFound)?;
+        Ok(if ent.entry.is_dir() {
+            self.fs.dir_node(ent.entry.cluster())
+        } else {
+            self.fs.file_node(scan.slots[ent.last_slot])
+        })
+    }
+
+    fn create_child(&self, name: &str, ty: VfsNodeType) -> VfsResult {
+        let mut inner = self.fs.inner.lock();
+        match ty {
+            VfsNodeType::File => {
+                inner.add_entry(self.cluster, name, ATTR_ARCHIVE, 0)?;
+            }
+            VfsNodeType::Dir => {
+                if inner.scan_dir(self.cluster)?.find(name).is_some() {
+                    return Err(VfsError::AlreadyExists);
+                }
+                let cluster = inner.alloc_cluster(None)?;
+                let parent = if self.is_root() { 0 } else { self.cluster };
+                let pos = inner.cluster_pos(cluster);
+                let dot = ShortEntry::new(*b".          ", 0, ATTR_DIRECTORY, cluster);
+                let dotdot = ShortEntry::new(*b"..         ", 0, ATTR_DIRECTORY, parent);
+                inner.write_entry(pos, &dot)?;
+                inner.write_entry(pos + DIR_ENTRY_SIZE as u64, &dotdot)?;
+                if let Err(e) = inner.add_entry(self.cluster, name, ATTR_DIRECTORY, cluster) {
+                    inner.free_chain(cluster)?;
+                    return Err(e);
+                }
+            }
+            _ => return Err(VfsError::Unsupported),
+        }
+        Ok(())
+    }
+
+    fn remove_child(&self, name: &str) -> VfsResult {
+        let mut inner = self.fs.inner.lock();
+        let scan = inner.scan_dir(self.cluster)?;
+        let ent = scan.find(name).ok_or(VfsError::NotFound)?;
+        let cluster = ent.entry.cluster();
+        if ent.entry.is_dir() {
+            let sub = inner.scan_dir(cluster)?;
+            if sub.entries.iter().any(|e| !e.entry.is_dot()) {
+                return Err(VfsError::DirectoryNotEmpty);
+            }
+        }
+        inner.remove_entry(&scan, ent)?;
+        if cluster != 0 {
+            inner.free_chain(cluster)?;
+        }
+        Ok(())
+    }
+}
+
+impl VfsNodeOps for FatDirNode {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        let mut inner = self.fs.inner.lock();
+        let size = (inner.cluster_chain(self.cluster)?.len() * inner.cluster_size) as u64;
+        Ok(VfsNodeAttr::new_dir(size, size / 512))
+    }
+
+    fn parent(&self) -> Option<VfsNodeRef> {
+        if self.is_root() {
+            return self.fs.parent.get().cloned();
+        }
+        let mut inner = self.fs.inner.lock();
+        let pos = inner.cluster_pos(self.cluster) + DIR_ENTRY_SIZE as u64;
+        let dotdot = inner.read_entry(pos).ok()?;
+        Some(self.fs.dir_node(dotdot.cluster()))
+    }
+
+    fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+        let (name, rest) = split_path(path);
+        let node = match name {
+            "" | "." => Ok(self.clone() as VfsNodeRef),
+            ".." => self.parent().ok_or(VfsError::NotFound),
+            _ => self.lookup_child(name),
+        }?;
+
+        if let Some(rest) = rest {
+            node.lookup(rest)
+        } else {
+            Ok(node)
+        }
+    }
+
+    fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+        let scan = self.fs.inner.lock().scan_dir(self.cluster)?;
+        let mut children = scan
+            .entries
+            .iter()
+            .filter(|e| !e.entry.is_dot())
+            .skip(start_idx.max(2) - 2);
+        for (i, ent) in dirents.iter_mut().enumerate() {
+            match i + start_idx {
+                0 => *ent = VfsDirEntry::new(".", VfsNodeType::Dir),
+                1 => *ent = VfsDirEntry::new("..", VfsNodeType::Dir),
+                _ => {
+                    if let Some(child) = children.next() {
+                        let ty = if child.entry.is_dir() {
+                            VfsNodeType::Dir
+                        } else {
+                            VfsNodeType::File
+                        };
+                        *ent = VfsDirEntry::new(&child.name, ty);
+                    } else {
+                        return Ok(i);
+                    }
+                }
+            }
+        }
+        Ok(dirents.len())
+    }
+
+    fn create(&self, path: &str, ty: VfsNodeType) -> VfsResult {
+        debug!("create {:?} at fatfs: {}", ty, path);
+        let (name, rest) = split_path(path);
+        if let Some(rest) = rest {
+            match name {
+                "" | "." => self.create(rest, ty),
+                ".." => self.parent().ok_or(VfsError::NotFound)?.create(rest, ty),
+                _ => self.lookup_child(name)?.create(rest, ty),
+            }
+        } else if name.is_empty() || name == "." || name == ".." {
+            Ok(()) // already exists
+        } else {
+            self.create_child(name, ty)
+        }
+    }
+
+    fn remove(&self, path: &str) -> VfsResult {
+        debug!("remove at fatfs: {}", path);
+        let (name, rest) = split_path(path);
+        if let Some(rest) = rest {
+            match name {
+                "" | "." => self.remove(rest),
+                ".." => self.parent().ok_or(VfsError::NotFound)?.remove(rest),
+                _ => self.lookup_child(name)?.remove(rest),
+            }
+        } else if name.is_empty() || name == "." || name == ".." {
+            Err(VfsError::InvalidInput) // remove '.' or '..
+        } else {
+            self.remove_child(name)
+        }
+    }
+
+    axfs_vfs::impl_vfs_dir_default! {}
+}
+
+impl FatFileNode {
+    pub(super) fn new(fs: Arc<FatFileSystem>, entry_pos: u64) -> Self {
+        Self { fs, entry_pos }
+    }
+}
+
+impl FatInner {
+    /// Resizes the file to `size` bytes, zero-filling the extended part.
+    fn resize_file(&mut self, entry: &mut ShortEntry, size: u64) -> VfsResult<alloc::vec::Vec<u32>> {
+        if size > u32::MAX as u64 {
+            return Err(VfsError::StorageFull);
+        }
+        let old_size = entry.size() as u64;
+        let chain = self.resize_chain(entry.cluster(), size.div_ceil(self.cluster_size as u64) as usize)?;
+        if size > old_size {
+            self.zero_chain(&chain, old_size, (size - old_size) as usize)?;
+        }
+        entry.set_cluster(chain.first().copied().unwrap_or(0));
+        entry.set_size(size as u32);
+        Ok(chain)
+    }
+}
+
+impl VfsNodeOps for FatFileNode {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        let mut inner = self.fs.inner.lock();
+        let size = inner.read_entry(self.entry_pos)?.size() as u64;
+        let cs = inner.cluster_size as u64;
+        Ok(VfsNodeAttr::new_file(size, size.div_ceil(cs) * cs / 512))
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let mut inner = self.fs.inner.lock();
+        let entry = inner.read_entry(self.entry_pos)?;
+        let size = entry.size() as u64;
+        if offset >= size {
+            return Ok(0);
+        }
+        let len = buf.len().min((size - offset) as usize);
+        let chain = inner.cluster_chain(entry.cluster())?;
+        inner.read_chain(&chain, offset, &mut buf[..len])?;
+        Ok(len)
+    }
+
+    fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        let mut inner = self.fs.inner.lock();
+        let mut entry = inner.read_entry(self.entry_pos)?;
+        let end = offset + buf.len() as u64;
+        let chain = if end > entry.size() as u64 {
+            let chain = inner.resize_file(&mut entry, end)?;
+            inner.write_entry(self.entry_pos, &entry)?;
+            chain
+        } else {
+            inner.cluster_chain(entry.cluster())?
+        };
+        inner.write_chain(&chain, offset, buf)?;
+        Ok(buf.len())
+    }
+
+    fn truncate(&self, size: u64) -> VfsResult {
+        let mut inner = self.fs.inner.lock();
+        let mut entry = inner.read_entry(self.entry_pos)?;
+        inner.resize_file(&mut entry, size)?;
+        inner.write_entry(self.entry_pos, &entry)
+    }
+
+    fn fsync(&self) -> VfsResult {
+        self.fs.inner.lock().disk.flush()
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
@@ -0,0 +1,214 @@
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use axdriver::block::RamDisk;
+use axfs_vfs::{VfsDirEntry, VfsError, VfsNodeRef, VfsNodeType, VfsOps};
+
+use super::FatFileSystem;
+use crate::dev::Disk;
+
+const SECTOR_SIZE: usize = 512;
+const RESERVED_SECTORS: usize = 32;
+const NUM_FATS: usize = 2;
+
+/// Formats a FAT32 image of `sectors` sectors, like `mkfs.fat -F 32`.
+fn mkfs(sectors: usize, sectors_per_cluster: usize) -> RamDisk {
+    let mut img = vec![0u8; sectors * SECTOR_SIZE];
+    let fat_sectors = (sectors / sectors_per_cluster * 4).div_ceil(SECTOR_SIZE);
+    let root_cluster = 2u32;
+
+    let bpb = &mut img[..SECTOR_SIZE];
+    bpb[..3].copy_from_slice(&[0xEB, 0x58, 0x90]);
+    bpb[3..11].copy_from_slice(b"mkfs.fat");
+    bpb[11..13].copy_from_slice(&(SECTOR_SIZE as u16).to_le_bytes());
+    bpb[13] = sectors_per_cluster as u8;
+    bpb[14..16].copy_from_slice(&(RESERVED_SECTORS as u16).to_le_bytes());
+    bpb[16] = NUM_FATS as u8;
+    bpb[21] = 0xF8;
+    bpb[32..36].copy_from_slice(&(sectors as u32).to_le_bytes());
+    bpb[36..40].copy_from_slice(&(fat_sectors as u32).to_le_bytes());
+    bpb[44..48].copy_from_slice(&root_cluster.to_le_bytes());
+    bpb[48..50].copy_from_slice(&1u16.to_le_bytes());
+    bpb[66] = 0x29;
+    bpb[71..82].copy_from_slice(b"NO NAME    ");
+    bpb[82..90].copy_from_slice(b"FAT32   ");
+    bpb[510..512].copy_from_slice(&[0x55, 0xAA]);
+
+    let fsinfo = &mut img[SECTOR_SIZE..SECTOR_SIZE * 2];
+    fsinfo[..4].copy_from_slice(&0x4161_5252u32.to_le_bytes());
+    fsinfo[484..488].copy_from_slice(&0x6141_7272u32.to_le_bytes());
+    fsinfo[488..492].copy_from_slice(&0xFFFF_FFFFu32.to_le_bytes());
+    fsinfo[492..496].copy_from_slice(&0xFFFF_FFFFu32.to_le_bytes());
+    fsinfo[508..512].copy_from_slice(&0xAA55_0000u32.to_le_bytes());
+
+    for i in 0..NUM_FATS {
+        let start = (RESERVED_SECTORS + i * fat_sectors) * SECTOR_SIZE;
+        let fat = &mut img[start..start + 12];
+        fat[..4].copy_from_slice(&0x0FFF_FFF8u32.to_le_bytes());
+        fat[4..8].copy_from_slice(&0x0FFF_FFFFu32.to_le_bytes());
+        fat[8..12].copy_from_slice(&0x0FFF_FFFFu32.to_le_bytes()); // root directory
+    }
+    RamDisk::from(&img)
+}
+
+fn new_fs(sectors: usize, sectors_per_cluster: usize) -> Arc<FatFileSystem> {
+    let disk = Disk::new(alloc::boxed::Box::new(mkfs(sectors, sectors_per_cluster)));
+    FatFileSystem::new(disk).unwrap()
+}
+
+fn read_dir_names(dir: &VfsNodeRef) -> Vec<String> {
+    let mut entries: [VfsDirEntry; 8] = core::array::from_fn(|_| VfsDirEntry::default());
+    let mut names = Vec::new();
+    loop {
+        let n = dir.read_dir(names.len(), &mut entries).unwrap();
+        if n == 0 {
+            return names;
+        }
+        for ent in &entries[..n] {
+            names.push(String::from_utf8(ent.name_as_bytes().to_vec()).unwrap());
+        }
+    }
+}
+
+#[test]
+fn test_reject_invalid() {
+    let disk = Disk::new(alloc::boxed::Box::new(RamDisk::new(0x10000)));
+    assert_eq!(FatFileSystem::new(disk).err(), Some(VfsError::InvalidData));
+}
+
+#[test]
+fn test_create_lookup() {
+    let fs = new_fs(8192, 1);
+    let root = fs.root_dir();
+    root.create("short.txt", VfsNodeType::File).unwrap();
+    root.create("A Long File Name.text", VfsNodeType::File).unwrap();
+    root.create("dir", VfsNodeType::Dir).unwrap();
+    root.create("dir/sub", VfsNodeType::Dir).unwrap();
+    root.create("dir/sub/f", VfsNodeType::File).unwrap();
+    assert_eq!(
+        root.create("SHORT.TXT", VfsNodeType::File),
+        Err(VfsError::AlreadyExists)
+    );
+    assert_eq!(
+        root.create("bad:name", VfsNodeType::File),
+        Err(VfsError::InvalidInput)
+    );
+
+    assert!(root.clone().lookup("Short.Txt").unwrap().get_attr().unwrap().is_file());
+    assert!(root.clone().lookup("a long file name.TEXT").is_ok());
+    let sub = root.clone().lookup("/dir/./sub/").unwrap();
+    assert!(sub.get_attr().unwrap().is_dir());
+    assert!(sub.clone().lookup("../sub/f").is_ok());
+    assert!(sub.clone().lookup("../../dir").is_ok());
+    assert_eq!(
+        root.clone().lookup("dir/nothing").err(),
+        Some(VfsError::NotFound)
+    );
+    assert_eq!(
+        root.clone().lookup("short.txt/x").err(),
+        Some(VfsError::NotADirectory)
+    );
+
+    assert_eq!(
+        read_dir_names(&root),
+        [".", "..", "short.txt", "A Long File Name.text", "dir"]
+    );
+    assert_eq!(read_dir_names(&sub), [".", "..", "f"]);
+}
+
+#[test]
+fn test_read_write() {
+    let fs = new_fs(8192, 1);
+    let root = fs.root_dir();
+    root.create("file", VfsNodeType::File).unwrap();
+    let file = root.clone().lookup("file").unwrap();
+
+    // spans several clusters
+    let data: Vec<u8> = (0..3000).map(|i| i as u8).collect();
+    assert_eq!(file.write_at(0, &data).unwrap(), data.len());
+    let mut buf = vec![0; 4096];
+    assert_eq!(file.read_at(0, &mut buf).unwrap(), data.len());
+    assert_eq!(&buf[..data.len()], data);
+    assert_eq!(file.read_at(1000, &mut buf[..10]).unwrap(), 10);
+    assert_eq!(&buf[..10], &data[1000..1010]);
+
+    // writing past the end leaves a zero-filled hole
+    file.write_at(5000, b"tail").unwrap();
+    assert_eq!(file.get_attr().unwrap().size(), 5004);
+    assert_eq!(file.read_at(3000, &mut buf).unwrap(), 2004);
+    assert!(buf[..2000].iter().all(|&b| b == 0));
+    assert_eq!(&buf[2000..2004], b"tail");
+
+    file.truncate(10).unwrap();
+    assert_eq!(file.read_at(0, &mut buf).unwrap(), 10);
+    file.truncate(20).unwrap();
+    assert_eq!(file.read_at(0, &mut buf).unwrap(), 20);
+    assert_eq!(&buf[..10], &data[..10]);
+    assert!(buf[10..20].iter().all(|&b| b == 0));
+    assert_eq!(file.read_at(100, &mut buf).unwrap(), 0);
+}
+
+#[test]
+fn test_remove() {
+    let fs = new_fs(8192, 1);
+    let root = fs.root_dir();
+    root.create("dir", VfsNodeType::Dir).unwrap();
+    root.create("dir/a long name", VfsNodeType::File).unwrap();
+    let file = root.clone().lookup("dir/a long name").unwrap();
+    file.write_at(0, &[1; 2000]).unwrap();
+
+    assert_eq!(root.remove("dir"), Err(VfsError::DirectoryNotEmpty));
+    assert_eq!(root.remove("dir/."), Err(VfsError::InvalidInput));
+    root.remove("dir/A LONG NAME").unwrap();
+    assert_eq!(file.get_attr().err(), Some(VfsError::NotFound));
+    assert_eq!(root.remove("dir/a long name"), Err(VfsError::NotFound));
+    root.remove("dir").unwrap();
+    assert_eq!(read_dir_names(&root), [".", ".."]);
+
+    // freed slots and clusters are reused
+    root.create("again", VfsNodeType::File).unwrap();
+    assert_eq!(read_dir_names(&root), [".", "..", "again"]);
+}
+
+#[test]
+fn test_grow_dir_and_remount() {
+    let fs = new_fs(8192, 1);
+    let root = fs.root_dir();
+    // one cluster holds 16 slots, each long name takes 3
+    for i in 0..40 {
+        let name = alloc::format!("a long file name {}", i);
+        root.create(&name, VfsNodeType::File).unwrap();
+        let file = root.clone().lookup(&name).unwrap();
+        file.write_at(0, name.as_bytes()).unwrap();
+    }
+    drop(root);
+
+    // open the disk again, all data must be found there
+    let fs = FatFileSystem::new(fs.into_disk()).unwrap();
+    let root = fs.root_dir();
+    assert_eq!(read_dir_names(&root).len(), 42);
+    let mut buf = [0; 64];
+    for i in 0..40 {
+        let name = alloc::format!("a long file name {}", i);
+        let file = root.clone().lookup(&name).unwrap();
+        let n = file.read_at(0, &mut buf).unwrap();
+        assert_eq!(&buf[..n], name.as_bytes());
+    }
+}
+
+#[test]
+fn test_storage_full() {
+    // 64 data clusters at most
+    let fs = new_fs(RESERVED_SECTORS + 2 + 64, 1);
+    let root = fs.root_dir();
+    root.create("big", VfsNodeType::File).unwrap();
+    let file = root.clone().lookup("big").unwrap();
+    assert_eq!(
+        file.write_at(0, &[0xAA; 64 * SECTOR_SIZE]),
+        Err(VfsError::StorageFull)
+    );
+    file.truncate(0).unwrap();
+    assert_eq!(file.write_at(0, &[0xAA; 32 * SECTOR_SIZE]).unwrap(), 32 * SECTOR_SIZE);
+}
@@ -0,0 +1,3 @@
+pub mod fat;
+
+pub use axfs_ramfs as ramfs;
@@ -0,0 +1,40 @@
+//! Filesystem module of [ArceOS](https://github.com/rcore-os/arceos).
+//!
+//! The root of the filesystem tree is a RAM filesystem. If a block device is
+//! found, the FAT32 filesystem on it is mounted at `/mnt`.
+//!
+//! Files and directories are accessed by path with [`fops`] and [`api`].
+
+#![no_std]
+
+#[macro_use]
+extern crate log;
+extern crate alloc;
+
+mod dev;
+mod root;
+
+pub mod api;
+pub mod fops;
+pub mod fs;
+
+use alloc::vec::Vec;
+use axdriver::AxBlockDevice;
+
+pub use axfs_vfs::{VfsDirEntry, VfsError, VfsNodeAttr, VfsNodePerm, VfsNodeType, VfsResult};
+
+/// Initializes the filesystems on the given block devices.
+///
+/// Only the last device is used currently.
+pub fn init_filesystems(mut blk_devs: Vec<AxBlockDevice>) {
+    info!("Initialize filesystems...");
+
+    let disk = blk_devs.pop().map(|dev| {
+        info!("  use block device: {}", dev.device_name());
+        dev::Disk::new(dev)
+    });
+    if disk.is_none() {
+        warn!("  no block device found, only the RAM filesystem is available");
+    }
+    root::init_rootfs(disk);
+}
@@ -0,0 +1,208 @@
+//! Root directory of the filesystem tree, where other filesystems are mounted.
+
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use axfs_vfs::path::canonicalize;
+use axfs_vfs::{VfsError, VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType, VfsOps, VfsResult};
+use spin::Once;
+
+use crate::dev::Disk;
+use crate::fs;
+
+/// Where the FAT filesystem on the disk is mounted.
+const DISK_MOUNT_POINT: &str = "/mnt";
+
+struct MountPoint {
+    path: &'static str,
+    fs: Arc<dyn VfsOps>,
+}
+
+struct RootDirectory {
+    main_fs: Arc<dyn VfsOps>,
+    mounts: Vec<MountPoint>,
+}
+
+static ROOT_DIR: Once<Arc<RootDirectory>> = Once::new();
+
+impl RootDirectory {
+    const fn new(main_fs: Arc<dyn VfsOps>) -> Self {
+        Self {
+            main_fs,
+            mounts: Vec::new(),
+        }
+    }
+
+    fn mount(&mut self, path: &'static str, fs: Arc<dyn VfsOps>) -> VfsResult {
+        if path == "/" || !path.starts_with('/') {
+            return Err(VfsError::InvalidInput);
+        }
+        if self.mounts.iter().any(|mp| mp.path == path) {
+            return Err(VfsError::AlreadyExists);
+        }
+        // create the mount point in the main filesystem if it does not exist
+        let main_root = self.main_fs.root_dir();
+        match main_root.create(path, VfsNodeType::Dir) {
+            Ok(()) | Err(VfsError::AlreadyExists) => {}
+            Err(e) => return Err(e),
+        }
+        fs.mount(path, main_root.lookup(path)?)?;
+        self.mounts.push(MountPoint { path, fs });
+        Ok(())
+    }
+
+    fn is_mount_point(&self, path: &str) -> bool {
+        self.mounts.iter().any(|mp| mp.path == path)
+    }
+
+    /// Finds the filesystem that `path` belongs to, and calls `f` with it and
+    /// the rest of the path relative to its root.
+    fn lookup_mounted_fs<F, T>(&self, path: &str, f: F) -> VfsResult<T>
+    where
+        F: FnOnce(&Arc<dyn VfsOps>, &str) -> VfsResult<T>,
+    {
+        debug!("lookup at root: {}", path);
+        let path = path.trim_matches('/');
+        let mut best: Option<(&MountPoint, usize)> = None;
+        for mp in &self.mounts {
+            let prefix = mp.path.trim_start_matches('/');
+            let matched = path
+                .strip_prefix(prefix)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'));
+            if matched && best.is_none_or(|(_, len)| prefix.len() > len) {
+                best = Some((mp, prefix.len()));
+            }
+        }
+        match best {
+            Some((mp, len)) => f(&mp.fs, &path[len..]),
+            None => f(&self.main_fs, path),
+        }
+    }
+}
+
+impl VfsNodeOps for RootDirectory {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        self.main_fs.root_dir().get_attr()
+    }
+
+    fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+        self.lookup_mounted_fs(path, |fs, rest| fs.root_dir().lookup(rest))
+    }
+
+    fn create(&self, path: &str, ty: VfsNodeType) -> VfsResult {
+        self.lookup_mounted_fs(path, |fs, rest| {
+            if rest.is_empty() {
+                Ok(()) // already exists
+            } else {
+                fs.root_dir().create(rest, ty)
+            }
+        })
+    }
+
+    fn remove(&self, path: &str) -> VfsResult {
+        self.lookup_mounted_fs(path, |fs, rest| {
+            if rest.is_empty() {
+                Err(VfsError::PermissionDenied) // cannot remove mount points
+            } else {
+                fs.root_dir().remove(rest)
+            }
+        })
+    }
+
+    fn read_dir(&self, start_idx: usize, dirents: &mut [axfs_vfs::VfsDirEntry]) -> VfsResult<usize> {
+        self.main_fs.root_dir().read_dir(start_idx, dirents)
+    }
+
+    axfs_vfs::impl_vfs_dir_default! {}
+}
+
+pub(crate) fn init_rootfs(disk: Option<Disk>) {
+    let mut root_dir = RootDirectory::new(Arc::new(fs::ramfs::RamFileSystem::new()));
+    if let Some(disk) = disk {
+        match fs::fat::FatFileSystem::new(disk) {
+            Ok(fat_fs) => {
+                root_dir
+                    .mount(DISK_MOUNT_POINT, fat_fs)
+                    .expect("failed to mount the disk");
+                info!("  mounted FAT32 filesystem at {}", DISK_MOUNT_POINT);
+            }
+            Err(e) => warn!("  failed to open the disk: {:?}", e),
+        }
+    }
+    ROOT_DIR.call_once(|| Arc::new(root_dir));
+}
+
+fn root_dir() -> Arc<RootDirectory> {
+    ROOT_DIR
+        .get()
+        .expect("filesystems are not initialized")
+        .clone()
+}
+
+/// Returns the absolute, canonical form of `path`.
+///
+/// There is no current working directory yet, so relative paths start from
+/// the root.
+pub(crate) fn absolute_path(path: &str) -> String {
+    if path.starts_with('/') {
+        canonicalize(path)
+    } else {
+        canonicalize(&alloc::format!("/{}", path))
+    }
+}
+
+pub(crate) fn lookup(path: &str) -> VfsResult<VfsNodeRef> {
+    if path.is_empty() {
+        return Err(VfsError::NotFound);
+    }
+    let node = root_dir().lookup(&absolute_path(path))?;
+    if path.ends_with('/') && !node.get_attr()?.is_dir() {
+        Err(VfsError::NotADirectory)
+    } else {
+        Ok(node)
+    }
+}
+
+pub(crate) fn create_file(path: &str) -> VfsResult<VfsNodeRef> {
+    if path.is_empty() {
+        return Err(VfsError::NotFound);
+    } else if path.ends_with('/') {
+        return Err(VfsError::NotADirectory);
+    }
+    let path = absolute_path(path);
+    let root = root_dir();
+    root.create(&path, VfsNodeType::File)?;
+    root.lookup(&path)
+}
+
+pub(crate) fn create_dir(path: &str) -> VfsResult {
+    match lookup(path) {
+        Ok(_) => Err(VfsError::AlreadyExists),
+        Err(VfsError::NotFound) => root_dir().create(&absolute_path(path), VfsNodeType::Dir),
+        Err(e) => Err(e),
+    }
+}
+
+pub(crate) fn remove_file(path: &str) -> VfsResult {
+    if lookup(path)?.get_attr()?.is_dir() {
+        Err(VfsError::IsADirectory)
+    } else {
+        root_dir().remove(&absolute_path(path))
+    }
+}
+
+pub(crate) fn remove_dir(path: &str) -> VfsResult {
+    if path.is_empty() {
+        return Err(VfsError::NotFound);
+    }
+    let path = absolute_path(path);
+    let root = root_dir();
+    if path == "/" || root.is_mount_point(&path) {
+        return Err(VfsError::PermissionDenied);
+    }
+    if !root.clone().lookup(&path)?.get_attr()?.is_dir() {
+        return Err(VfsError::NotADirectory);
+    }
+    root.remove(&path)
+}
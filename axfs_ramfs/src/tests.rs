@@ -0,0 +1,84 @@
+use alloc::sync::Arc;
+use axfs_vfs::{VfsDirEntry, VfsError, VfsNodeOps, VfsNodeType, VfsOps};
+
+use crate::*;
+
+fn read_dir_names(node: &dyn VfsNodeOps) -> alloc::vec::Vec<alloc::string::String> {
+    let mut entries: [VfsDirEntry; 8] = core::array::from_fn(|_| VfsDirEntry::default());
+    let n = node.read_dir(0, &mut entries).unwrap();
+    entries[..n]
+        .iter()
+        .map(|e| core::str::from_utf8(e.name_as_bytes()).unwrap().into())
+        .collect()
+}
+
+#[test]
+fn test_ramfs_create_lookup() {
+    let ramfs = RamFileSystem::new();
+    let root = ramfs.root_dir();
+
+    root.create("f1", VfsNodeType::File).unwrap();
+    root.create("d1", VfsNodeType::Dir).unwrap();
+    root.create("d1/f2", VfsNodeType::File).unwrap();
+    root.create("d1/d2", VfsNodeType::Dir).unwrap();
+    root.create("./d1/../d1/d2/f3", VfsNodeType::File).unwrap();
+
+    assert_eq!(root.create("f1", VfsNodeType::File), Err(VfsError::AlreadyExists));
+    assert_eq!(root.create("d3/f4", VfsNodeType::File), Err(VfsError::NotFound));
+    assert_eq!(root.create("f1/f5", VfsNodeType::File), Err(VfsError::NotADirectory));
+
+    let f3 = root.clone().lookup("/d1/d2/f3").unwrap();
+    assert!(f3.get_attr().unwrap().is_file());
+    let d2 = root.clone().lookup("d1/d2").unwrap();
+    assert!(d2.get_attr().unwrap().is_dir());
+    assert!(Arc::ptr_eq(&d2.parent().unwrap(), &root.clone().lookup("d1").unwrap()));
+    assert!(Arc::ptr_eq(&d2.clone().lookup("..").unwrap(), &root.clone().lookup("d1").unwrap()));
+    assert_eq!(root.clone().lookup("d1/none").err(), Some(VfsError::NotFound));
+
+    assert_eq!(read_dir_names(root.as_ref()), [".", "..", "d1", "f1"]);
+    assert_eq!(read_dir_names(root.clone().lookup("d1").unwrap().as_ref()), [".", "..", "d2", "f2"]);
+}
+
+#[test]
+fn test_ramfs_read_write() {
+    let ramfs = RamFileSystem::new();
+    let root = ramfs.root_dir();
+    root.create("f1", VfsNodeType::File).unwrap();
+    let f1 = root.clone().lookup("f1").unwrap();
+
+    assert_eq!(f1.write_at(0, b"hello").unwrap(), 5);
+    assert_eq!(f1.write_at(10, b"world").unwrap(), 5);
+    assert_eq!(f1.get_attr().unwrap().size(), 15);
+
+    let mut buf = [0xff; 32];
+    assert_eq!(f1.read_at(0, &mut buf).unwrap(), 15);
+    assert_eq!(&buf[..15], b"hello\0\0\0\0\0world");
+    assert_eq!(f1.read_at(12, &mut buf).unwrap(), 3);
+    assert_eq!(&buf[..3], b"rld");
+    assert_eq!(f1.read_at(100, &mut buf).unwrap(), 0);
+
+    f1.truncate(3).unwrap();
+    assert_eq!(f1.read_at(0, &mut buf).unwrap(), 3);
+    assert_eq!(&buf[..3], b"hel");
+    f1.truncate(6).unwrap();
+    assert_eq!(f1.read_at(0, &mut buf).unwrap(), 6);
+    assert_eq!(&buf[..6], b"hel\0\0\0");
+
+    assert_eq!(root.read_at(0, &mut buf), Err(VfsError::IsADirectory));
+    assert_eq!(f1.clone().lookup("x").err(), Some(VfsError::NotADirectory));
+}
+
+#[test]
+fn test_ramfs_remove() {
+    let ramfs = RamFileSystem::new();
+    let root = ramfs.root_dir();
+    root.create("d1", VfsNodeType::Dir).unwrap();
+    root.create("d1/f1", VfsNodeType::File).unwrap();
+
+    assert_eq!(root.remove("d1"), Err(VfsError::DirectoryNotEmpty));
+    assert_eq!(root.remove("d1/none"), Err(VfsError::NotFound));
+    assert_eq!(root.remove("."), Err(VfsError::InvalidInput));
+    root.remove("d1/f1").unwrap();
+    root.remove("d1").unwrap();
+    assert_eq!(read_dir_names(root.as_ref()), [".", ".."]);
+}
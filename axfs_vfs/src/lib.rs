@@ -0,0 +1,168 @@
+//! Virtual filesystem interfaces used by [ArceOS](https://github.com/rcore-os/arceos).
+//!
+//! A filesystem is a set of files and directories (symbol links are not
+//! supported currently), collectively referred to as **nodes**, which are
+//! conceptually similar to [inodes] in Linux. A file system needs to implement
+//! the [`VfsOps`] trait, its files and directories need to implement the
+//! [`VfsNodeOps`] trait.
+//!
+//! [inodes]: https://en.wikipedia.org/wiki/Inode
+
+#![no_std]
+
+#[macro_use]
+extern crate log;
+extern crate alloc;
+
+mod macros;
+mod structs;
+
+pub mod path;
+
+use alloc::sync::Arc;
+
+pub use self::structs::{FileSystemInfo, VfsDirEntry, VfsNodeAttr, VfsNodePerm, VfsNodeType};
+
+/// A wrapper of [`Arc<dyn VfsNodeOps>`].
+pub type VfsNodeRef = Arc<dyn VfsNodeOps>;
+
+/// The error type used by the filesystem layer.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum VfsError {
+    /// An entity already exists.
+    AlreadyExists,
+    /// The filesystem or the node is in a bad state.
+    BadState,
+    /// The directory is not empty.
+    DirectoryNotEmpty,
+    /// Data not valid for the operation were encountered.
+    InvalidData,
+    /// Invalid parameter/argument.
+    InvalidInput,
+    /// An I/O error of the underlying device.
+    Io,
+    /// The node is a directory.
+    IsADirectory,
+    /// The node is not a directory.
+    NotADirectory,
+    /// The requested entity is not found.
+    NotFound,
+    /// The operation lacked the necessary privileges to complete.
+    PermissionDenied,
+    /// No free space left on the device.
+    StorageFull,
+    /// An error returned when an operation could not be completed because an
+    /// "end of file" was reached prematurely.
+    UnexpectedEof,
+    /// This operation is unsupported or unimplemented.
+    Unsupported,
+}
+
+/// A [`Result`] type with [`VfsError`] as the error type.
+pub type VfsResult<T = ()> = Result<T, VfsError>;
+
+/// Filesystem operations.
+pub trait VfsOps: Send + Sync {
+    /// Do something when the filesystem is mounted.
+    fn mount(&self, _path: &str, _mount_point: VfsNodeRef) -> VfsResult {
+        Ok(())
+    }
+
+    /// Do something when the filesystem is unmounted.
+    fn umount(&self) -> VfsResult {
+        Ok(())
+    }
+
+    /// Get the attributes of the filesystem.
+    fn statfs(&self) -> VfsResult<FileSystemInfo> {
+        Err(VfsError::Unsupported)
+    }
+
+    /// Get the root directory of the filesystem.
+    fn root_dir(&self) -> VfsNodeRef;
+}
+
+/// Node (file/directory) operations.
+pub trait VfsNodeOps: Send + Sync {
+    /// Do something when the node is opened.
+    fn open(&self) -> VfsResult {
+        Ok(())
+    }
+
+    /// Do something when the node is closed.
+    fn release(&self) -> VfsResult {
+        Ok(())
+    }
+
+    /// Get the attributes of the node.
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Err(VfsError::Unsupported)
+    }
+
+    // file operations:
+
+    /// Read data from the file at the given offset.
+    fn read_at(&self, _offset: u64, _buf: &mut [u8]) -> VfsResult<usize> {
+        Err(VfsError::InvalidInput)
+    }
+
+    /// Write data to the file at the given offset.
+    fn write_at(&self, _offset: u64, _buf: &[u8]) -> VfsResult<usize> {
+        Err(VfsError::InvalidInput)
+    }
+
+    /// Flush the file, synchronize the data to disk.
+    fn fsync(&self) -> VfsResult {
+        Err(VfsError::InvalidInput)
+    }
+
+    /// Truncate the file to the given size.
+    fn truncate(&self, _size: u64) -> VfsResult {
+        Err(VfsError::InvalidInput)
+    }
+
+    // directory operations:
+
+    /// Get the parent directory of this directory.
+    ///
+    /// Return `None` if the node is a file.
+    fn parent(&self) -> Option<VfsNodeRef> {
+        None
+    }
+
+    /// Lookup the node with given `path` in the directory.
+    ///
+    /// Return the node if found.
+    fn lookup(self: Arc<Self>, _path: &str) -> VfsResult<VfsNodeRef> {
+        Err(VfsError::Unsupported)
+    }
+
+    /// Create a new node with the given `path` in the directory.
+    ///
+    /// Return [`VfsError::AlreadyExists`] if it already exists.
+    fn create(&self, _path: &str, _ty: VfsNodeType) -> VfsResult {
+        Err(VfsError::Unsupported)
+    }
+
+    /// Remove the node with the given `path` in the directory.
+    fn remove(&self, _path: &str) -> VfsResult {
+        Err(VfsError::Unsupported)
+    }
+
+    /// Read directory entries into `dirents`, starting from `start_idx`.
+    fn read_dir(&self, _start_idx: usize, _dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+        Err(VfsError::Unsupported)
+    }
+
+    /// Convert `&self` to [`&dyn Any`][1] that can use
+    /// [`Any::downcast_ref`][2].
+    ///
+    /// [1]: core::any::Any
+    /// [2]: core::any::Any#method.downcast_ref
+    fn as_any(&self) -> &dyn core::any::Any {
+        unimplemented!()
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,72 @@
+//! Utilities for path manipulation.
+
+use alloc::string::String;
+
+/// Returns the canonical form of the path with all intermediate components
+/// normalized.
+///
+/// It won't force convert the path to an absolute form.
+///
+/// # Examples
+///
+/// ```
+/// use axfs_vfs::path::canonicalize;
+///
+/// assert_eq!(canonicalize("/path/./to//foo"), "/path/to/foo");
+/// assert_eq!(canonicalize("/./path/to/../bar.rs"), "/path/bar.rs");
+/// assert_eq!(canonicalize("./foo/./bar"), "foo/bar");
+/// ```
+pub fn canonicalize(path: &str) -> String {
+    let mut buf = String::new();
+    let is_absolute = path.starts_with('/');
+    for part in path.split('/') {
+        match part {
+            "" | "." => continue,
+            ".." => {
+                while !buf.is_empty() {
+                    if buf == "/" {
+                        break;
+                    }
+                    let c = buf.pop().unwrap();
+                    if c == '/' {
+                        break;
+                    }
+                }
+            }
+            _ => {
+                if buf.is_empty() {
+                    if is_absolute {
+                        buf.push('/');
+                    }
+                } else if &buf[buf.len() - 1..] != "/" {
+                    buf.push('/');
+                }
+                buf.push_str(part);
+            }
+        }
+    }
+    if is_absolute && buf.is_empty() {
+        buf.push('/');
+    }
+    buf
+}
+
+/// Splits the path into the first component and the rest.
+///
+/// Leading and trailing slashes of the rest are removed.
+///
+/// # Examples
+///
+/// ```
+/// use axfs_vfs::path::split_path;
+///
+/// assert_eq!(split_path("foo/bar/baz"), ("foo", Some("bar/baz")));
+/// assert_eq!(split_path("/foo//"), ("foo", None));
+/// ```
+pub fn split_path(path: &str) -> (&str, Option<&str>) {
+    let trimmed_path = path.trim_start_matches('/');
+    trimmed_path.find('/').map_or((trimmed_path, None), |n| {
+        let rest = trimmed_path[n + 1..].trim_matches('/');
+        (&trimmed_path[..n], (!rest.is_empty()).then_some(rest))
+    })
+}
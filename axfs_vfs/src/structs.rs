@@ -0,0 +1,280 @@
+/// Filesystem attributes.
+///
+/// Currently not used.
+#[non_exhaustive]
+pub struct FileSystemInfo;
+
+/// Node (file/directory) attributes.
+#[derive(Debug, Clone, Copy)]
+pub struct VfsNodeAttr {
+    /// File permission mode.
+    mode: VfsNodePerm,
+    /// File type.
+    ty: VfsNodeType,
+    /// Total size, in bytes.
+    size: u64,
+    /// Number of 512B blocks allocated.
+    blocks: u64,
+}
+
+/// Node (file/directory) permission mode, in the unix style.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct VfsNodePerm(u16);
+
+impl VfsNodePerm {
+    /// Owner has read permission.
+    pub const OWNER_READ: Self = Self(0o400);
+    /// Owner has write permission.
+    pub const OWNER_WRITE: Self = Self(0o200);
+    /// Owner has execute permission.
+    pub const OWNER_EXEC: Self = Self(0o100);
+
+    /// Group has read permission.
+    pub const GROUP_READ: Self = Self(0o40);
+    /// Group has write permission.
+    pub const GROUP_WRITE: Self = Self(0o20);
+    /// Group has execute permission.
+    pub const GROUP_EXEC: Self = Self(0o10);
+
+    /// Others have read permission.
+    pub const OTHER_READ: Self = Self(0o4);
+    /// Others have write permission.
+    pub const OTHER_WRITE: Self = Self(0o2);
+    /// Others have execute permission.
+    pub const OTHER_EXEC: Self = Self(0o1);
+
+    /// Creates a permission mode from the raw bits.
+    pub const fn from_bits_truncate(bits: u16) -> Self {
+        Self(bits & 0o777)
+    }
+
+    /// Returns the raw bits of the permission mode.
+    pub const fn bits(&self) -> u16 {
+        self.0
+    }
+
+    /// Returns `true` if all bits in `other` are set in `self`.
+    pub const fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns the default permission for a file.
+    ///
+    /// The default permission is `0o666` (owner/group/others can read and write).
+    pub const fn default_file() -> Self {
+        Self(0o666)
+    }
+
+    /// Returns the default permission for a directory.
+    ///
+    /// The default permission is `0o755` (owner can read, write and execute,
+    /// group/others can read and execute).
+    pub const fn default_dir() -> Self {
+        Self(0o755)
+    }
+
+    /// Returns the underlying raw `st_mode` bits that contain the standard
+    /// Unix permissions for this file.
+    pub const fn mode(&self) -> u32 {
+        self.0 as u32
+    }
+
+    /// Returns a 9-bytes string representation of the permission.
+    ///
+    /// For example, `0o755` is represented as `rwxr-xr-x`.
+    pub const fn rwx_buf(&self) -> [u8; 9] {
+        let mut perm = [b'-'; 9];
+        let mut i = 0;
+        while i < 9 {
+            if self.0 & (0o400 >> i) != 0 {
+                perm[i] = b"rwx"[i % 3];
+            }
+            i += 1;
+        }
+        perm
+    }
+
+    /// Whether the owner has read permission.
+    pub const fn owner_readable(&self) -> bool {
+        self.contains(Self::OWNER_READ)
+    }
+
+    /// Whether the owner has write permission.
+    pub const fn owner_writable(&self) -> bool {
+        self.contains(Self::OWNER_WRITE)
+    }
+
+    /// Whether the owner has execute permission.
+    pub const fn owner_executable(&self) -> bool {
+        self.contains(Self::OWNER_EXEC)
+    }
+}
+
+/// Node (file/directory) type.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum VfsNodeType {
+    /// FIFO (named pipe)
+    Fifo = 0o1,
+    /// Character device
+    CharDevice = 0o2,
+    /// Directory
+    Dir = 0o4,
+    /// Block device
+    BlockDevice = 0o6,
+    /// Regular file
+    File = 0o10,
+    /// Symbolic link
+    SymLink = 0o12,
+    /// Socket
+    Socket = 0o14,
+}
+
+/// Directory entry.
+pub struct VfsDirEntry {
+    d_type: VfsNodeType,
+    d_name: [u8; 63],
+}
+
+impl VfsNodeType {
+    /// Tests whether this node type represents a regular file.
+    pub const fn is_file(self) -> bool {
+        matches!(self, Self::File)
+    }
+
+    /// Tests whether this node type represents a directory.
+    pub const fn is_dir(self) -> bool {
+        matches!(self, Self::Dir)
+    }
+
+    /// Tests whether this node type represents a symbolic link.
+    pub const fn is_symlink(self) -> bool {
+        matches!(self, Self::SymLink)
+    }
+
+    /// Returns a character representation of the node type.
+    ///
+    /// For example, `d` for directory, `-` for regular file, etc.
+    pub const fn as_char(self) -> char {
+        match self {
+            Self::Fifo => 'p',
+            Self::CharDevice => 'c',
+            Self::Dir => 'd',
+            Self::BlockDevice => 'b',
+            Self::File => '-',
+            Self::SymLink => 'l',
+            Self::Socket => 's',
+        }
+    }
+}
+
+impl VfsNodeAttr {
+    /// Creates a new `VfsNodeAttr` with the given permission mode, type, size
+    /// and number of blocks.
+    pub const fn new(mode: VfsNodePerm, ty: VfsNodeType, size: u64, blocks: u64) -> Self {
+        Self {
+            mode,
+            ty,
+            size,
+            blocks,
+        }
+    }
+
+    /// Creates a new `VfsNodeAttr` for a file, with the default file permission.
+    pub const fn new_file(size: u64, blocks: u64) -> Self {
+        Self {
+            mode: VfsNodePerm::default_file(),
+            ty: VfsNodeType::File,
+            size,
+            blocks,
+        }
+    }
+
+    /// Creates a new `VfsNodeAttr` for a directory, with the default directory
+    /// permission.
+    pub const fn new_dir(size: u64, blocks: u64) -> Self {
+        Self {
+            mode: VfsNodePerm::default_dir(),
+            ty: VfsNodeType::Dir,
+            size,
+            blocks,
+        }
+    }
+
+    /// Returns the size of the node.
+    pub const fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Returns the number of blocks the node occupies on the disk.
+    pub const fn blocks(&self) -> u64 {
+        self.blocks
+    }
+
+    /// Returns the permission of the node.
+    pub const fn perm(&self) -> VfsNodePerm {
+        self.mode
+    }
+
+    /// Sets the permission of the node.
+    pub fn set_perm(&mut self, perm: VfsNodePerm) {
+        self.mode = perm
+    }
+
+    /// Returns the type of the node.
+    pub const fn file_type(&self) -> VfsNodeType {
+        self.ty
+    }
+
+    /// Whether the node is a file.
+    pub const fn is_file(&self) -> bool {
+        self.ty.is_file()
+    }
+
+    /// Whether the node is a directory.
+    pub const fn is_dir(&self) -> bool {
+        self.ty.is_dir()
+    }
+}
+
+impl VfsDirEntry {
+    /// Creates an empty `VfsDirEntry`.
+    pub const fn default() -> Self {
+        Self {
+            d_type: VfsNodeType::File,
+            d_name: [0; 63],
+        }
+    }
+
+    /// Creates a new `VfsDirEntry` with the given name and type.
+    ///
+    /// Names longer than 63 bytes are truncated.
+    pub fn new(name: &str, ty: VfsNodeType) -> Self {
+        let mut d_name = [0; 63];
+        if name.len() > d_name.len() {
+            warn!(
+                "directory entry name too long: {} > {}",
+                name.len(),
+                d_name.len()
+            );
+        }
+        let len = name.len().min(d_name.len());
+        d_name[..len].copy_from_slice(&name.as_bytes()[..len]);
+        Self { d_type: ty, d_name }
+    }
+
+    /// Returns the type of the entry.
+    pub fn entry_type(&self) -> VfsNodeType {
+        self.d_type
+    }
+
+    /// Converts the name of the entry to a byte slice.
+    pub fn name_as_bytes(&self) -> &[u8] {
+        let len = self
+            .d_name
+            .iter()
+            .position(|&c| c == 0)
+            .unwrap_or(self.d_name.len());
+        &self.d_name[..len]
+    }
+}
@@ -0,0 +1,26 @@
+use crate::path::{canonicalize, split_path};
+
+#[test]
+fn test_path_canonicalize() {
+    assert_eq!(canonicalize(""), "");
+    assert_eq!(canonicalize("/"), "/");
+    assert_eq!(canonicalize("//"), "/");
+    assert_eq!(canonicalize("/."), "/");
+    assert_eq!(canonicalize("/.."), "/");
+    assert_eq!(canonicalize("/../.."), "/");
+    assert_eq!(canonicalize("/a/../b"), "/b");
+    assert_eq!(canonicalize("a/b/../../c"), "c");
+    assert_eq!(canonicalize("a/../../c"), "c");
+    assert_eq!(canonicalize("../a"), "a");
+    assert_eq!(canonicalize("/path/./to//foo/"), "/path/to/foo");
+}
+
+#[test]
+fn test_split_path() {
+    assert_eq!(split_path(""), ("", None));
+    assert_eq!(split_path("/"), ("", None));
+    assert_eq!(split_path("foo"), ("foo", None));
+    assert_eq!(split_path("foo/"), ("foo", None));
+    assert_eq!(split_path("/foo/bar//"), ("foo", Some("bar")));
+    assert_eq!(split_path("foo/bar/baz"), ("foo", Some("bar/baz")));
+}
@@ -0,0 +1,361 @@
+//! A stub of the [GDB remote serial protocol][rsp], for debugging the kernel
+//! from GDB.
+//!
+//! The stub runs on the target when it stops, at a breakpoint or after a
+//! single step: [`GdbStub::handle_stop`] talks to GDB over a [`Connection`]
+//! until GDB resumes the target, and returns how to resume it. What is
+//! debugged is behind the [`Target`] trait, so that the protocol is
+//! independent of the kernel, and tested on the host.
+//!
+//! It speaks the minimum GDB needs for RISC-V: reading and writing registers
+//! and memory, software breakpoints, continuing and single-stepping, and
+//! listing threads, which are tasks of the kernel. Registers are the 33 of
+//! the RV64 target description without floating point: `x0`-`x31` then `pc`.
+//!
+//! [rsp]: https://sourceware.org/gdb/current/onlinedocs/gdb.html/Remote-Protocol.html
+
+#![no_std]
+
+extern crate alloc;
+
+mod packet;
+pub mod riscv;
+
+#[cfg(test)]
+mod tests;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use packet::{decode_hex, encode_hex, parse_hex, PacketIo};
+
+/// The number of registers GDB reads and writes: `x0`-`x31` and `pc`.
+pub const NUM_REGS: usize = 33;
+
+/// The largest packet the stub accepts, which bounds memory reads too.
+const PACKET_SIZE: usize = 0x1000;
+
+/// The error number of memory accesses to bad addresses, `EFAULT`.
+const BAD_ADDRESS: u8 = 14;
+
+/// A byte stream to GDB, such as a serial port.
+pub trait Connection {
+    /// Waits for a byte from GDB.
+    fn read_byte(&mut self) -> u8;
+    /// Sends a byte to GDB.
+    fn write_byte(&mut self, byte: u8);
+}
+
+/// What is debugged.
+///
+/// Threads are identified by IDs that are not 0, as GDB reserves it.
+pub trait Target {
+    /// Returns the registers of the thread `tid`, or `None` if they can't be
+    /// read, for example if the thread runs on another CPU.
+    fn read_registers(&mut self, tid: u64) -> Option<[usize; NUM_REGS]>;
+    /// Writes the registers of the thread `tid`, and returns whether it can.
+    fn write_registers(&mut self, tid: u64, regs: &[usize; NUM_REGS]) -> bool;
+    /// Reads memory at `addr` into `buf`, and returns whether it can.
+    fn read_memory(&mut self, addr: usize, buf: &mut [u8]) -> bool;
+    /// Writes `data` to memory at `addr`, and returns whether it can.
+    fn write_memory(&mut self, addr: usize, data: &[u8]) -> bool;
+    /// Inserts a software breakpoint at `addr`, for an instruction of `kind`
+    /// bytes, and returns whether it can.
+    fn insert_breakpoint(&mut self, addr: usize, kind: usize) -> bool;
+    /// Removes the software breakpoint at `addr`, and returns whether there
+    /// was one.
+    fn remove_breakpoint(&mut self, addr: usize, kind: usize) -> bool;
+    /// Calls `f` with the ID of each thread.
+    fn for_each_thread(&mut self, f: &mut dyn FnMut(u64));
+    /// Returns a description of the thread `tid` for `info threads`, or
+    /// `None` if there is no such thread.
+    fn thread_info(&mut self, tid: u64) -> Option<String>;
+    /// Returns the ID of the thread that stopped.
+    fn stopped_thread(&self) -> u64;
+}
+
+/// How GDB asks to resume the target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resume {
+    /// Runs until the next breakpoint.
+    Continue,
+    /// Runs the next instruction of the stopped thread, and stops again.
+    Step,
+    /// Runs without the debugger, which has detached or killed the target.
+    Detach,
+}
+
+/// The GDB stub, which keeps the state of the session across stops.
+pub struct GdbStub<C> {
+    io: PacketIo<C>,
+    /// The thread of register accesses, chosen with `Hg`.
+    reg_thread: u64,
+    /// Whether GDB waits for a stop reply, having resumed the target.
+    resumed: bool,
+}
+
+impl<C: Connection> GdbStub<C> {
+    /// Creates a stub talking to GDB over `conn`.
+    pub const fn new(conn: C) -> Self {
+        Self {
+            io: PacketIo::new(conn),
+            reg_thread: 0,
+            resumed: false,
+        }
+    }
+
+    /// Returns the connection to GDB.
+    pub fn connection(&mut self) -> &mut C {
+        &mut self.io.conn
+    }
+
+    /// Handles a stop of `target` with `signal`, such as 5 (`SIGTRAP`) at
+    /// breakpoints, and returns when GDB resumes it.
+    ///
+    /// GDB is told of the stop if it resumed the target. Otherwise, it is
+    /// attaching and asks why the target stopped itself.
+    pub fn handle_stop(&mut self, target: &mut dyn Target, signal: u8) -> Resume {
+        self.reg_thread = target.stopped_thread();
+        if core::mem::take(&mut self.resumed) {
+            let reply = stop_reply(target, signal);
+            self.io.send(&reply);
+        }
+        loop {
+            let packet = self.io.recv();
+            let mut reply = Vec::new();
+            match self.handle_packet(target, signal, &packet, &mut reply) {
+                Ok(Some(Resume::Detach)) => {
+                    // `k` gets no reply.
+                    if packet.first() == Some(&b'D') {
+                        self.io.send(b"OK");
+                    }
+                    return Resume::Detach;
+                }
+                Ok(Some(resume)) => {
+                    self.resumed = true;
+                    return resume;
+                }
+                Ok(None) => self.io.send(&reply),
+                Err(errno) => {
+                    let mut reply = alloc::vec![b'E'];
+                    encode_hex(&mut reply, &[errno]);
+                    self.io.send(&reply);
+                }
+            }
+        }
+    }
+
+    /// Handles a packet, and either puts the reply in `reply`, or returns
+    /// how to resume the target. Unsupported packets get an empty reply,
+    /// and failed ones an error number.
+    fn handle_packet(
+        &mut self,
+        target: &mut dyn Target,
+        signal: u8,
+        packet: &[u8],
+        reply: &mut Vec<u8>,
+    ) -> Result<Option<Resume>, u8> {
+        let Some((&cmd, args)) = packet.split_first() else {
+            return Ok(None);
+        };
+        match cmd {
+            b'?' => *reply = stop_reply(target, signal),
+            b'q' => handle_query(target, args, reply),
+            b'H' => match args.split_first() {
+                Some((b'g', tid)) => {
+                    self.reg_thread = thread_id(target, tid)?;
+                    reply.extend_from_slice(b"OK");
+                }
+                // All threads resume together.
+                Some((b'c', _)) => reply.extend_from_slice(b"OK"),
+                _ => {}
+            },
+            b'T' => {
+                target.thread_info(thread_id(target, args)?).ok_or(1)?;
+                reply.extend_from_slice(b"OK");
+            }
+            b'g' => {
+                let regs = target.read_registers(self.reg_thread).ok_or(1)?;
+                for reg in regs {
+                    encode_hex(reply, &reg.to_le_bytes());
+                }
+            }
+            b'G' => {
+                let regs = decode_regs(args).ok_or(2)?;
+                write_registers(target, self.reg_thread, &regs)?;
+                reply.extend_from_slice(b"OK");
+            }
+            b'p' => {
+                let n = parse_hex(args).ok_or(2)?;
+                let regs = target.read_registers(self.reg_thread).ok_or(1)?;
+                match regs.get(n) {
+                    Some(reg) => encode_hex(reply, &reg.to_le_bytes()),
+                    // Registers the stub does not know are unavailable.
+                    None => reply.resize(2 * core::mem::size_of::<usize>(), b'x'),
+                }
+            }
+            b'P' => {
+                let (n, val) = split(args, b'=').ok_or(2)?;
+                let n = parse_hex(n).ok_or(2)?;
+                let val = decode_hex(val)
+                    .and_then(|bytes| Some(usize::from_le_bytes(bytes.try_into().ok()?)))
+                    .ok_or(2)?;
+                let mut regs = target.read_registers(self.reg_thread).ok_or(1)?;
+                *regs.get_mut(n).ok_or(2)? = val;
+                write_registers(target, self.reg_thread, &regs)?;
+                reply.extend_from_slice(b"OK");
+            }
+            b'm' => {
+                let (addr, len) = parse_addr_len(args).ok_or(2)?;
+                // Each byte takes two in the reply.
+                let mut buf = alloc::vec![0; len.min(PACKET_SIZE / 2 - 8)];
+                if !target.read_memory(addr, &mut buf) {
+                    return Err(BAD_ADDRESS);
+                }
+                encode_hex(reply, &buf);
+            }
+            b'M' => {
+                let (addr_len, data) = split(args, b':').ok_or(2)?;
+                let (addr, len) = parse_addr_len(addr_len).ok_or(2)?;
+                let data = decode_hex(data).filter(|data| data.len() == len).ok_or(2)?;
+                if !target.write_memory(addr, &data) {
+                    return Err(BAD_ADDRESS);
+                }
+                reply.extend_from_slice(b"OK");
+            }
+            b'Z' | b'z' => {
+                let mut fields = args.split(|&b| b == b',');
+                // Only software breakpoints, type 0, are supported.
+                if fields.next() != Some(b"0") {
+                    return Ok(None);
+                }
+                let addr = fields.next().and_then(parse_hex).ok_or(2)?;
+                let kind = fields.next().and_then(parse_hex).ok_or(2)?;
+                let done = if cmd == b'Z' {
+                    target.insert_breakpoint(addr, kind)
+                } else {
+                    target.remove_breakpoint(addr, kind)
+                };
+                if !done {
+                    return Err(1);
+                }
+                reply.extend_from_slice(b"OK");
+            }
+            b'c' | b's' => {
+                // Resumes at the address, if given.
+                if !args.is_empty() {
+                    let pc = parse_hex(args).ok_or(2)?;
+                    let tid = target.stopped_thread();
+                    let mut regs = target.read_registers(tid).ok_or(1)?;
+                    regs[NUM_REGS - 1] = pc;
+                    write_registers(target, tid, &regs)?;
+                }
+                return Ok(Some(if cmd == b'c' { Resume::Continue } else { Resume::Step }));
+            }
+            b'D' | b'k' => return Ok(Some(Resume::Detach)),
+            _ => {}
+        }
+        Ok(None)
+    }
+}
+
+/// Handles a `q` packet, without the `q`.
+fn handle_query(target: &mut dyn Target, query: &[u8], reply: &mut Vec<u8>) {
+    let (name, args) = query
+        .iter()
+        .position(|&b| b == b':' || b == b',')
+        .map_or((query, &[][..]), |pos| (&query[..pos], &query[pos + 1..]));
+    match name {
+        b"Supported" => {
+            reply.extend_from_slice(b"PacketSize=");
+            push_hex(reply, PACKET_SIZE as u64);
+        }
+        // The target existed before GDB, which detaches rather than kills.
+        b"Attached" => reply.push(b'1'),
+        b"C" => {
+            reply.extend_from_slice(b"QC");
+            push_hex(reply, target.stopped_thread());
+        }
+        b"fThreadInfo" => {
+            reply.push(b'm');
+            target.for_each_thread(&mut |tid| {
+                push_hex(reply, tid);
+                reply.push(b',');
+            });
+            reply.pop();
+        }
+        // All threads are in the first reply.
+        b"sThreadInfo" => reply.push(b'l'),
+        b"ThreadExtraInfo" => match parse_tid(args).and_then(|tid| target.thread_info(tid)) {
+            Some(info) => encode_hex(reply, info.as_bytes()),
+            None => reply.extend_from_slice(b"E01"),
+        },
+        _ => {}
+    }
+}
+
+/// Returns the reply telling GDB that the target stopped with `signal`.
+fn stop_reply(target: &dyn Target, signal: u8) -> Vec<u8> {
+    let mut reply = alloc::vec![b'T'];
+    encode_hex(&mut reply, &[signal]);
+    reply.extend_from_slice(b"thread:");
+    push_hex(&mut reply, target.stopped_thread());
+    reply.push(b';');
+    reply
+}
+
+fn write_registers(target: &mut dyn Target, tid: u64, regs: &[usize; NUM_REGS]) -> Result<(), u8> {
+    if target.write_registers(tid, regs) {
+        Ok(())
+    } else {
+        Err(1)
+    }
+}
+
+/// Decodes the registers of a `G` packet, which may leave out the last ones.
+fn decode_regs(data: &[u8]) -> Option<[usize; NUM_REGS]> {
+    const REG_BYTES: usize = core::mem::size_of::<usize>();
+    let bytes = decode_hex(data)?;
+    if bytes.len() % REG_BYTES != 0 || bytes.len() > NUM_REGS * REG_BYTES {
+        return None;
+    }
+    let mut regs = [0; NUM_REGS];
+    for (reg, chunk) in regs.iter_mut().zip(bytes.chunks(REG_BYTES)) {
+        *reg = usize::from_le_bytes(chunk.try_into().unwrap());
+    }
+    Some(regs)
+}
+
+/// Appends `n` in hex, without leading zeros.
+fn push_hex(out: &mut Vec<u8>, n: u64) {
+    let digits = (u64::BITS - n.leading_zeros()).div_ceil(4).max(1);
+    for i in (0..digits).rev() {
+        out.push(b"0123456789abcdef"[(n >> (i * 4)) as usize & 0xf]);
+    }
+}
+
+/// Splits `s` at the first `sep`.
+fn split(s: &[u8], sep: u8) -> Option<(&[u8], &[u8])> {
+    let pos = s.iter().position(|&b| b == sep)?;
+    Some((&s[..pos], &s[pos + 1..]))
+}
+
+/// Parses `addr,len`.
+fn parse_addr_len(s: &[u8]) -> Option<(usize, usize)> {
+    let (addr, len) = split(s, b',')?;
+    Some((parse_hex(addr)?, parse_hex(len)?))
+}
+
+/// Parses a thread ID, which is neither `0` (any thread) nor `-1` (all).
+fn parse_tid(s: &[u8]) -> Option<u64> {
+    parse_hex(s).map(|tid| tid as u64).filter(|&tid| tid != 0)
+}
+
+/// Parses the thread ID of `H` and `T`, where any or all threads stand for
+/// the stopped one.
+fn thread_id(target: &dyn Target, s: &[u8]) -> Result<u64, u8> {
+    if s == b"-1" || s == b"0" {
+        return Ok(target.stopped_thread());
+    }
+    parse_tid(s).ok_or(2)
+}
@@ -0,0 +1,111 @@
+//! Framing of packets, `$<data>#<checksum>`, and hex encoding.
+
+use alloc::vec::Vec;
+
+use crate::Connection;
+
+/// The byte GDB sends to interrupt the target, outside of packets.
+const INTERRUPT: u8 = 0x03;
+
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, &b| sum.wrapping_add(b))
+}
+
+pub(crate) fn hex_digit(c: u8) -> Option<u8> {
+    match c {
+        b'0'..=b'9' => Some(c - b'0'),
+        b'a'..=b'f' => Some(c - b'a' + 10),
+        b'A'..=b'F' => Some(c - b'A' + 10),
+        _ => None,
+    }
+}
+
+/// Parses a hex number, as addresses and lengths are sent.
+pub(crate) fn parse_hex(s: &[u8]) -> Option<usize> {
+    if s.is_empty() || s.len() > 2 * core::mem::size_of::<usize>() {
+        return None;
+    }
+    s.iter()
+        .try_fold(0, |n, &c| Some(n << 4 | hex_digit(c)? as usize))
+}
+
+/// Decodes hex-encoded bytes.
+pub(crate) fn decode_hex(s: &[u8]) -> Option<Vec<u8>> {
+    if s.len() % 2 != 0 {
+        return None;
+    }
+    s.chunks(2)
+        .map(|pair| Some(hex_digit(pair[0])? << 4 | hex_digit(pair[1])?))
+        .collect()
+}
+
+pub(crate) fn encode_hex(out: &mut Vec<u8>, bytes: &[u8]) {
+    const DIGITS: &[u8; 16] = b"0123456789abcdef";
+    for &b in bytes {
+        out.push(DIGITS[(b >> 4) as usize]);
+        out.push(DIGITS[(b & 0xf) as usize]);
+    }
+}
+
+/// Reads packets from GDB and sends replies, with acknowledgments.
+pub(crate) struct PacketIo<C> {
+    pub conn: C,
+    /// The last reply, sent again if GDB asks for it.
+    last_reply: Vec<u8>,
+}
+
+impl<C: Connection> PacketIo<C> {
+    pub const fn new(conn: C) -> Self {
+        Self {
+            conn,
+            last_reply: Vec::new(),
+        }
+    }
+
+    /// Waits for the next valid packet, and returns its data.
+    ///
+    /// Acknowledgments and interrupts from GDB are skipped, except that a
+    /// negative one resends the last reply.
+    pub fn recv(&mut self) -> Vec<u8> {
+        loop {
+            match self.conn.read_byte() {
+                b'$' => {}
+                b'-' => {
+                    let reply = core::mem::take(&mut self.last_reply);
+                    self.send(&reply);
+                    continue;
+                }
+                // `+`, interrupts while already stopped, and noise.
+                _ => continue,
+            }
+            let mut data = Vec::new();
+            loop {
+                match self.conn.read_byte() {
+                    b'#' => break,
+                    INTERRUPT => {}
+                    b => data.push(b),
+                }
+            }
+            let high = hex_digit(self.conn.read_byte());
+            let low = hex_digit(self.conn.read_byte());
+            match (high, low) {
+                (Some(high), Some(low)) if high << 4 | low == checksum(&data) => {
+                    self.conn.write_byte(b'+');
+                    return data;
+                }
+                _ => self.conn.write_byte(b'-'),
+            }
+        }
+    }
+
+    pub fn send(&mut self, data: &[u8]) {
+        let mut trailer = alloc::vec![b'#'];
+        encode_hex(&mut trailer, &[checksum(data)]);
+        self.conn.write_byte(b'$');
+        for &b in data.iter().chain(&trailer) {
+            self.conn.write_byte(b);
+        }
+        self.last_reply.clear();
+        self.last_reply.extend_from_slice(data);
+    }
+}
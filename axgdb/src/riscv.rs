@@ -0,0 +1,122 @@
+//! RISC-V instructions the stub needs to know: breakpoints, and where
+//! control flow goes for single-stepping, which has no hardware support in
+//! supervisor mode.
+
+/// `ebreak`.
+pub const EBREAK: u32 = 0x0010_0073;
+/// `c.ebreak`.
+pub const C_EBREAK: u16 = 0x9002;
+
+/// Returns the length in bytes of the instruction whose lower 16 bits are
+/// `low`: 2 for compressed instructions, 4 otherwise.
+pub const fn inst_len(low: u16) -> usize {
+    if low & 0b11 == 0b11 {
+        4
+    } else {
+        2
+    }
+}
+
+/// Returns whether `inst` is a breakpoint, of either length.
+pub fn is_ebreak(inst: u32) -> bool {
+    match inst_len(inst as u16) {
+        2 => inst as u16 == C_EBREAK,
+        _ => inst == EBREAK,
+    }
+}
+
+/// Returns bits `hi..=lo` of `inst`, shifted to `to`.
+const fn bits(inst: u32, hi: u32, lo: u32, to: u32) -> usize {
+    (((inst >> lo) & ((1 << (hi - lo + 1)) - 1)) as usize) << to
+}
+
+/// Sign-extends the `width`-bit `imm`.
+const fn sext(imm: usize, width: u32) -> usize {
+    let shift = usize::BITS - width;
+    ((imm << shift) as isize >> shift) as usize
+}
+
+/// Returns the address of the instruction that runs after `inst` at `pc`,
+/// with the registers `x0`-`x31` in `regs`.
+pub fn next_pc(inst: u32, pc: usize, regs: &[usize; 32]) -> usize {
+    let len = inst_len(inst as u16);
+    let fallthrough = pc.wrapping_add(len);
+    if len == 2 {
+        return next_pc_compressed(inst, pc, regs).unwrap_or(fallthrough);
+    }
+
+    let rs1 = regs[bits(inst, 19, 15, 0)];
+    let rs2 = regs[bits(inst, 24, 20, 0)];
+    match inst & 0x7f {
+        // jal
+        0x6f => {
+            let imm = bits(inst, 31, 31, 20)
+                | bits(inst, 19, 12, 12)
+                | bits(inst, 20, 20, 11)
+                | bits(inst, 30, 21, 1);
+            pc.wrapping_add(sext(imm, 21))
+        }
+        // jalr
+        0x67 => rs1.wrapping_add(sext(bits(inst, 31, 20, 0), 12)) & !1,
+        // branches
+        0x63 => {
+            let taken = match bits(inst, 14, 12, 0) {
+                0 => rs1 == rs2,
+                1 => rs1 != rs2,
+                4 => (rs1 as isize) < (rs2 as isize),
+                5 => (rs1 as isize) >= (rs2 as isize),
+                6 => rs1 < rs2,
+                7 => rs1 >= rs2,
+                _ => false,
+            };
+            if !taken {
+                return fallthrough;
+            }
+            let imm = bits(inst, 31, 31, 12)
+                | bits(inst, 7, 7, 11)
+                | bits(inst, 30, 25, 5)
+                | bits(inst, 11, 8, 1);
+            pc.wrapping_add(sext(imm, 13))
+        }
+        _ => fallthrough,
+    }
+}
+
+/// Returns where a compressed jump or branch goes, or `None` for other
+/// instructions and branches not taken.
+fn next_pc_compressed(inst: u32, pc: usize, regs: &[usize; 32]) -> Option<usize> {
+    let funct3 = bits(inst, 15, 13, 0);
+    match (inst & 0b11, funct3) {
+        // c.j
+        (0b01, 5) => {
+            let imm = bits(inst, 12, 12, 11)
+                | bits(inst, 11, 11, 4)
+                | bits(inst, 10, 9, 8)
+                | bits(inst, 8, 8, 10)
+                | bits(inst, 7, 7, 6)
+                | bits(inst, 6, 6, 7)
+                | bits(inst, 5, 3, 1)
+                | bits(inst, 2, 2, 5);
+            Some(pc.wrapping_add(sext(imm, 12)))
+        }
+        // c.beqz, c.bnez
+        (0b01, 6 | 7) => {
+            let rs1 = regs[bits(inst, 9, 7, 0) + 8];
+            if (rs1 == 0) != (funct3 == 6) {
+                return None;
+            }
+            let imm = bits(inst, 12, 12, 8)
+                | bits(inst, 11, 10, 3)
+                | bits(inst, 6, 5, 6)
+                | bits(inst, 4, 3, 1)
+                | bits(inst, 2, 2, 5);
+            Some(pc.wrapping_add(sext(imm, 9)))
+        }
+        // c.jr, c.jalr, but not c.mv, c.add or c.ebreak
+        (0b10, 4) => {
+            let rs1 = bits(inst, 11, 7, 0);
+            (rs1 != 0 && bits(inst, 6, 2, 0) == 0).then(|| regs[rs1])
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,317 @@
+extern crate std;
+
+use alloc::collections::{BTreeSet, VecDeque};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::packet::{encode_hex, PacketIo};
+use crate::riscv::{is_ebreak, next_pc};
+use crate::{Connection, GdbStub, Resume, Target, NUM_REGS};
+
+/// Bytes from GDB, scripted, and the bytes sent to it.
+#[derive(Default)]
+struct Script {
+    input: VecDeque<u8>,
+    output: Vec<u8>,
+}
+
+impl Connection for Script {
+    fn read_byte(&mut self) -> u8 {
+        self.input.pop_front().expect("GDB has nothing more to say")
+    }
+
+    fn write_byte(&mut self, byte: u8) {
+        self.output.push(byte);
+    }
+}
+
+/// Frames `data` as a packet.
+fn packet(data: &str) -> String {
+    let sum = data.bytes().fold(0u8, |sum, b| sum.wrapping_add(b));
+    format!("${data}#{sum:02x}")
+}
+
+/// Returns the packets in `output`, without acknowledgments.
+fn packets(output: &[u8]) -> Vec<String> {
+    let output = core::str::from_utf8(output).unwrap();
+    output
+        .split('$')
+        .skip(1)
+        .map(|p| String::from(p.split('#').next().unwrap()))
+        .collect()
+}
+
+const MEM_BASE: usize = 0x1000;
+
+/// Two threads, 1 stopped and 2 switched out, and 256 bytes of memory.
+struct MockTarget {
+    regs: [[usize; NUM_REGS]; 2],
+    mem: [u8; 256],
+    breakpoints: BTreeSet<usize>,
+}
+
+impl MockTarget {
+    fn new() -> Self {
+        let mut regs = [[0; NUM_REGS]; 2];
+        for (i, reg) in regs[0].iter_mut().enumerate() {
+            *reg = i;
+        }
+        regs[0][NUM_REGS - 1] = 0x8020_0000;
+        regs[1][1] = 0x8020_1234;
+        Self {
+            regs,
+            mem: core::array::from_fn(|i| i as u8),
+            breakpoints: BTreeSet::new(),
+        }
+    }
+
+    fn mem_range(&self, addr: usize, len: usize) -> Option<core::ops::Range<usize>> {
+        let start = addr.checked_sub(MEM_BASE)?;
+        (start + len <= self.mem.len()).then_some(start..start + len)
+    }
+}
+
+impl Target for MockTarget {
+    fn read_registers(&mut self, tid: u64) -> Option<[usize; NUM_REGS]> {
+        self.regs.get(tid as usize - 1).copied()
+    }
+
+    fn write_registers(&mut self, tid: u64, regs: &[usize; NUM_REGS]) -> bool {
+        // Only the stopped thread can be changed.
+        if tid != 1 {
+            return false;
+        }
+        self.regs[0] = *regs;
+        true
+    }
+
+    fn read_memory(&mut self, addr: usize, buf: &mut [u8]) -> bool {
+        let Some(range) = self.mem_range(addr, buf.len()) else {
+            return false;
+        };
+        buf.copy_from_slice(&self.mem[range]);
+        true
+    }
+
+    fn write_memory(&mut self, addr: usize, data: &[u8]) -> bool {
+        let Some(range) = self.mem_range(addr, data.len()) else {
+            return false;
+        };
+        self.mem[range].copy_from_slice(data);
+        true
+    }
+
+    fn insert_breakpoint(&mut self, addr: usize, _kind: usize) -> bool {
+        self.mem_range(addr, 2).is_some() && self.breakpoints.insert(addr)
+    }
+
+    fn remove_breakpoint(&mut self, addr: usize, _kind: usize) -> bool {
+        self.breakpoints.remove(&addr)
+    }
+
+    fn for_each_thread(&mut self, f: &mut dyn FnMut(u64)) {
+        f(1);
+        f(2);
+    }
+
+    fn thread_info(&mut self, tid: u64) -> Option<String> {
+        match tid {
+            1 => Some(String::from("main")),
+            2 => Some(String::from("idle")),
+            _ => None,
+        }
+    }
+
+    fn stopped_thread(&self) -> u64 {
+        1
+    }
+}
+
+/// Runs the stub on a stop of `target` with the packets `from_gdb`, and
+/// returns how it resumes and its replies.
+fn session(target: &mut MockTarget, from_gdb: &[&str]) -> (Resume, Vec<String>) {
+    let mut stub = GdbStub::new(Script::default());
+    for p in from_gdb {
+        stub.connection().input.extend(packet(p).bytes());
+    }
+    let resume = stub.handle_stop(target, 5);
+    (resume, packets(&stub.connection().output))
+}
+
+#[test]
+fn test_packet_framing() {
+    let mut io = PacketIo::new(Script::default());
+    // Noise and an acknowledgment, a corrupted packet, then a good one.
+    io.conn.input.extend(b"+x$g#00$m1,2#fc");
+    assert_eq!(io.recv(), b"m1,2");
+    assert_eq!(io.conn.output, b"-+");
+
+    io.send(b"OK");
+    assert_eq!(io.conn.output, b"-+$OK#9a");
+    // A negative acknowledgment resends the reply.
+    io.conn.input.extend(b"-$?#3f");
+    assert_eq!(io.recv(), b"?");
+    assert_eq!(&io.conn.output[8..], b"$OK#9a+");
+}
+
+#[test]
+fn test_queries_and_threads() {
+    let mut target = MockTarget::new();
+    let (resume, replies) = session(
+        &mut target,
+        &[
+            "qSupported:multiprocess+;swbreak+",
+            "qAttached",
+            "?",
+            "qC",
+            "qfThreadInfo",
+            "qsThreadInfo",
+            "qThreadExtraInfo,2",
+            "T2",
+            "T3",
+            "vMustReplyEmpty",
+            "c",
+        ],
+    );
+    assert_eq!(resume, Resume::Continue);
+    assert_eq!(
+        replies,
+        [
+            "PacketSize=1000",
+            "1",
+            "T05thread:1;",
+            "QC1",
+            "m1,2",
+            "l",
+            "69646c65",
+            "OK",
+            "E01",
+            "",
+        ]
+    );
+}
+
+#[test]
+fn test_registers() {
+    let mut target = MockTarget::new();
+    let mut pc = String::new();
+    let mut bytes = Vec::new();
+    encode_hex(&mut bytes, &0x8020_0000_usize.to_le_bytes());
+    pc.push_str(core::str::from_utf8(&bytes).unwrap());
+
+    let (_, replies) = session(
+        &mut target,
+        &[
+            "p20",
+            "p21",
+            "P5=efbeadde00000000",
+            "p5",
+            "Hg2",
+            "p1",
+            "P1=0000000000000000",
+            "Hg0",
+            "g",
+            "s",
+        ],
+    );
+    assert_eq!(replies[0], pc);
+    assert_eq!(replies[1], "xxxxxxxxxxxxxxxx");
+    assert_eq!(replies[2], "OK");
+    assert_eq!(replies[3], "efbeadde00000000");
+    assert_eq!(replies[4], "OK");
+    assert_eq!(replies[5], "3412208000000000");
+    // Switched-out threads are read-only here.
+    assert_eq!(replies[6], "E01");
+    assert_eq!(replies[7], "OK");
+    assert_eq!(replies[8].len(), NUM_REGS * 16);
+    assert!(replies[8].ends_with(&pc));
+    assert_eq!(target.regs[0][5], 0xdead_beef);
+
+    // `G` writes all registers, and `c` with an address sets `pc`.
+    let mut regs = String::new();
+    let mut bytes = Vec::new();
+    for i in 0..NUM_REGS {
+        encode_hex(&mut bytes, &(i * 2).to_le_bytes());
+    }
+    regs.push_str(core::str::from_utf8(&bytes).unwrap());
+    let (resume, replies) = session(&mut target, &[&format!("G{regs}"), "c80200100"]);
+    assert_eq!(resume, Resume::Continue);
+    assert_eq!(replies, ["OK"]);
+    assert_eq!(target.regs[0][31], 62);
+    assert_eq!(target.regs[0][NUM_REGS - 1], 0x8020_0100);
+}
+
+#[test]
+fn test_memory_and_breakpoints() {
+    let mut target = MockTarget::new();
+    let (resume, replies) = session(
+        &mut target,
+        &[
+            "m1004,4",
+            "mff0,4",
+            "M1010,3:aabbcc",
+            "M1010,2:aabbcc",
+            "m100f,5",
+            "Z0,1020,4",
+            "Z0,1020,4",
+            "Z1,1020,4",
+            "z0,1020,4",
+            "z0,1020,4",
+            "D",
+        ],
+    );
+    assert_eq!(resume, Resume::Detach);
+    assert_eq!(
+        replies,
+        ["04050607", "E0e", "OK", "E02", "0faabbcc13", "OK", "E01", "", "OK", "E01", "OK"]
+    );
+    assert!(target.breakpoints.is_empty());
+}
+
+#[test]
+fn test_stop_reply_after_resume() {
+    let mut target = MockTarget::new();
+    let mut stub = GdbStub::new(Script::default());
+    stub.connection().input.extend(packet("s").bytes());
+    assert_eq!(stub.handle_stop(&mut target, 5), Resume::Step);
+    assert!(packets(&stub.connection().output).is_empty());
+
+    // GDB waits for the stop after the step.
+    stub.connection().input.extend(packet("k").bytes());
+    assert_eq!(stub.handle_stop(&mut target, 5), Resume::Detach);
+    assert_eq!(packets(&stub.connection().output), ["T05thread:1;"]);
+}
+
+#[test]
+fn test_next_pc() {
+    let pc = 0x8020_0000;
+    let mut regs = [0; 32];
+    regs[9] = 1; // s1
+    regs[10] = 0x8030_0000; // a0
+    regs[11] = 0x8030_0000; // a1
+
+    // Branches, taken and not.
+    assert_eq!(next_pc(0x00b50863, pc, &regs), pc + 16); // beq a0, a1, 16
+    assert_eq!(next_pc(0xfeb51ce3, pc, &regs), pc + 4); // bne a0, a1, -8
+    assert_eq!(next_pc(0x00b540e3, pc, &regs), pc + 4); // blt a0, a1, 2048
+    assert_eq!(next_pc(0x80b57063, pc, &regs), pc - 4096); // bgeu a0, a1, -4096
+    // Jumps.
+    assert_eq!(next_pc(0x000010ef, pc, &regs), pc + 4096); // jal ra, 4096
+    assert_eq!(next_pc(0x008500e7, pc, &regs), 0x8030_0008); // jalr ra, 8(a0)
+    assert_eq!(next_pc(0xfff58067, pc, &regs), 0x8030_0000 - 2); // jalr zero, -1(a1)
+    assert_eq!(next_pc(0xbffd, pc, &regs), pc - 2); // c.j -2
+    assert_eq!(next_pc(0xa095, pc, &regs), pc + 100); // c.j 100
+    assert_eq!(next_pc(0xb001, pc, &regs), pc - 2048); // c.j -2048
+    assert_eq!(next_pc(0xcd19, pc, &regs), pc + 2); // c.beqz a0, 30
+    assert_eq!(next_pc(0xf081, pc, &regs), pc - 256); // c.bnez s1, -256
+    assert_eq!(next_pc(0x8502, pc, &regs), 0x8030_0000); // c.jr a0
+    assert_eq!(next_pc(0x9582, pc, &regs), 0x8030_0000); // c.jalr a1
+    // Others fall through.
+    assert_eq!(next_pc(0x9002, pc, &regs), pc + 2); // c.ebreak
+    assert_eq!(next_pc(0x00b50533, pc, &regs), pc + 4); // add a0, a0, a1
+
+    assert!(is_ebreak(0x0010_0073));
+    assert!(is_ebreak(0x9002));
+    assert!(!is_ebreak(0x0000_9582));
+}
@@ -0,0 +1,22 @@
+//! Generates the linker script of the platform from `linker.lds.S`.
+//!
+//! It is written to `OUT_DIR`, which is added to the library search path of
+//! the final link, so the kernel links with `-T linker.lds`.
+
+use std::path::Path;
+
+fn main() {
+    let out_dir = std::env::var("OUT_DIR").unwrap();
+    gen_linker_script(&Path::new(&out_dir).join("linker.lds")).unwrap();
+    println!("cargo:rustc-link-search={}", out_dir);
+    println!("cargo:rerun-if-changed=linker.lds.S");
+}
+
+fn gen_linker_script(fname: &Path) -> std::io::Result<()> {
+    let ld_content = std::fs::read_to_string("linker.lds.S")?;
+    let ld_content = ld_content.replace(
+        "%KERNEL_BASE%",
+        &format!("{:#x}", axconfig::KERNEL_BASE_VADDR),
+    );
+    std::fs::write(fname, ld_content)
+}
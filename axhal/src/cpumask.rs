@@ -0,0 +1,52 @@
+//! Sets of CPUs, such as the CPUs a task may run on, or the targets of IPIs.
+
+use axconfig::SMP;
+
+/// A set of CPUs, one bit for each CPU ID.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct CpuMask(u64);
+
+impl CpuMask {
+    /// All the CPUs.
+    pub const fn full() -> Self {
+        Self(u64::MAX >> (u64::BITS as usize - SMP))
+    }
+
+    /// Only the CPU `cpu_id`, or none if it is not present.
+    pub const fn one(cpu_id: usize) -> Self {
+        if cpu_id < SMP {
+            Self(1 << cpu_id)
+        } else {
+            Self(0)
+        }
+    }
+
+    /// The CPUs whose bits are set, ignoring the CPUs not present.
+    pub const fn from_bits(bits: u64) -> Self {
+        Self(bits & Self::full().0)
+    }
+
+    pub const fn bits(&self) -> u64 {
+        self.0
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub const fn contains(&self, cpu_id: usize) -> bool {
+        cpu_id < SMP && self.0 & (1 << cpu_id) != 0
+    }
+
+    /// Iterates over the IDs of the CPUs in the set.
+    pub fn iter(&self) -> impl Iterator<Item = usize> {
+        let bits = self.0;
+        (0..SMP).filter(move |&cpu_id| bits & (1 << cpu_id) != 0)
+    }
+}
+
+impl Default for CpuMask {
+    fn default() -> Self {
+        Self::full()
+    }
+}
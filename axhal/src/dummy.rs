@@ -15,9 +15,10 @@ pub mod time {
 
 pub mod misc {
     /// Shutdown the whole system, including all CPUs.
-    pub fn terminate() -> ! {
+    pub fn terminate(_exit_code: i32) -> ! {
         unimplemented!()
     }
+    pub fn wait_for_irqs() {}
 }
 
 pub mod mem {
@@ -34,7 +35,7 @@ pub mod context {
             Self
         }
 
-        pub fn init(&mut self, _entry: usize, _kstack_top: usize) {
+        pub fn init(&mut self, _entry: usize, _kstack_top: usize, _tls_area: usize) {
             unimplemented!();
         }
 
@@ -50,8 +51,21 @@ pub mod cpu {
         unimplemented!()
     }
     pub unsafe fn set_current_task_ptr<T>(_ptr: *const T) {}
+    pub fn this_cpu_id() -> usize {
+        0
+    }
+}
+
+#[cfg(all(feature = "smp", feature = "irq"))]
+pub mod ipi {
+    pub enum IpiMessage {
+        Kick,
+    }
+    pub fn send(_cpu_id: usize, _msg: IpiMessage) {}
 }
 
+#[cfg(feature = "irq")]
 pub mod irq {
     pub fn enable_irqs() {}
+    pub fn disable_irqs() {}
 }
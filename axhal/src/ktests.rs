@@ -0,0 +1,56 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use axtest::ax_test;
+use kernel_guard::IrqSave;
+
+use crate::irq::{self, HandlerFn};
+
+/// An external IRQ no device of the platforms uses.
+const UNUSED_IRQ: usize = 60;
+
+#[ax_test]
+fn test_dispatch_external_irq() {
+    static SEEN: AtomicUsize = AtomicUsize::new(0);
+    static HANDLER: &(dyn Fn(usize) + Sync) = &|irq_num| SEEN.store(irq_num, Ordering::Relaxed);
+    let handler = HandlerFn::Closure(HANDLER);
+
+    let count = irq::irq_stats(UNUSED_IRQ).unwrap().count();
+    assert!(irq::register_handler_fn(UNUSED_IRQ, handler));
+    {
+        // What the handler of S_EXT does with the IRQ claimed from the PLIC,
+        // as the source can't be raised here.
+        let _guard = IrqSave::new();
+        irq::dispatch_irq_common(UNUSED_IRQ);
+    }
+    assert_eq!(SEEN.load(Ordering::Relaxed), UNUSED_IRQ);
+    assert_eq!(irq::irq_stats(UNUSED_IRQ).unwrap().count(), count + 1);
+    assert!(irq::unregister_handler(UNUSED_IRQ, handler));
+    // Source 0 means there is no IRQ to claim.
+    assert!(!irq::register_handler_fn(0, handler));
+}
+
+#[cfg(feature = "nested-irq")]
+#[ax_test]
+fn test_external_irq_nests() {
+    use riscv::register::{sie, sstatus};
+
+    /// Whether IRQs were enabled in the handler, and only external ones.
+    static NESTED: AtomicUsize = AtomicUsize::new(0);
+    fn handler() {
+        let only_ext = sie::read().sext() && !sie::read().stimer() && !sie::read().ssoft();
+        NESTED.store((sstatus::read().sie() && only_ext) as usize, Ordering::Relaxed);
+    }
+
+    assert!(!irq::set_priority(UNUSED_IRQ, irq::MAX_IRQ_PRIORITY + 1));
+    assert!(irq::set_priority(UNUSED_IRQ, 3));
+    assert!(irq::register_handler(UNUSED_IRQ, handler));
+    {
+        let _guard = IrqSave::new();
+        irq::dispatch_irq_common(UNUSED_IRQ);
+        // Masked again on return.
+        assert!(!sstatus::read().sie());
+        assert!(sie::read().stimer());
+    }
+    assert_eq!(NESTED.load(Ordering::Relaxed), 1);
+    assert!(irq::unregister_handler(UNUSED_IRQ, (handler as fn()).into()));
+}
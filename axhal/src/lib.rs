@@ -3,7 +3,7 @@
 #![feature(naked_functions)]
 #![feature(const_maybe_uninit_zeroed)]
 #![feature(const_option)]
-#![feature(doc_auto_cfg)]
+#![feature(doc_cfg)]
 
 #[cfg(target_arch = "riscv64")]
 mod riscv64;
@@ -14,3 +14,11 @@ pub use self::riscv64::*;
 mod dummy;
 #[cfg(not(target_arch = "riscv64"))]
 pub use self::dummy::*;
+
+pub mod cpumask;
+
+#[cfg(all(ktest, feature = "irq"))]
+mod ktests;
+
+#[cfg(any(feature = "tls", feature = "smp"))]
+extern crate alloc;
@@ -1,7 +1,11 @@
 mod boot;
 
 mod context;
-pub use context::{TaskContext};
+mod fp;
+pub use context::{GeneralRegisters, TaskContext, TrapFrame};
+pub use fp::FpState;
+#[cfg(feature = "vector")]
+pub use fp::VectorState;
 
 pub mod paging;
 pub mod console;
@@ -9,8 +13,16 @@ pub mod time;
 pub mod misc;
 pub mod mem;
 pub mod cpu;
+pub mod backtrace;
 pub mod trap;
+#[cfg(feature = "tls")]
+pub mod tls;
+#[cfg(feature = "irq")]
 pub mod irq;
+#[cfg(feature = "smp")]
+pub mod mp;
+#[cfg(all(feature = "smp", feature = "irq"))]
+pub mod ipi;
 
 unsafe extern "C" fn rust_entry(hartid: usize, dtb: usize) {
     extern "C" {
@@ -19,10 +31,36 @@ unsafe extern "C" fn rust_entry(hartid: usize, dtb: usize) {
     }
 
     trap::set_trap_vector_base(trap_vector_base as usize);
+    cpu::init_percpu(hartid);
+    fp::init_percpu();
     rust_main(hartid, dtb);
 }
 
+#[cfg(feature = "smp")]
+unsafe extern "C" fn rust_entry_secondary(hartid: usize) {
+    extern "C" {
+        fn trap_vector_base();
+        fn rust_main_secondary(hartid: usize);
+    }
+
+    trap::set_trap_vector_base(trap_vector_base as *const () as usize);
+    cpu::init_percpu(hartid);
+    fp::init_percpu();
+    rust_main_secondary(hartid);
+}
+
 pub fn platform_init() {
+    #[cfg(feature = "irq")]
+    self::irq::init_percpu();
+    #[cfg(all(feature = "smp", feature = "irq"))]
+    self::ipi::init();
+    self::time::init_percpu();
+}
+
+/// Initializes the interrupts and the timer of a secondary CPU.
+#[cfg(feature = "smp")]
+pub fn platform_init_secondary() {
+    #[cfg(feature = "irq")]
     self::irq::init_percpu();
     self::time::init_percpu();
 }
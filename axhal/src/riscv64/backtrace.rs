@@ -0,0 +1,44 @@
+//! Stack unwinding with frame pointers.
+//!
+//! The kernel must be built with `-C force-frame-pointers=yes`. On RISC-V,
+//! `fp` (`s0`) points to the end of the frame, where the return address and
+//! the caller's `fp` are saved at `fp - 8` and `fp - 16`.
+
+use axconfig::{phys_to_virt, PHYS_MEMORY_BASE, PHYS_MEMORY_SIZE};
+
+/// Maximum number of frames to walk.
+pub const MAX_FRAMES: usize = 32;
+
+/// Returns the frame pointer of the current function.
+#[inline(always)]
+pub fn current_fp() -> usize {
+    let fp;
+    unsafe { core::arch::asm!("mv {}, s0", out(reg) fp) };
+    fp
+}
+
+/// Walks the stack from the frame `fp`, and calls `f` with the return address
+/// of each frame, innermost first.
+///
+/// It stops at a frame pointer that is misaligned, outside the physical
+/// memory, or not above the previous one, so a corrupted stack does not
+/// cause a fault.
+pub fn walk(mut fp: usize, mut f: impl FnMut(usize)) {
+    let mem_start = phys_to_virt(PHYS_MEMORY_BASE);
+    let mem_end = mem_start + PHYS_MEMORY_SIZE;
+    for _ in 0..MAX_FRAMES {
+        if !fp.is_multiple_of(8) || fp < mem_start + 16 || fp > mem_end {
+            break;
+        }
+        let ra = unsafe { *((fp - 8) as *const usize) };
+        let prev_fp = unsafe { *((fp - 16) as *const usize) };
+        if ra == 0 {
+            break;
+        }
+        f(ra);
+        if prev_fp <= fp {
+            break;
+        }
+        fp = prev_fp;
+    }
+}
@@ -15,6 +15,7 @@ unsafe extern "C" fn _start() -> ! {
 
         mv      s0, a0                  // save hartid
         mv      s1, a1                  // save DTB pointer
+        mv      gp, a0                  // CPU ID, see `cpu::this_cpu_id`
 
         la      sp, boot_stack_top      // setup boot stack
 
@@ -38,3 +39,35 @@ unsafe extern "C" fn _start() -> ! {
         options(noreturn),
     )
 }
+
+/// The entry of secondary CPUs, started by [`start_secondary_cpu`].
+///
+/// [`start_secondary_cpu`]: super::mp::start_secondary_cpu
+#[cfg(feature = "smp")]
+#[no_mangle]
+#[link_section = ".text.boot"]
+unsafe extern "C" fn _start_secondary() -> ! {
+    // a0 = hartid
+    // a1 = SP (physical)
+    core::arch::asm!("
+        mv      s0, a0                  // save hartid
+        mv      sp, a1                  // setup boot stack
+        mv      gp, a0                  // CPU ID, see `cpu::this_cpu_id`
+
+        call    {init_mmu}              // enable MMU with the boot page table
+
+        li      s2, {phys_virt_offset}  // fix up virtual high address
+        add     sp, sp, s2              // readjust stack address
+
+        mv      a0, s0                  // restore hartid
+
+        la      a1, {entry}
+        add     a1, a1, s2              // readjust rust_entry_secondary address
+        jalr    a1                      // call rust_entry_secondary(hartid)
+        j       .",
+        init_mmu = sym super::paging::init_mmu,
+        phys_virt_offset = const axconfig::PHYS_VIRT_OFFSET,
+        entry = sym super::rust_entry_secondary,
+        options(noreturn),
+    )
+}
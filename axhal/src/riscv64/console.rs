@@ -4,6 +4,15 @@ pub fn putchar(c: u8) {
     sbi_rt::legacy::console_putchar(c as usize);
 }
 
+/// Reads a byte from the console, or returns `None` if there is none.
+pub fn getchar() -> Option<u8> {
+    #[allow(deprecated)]
+    match sbi_rt::legacy::console_getchar() {
+        usize::MAX => None,
+        c => Some(c as u8),
+    }
+}
+
 /// Write a slice of bytes to the console.
 pub fn write_bytes(bytes: &[u8]) {
     for c in bytes {
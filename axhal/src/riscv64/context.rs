@@ -1,5 +1,9 @@
 use core::arch::asm;
 
+#[cfg(feature = "vector")]
+use super::fp::VectorState;
+use super::fp::FpState;
+
 #[repr(C)]
 #[derive(Debug, Default, Clone)]
 pub struct GeneralRegisters {
@@ -36,6 +40,18 @@ pub struct GeneralRegisters {
     pub t6: usize,
 }
 
+impl GeneralRegisters {
+    /// Returns the registers as `x1`-`x31`, in which order they are laid out.
+    pub fn as_array(&self) -> &[usize; 31] {
+        unsafe { &*(self as *const Self as *const [usize; 31]) }
+    }
+
+    /// Returns the registers as `x1`-`x31`, to change them.
+    pub fn as_array_mut(&mut self) -> &mut [usize; 31] {
+        unsafe { &mut *(self as *mut Self as *mut [usize; 31]) }
+    }
+}
+
 #[repr(C)]
 #[derive(Debug, Default, Clone)]
 pub struct TrapFrame {
@@ -45,6 +61,11 @@ pub struct TrapFrame {
     pub sepc: usize,
     /// Supervisor Status Register.
     pub sstatus: usize,
+    /// The caller-saved floating-point registers `ft0`-`ft7`, `fa0`-`fa7`
+    /// and `ft8`-`ft11`, only saved if the FPU is on.
+    pub fp_regs: [u64; 20],
+    /// Floating-point Control and Status Register, saved with `fp_regs`.
+    pub fcsr: usize,
 }
 
 #[repr(C)]
@@ -66,6 +87,14 @@ pub struct TaskContext {
     pub s9: usize,
     pub s10: usize,
     pub s11: usize,
+
+    pub tp: usize, // thread pointer (x4)
+
+    /// Floating-point registers, saved lazily on context switches.
+    pub fp_state: FpState,
+    /// Vector registers, saved lazily on context switches.
+    #[cfg(feature = "vector")]
+    pub vector_state: VectorState,
 }
 
 impl TaskContext {
@@ -73,19 +102,23 @@ impl TaskContext {
         unsafe { core::mem::MaybeUninit::zeroed().assume_init() }
     }
 
-    pub fn init(&mut self, entry: usize, kstack_top: usize) {
+    pub fn init(&mut self, entry: usize, kstack_top: usize, tls_area: usize) {
         self.sp = kstack_top;
         self.ra = entry;
+        self.tp = tls_area;
     }
 
     pub fn switch_to(&mut self, next_ctx: &Self) {
+        self.fp_state.switch_to(&next_ctx.fp_state);
+        #[cfg(feature = "vector")]
+        self.vector_state.switch_to(&next_ctx.vector_state);
         unsafe { context_switch(self, next_ctx) }
     }
 }
 
-#[naked]
+#[unsafe(naked)]
 unsafe extern "C" fn context_switch(_current_task: &mut TaskContext, _next_task: &TaskContext) {
-    asm!("
+    core::arch::naked_asm!("
         // save old context (callee-saved registers)
         sd     ra, 0*8(a0)
         sd     sp, 1*8(a0)
@@ -101,8 +134,10 @@ unsafe extern "C" fn context_switch(_current_task: &mut TaskContext, _next_task:
         sd     s9, 11*8(a0)
         sd     s10, 12*8(a0)
         sd     s11, 13*8(a0)
+        sd     tp, 14*8(a0)
 
         // restore new context
+        ld     tp, 14*8(a1)
         ld     s11, 13*8(a1)
         ld     s10, 12*8(a1)
         ld     s9, 11*8(a1)
@@ -119,6 +154,5 @@ unsafe extern "C" fn context_switch(_current_task: &mut TaskContext, _next_task:
         ld     ra,  0*8(a1)
 
         ret",
-        options(noreturn),
     )
 }
@@ -1,13 +1,37 @@
-static mut CURRENT_TASK_PTR: usize = 0;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use axconfig::SMP;
+
+/// The current task of each CPU.
+static CURRENT_TASK_PTRS: [AtomicUsize; SMP] = [const { AtomicUsize::new(0) }; SMP];
 
 #[inline]
 pub fn current_task_ptr<T>() -> *const T {
     let _guard = kernel_guard::IrqSave::new();
-    unsafe { CURRENT_TASK_PTR as _ }
+    CURRENT_TASK_PTRS[this_cpu_id()].load(Ordering::Relaxed) as _
 }
 
 #[inline]
 pub unsafe fn set_current_task_ptr<T>(ptr: *const T) {
     let _guard = kernel_guard::IrqSave::new();
-    CURRENT_TASK_PTR = ptr as usize
+    CURRENT_TASK_PTRS[this_cpu_id()].store(ptr as usize, Ordering::Relaxed)
+}
+
+/// Returns the ID of the current CPU, which is its hart ID.
+///
+/// It is kept in `gp` from the boot code on, as the compiler never
+/// allocates `gp`.
+#[inline]
+pub fn this_cpu_id() -> usize {
+    let cpu_id;
+    unsafe { core::arch::asm!("mv {}, gp", out(reg) cpu_id, options(pure, nomem, nostack)) };
+    cpu_id
+}
+
+pub(super) fn init_percpu(cpu_id: usize) {
+    assert!(
+        cpu_id < SMP,
+        "CPU {} is out of the {} CPUs configured",
+        cpu_id,
+        SMP
+    );
 }
@@ -0,0 +1,374 @@
+//! Floating-point and vector register state of tasks.
+//!
+//! The state is switched lazily, as the `FS` (or `VS`) field of `sstatus`
+//! says. A context switch only saves the registers if they are dirty, i.e.
+//! the task has written them since they were last restored, and turns the
+//! unit off rather than restoring the registers of the next task. Its first
+//! FP instruction then traps, and the trap restores them, marked clean.
+//!
+//! A handler may switch tasks in an IRQ, and return to the trap of the next
+//! task with the unit still off. The registers are restored then, before the
+//! ones in its trap frame, if it was using them.
+
+use core::arch::asm;
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+use axconfig::SMP;
+use riscv::register::sstatus::{self, FS};
+
+use super::context::TrapFrame;
+use crate::cpu::this_cpu_id;
+
+const SSTATUS_FS: usize = 0b11 << 13;
+const SSTATUS_FS_CLEAN: usize = 0b10 << 13;
+
+/// The state of the current task of each CPU, restored on its first use.
+static CURRENT_FP_STATE: [AtomicPtr<FpState>; SMP] =
+    [const { AtomicPtr::new(core::ptr::null_mut()) }; SMP];
+
+/// The floating-point registers `f0`-`f31` and `fcsr`.
+#[repr(C)]
+#[derive(Debug, Default)]
+pub struct FpState {
+    pub fp: [u64; 32],
+    pub fcsr: usize,
+}
+
+impl FpState {
+    pub const fn new() -> Self {
+        Self {
+            fp: [0; 32],
+            fcsr: 0,
+        }
+    }
+
+    /// Saves the registers of the current task if they are dirty, and turns
+    /// the FPU off, for the registers of the next task to be restored on its
+    /// first use.
+    pub fn switch_to(&mut self, next: &Self) {
+        if sstatus::read().fs() == FS::Dirty {
+            unsafe { save_fp_registers(self) };
+        }
+        CURRENT_FP_STATE[this_cpu_id()].store(next as *const _ as *mut _, Ordering::Relaxed);
+        unsafe { sstatus::set_fs(FS::Off) };
+    }
+}
+
+/// Restores the registers of the current task, and turns the FPU on.
+fn restore_current() -> Option<&'static FpState> {
+    let state = CURRENT_FP_STATE[this_cpu_id()].load(Ordering::Relaxed);
+    // The task is running, so its state is alive and not written meanwhile.
+    let state = unsafe { state.as_ref()? };
+    unsafe {
+        sstatus::set_fs(FS::Clean);
+        restore_fp_registers(state);
+    }
+    Some(state)
+}
+
+/// Handles the illegal instruction trap with the frame `tf` if it is the
+/// first use of the FPU (or vector unit) by the current task since it was
+/// switched to, and returns whether it is. The instruction runs again with
+/// the registers restored.
+pub(super) fn handle_unit_off(tf: &mut TrapFrame) -> bool {
+    if tf.sstatus & SSTATUS_FS == 0 {
+        let Some(state) = restore_current() else {
+            return false;
+        };
+        // Returning from the trap restores the caller-saved registers and
+        // `fcsr` from the frame, which has none, as the FPU was off.
+        let caller_saved = (0..8).chain(10..18).chain(28..32);
+        for (reg, i) in tf.fp_regs.iter_mut().zip(caller_saved) {
+            *reg = state.fp[i];
+        }
+        tf.fcsr = state.fcsr;
+        tf.sstatus = tf.sstatus & !SSTATUS_FS | SSTATUS_FS_CLEAN;
+        return true;
+    }
+    #[cfg(feature = "vector")]
+    if vector::handle_unit_off(tf) {
+        return true;
+    }
+    false
+}
+
+/// Restores the registers of the current task before returning from the
+/// trap with the frame `tf`, if it was using them and a context switch in
+/// the handler turned the FPU (or vector unit) off.
+pub(super) fn restore_on_trap_return(tf: &TrapFrame) {
+    if tf.sstatus & SSTATUS_FS != 0 && sstatus::read().fs() == FS::Off {
+        restore_current();
+    }
+    #[cfg(feature = "vector")]
+    vector::restore_on_trap_return(tf);
+}
+
+unsafe fn save_fp_registers(state: &mut FpState) {
+    asm!("
+        fsd     f0,  0*8({0})
+        fsd     f1,  1*8({0})
+        fsd     f2,  2*8({0})
+        fsd     f3,  3*8({0})
+        fsd     f4,  4*8({0})
+        fsd     f5,  5*8({0})
+        fsd     f6,  6*8({0})
+        fsd     f7,  7*8({0})
+        fsd     f8,  8*8({0})
+        fsd     f9,  9*8({0})
+        fsd     f10, 10*8({0})
+        fsd     f11, 11*8({0})
+        fsd     f12, 12*8({0})
+        fsd     f13, 13*8({0})
+        fsd     f14, 14*8({0})
+        fsd     f15, 15*8({0})
+        fsd     f16, 16*8({0})
+        fsd     f17, 17*8({0})
+        fsd     f18, 18*8({0})
+        fsd     f19, 19*8({0})
+        fsd     f20, 20*8({0})
+        fsd     f21, 21*8({0})
+        fsd     f22, 22*8({0})
+        fsd     f23, 23*8({0})
+        fsd     f24, 24*8({0})
+        fsd     f25, 25*8({0})
+        fsd     f26, 26*8({0})
+        fsd     f27, 27*8({0})
+        fsd     f28, 28*8({0})
+        fsd     f29, 29*8({0})
+        fsd     f30, 30*8({0})
+        fsd     f31, 31*8({0})
+        frcsr   {1}
+        sd      {1}, 32*8({0})",
+        in(reg) state,
+        out(reg) _,
+    )
+}
+
+unsafe fn restore_fp_registers(state: &FpState) {
+    asm!("
+        fld     f0,  0*8({0})
+        fld     f1,  1*8({0})
+        fld     f2,  2*8({0})
+        fld     f3,  3*8({0})
+        fld     f4,  4*8({0})
+        fld     f5,  5*8({0})
+        fld     f6,  6*8({0})
+        fld     f7,  7*8({0})
+        fld     f8,  8*8({0})
+        fld     f9,  9*8({0})
+        fld     f10, 10*8({0})
+        fld     f11, 11*8({0})
+        fld     f12, 12*8({0})
+        fld     f13, 13*8({0})
+        fld     f14, 14*8({0})
+        fld     f15, 15*8({0})
+        fld     f16, 16*8({0})
+        fld     f17, 17*8({0})
+        fld     f18, 18*8({0})
+        fld     f19, 19*8({0})
+        fld     f20, 20*8({0})
+        fld     f21, 21*8({0})
+        fld     f22, 22*8({0})
+        fld     f23, 23*8({0})
+        fld     f24, 24*8({0})
+        fld     f25, 25*8({0})
+        fld     f26, 26*8({0})
+        fld     f27, 27*8({0})
+        fld     f28, 28*8({0})
+        fld     f29, 29*8({0})
+        fld     f30, 30*8({0})
+        fld     f31, 31*8({0})
+        ld      {1}, 32*8({0})
+        fscsr   {1}",
+        in(reg) state,
+        out(reg) _,
+    )
+}
+
+#[cfg(feature = "vector")]
+pub use self::vector::VectorState;
+
+#[cfg(feature = "vector")]
+mod vector {
+    use core::arch::asm;
+    use core::sync::atomic::{AtomicPtr, Ordering};
+
+    use axconfig::SMP;
+
+    use crate::cpu::this_cpu_id;
+    use crate::riscv64::context::TrapFrame;
+
+    /// Maximum supported `VLEN` in bytes (`vlenb`), i.e. 256-bit vectors.
+    pub const MAX_VLENB: usize = 32;
+
+    const SSTATUS_VS: usize = 0b11 << 9;
+    const SSTATUS_VS_INITIAL: usize = 0b01 << 9;
+    const SSTATUS_VS_CLEAN: usize = 0b10 << 9;
+
+    static CURRENT_VECTOR_STATE: [AtomicPtr<VectorState>; SMP] =
+        [const { AtomicPtr::new(core::ptr::null_mut()) }; SMP];
+
+    /// The vector registers `v0`-`v31` and the vector CSRs.
+    #[repr(C, align(16))]
+    #[derive(Debug)]
+    pub struct VectorState {
+        pub vstart: usize,
+        pub vl: usize,
+        pub vtype: usize,
+        pub vcsr: usize,
+        pub regs: [u8; 32 * MAX_VLENB],
+    }
+
+    impl VectorState {
+        pub const fn new() -> Self {
+            Self {
+                vstart: 0,
+                vl: 0,
+                vtype: 0,
+                vcsr: 0,
+                regs: [0; 32 * MAX_VLENB],
+            }
+        }
+
+        /// Saves the registers of the current task if they are dirty, and
+        /// turns the vector unit off, for the registers of the next task to
+        /// be restored on its first use.
+        pub fn switch_to(&mut self, next: &Self) {
+            if read_sstatus() & SSTATUS_VS == SSTATUS_VS {
+                unsafe { save_vector_registers(self) };
+            }
+            let next = next as *const _ as *mut _;
+            CURRENT_VECTOR_STATE[this_cpu_id()].store(next, Ordering::Relaxed);
+            unsafe { asm!("csrc sstatus, {}", in(reg) SSTATUS_VS) };
+        }
+    }
+
+    fn read_sstatus() -> usize {
+        let sstatus;
+        unsafe { asm!("csrr {}, sstatus", out(reg) sstatus) };
+        sstatus
+    }
+
+    /// Restores the registers of the current task, and turns the vector unit
+    /// on.
+    fn restore_current() -> bool {
+        let state = CURRENT_VECTOR_STATE[this_cpu_id()].load(Ordering::Relaxed);
+        let Some(state) = (unsafe { state.as_ref() }) else {
+            return false;
+        };
+        unsafe {
+            asm!("csrs sstatus, {}", in(reg) SSTATUS_VS_CLEAN);
+            restore_vector_registers(state);
+        }
+        true
+    }
+
+    pub(super) fn handle_unit_off(tf: &mut TrapFrame) -> bool {
+        if tf.sstatus & SSTATUS_VS != 0 || !restore_current() {
+            return false;
+        }
+        tf.sstatus |= SSTATUS_VS_CLEAN;
+        true
+    }
+
+    pub(super) fn restore_on_trap_return(tf: &TrapFrame) {
+        if tf.sstatus & SSTATUS_VS != 0 && read_sstatus() & SSTATUS_VS == 0 {
+            restore_current();
+        }
+    }
+
+    impl Default for VectorState {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    /// Enables the vector unit, and checks that the vectors fit in
+    /// [`VectorState`].
+    pub(super) fn init_percpu() {
+        unsafe { asm!("csrs sstatus, {}", in(reg) SSTATUS_VS_INITIAL) };
+        let vlenb: usize;
+        unsafe {
+            asm!(
+                ".option push",
+                ".option arch, +v",
+                "csrr {}, vlenb",
+                ".option pop",
+                out(reg) vlenb,
+            )
+        };
+        assert!(
+            vlenb <= MAX_VLENB,
+            "VLEN {} is larger than the supported {}",
+            vlenb * 8,
+            MAX_VLENB * 8
+        );
+    }
+
+    // Whole register loads and stores do not depend on `vl` and `vtype`, and
+    // 4 groups of 8 registers cover all of them.
+    unsafe fn save_vector_registers(state: &mut VectorState) {
+        asm!("
+            .option push
+            .option arch, +v
+            csrr    {tmp}, vstart
+            sd      {tmp}, 0*8({state})
+            csrr    {tmp}, vl
+            sd      {tmp}, 1*8({state})
+            csrr    {tmp}, vtype
+            sd      {tmp}, 2*8({state})
+            csrr    {tmp}, vcsr
+            sd      {tmp}, 3*8({state})
+            csrr    {step}, vlenb
+            slli    {step}, {step}, 3
+            addi    {tmp}, {state}, 4*8
+            vs8r.v  v0, ({tmp})
+            add     {tmp}, {tmp}, {step}
+            vs8r.v  v8, ({tmp})
+            add     {tmp}, {tmp}, {step}
+            vs8r.v  v16, ({tmp})
+            add     {tmp}, {tmp}, {step}
+            vs8r.v  v24, ({tmp})
+            .option pop",
+            state = in(reg) state,
+            tmp = out(reg) _,
+            step = out(reg) _,
+        )
+    }
+
+    unsafe fn restore_vector_registers(state: &VectorState) {
+        asm!("
+            .option push
+            .option arch, +v
+            csrr    {step}, vlenb
+            slli    {step}, {step}, 3
+            addi    {tmp}, {state}, 4*8
+            vl8re8.v v0, ({tmp})
+            add     {tmp}, {tmp}, {step}
+            vl8re8.v v8, ({tmp})
+            add     {tmp}, {tmp}, {step}
+            vl8re8.v v16, ({tmp})
+            add     {tmp}, {tmp}, {step}
+            vl8re8.v v24, ({tmp})
+            ld      {tmp}, 1*8({state})
+            ld      {step}, 2*8({state})
+            vsetvl  x0, {tmp}, {step}
+            ld      {tmp}, 0*8({state})
+            csrw    vstart, {tmp}
+            ld      {tmp}, 3*8({state})
+            csrw    vcsr, {tmp}
+            .option pop",
+            state = in(reg) state,
+            tmp = out(reg) _,
+            step = out(reg) _,
+        )
+    }
+}
+
+/// Sets the floating-point (and vector) unit to the initial state, so that
+/// the registers are not saved before any task writes them.
+pub(super) fn init_percpu() {
+    unsafe { sstatus::set_fs(FS::Initial) };
+    #[cfg(feature = "vector")]
+    vector::init_percpu();
+}
@@ -0,0 +1,131 @@
+//! Inter-processor interrupts.
+//!
+//! Each CPU has a queue of messages. [`send`] queues a message and raises a
+//! software interrupt on the target CPU through SBI, whose handler takes the
+//! messages out of the queue of its CPU.
+
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use axconfig::SMP;
+use spinlock::SpinNoIrq;
+
+use crate::cpu::this_cpu_id;
+use crate::cpumask::CpuMask;
+use crate::irq::IPI_IRQ_NUM;
+
+/// A function called on other CPUs, see [`smp_call_function`].
+pub type IpiFunction = Arc<dyn Fn() + Send + Sync>;
+
+/// A message to another CPU.
+pub enum IpiMessage {
+    /// Only interrupts the CPU, e.g. to wake it up from
+    /// [`wait_for_irqs`](crate::misc::wait_for_irqs).
+    Kick,
+    /// Calls a function with IRQs disabled.
+    Call(IpiFunction),
+    /// Flushes the TLB entries of a virtual address, or all of them with
+    /// `None`.
+    FlushTlb(Option<usize>),
+    /// Halts the CPU with IRQs disabled, e.g. before shutting down.
+    Stop,
+}
+
+static IPI_QUEUES: [SpinNoIrq<VecDeque<IpiMessage>>; SMP] =
+    [const { SpinNoIrq::new(VecDeque::new()) }; SMP];
+
+/// Sends `msg` to the CPU `cpu_id`, which handles it in its IPI handler.
+pub fn send(cpu_id: usize, msg: IpiMessage) {
+    assert!(cpu_id < SMP, "invalid CPU {}", cpu_id);
+    IPI_QUEUES[cpu_id].lock().push_back(msg);
+    if let Some(err) = sbi_rt::send_ipi(1 << cpu_id, 0).err() {
+        log::warn!("failed to send IPI to CPU {}: {:?}", cpu_id, err);
+    }
+}
+
+/// Calls `f` on each CPU in `cpu_mask`, and with `wait`, waits until all of
+/// them have returned.
+///
+/// It is called directly if the current CPU is in `cpu_mask`, and with IPIs
+/// on the others. The messages to the current CPU are handled while waiting,
+/// so CPUs calling functions on each other do not deadlock even with IRQs
+/// disabled.
+pub fn smp_call_function<F>(cpu_mask: CpuMask, f: F, wait: bool)
+where
+    F: Fn() + Send + Sync + 'static,
+{
+    let _guard = kernel_guard::NoPreempt::new();
+    let this_cpu = this_cpu_id();
+    let pending = Arc::new(AtomicUsize::new(0));
+    let func: IpiFunction = {
+        let pending = pending.clone();
+        Arc::new(move || {
+            f();
+            pending.fetch_sub(1, Ordering::Release);
+        })
+    };
+    for cpu_id in cpu_mask.iter().filter(|&cpu_id| cpu_id != this_cpu) {
+        pending.fetch_add(1, Ordering::Relaxed);
+        send(cpu_id, IpiMessage::Call(func.clone()));
+    }
+    if cpu_mask.contains(this_cpu) {
+        pending.fetch_add(1, Ordering::Relaxed);
+        let _guard = kernel_guard::IrqSave::new();
+        func();
+    }
+    if wait {
+        while pending.load(Ordering::Acquire) > 0 {
+            let _guard = kernel_guard::IrqSave::new();
+            handle_ipi();
+            core::hint::spin_loop();
+        }
+    }
+}
+
+/// Flushes the TLB entries of `vaddr`, or all of them with `None`, on all
+/// CPUs, and waits until they are done.
+pub fn flush_tlb_all(vaddr: Option<usize>) {
+    smp_call_function(CpuMask::full(), move || flush_tlb(vaddr), true);
+}
+
+/// Halts all CPUs but the current one.
+pub fn stop_other_cpus() {
+    let this_cpu = this_cpu_id();
+    for cpu_id in CpuMask::full().iter().filter(|&cpu_id| cpu_id != this_cpu) {
+        send(cpu_id, IpiMessage::Stop);
+    }
+}
+
+fn flush_tlb(vaddr: Option<usize>) {
+    match vaddr {
+        Some(vaddr) => unsafe { riscv::asm::sfence_vma(0, vaddr) },
+        None => unsafe { riscv::asm::sfence_vma_all() },
+    }
+}
+
+/// Handles the messages to the current CPU, with IRQs disabled.
+fn handle_ipi() {
+    let queue = &IPI_QUEUES[this_cpu_id()];
+    loop {
+        // Not locked while handling, as the handlers may send IPIs.
+        let Some(msg) = queue.lock().pop_front() else {
+            break;
+        };
+        match msg {
+            IpiMessage::Kick => {}
+            IpiMessage::Call(func) => func(),
+            IpiMessage::FlushTlb(vaddr) => flush_tlb(vaddr),
+            IpiMessage::Stop => {
+                log::info!("CPU {} stopped", this_cpu_id());
+                loop {
+                    crate::misc::halt();
+                }
+            }
+        }
+    }
+}
+
+pub(super) fn init() {
+    crate::irq::register_handler(IPI_IRQ_NUM, handle_ipi);
+}
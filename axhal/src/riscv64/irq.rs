@@ -3,18 +3,33 @@ use handler_table::HandlerTable;
 use riscv::register::{sstatus, sie};
 
 pub const MAX_IRQ_COUNT: usize = 1024;
+/// The highest priority of external IRQs the PLIC supports on all platforms.
+pub const MAX_IRQ_PRIORITY: u32 = 7;
 pub(super) const INTC_IRQ_BASE: usize = 1 << (usize::BITS - 1);
 
-#[allow(unused)]
 pub(super) const S_SOFT: usize = INTC_IRQ_BASE + 1;
 pub(super) const S_TIMER: usize = INTC_IRQ_BASE + 5;
 pub(super) const S_EXT: usize = INTC_IRQ_BASE + 9;
 
 pub const TIMER_IRQ_NUM: usize = S_TIMER;
 
+/// The IRQ number of inter-processor interrupts.
+pub const IPI_IRQ_NUM: usize = S_SOFT;
+
+/// The slots of software and timer interrupts, after the external IRQs.
+/// Handlers of software interrupts are in the handler table too.
+const S_SOFT_SLOT: usize = MAX_IRQ_COUNT;
+const S_TIMER_SLOT: usize = MAX_IRQ_COUNT + 1;
+const NUM_SLOTS: usize = MAX_IRQ_COUNT + 2;
+
+mod plic;
+mod stats;
+pub use self::stats::{cpu_irq_stats, for_each_irq_stats, irq_stats, CpuIrqStats, IrqStats};
+
 pub type IrqHandler = handler_table::Handler;
+pub use handler_table::HandlerFn;
 
-static IRQ_HANDLER_TABLE: HandlerTable<MAX_IRQ_COUNT> = HandlerTable::new();
+static IRQ_HANDLER_TABLE: HandlerTable<{ MAX_IRQ_COUNT + 1 }> = HandlerTable::new();
 
 static TIMER_HANDLER: BootOnceCell<IrqHandler> = unsafe {
     BootOnceCell::new()
@@ -22,36 +37,133 @@ static TIMER_HANDLER: BootOnceCell<IrqHandler> = unsafe {
 
 pub fn dispatch_irq(scause: usize) {
     match scause {
+        S_SOFT => {
+            log::trace!("IRQ: IPI");
+            unsafe { core::arch::asm!("csrc sip, {}", in(reg) 1 << 1) };
+            // Without a handler, an IPI only kicks the CPU.
+            handle_counted(S_SOFT_SLOT, scause, || IRQ_HANDLER_TABLE.handle(S_SOFT_SLOT));
+        },
         S_TIMER => {
             log::trace!("IRQ: timer");
-            TIMER_HANDLER.get()();
+            handle_counted(S_TIMER_SLOT, scause, || TIMER_HANDLER.get()());
         },
         S_EXT => {
-            crate::irq::dispatch_irq_common(0);
+            while let Some(irq_num) = plic::claim() {
+                dispatch_irq_common(irq_num);
+                plic::complete(irq_num);
+            }
         },
         _ => panic!("invalid trap cause: {:#x}", scause),
     }
 }
 
-#[allow(dead_code)]
+/// Runs the handlers of the external IRQ `irq_num`, claimed from the PLIC.
 pub(crate) fn dispatch_irq_common(irq_num: usize) {
     log::trace!("IRQ {}", irq_num);
-    if !IRQ_HANDLER_TABLE.handle(irq_num) {
+    if !handle_counted(irq_num, S_EXT, || IRQ_HANDLER_TABLE.handle(irq_num)) {
         log::warn!("Unhandled IRQ {}", irq_num);
     }
 }
 
-#[allow(dead_code)]
-pub(crate) fn register_handler_common(irq_num: usize, handler: IrqHandler) -> bool {
-    if irq_num < MAX_IRQ_COUNT && IRQ_HANDLER_TABLE.register_handler(irq_num, handler) {
+/// Runs `handler` of the IRQ in `slot`, and counts it in the statistics.
+#[cfg_attr(not(feature = "nested-irq"), allow(unused_variables))]
+fn handle_counted<R>(slot: usize, scause: usize, handler: impl FnOnce() -> R) -> R {
+    let cpu_id = crate::cpu::this_cpu_id();
+    stats::enter(cpu_id);
+    let start = crate::time::current_time_nanos();
+    let ret = {
+        #[cfg(feature = "nested-irq")]
+        let _nested = NestedIrqs::enable(scause, slot);
+        handler()
+    };
+    stats::exit(slot, cpu_id, crate::time::current_time_nanos() - start);
+    ret
+}
+
+/// Lets the interrupts of higher priority preempt the handler of `scause`
+/// in `slot`, until it is dropped.
+///
+/// External interrupts come first, then software and timer interrupts, as
+/// when they are pending together. Each nested trap saves its own frame
+/// below the interrupted one on the stack, and `sepc` and `sstatus` are in
+/// the frame before IRQs are enabled again, so the frames stay consistent.
+///
+/// External IRQs are all one source to the CPU, so the PLIC threshold of the
+/// CPU is raised to the priority of the one handled: only those of higher
+/// priority are routed to it, and nest in the handler.
+#[cfg(feature = "nested-irq")]
+struct NestedIrqs {
+    /// `sie` before, if anything nests.
+    sie: Option<usize>,
+    /// The PLIC threshold before, if an external IRQ raised it.
+    threshold: Option<u32>,
+}
+
+#[cfg(feature = "nested-irq")]
+impl NestedIrqs {
+    fn enable(scause: usize, slot: usize) -> Self {
+        const SSIE: usize = 1 << 1;
+        const SEIE: usize = 1 << 9;
+        let mut threshold = None;
+        let allowed = match scause {
+            S_TIMER => SSIE | SEIE,
+            S_SOFT => SEIE,
+            S_EXT => {
+                threshold = Some(plic::threshold());
+                plic::set_threshold(plic::priority(slot));
+                SEIE
+            },
+            _ => return Self { sie: None, threshold },
+        };
+        let sie: usize;
+        unsafe {
+            core::arch::asm!("csrrc {}, sie, {}", out(reg) sie, in(reg) !allowed);
+            sstatus::set_sie();
+        }
+        Self { sie: Some(sie), threshold }
+    }
+}
+
+#[cfg(feature = "nested-irq")]
+impl Drop for NestedIrqs {
+    fn drop(&mut self) {
+        if let Some(sie) = self.sie {
+            unsafe {
+                sstatus::clear_sie();
+                core::arch::asm!("csrw sie, {}", in(reg) sie);
+            }
+        }
+        if let Some(threshold) = self.threshold {
+            plic::set_threshold(threshold);
+        }
+    }
+}
+
+/// Registers `handler` for the external IRQ `irq_num`, and routes it from the
+/// PLIC to all CPUs.
+pub(crate) fn register_handler_common(irq_num: usize, handler: HandlerFn) -> bool {
+    // Source 0 doesn't exist, it means no IRQ to claim.
+    if irq_num != 0
+        && irq_num < MAX_IRQ_COUNT
+        && IRQ_HANDLER_TABLE.register_handler(irq_num, handler)
+    {
+        plic::enable(irq_num);
         return true;
     }
     log::warn!("register handler for IRQ {} failed", irq_num);
     false
 }
 
-pub fn register_handler(scause: usize, handler: IrqHandler) -> bool {
-    match scause {
+/// Registers `handler` for the IRQ `irq_num`, and returns whether it succeeds.
+///
+/// `irq_num` is [`TIMER_IRQ_NUM`], [`IPI_IRQ_NUM`], or the source number of an
+/// external IRQ in the PLIC, below [`MAX_IRQ_COUNT`]. The timer IRQ takes one
+/// handler. Other IRQs can be shared by up to [`MAX_SHARED_HANDLERS`] handlers,
+/// which are all called.
+///
+/// [`MAX_SHARED_HANDLERS`]: handler_table::MAX_SHARED_HANDLERS
+pub fn register_handler(irq_num: usize, handler: IrqHandler) -> bool {
+    match irq_num {
         S_TIMER => {
             if !TIMER_HANDLER.is_init() {
                 TIMER_HANDLER.init(handler);
@@ -60,10 +172,53 @@ pub fn register_handler(scause: usize, handler: IrqHandler) -> bool {
                 false
             }
         },
-        S_EXT => {
-            crate::irq::register_handler_common(scause & !INTC_IRQ_BASE, handler)
+        _ => register_handler_fn(irq_num, handler.into()),
+    }
+}
+
+/// Registers a handler that may carry context, such as the state of a
+/// device instance, for the IRQ `irq_num`, as [`register_handler`] does. It is
+/// called with the IRQ number.
+///
+/// The timer IRQ only takes a plain handler, with [`register_handler`].
+pub fn register_handler_fn(irq_num: usize, handler: HandlerFn) -> bool {
+    match irq_num {
+        S_TIMER => {
+            log::warn!("the timer IRQ only takes a plain handler");
+            false
         },
-        _ => panic!("invalid trap cause: {:#x}", scause),
+        S_SOFT => {
+            IRQ_HANDLER_TABLE.register_handler(S_SOFT_SLOT, handler)
+        },
+        _ => register_handler_common(irq_num, handler),
+    }
+}
+
+/// Sets the priority of the external IRQ `irq_num` in the PLIC, from 1 to
+/// [`MAX_IRQ_PRIORITY`], and returns whether it is valid. IRQs pending
+/// together are claimed by priority, and with the `nested-irq` feature, an
+/// IRQ preempts the handlers of those of lower priority.
+///
+/// Registering a handler routes the IRQ with priority 1 if it has none.
+pub fn set_priority(irq_num: usize, priority: u32) -> bool {
+    if irq_num == 0 || irq_num >= MAX_IRQ_COUNT || !(1..=MAX_IRQ_PRIORITY).contains(&priority) {
+        return false;
+    }
+    plic::set_priority(irq_num, priority);
+    true
+}
+
+/// Removes a handler of the IRQ `irq_num`, and returns whether it is found.
+/// External IRQs stay routed from the PLIC, and are only counted as unhandled
+/// without handlers.
+///
+/// Once it returns, the handler is not running on any CPU. It must not be
+/// called from an IRQ handler. The timer handler can't be removed.
+pub fn unregister_handler(irq_num: usize, handler: HandlerFn) -> bool {
+    match irq_num {
+        S_TIMER => false,
+        S_SOFT => IRQ_HANDLER_TABLE.unregister_handler(S_SOFT_SLOT, handler),
+        _ => irq_num < MAX_IRQ_COUNT && IRQ_HANDLER_TABLE.unregister_handler(irq_num, handler),
     }
 }
 
@@ -72,7 +227,13 @@ pub fn enable_irqs() {
     unsafe { sstatus::set_sie() }
 }
 
+#[inline]
+pub fn disable_irqs() {
+    unsafe { sstatus::clear_sie() }
+}
+
 pub(super) fn init_percpu() {
+    plic::init_percpu();
     unsafe {
         sie::set_ssoft();
         sie::set_stimer();
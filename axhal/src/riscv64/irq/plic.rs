@@ -0,0 +1,89 @@
+//! The PLIC (Platform-Level Interrupt Controller), which routes the IRQs of
+//! devices to the external interrupt of the CPUs.
+//!
+//! Each CPU claims the IRQs routed to its supervisor context, and completes
+//! them once handled. An IRQ is only routed to contexts it is enabled for,
+//! when its priority is above their threshold.
+
+use axconfig::{phys_to_virt, PLIC_PADDR, SMP};
+
+const PRIORITY_BASE: usize = 0;
+const ENABLE_BASE: usize = 0x2000;
+const ENABLE_STRIDE: usize = 0x80;
+const CONTEXT_BASE: usize = 0x20_0000;
+const CONTEXT_STRIDE: usize = 0x1000;
+const THRESHOLD: usize = 0;
+const CLAIM_COMPLETE: usize = 4;
+
+fn reg(offset: usize) -> *mut u32 {
+    phys_to_virt(PLIC_PADDR + offset) as *mut u32
+}
+
+fn read(offset: usize) -> u32 {
+    unsafe { reg(offset).read_volatile() }
+}
+
+fn write(offset: usize, value: u32) {
+    unsafe { reg(offset).write_volatile(value) }
+}
+
+/// Returns the supervisor context of the CPU `cpu_id`, after its machine one.
+const fn context(cpu_id: usize) -> usize {
+    2 * cpu_id + 1
+}
+
+fn context_reg(reg: usize) -> usize {
+    CONTEXT_BASE + context(crate::cpu::this_cpu_id()) * CONTEXT_STRIDE + reg
+}
+
+/// Returns the priority of `irq`, 0 if it is never routed.
+pub(super) fn priority(irq: usize) -> u32 {
+    read(PRIORITY_BASE + irq * 4)
+}
+
+pub(super) fn set_priority(irq: usize, priority: u32) {
+    write(PRIORITY_BASE + irq * 4, priority);
+}
+
+/// Routes `irq` to all CPUs, with the lowest priority above 0 unless it has
+/// one already.
+pub(super) fn enable(irq: usize) {
+    if priority(irq) == 0 {
+        set_priority(irq, 1);
+    }
+    for cpu_id in 0..SMP {
+        let offset = ENABLE_BASE + context(cpu_id) * ENABLE_STRIDE + irq / 32 * 4;
+        write(offset, read(offset) | 1 << (irq % 32));
+    }
+}
+
+/// Returns the threshold of this CPU.
+#[cfg_attr(not(feature = "nested-irq"), allow(dead_code))]
+pub(super) fn threshold() -> u32 {
+    read(context_reg(THRESHOLD))
+}
+
+/// Masks the IRQs whose priority is not above `threshold` on this CPU.
+pub(super) fn set_threshold(threshold: u32) {
+    write(context_reg(THRESHOLD), threshold);
+}
+
+/// Claims the pending IRQ of the highest priority for this CPU, or returns
+/// `None` if there is none left.
+pub(super) fn claim() -> Option<usize> {
+    match read(context_reg(CLAIM_COMPLETE)) {
+        0 => None,
+        irq => Some(irq as usize),
+    }
+}
+
+/// Tells the PLIC that `irq`, claimed by this CPU, is handled, so that it
+/// can be routed again.
+pub(super) fn complete(irq: usize) {
+    write(context_reg(CLAIM_COMPLETE), irq as u32);
+}
+
+/// Lets all the enabled IRQs reach this CPU.
+pub(super) fn init_percpu() {
+    set_threshold(0);
+}
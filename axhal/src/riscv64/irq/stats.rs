@@ -0,0 +1,123 @@
+//! Interrupt statistics, like `/proc/interrupts`.
+
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use axconfig::SMP;
+
+use super::{IPI_IRQ_NUM, MAX_IRQ_COUNT, NUM_SLOTS, S_SOFT_SLOT, S_TIMER_SLOT, TIMER_IRQ_NUM};
+use crate::time::TimeValue;
+
+struct Counters {
+    counts: [AtomicU64; SMP],
+    total_nanos: AtomicU64,
+    max_nanos: AtomicU64,
+}
+
+static IRQ_COUNTERS: [Counters; NUM_SLOTS] = [const {
+    Counters {
+        counts: [const { AtomicU64::new(0) }; SMP],
+        total_nanos: AtomicU64::new(0),
+        max_nanos: AtomicU64::new(0),
+    }
+}; NUM_SLOTS];
+
+static CPU_COUNTS: [AtomicU64; SMP] = [const { AtomicU64::new(0) }; SMP];
+static CPU_NESTED: [AtomicU64; SMP] = [const { AtomicU64::new(0) }; SMP];
+/// Number of handlers running on each CPU, more than one when they nest.
+static CPU_DEPTH: [AtomicUsize; SMP] = [const { AtomicUsize::new(0) }; SMP];
+
+/// Statistics of an IRQ since boot.
+#[derive(Debug, Clone, Copy)]
+pub struct IrqStats {
+    /// How many times it is handled on each CPU.
+    pub counts: [u64; SMP],
+    /// The time spent in its handlers, including the ones nested in them.
+    pub total_time: TimeValue,
+    /// The longest time one dispatch took.
+    pub max_latency: TimeValue,
+}
+
+impl IrqStats {
+    /// Returns how many times it is handled on all CPUs.
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+}
+
+/// Interrupt statistics of a CPU since boot.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CpuIrqStats {
+    /// How many interrupts it handled.
+    pub count: u64,
+    /// How many of them arrived while it was handling another one.
+    pub nested: u64,
+}
+
+pub(super) fn enter(cpu_id: usize) {
+    if CPU_DEPTH[cpu_id].fetch_add(1, Ordering::Relaxed) > 0 {
+        CPU_NESTED[cpu_id].fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+pub(super) fn exit(slot: usize, cpu_id: usize, nanos: u64) {
+    CPU_DEPTH[cpu_id].fetch_sub(1, Ordering::Relaxed);
+    CPU_COUNTS[cpu_id].fetch_add(1, Ordering::Relaxed);
+    let counters = &IRQ_COUNTERS[slot];
+    counters.counts[cpu_id].fetch_add(1, Ordering::Relaxed);
+    counters.total_nanos.fetch_add(nanos, Ordering::Relaxed);
+    counters.max_nanos.fetch_max(nanos, Ordering::Relaxed);
+}
+
+fn slot_of(irq_num: usize) -> Option<usize> {
+    match irq_num {
+        TIMER_IRQ_NUM => Some(S_TIMER_SLOT),
+        IPI_IRQ_NUM => Some(S_SOFT_SLOT),
+        _ if irq_num < MAX_IRQ_COUNT => Some(irq_num),
+        _ => None,
+    }
+}
+
+fn irq_num_of(slot: usize) -> usize {
+    match slot {
+        S_TIMER_SLOT => TIMER_IRQ_NUM,
+        S_SOFT_SLOT => IPI_IRQ_NUM,
+        _ => slot,
+    }
+}
+
+fn stats_of(counters: &Counters) -> IrqStats {
+    IrqStats {
+        counts: core::array::from_fn(|cpu_id| counters.counts[cpu_id].load(Ordering::Relaxed)),
+        total_time: TimeValue::from_nanos(counters.total_nanos.load(Ordering::Relaxed)),
+        max_latency: TimeValue::from_nanos(counters.max_nanos.load(Ordering::Relaxed)),
+    }
+}
+
+/// Returns the statistics of an IRQ, which is [`TIMER_IRQ_NUM`],
+/// [`IPI_IRQ_NUM`] or an external IRQ number, or `None` if there is no such
+/// IRQ.
+pub fn irq_stats(irq_num: usize) -> Option<IrqStats> {
+    slot_of(irq_num).map(|slot| stats_of(&IRQ_COUNTERS[slot]))
+}
+
+/// Calls `f` with the number and the statistics of each IRQ handled so far:
+/// the external IRQs in order, then IPIs and the timer.
+pub fn for_each_irq_stats<F>(mut f: F)
+where
+    F: FnMut(usize, &IrqStats),
+{
+    for (slot, counters) in IRQ_COUNTERS.iter().enumerate() {
+        let stats = stats_of(counters);
+        if stats.count() > 0 {
+            f(irq_num_of(slot), &stats);
+        }
+    }
+}
+
+/// Returns the interrupt statistics of the CPU `cpu_id`, or `None` if there
+/// is no such CPU.
+pub fn cpu_irq_stats(cpu_id: usize) -> Option<CpuIrqStats> {
+    (cpu_id < SMP).then(|| CpuIrqStats {
+        count: CPU_COUNTS[cpu_id].load(Ordering::Relaxed),
+        nested: CPU_NESTED[cpu_id].load(Ordering::Relaxed),
+    })
+}
@@ -1,6 +1,6 @@
 use axconfig::{PAGE_SIZE, align_up, align_down};
-use axconfig::virt_to_phys;
-use page_table::{PAGE_KERNEL_RO, PAGE_KERNEL_RW, PAGE_KERNEL_RX};
+use axconfig::{virt_to_phys, PLIC_PADDR};
+use page_table::{PAGE_KERNEL_RO, PAGE_KERNEL_RW, PAGE_KERNEL_RX, PAGE_KERNEL_RWX};
 
 /// A physical memory region.
 #[derive(Debug)]
@@ -21,7 +21,12 @@ pub fn kernel_image_regions() -> impl Iterator<Item = MemRegion> {
         MemRegion {
             paddr: virt_to_phys((_stext as usize).into()),
             size: _etext as usize - _stext as usize,
-            flags: PAGE_KERNEL_RX,
+            // A debugger writes its breakpoints into the code.
+            flags: if cfg!(feature = "writable-text") {
+                PAGE_KERNEL_RWX
+            } else {
+                PAGE_KERNEL_RX
+            },
             name: ".text",
         },
         MemRegion {
@@ -70,6 +75,19 @@ pub fn free_regions(phys_mem_size: usize) -> impl Iterator<Item = MemRegion> {
     })
 }
 
+/// Size of the register space of the PLIC, as defined by the spec.
+const PLIC_SIZE: usize = 0x400_0000;
+
+/// Returns the MMIO regions of the devices driven by this crate.
+pub fn mmio_regions() -> impl Iterator<Item = MemRegion> {
+    core::iter::once(MemRegion {
+        paddr: PLIC_PADDR,
+        size: PLIC_SIZE,
+        flags: PAGE_KERNEL_RW,
+        name: "plic",
+    })
+}
+
 extern "C" {
     fn _skernel();
     fn _stext();
@@ -1,9 +1,18 @@
 use riscv::register::sstatus;
 
 /// Shutdown the whole system, including all CPUs.
-pub fn terminate() -> ! {
-    axlog::info!("Shutting down...");
-    sbi_rt::system_reset(sbi_rt::Shutdown, sbi_rt::NoReason);
+///
+/// A non-zero `exit_code` is reported as a system failure, so that QEMU exits
+/// with a non-zero status.
+pub fn terminate(exit_code: i32) -> ! {
+    axlog::info!("Shutting down... exit_code={}", exit_code);
+    #[cfg(all(feature = "smp", feature = "irq"))]
+    crate::ipi::stop_other_cpus();
+    if exit_code == 0 {
+        sbi_rt::system_reset(sbi_rt::Shutdown, sbi_rt::NoReason);
+    } else {
+        sbi_rt::system_reset(sbi_rt::Shutdown, sbi_rt::SystemFailure);
+    }
     axlog::warn!("It should shutdown!");
     loop {
         halt();
@@ -17,6 +26,16 @@ pub fn halt() {
     unsafe { riscv::asm::wfi() } // should never return
 }
 
+/// Waits for the next interrupt on the current CPU.
+///
+/// It also returns if an interrupt is pending while IRQs are disabled, and the
+/// interrupt is taken once IRQs are enabled again. So callers can disable IRQs,
+/// check that there is nothing to do, and wait without missing a wakeup.
+#[inline]
+pub fn wait_for_irqs() {
+    unsafe { riscv::asm::wfi() }
+}
+
 /// Makes the current CPU to ignore interrupts.
 #[inline]
 pub fn disable_irqs() {
@@ -0,0 +1,16 @@
+//! Booting of the secondary CPUs.
+
+/// Starts the secondary CPU `hartid` on the boot stack whose top is at the
+/// physical address `stack_top_paddr`.
+///
+/// The CPU enables the MMU with the boot page table, and calls
+/// `rust_main_secondary(hartid)`.
+pub fn start_secondary_cpu(hartid: usize, stack_top_paddr: usize) {
+    extern "C" {
+        fn _start_secondary();
+    }
+    let entry = axconfig::virt_to_phys(_start_secondary as *const () as usize);
+    if let Some(err) = sbi_rt::hart_start(hartid, entry, stack_top_paddr).err() {
+        log::warn!("failed to start CPU {}: {:?}", hartid, err);
+    }
+}
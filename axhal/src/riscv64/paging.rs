@@ -1,12 +1,20 @@
 use riscv::register::satp;
-use axconfig::SIZE_1G;
-use page_table::{PageTable, phys_pfn, PAGE_KERNEL_RWX};
+use axconfig::{align_down, phys_to_virt, PHYS_MEMORY_BASE, PLIC_PADDR, SIZE_1G};
+use page_table::{PageTable, phys_pfn, PAGE_KERNEL_RW, PAGE_KERNEL_RWX};
 
 pub unsafe fn init_boot_page_table() {
     let mut pt: PageTable = PageTable::init(boot_page_table as usize, 0);
 
-    let _ = pt.map(0x8000_0000, 0x8000_0000, SIZE_1G, SIZE_1G, PAGE_KERNEL_RWX);
-    let _ = pt.map(0xffff_ffc0_8000_0000, 0x8000_0000, SIZE_1G, SIZE_1G, PAGE_KERNEL_RWX);
+    // Map the 1G region containing the kernel, both identically and linearly.
+    let base = align_down(PHYS_MEMORY_BASE, SIZE_1G);
+    let _ = pt.map(base, base, SIZE_1G, SIZE_1G, PAGE_KERNEL_RWX);
+    let _ = pt.map(phys_to_virt(base), base, SIZE_1G, SIZE_1G, PAGE_KERNEL_RWX);
+
+    // And the 1G region containing the PLIC linearly, if it is another one.
+    let plic_base = align_down(PLIC_PADDR, SIZE_1G);
+    if plic_base != base {
+        let _ = pt.map(phys_to_virt(plic_base), plic_base, SIZE_1G, SIZE_1G, PAGE_KERNEL_RW);
+    }
 }
 
 pub unsafe fn init_mmu() {
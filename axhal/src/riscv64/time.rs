@@ -4,7 +4,7 @@ use core::time::Duration;
 use riscv::register::time;
 
 /// Timer interrupt frequency in Hz.
-const TIMER_FREQUENCY: u64 = 10_000_000;    // 10MHz
+const TIMER_FREQUENCY: u64 = axconfig::TIMER_FREQUENCY as u64;
 
 /// Number of nanoseconds in a second.
 pub const NANOS_PER_SEC: u64 = 1_000_000_000;
@@ -0,0 +1,75 @@
+//! Thread-local storage.
+//!
+//! RISC-V uses TLS variant I: `tp` points to the start of the TLS block, and
+//! the linker resolves each `#[thread_local]` variable to a fixed offset from
+//! it. Every task gets its own block, initialized from the `.tdata` template
+//! followed by the zeroed `.tbss`.
+
+use alloc::alloc::{alloc_zeroed, dealloc};
+use core::alloc::Layout;
+use core::ptr::{addr_of, NonNull};
+
+/// Alignment of the TLS blocks, no less than that of `.tdata` and `.tbss`.
+const TLS_ALIGN: usize = 0x40;
+
+/// The TLS block of a task.
+pub struct TlsArea {
+    base: NonNull<u8>,
+    layout: Layout,
+}
+
+impl TlsArea {
+    /// Allocates a TLS block and initializes it from the template.
+    pub fn alloc() -> Self {
+        let layout = Layout::from_size_align(tls_area_size().max(1), TLS_ALIGN).unwrap();
+        let base = NonNull::new(unsafe { alloc_zeroed(layout) }).expect("failed to alloc TLS");
+        unsafe {
+            // `.tbss` is already zeroed
+            core::ptr::copy_nonoverlapping(
+                addr_of!(_stdata),
+                base.as_ptr(),
+                addr_of!(_etdata) as usize - addr_of!(_stdata) as usize,
+            );
+        }
+        Self { base, layout }
+    }
+
+    /// Returns the value of `tp` for the task.
+    pub fn tls_ptr(&self) -> usize {
+        self.base.as_ptr() as usize
+    }
+}
+
+impl Drop for TlsArea {
+    fn drop(&mut self) {
+        unsafe { dealloc(self.base.as_ptr(), self.layout) }
+    }
+}
+
+fn tls_area_size() -> usize {
+    addr_of!(_etbss) as usize - addr_of!(_stdata) as usize
+}
+
+/// Reads the thread pointer `tp` of the current CPU.
+#[inline]
+pub fn read_thread_pointer() -> usize {
+    let tp;
+    unsafe { core::arch::asm!("mv {}, tp", out(reg) tp) };
+    tp
+}
+
+/// Sets the thread pointer `tp` of the current CPU.
+///
+/// # Safety
+///
+/// `tp` must point to a TLS block that lives as long as it is in use.
+#[inline]
+pub unsafe fn write_thread_pointer(tp: usize) {
+    core::arch::asm!("mv tp, {}", in(reg) tp)
+}
+
+extern "C" {
+    static _stdata: u8;
+    static _etdata: u8;
+    static _etbss: u8;
+}
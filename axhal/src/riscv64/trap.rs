@@ -1,8 +1,15 @@
-use riscv::register::stvec;
-use riscv::register::scause::{self, Exception as E, Trap};
+use core::fmt;
+use riscv::register::{stval, stvec};
+use riscv::register::scause::{self, Trap};
 use super::context::TrapFrame;
+use super::fp;
 use crate_interface::{call_interface, def_interface};
 
+mod misaligned;
+
+/// Bit 5 of `sstatus`: whether IRQs were enabled before the trap.
+const SSTATUS_SPIE: usize = 1 << 5;
+
 core::arch::global_asm!(
     include_str!("trap.S"),
     trapframe_size = const core::mem::size_of::<TrapFrame>(),
@@ -18,9 +25,14 @@ pub fn set_trap_vector_base(stvec: usize) {
 fn riscv_trap_handler(tf: &mut TrapFrame) {
     let scause = scause::read();
     match scause.cause() {
-        Trap::Exception(E::Breakpoint) => handle_breakpoint(&mut tf.sepc),
-        Trap::Interrupt(_) => handle_irq_extern(scause.bits()),
-        _ => {
+        Trap::Exception(_) => handle_exception(tf, scause.code()),
+        #[cfg(feature = "irq")]
+        Trap::Interrupt(_) => {
+            handle_irq_extern(scause.bits());
+            call_interface!(TrapHandler::irq_exit);
+        }
+        #[cfg(not(feature = "irq"))]
+        Trap::Interrupt(_) => {
             panic!(
                 "Unhandled trap {:?} @ {:#x}:\n{:#x?}",
                 scause.cause(),
@@ -29,11 +41,150 @@ fn riscv_trap_handler(tf: &mut TrapFrame) {
             );
         }
     }
+    // The handler may have switched to a task that returns here.
+    fp::restore_on_trap_return(tf);
+}
+
+/// Handles an exception: restores the FP registers of the current task on
+/// their first use, emulates misaligned accesses, then leaves it to
+/// [`TrapHandler::handle_exception`], and skips breakpoints it does not
+/// handle.
+fn handle_exception(tf: &mut TrapFrame, code: usize) {
+    let exception = Exception::decode(code, stval::read(), tf.sepc);
+    if let Exception::IllegalInstruction { .. } = exception {
+        if fp::handle_unit_off(tf) {
+            return;
+        }
+    }
+    if let Exception::LoadMisaligned { addr } | Exception::StoreMisaligned { addr } = exception {
+        if misaligned::emulate(tf, addr) {
+            return;
+        }
+    }
+    if call_interface!(TrapHandler::handle_exception, tf, exception) {
+        return;
+    }
+    match exception {
+        Exception::Breakpoint { len } => {
+            log::debug!("Exception(Breakpoint) @ {:#x} ", tf.sepc);
+            tf.sepc += len;
+        }
+        _ => panic!("Unhandled {} @ {:#x}:\n{:#x?}", exception, tf.sepc, tf),
+    }
+}
+
+/// Reads the instruction at `pc`, and returns it with its length in bytes,
+/// 2 for compressed instructions.
+pub fn fetch_inst(pc: usize) -> (u32, usize) {
+    // Instructions are only 2-byte aligned with compressed ones around.
+    let low = unsafe { (pc as *const u16).read() } as u32;
+    if low & 0b11 != 0b11 {
+        return (low, 2);
+    }
+    let high = unsafe { ((pc + 2) as *const u16).read() } as u32;
+    (low | high << 16, 4)
 }
 
-fn handle_breakpoint(sepc: &mut usize) {
-    log::debug!("Exception(Breakpoint) @ {:#x} ", sepc);
-    *sepc += 2
+/// An exception, decoded from `scause` and `stval`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exception {
+    /// A jump or branch to a misaligned address.
+    InstructionMisaligned { addr: usize },
+    /// An instruction fetch from memory it can't be fetched from.
+    InstructionFault { addr: usize },
+    /// An invalid instruction, or one not allowed in supervisor mode.
+    IllegalInstruction { inst: u32, len: usize },
+    /// `ebreak`, or `c.ebreak` if `len` is 2.
+    Breakpoint { len: usize },
+    LoadMisaligned { addr: usize },
+    LoadFault { addr: usize },
+    StoreMisaligned { addr: usize },
+    StoreFault { addr: usize },
+    UserEnvCall,
+    SupervisorEnvCall,
+    InstructionPageFault { addr: usize },
+    LoadPageFault { addr: usize },
+    StorePageFault { addr: usize },
+    /// An exception code this kernel does not know.
+    Unknown { code: usize, stval: usize },
+}
+
+impl Exception {
+    fn decode(code: usize, stval: usize, sepc: usize) -> Self {
+        match code {
+            0 => Self::InstructionMisaligned { addr: stval },
+            1 => Self::InstructionFault { addr: stval },
+            2 => {
+                // Harts may leave `stval` zero rather than the instruction.
+                let (inst, len) = fetch_inst(sepc);
+                let inst = if stval != 0 { stval as u32 } else { inst };
+                Self::IllegalInstruction { inst, len }
+            }
+            3 => Self::Breakpoint {
+                len: fetch_inst(sepc).1,
+            },
+            4 => Self::LoadMisaligned { addr: stval },
+            5 => Self::LoadFault { addr: stval },
+            6 => Self::StoreMisaligned { addr: stval },
+            7 => Self::StoreFault { addr: stval },
+            8 => Self::UserEnvCall,
+            9 => Self::SupervisorEnvCall,
+            12 => Self::InstructionPageFault { addr: stval },
+            13 => Self::LoadPageFault { addr: stval },
+            15 => Self::StorePageFault { addr: stval },
+            _ => Self::Unknown { code, stval },
+        }
+    }
+
+    /// Returns the address the exception is about, for the ones that have
+    /// one.
+    pub fn fault_addr(&self) -> Option<usize> {
+        match *self {
+            Self::InstructionMisaligned { addr }
+            | Self::InstructionFault { addr }
+            | Self::LoadMisaligned { addr }
+            | Self::LoadFault { addr }
+            | Self::StoreMisaligned { addr }
+            | Self::StoreFault { addr }
+            | Self::InstructionPageFault { addr }
+            | Self::LoadPageFault { addr }
+            | Self::StorePageFault { addr } => Some(addr),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Exception {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::InstructionMisaligned { addr } => {
+                write!(f, "misaligned instruction fetch at {:#x}", addr)
+            }
+            Self::InstructionFault { addr } => write!(f, "instruction access fault at {:#x}", addr),
+            Self::IllegalInstruction { inst, len: 2 } => write!(f, "illegal instruction {:#06x}", inst),
+            Self::IllegalInstruction { inst, .. } => write!(f, "illegal instruction {:#010x}", inst),
+            Self::Breakpoint { .. } => f.write_str("breakpoint"),
+            Self::LoadMisaligned { addr } => write!(f, "misaligned load at {:#x}", addr),
+            Self::LoadFault { addr } => write!(f, "load access fault at {:#x}", addr),
+            Self::StoreMisaligned { addr } => write!(f, "misaligned store at {:#x}", addr),
+            Self::StoreFault { addr } => write!(f, "store access fault at {:#x}", addr),
+            Self::UserEnvCall => f.write_str("ecall from user mode"),
+            Self::SupervisorEnvCall => f.write_str("ecall from supervisor mode"),
+            Self::InstructionPageFault { addr } => write!(f, "instruction page fault at {:#x}", addr),
+            Self::LoadPageFault { addr } => write!(f, "load page fault at {:#x}", addr),
+            Self::StorePageFault { addr } => write!(f, "store page fault at {:#x}", addr),
+            Self::Unknown { code, stval } => {
+                write!(f, "unknown exception {} (stval {:#x})", code, stval)
+            }
+        }
+    }
+}
+
+impl TrapFrame {
+    /// Returns whether IRQs were enabled in the trapped code.
+    pub fn irqs_enabled(&self) -> bool {
+        self.sstatus & SSTATUS_SPIE != 0
+    }
 }
 
 /// Trap handler interface.
@@ -47,7 +198,16 @@ fn handle_breakpoint(sepc: &mut usize) {
 pub trait TrapHandler {
     /// Handles interrupt requests for the given IRQ number.
     fn handle_irq(irq_num: usize);
-    // more e.g.: handle_page_fault();
+    /// Called after the IRQ handler returns, right before returning to the
+    /// interrupted code. Preemption requested by the handler, and the bottom
+    /// halves it defers, happen here.
+    fn irq_exit();
+    /// Handles an exception, and returns whether it did. The ones it does
+    /// not handle panic, except breakpoints, which are skipped.
+    ///
+    /// It may change the trap frame to resume somewhere else, or never
+    /// return, such as when it terminates the faulting task.
+    fn handle_exception(tf: &mut TrapFrame, exception: Exception) -> bool;
 }
 
 /// Call the external IRQ handler.
@@ -0,0 +1,97 @@
+//! Emulation of misaligned loads and stores.
+//!
+//! Harts may trap on misaligned accesses rather than doing them in hardware,
+//! and leave them to the firmware or the kernel. They are done here byte by
+//! byte. Floating-point loads and stores are not emulated.
+
+use super::fetch_inst;
+use crate::riscv64::context::TrapFrame;
+
+/// A load or store, decoded from its instruction.
+struct Access {
+    /// The destination register of a load, or the source of a store.
+    reg: usize,
+    /// The width in bytes.
+    width: usize,
+    /// Whether a load sign-extends.
+    signed: bool,
+    is_load: bool,
+}
+
+impl Access {
+    const fn load(reg: usize, width: usize, signed: bool) -> Option<Self> {
+        Some(Self { reg, width, signed, is_load: true })
+    }
+
+    const fn store(reg: usize, width: usize) -> Option<Self> {
+        Some(Self { reg, width, signed: false, is_load: false })
+    }
+}
+
+fn decode(inst: u32, len: usize) -> Option<Access> {
+    let inst = inst as usize;
+    if len == 4 {
+        let funct3 = (inst >> 12) & 0b111;
+        let rd = (inst >> 7) & 0x1f;
+        let rs2 = (inst >> 20) & 0x1f;
+        return match (inst & 0x7f, funct3) {
+            // LOAD: lb, lh, lw, ld, lbu, lhu, lwu
+            (0x03, 0..=3) => Access::load(rd, 1 << funct3, funct3 != 3),
+            (0x03, 4..=6) => Access::load(rd, 1 << (funct3 - 4), false),
+            // STORE: sb, sh, sw, sd
+            (0x23, 0..=3) => Access::store(rs2, 1 << funct3),
+            _ => None,
+        };
+    }
+    let funct3 = (inst >> 13) & 0b111;
+    // rd' and rs2' of quadrant 0, which are x8-x15
+    let reg_prime = ((inst >> 2) & 0b111) + 8;
+    match (inst & 0b11, funct3) {
+        // c.lw, c.ld, c.sw, c.sd
+        (0b00, 2) => Access::load(reg_prime, 4, true),
+        (0b00, 3) => Access::load(reg_prime, 8, false),
+        (0b00, 6) => Access::store(reg_prime, 4),
+        (0b00, 7) => Access::store(reg_prime, 8),
+        // c.lwsp, c.ldsp, c.swsp, c.sdsp
+        (0b10, 2) => Access::load((inst >> 7) & 0x1f, 4, true),
+        (0b10, 3) => Access::load((inst >> 7) & 0x1f, 8, false),
+        (0b10, 6) => Access::store((inst >> 2) & 0x1f, 4),
+        (0b10, 7) => Access::store((inst >> 2) & 0x1f, 8),
+        _ => None,
+    }
+}
+
+/// Emulates the misaligned load or store at `tf.sepc` of `addr`, and
+/// returns whether it is one it can emulate.
+pub(super) fn emulate(tf: &mut TrapFrame, addr: usize) -> bool {
+    let (inst, len) = fetch_inst(tf.sepc);
+    let Some(access) = decode(inst, len) else {
+        return false;
+    };
+    // `gp` and `tp` are not saved in the frames of kernel traps.
+    if matches!(access.reg, 3 | 4) {
+        return false;
+    }
+    let regs = tf.regs.as_array_mut();
+    if access.is_load {
+        let mut val = 0;
+        for i in 0..access.width {
+            let byte = unsafe { ((addr + i) as *const u8).read_volatile() };
+            val |= (byte as usize) << (i * 8);
+        }
+        if access.signed && access.width < 8 {
+            let shift = usize::BITS as usize - access.width * 8;
+            val = (((val << shift) as isize) >> shift) as usize;
+        }
+        if access.reg != 0 {
+            regs[access.reg - 1] = val;
+        }
+    } else {
+        let val = if access.reg == 0 { 0 } else { regs[access.reg - 1] };
+        for i in 0..access.width {
+            unsafe { ((addr + i) as *mut u8).write_volatile((val >> (i * 8)) as u8) };
+        }
+    }
+    tf.sepc += len;
+    true
+}
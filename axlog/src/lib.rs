@@ -4,16 +4,30 @@
 //! The log macros, in descending order of level, are: [`error!`], [`warn!`],
 //! [`info!`], [`debug!`], and [`trace!`].
 //!
+//! Besides the console, records are kept in an in-memory ring buffer, which
+//! can be read back with [`records`] or printed with [`dump`], like `dmesg`.
+//! The ring buffer has its own level (see [`set_ring_level`]), so it can
+//! capture more than the console shows.
+//!
+//! # Cargo features
+//!
+//! - `log-level-off`, `log-level-error`, ..., `log-level-trace`: Compile out
+//!   the log macros of lower levels. Without these features, the level is
+//!   only checked at runtime (see [`set_max_level`] and [`set_filter`]).
 
 #![no_std]
 
+mod ring;
+
 use core::fmt::{self, Write};
 use core::str::FromStr;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 
 use log::{Level, LevelFilter, Log, Metadata, Record};
 use crate_interface::call_interface;
 
 pub use log::{debug, error, info, trace, warn};
+pub use ring::{RingRecord, MAX_MESSAGE_LEN};
 
 /// Prints to the console.
 ///
@@ -70,10 +84,93 @@ pub trait LogIf {
 
     /// Gets current clock time.
     fn current_time() -> core::time::Duration;
+
+    /// Gets the ID of the current CPU, if known.
+    fn current_cpu_id() -> Option<usize>;
+
+    /// Calls `f` with the ID and name of the current task, if any.
+    fn with_current_task(f: &mut dyn FnMut(u64, &str));
 }
 
 struct Logger;
 
+/// Maximum number of modules with their own log levels.
+const MAX_MODULE_LEVELS: usize = 8;
+
+/// The log level of modules without their own levels.
+static GLOBAL_LEVEL: AtomicUsize = AtomicUsize::new(LevelFilter::Warn as usize);
+
+static MODULE_LEVELS: spinlock::SpinNoIrq<[Option<(&str, LevelFilter)>; MAX_MODULE_LEVELS]> =
+    spinlock::SpinNoIrq::new([None; MAX_MODULE_LEVELS]);
+
+/// Number of records kept in the ring buffer.
+pub const RING_SIZE: usize = 256;
+
+static RING: ring::Ring<RING_SIZE> = ring::Ring::new();
+
+/// The log level of the ring buffer.
+static RING_LEVEL: AtomicUsize = AtomicUsize::new(LevelFilter::Trace as usize);
+
+static COLOR: AtomicBool = AtomicBool::new(true);
+static SHOW_CPU: AtomicBool = AtomicBool::new(false);
+static SHOW_TASK: AtomicBool = AtomicBool::new(false);
+
+/// The optional part of the record prefix: ` <cpu>` and ` <task id>:<name>`.
+struct Tags;
+
+impl fmt::Display for Tags {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if SHOW_CPU.load(Ordering::Relaxed) {
+            if let Some(cpu_id) = call_interface!(LogIf::current_cpu_id) {
+                write!(f, " {}", cpu_id)?;
+            }
+        }
+        if SHOW_TASK.load(Ordering::Relaxed) {
+            let mut res = Ok(());
+            call_interface!(LogIf::with_current_task, &mut |id, name| {
+                res = write!(f, " {}:{}", id, name);
+            });
+            res?;
+        }
+        Ok(())
+    }
+}
+
+fn level_filter_from_usize(level: usize) -> LevelFilter {
+    LevelFilter::iter().nth(level).unwrap_or(LevelFilter::Trace)
+}
+
+/// Returns the log level of `target`, which is a module path.
+fn level_of(target: &str) -> LevelFilter {
+    let mut found: Option<(&str, LevelFilter)> = None;
+    for &(module, level) in MODULE_LEVELS.lock().iter().flatten() {
+        let matched = target
+            .strip_prefix(module)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"));
+        // the longest match wins
+        if matched && found.is_none_or(|(m, _)| module.len() > m.len()) {
+            found = Some((module, level));
+        }
+    }
+    match found {
+        Some((_, level)) => level,
+        None => level_filter_from_usize(GLOBAL_LEVEL.load(Ordering::Relaxed)),
+    }
+}
+
+/// Lets through the records of the most verbose level in use.
+fn update_max_level() {
+    let global = level_filter_from_usize(GLOBAL_LEVEL.load(Ordering::Relaxed));
+    let ring = level_filter_from_usize(RING_LEVEL.load(Ordering::Relaxed));
+    let max = MODULE_LEVELS
+        .lock()
+        .iter()
+        .flatten()
+        .map(|&(_, level)| level)
+        .fold(core::cmp::max(global, ring), core::cmp::max);
+    log::set_max_level(max);
+}
+
 impl Write for Logger {
     fn write_str(&mut self, s: &str) -> fmt::Result {
         call_interface!(LogIf::console_write_str, s);
@@ -83,12 +180,25 @@ impl Write for Logger {
 
 impl Log for Logger {
     #[inline]
-    fn enabled(&self, _metadata: &Metadata) -> bool {
-        true
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= level_of(metadata.target())
+            || metadata.level() <= level_filter_from_usize(RING_LEVEL.load(Ordering::Relaxed))
     }
 
     fn log(&self, record: &Record) {
         let level = record.level();
+        let to_console = level <= level_of(record.target());
+        let to_ring = level <= level_filter_from_usize(RING_LEVEL.load(Ordering::Relaxed));
+        if !to_console && !to_ring {
+            return;
+        }
+        let now = call_interface!(LogIf::current_time);
+        if to_ring {
+            RING.push(now, level, format_args!("{}: {}", record.target(), record.args()));
+        }
+        if !to_console {
+            return;
+        }
         let line = record.line().unwrap_or(0);
         let path = record.target();
         let args_color = match level {
@@ -98,17 +208,30 @@ impl Log for Logger {
             Level::Debug => ColorCode::Cyan,
             Level::Trace => ColorCode::BrightBlack,
         };
-        let now = call_interface!(LogIf::current_time);
 
-        __print_impl(with_color!(
-            ColorCode::White,
-            "[{:>3}.{:06} {path}:{line}] {args}\n",
-            now.as_secs(),
-            now.subsec_micros(),
-            path = path,
-            line = line,
-            args = with_color!(args_color, "{}", record.args()),
-        ));
+        if COLOR.load(Ordering::Relaxed) {
+            __print_impl(with_color!(
+                ColorCode::White,
+                "[{:>3}.{:06}{tags} {path}:{line}] {args}\n",
+                now.as_secs(),
+                now.subsec_micros(),
+                tags = Tags,
+                path = path,
+                line = line,
+                args = with_color!(args_color, "{}", record.args()),
+            ));
+        } else {
+            __print_impl(format_args!(
+                "[{:>3}.{:06}{tags} {level:<5} {path}:{line}] {args}\n",
+                now.as_secs(),
+                now.subsec_micros(),
+                tags = Tags,
+                level = level,
+                path = path,
+                line = line,
+                args = record.args(),
+            ));
+        }
     }
 
     fn flush(&self) {}
@@ -134,17 +257,120 @@ pub fn __print_impl(args: fmt::Arguments) {
 /// nothing will be printed.
 pub fn init() {
     log::set_logger(&Logger).unwrap();
-    log::set_max_level(LevelFilter::Warn);
+    update_max_level();
 }
 
 /// Set the maximum log level.
 ///
 /// Unlike the features such as `log-level-error`, setting the logging level in
-/// this way incurs runtime overhead. In addition, this function is no effect
-/// when those features are enabled.
+/// this way incurs runtime overhead. In addition, levels compiled out by those
+/// features cannot be turned on here.
 ///
 /// `level` should be one of `off`, `error`, `warn`, `info`, `debug`, `trace`.
 pub fn set_max_level(level: &str) {
     let lf = LevelFilter::from_str(level).ok().unwrap_or(LevelFilter::Off);
-    log::set_max_level(lf);
+    GLOBAL_LEVEL.store(lf as usize, Ordering::Relaxed);
+    update_max_level();
 }
+
+/// Set the log level of a module and its submodules, e.g. `axtask`, which
+/// overrides the maximum log level.
+///
+/// At most 8 modules can have their own levels. Returns `false` if there is
+/// no room left, or `level` is invalid.
+pub fn set_module_level(module: &'static str, level: &str) -> bool {
+    let Ok(lf) = LevelFilter::from_str(level) else {
+        return false;
+    };
+    {
+        let mut levels = MODULE_LEVELS.lock();
+        let slot = match levels.iter().position(|l| l.is_some_and(|(m, _)| m == module)) {
+            Some(idx) => &mut levels[idx],
+            None => match levels.iter_mut().find(|l| l.is_none()) {
+                Some(slot) => slot,
+                None => return false,
+            },
+        };
+        *slot = Some((module, lf));
+    }
+    update_max_level();
+    true
+}
+
+/// Set the log levels from a filter such as `warn,axtask=debug,axalloc=error`.
+///
+/// Each comma-separated item is either a level, which is passed to
+/// [`set_max_level`], or `<module>=<level>`, which is passed to
+/// [`set_module_level`]. Returns `false` if any item is invalid, the valid
+/// ones still take effect.
+pub fn set_filter(filter: &'static str) -> bool {
+    let mut ok = true;
+    for item in filter.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        match item.split_once('=') {
+            Some((module, level)) => ok &= set_module_level(module.trim(), level.trim()),
+            None if LevelFilter::from_str(item).is_ok() => set_max_level(item),
+            None => ok = false,
+        }
+    }
+    ok
+}
+
+/// Enables or disables colors in log records, which is on by default.
+///
+/// Disable it when the console output is captured to a file.
+pub fn set_color(enabled: bool) {
+    COLOR.store(enabled, Ordering::Relaxed);
+}
+
+/// Shows or hides the current CPU ID in log records, which is off by default.
+pub fn set_show_cpu(enabled: bool) {
+    SHOW_CPU.store(enabled, Ordering::Relaxed);
+}
+
+/// Shows or hides the current task ID and name in log records, which is off by
+/// default.
+pub fn set_show_task(enabled: bool) {
+    SHOW_TASK.store(enabled, Ordering::Relaxed);
+}
+
+/// Set the log level of the ring buffer, which is `trace` by default.
+///
+/// Records of this level or above are kept in the ring buffer, whatever the
+/// console shows. `level` should be one of `off`, `error`, `warn`, `info`,
+/// `debug`, `trace`. Returns `false` if it is invalid.
+pub fn set_ring_level(level: &str) -> bool {
+    let Ok(lf) = LevelFilter::from_str(level) else {
+        return false;
+    };
+    RING_LEVEL.store(lf as usize, Ordering::Relaxed);
+    update_max_level();
+    true
+}
+
+/// Returns the records in the ring buffer, oldest first.
+///
+/// At most [`RING_SIZE`] records are kept, and older ones are overwritten.
+pub fn records() -> impl Iterator<Item = RingRecord> {
+    RING.records()
+}
+
+/// Prints all records in the ring buffer to the console.
+pub fn dump() {
+    for record in RING.records() {
+        __print_impl(format_args!("{}\n", record));
+    }
+}
+
+/// Prints the last `count` records in the ring buffer to the console.
+///
+/// It is called on panic, to show what happened before.
+pub fn dump_tail(count: usize) {
+    __print_impl(format_args!("---- last {} log records ----\n", count));
+    for record in RING.tail(count) {
+        __print_impl(format_args!("{}\n", record));
+    }
+    __print_impl(format_args!("---- end of log records ----\n"));
+}
+
+#[cfg(test)]
+mod tests;
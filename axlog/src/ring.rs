@@ -0,0 +1,196 @@
+//! A lock-free ring buffer of recent log records.
+//!
+//! Each record takes a slot with a sequence number. Writers reserve sequence
+//! numbers with an atomic counter, and readers check the slot state before
+//! and after copying a record out, so a record overwritten meanwhile is
+//! skipped rather than read torn.
+
+use core::fmt::{self, Write};
+use core::sync::atomic::{fence, AtomicU64, AtomicU8, Ordering};
+use core::time::Duration;
+
+use log::Level;
+
+/// Maximum length of the message of a record, longer ones are truncated.
+pub const MAX_MESSAGE_LEN: usize = 128;
+
+struct Slot {
+    /// `2 * seq + 1` while record `seq` is being written, `2 * seq + 2` once
+    /// it is complete, and 0 if the slot is never written.
+    state: AtomicU64,
+    nanos: AtomicU64,
+    level: AtomicU8,
+    len: AtomicU8,
+    text: [AtomicU8; MAX_MESSAGE_LEN],
+}
+
+/// A ring buffer of the last `N` log records.
+pub(crate) struct Ring<const N: usize> {
+    next_seq: AtomicU64,
+    slots: [Slot; N],
+}
+
+/// A log record read back from the ring buffer.
+#[derive(Clone)]
+pub struct RingRecord {
+    seq: u64,
+    time: Duration,
+    level: Level,
+    len: usize,
+    text: [u8; MAX_MESSAGE_LEN],
+}
+
+/// Formats a message into a fixed buffer, truncating it at a char boundary.
+struct MessageBuf {
+    buf: [u8; MAX_MESSAGE_LEN],
+    len: usize,
+    full: bool,
+}
+
+impl Write for MessageBuf {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.full {
+            return Ok(());
+        }
+        let mut n = s.len().min(MAX_MESSAGE_LEN - self.len);
+        if n < s.len() {
+            self.full = true;
+            while !s.is_char_boundary(n) {
+                n -= 1;
+            }
+        }
+        self.buf[self.len..self.len + n].copy_from_slice(&s.as_bytes()[..n]);
+        self.len += n;
+        Ok(())
+    }
+}
+
+fn level_from_u8(level: u8) -> Level {
+    (level as usize)
+        .checked_sub(1)
+        .and_then(|idx| Level::iter().nth(idx))
+        .unwrap_or(Level::Trace)
+}
+
+impl Slot {
+    const fn new() -> Self {
+        Self {
+            state: AtomicU64::new(0),
+            nanos: AtomicU64::new(0),
+            level: AtomicU8::new(0),
+            len: AtomicU8::new(0),
+            text: [const { AtomicU8::new(0) }; MAX_MESSAGE_LEN],
+        }
+    }
+}
+
+impl<const N: usize> Ring<N> {
+    pub const fn new() -> Self {
+        Self {
+            next_seq: AtomicU64::new(0),
+            slots: [const { Slot::new() }; N],
+        }
+    }
+
+    fn slot(&self, seq: u64) -> &Slot {
+        &self.slots[(seq % N as u64) as usize]
+    }
+
+    /// Appends a record, overwriting the oldest one if the ring is full.
+    pub fn push(&self, time: Duration, level: Level, args: fmt::Arguments) {
+        let mut msg = MessageBuf {
+            buf: [0; MAX_MESSAGE_LEN],
+            len: 0,
+            full: false,
+        };
+        let _ = msg.write_fmt(args);
+
+        let seq = self.next_seq.fetch_add(1, Ordering::Relaxed);
+        let slot = self.slot(seq);
+        slot.state.store(2 * seq + 1, Ordering::Relaxed);
+        fence(Ordering::Release);
+        slot.nanos.store(time.as_nanos() as u64, Ordering::Relaxed);
+        slot.level.store(level as u8, Ordering::Relaxed);
+        slot.len.store(msg.len as u8, Ordering::Relaxed);
+        for (dst, &src) in slot.text.iter().zip(&msg.buf[..msg.len]) {
+            dst.store(src, Ordering::Relaxed);
+        }
+        slot.state.store(2 * seq + 2, Ordering::Release);
+    }
+
+    /// Reads record `seq`, or returns `None` if it is not complete or has been
+    /// overwritten.
+    fn read(&self, seq: u64) -> Option<RingRecord> {
+        let slot = self.slot(seq);
+        if slot.state.load(Ordering::Acquire) != 2 * seq + 2 {
+            return None;
+        }
+        let mut record = RingRecord {
+            seq,
+            time: Duration::from_nanos(slot.nanos.load(Ordering::Relaxed)),
+            level: level_from_u8(slot.level.load(Ordering::Relaxed)),
+            len: (slot.len.load(Ordering::Relaxed) as usize).min(MAX_MESSAGE_LEN),
+            text: [0; MAX_MESSAGE_LEN],
+        };
+        for (dst, src) in record.text.iter_mut().zip(&slot.text) {
+            *dst = src.load(Ordering::Relaxed);
+        }
+        fence(Ordering::Acquire);
+        if slot.state.load(Ordering::Relaxed) != 2 * seq + 2 {
+            return None;
+        }
+        Some(record)
+    }
+
+    /// Returns the records in the ring, oldest first.
+    ///
+    /// Records pushed after this call are not included.
+    pub fn records(&self) -> impl Iterator<Item = RingRecord> + '_ {
+        self.tail(N)
+    }
+
+    /// Returns the last `count` records in the ring, oldest first.
+    pub fn tail(&self, count: usize) -> impl Iterator<Item = RingRecord> + '_ {
+        let end = self.next_seq.load(Ordering::Acquire);
+        let start = end.saturating_sub(count.min(N) as u64);
+        (start..end).filter_map(|seq| self.read(seq))
+    }
+}
+
+impl RingRecord {
+    /// Returns the sequence number of the record, counting from 0 at boot.
+    ///
+    /// Gaps in the numbers mean that records have been lost.
+    pub fn seq(&self) -> u64 {
+        self.seq
+    }
+
+    /// Returns the time when the record was logged.
+    pub fn time(&self) -> Duration {
+        self.time
+    }
+
+    /// Returns the level of the record.
+    pub fn level(&self) -> Level {
+        self.level
+    }
+
+    /// Returns the message of the record, which is truncated to
+    /// [`MAX_MESSAGE_LEN`] bytes.
+    pub fn message(&self) -> &str {
+        core::str::from_utf8(&self.text[..self.len]).unwrap_or("<invalid>")
+    }
+}
+
+impl fmt::Display for RingRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "[{:>3}.{:06} {:<5}] {}",
+            self.time.as_secs(),
+            self.time.subsec_micros(),
+            self.level,
+            self.message()
+        )
+    }
+}
@@ -0,0 +1,58 @@
+extern crate std;
+
+use core::time::Duration;
+use std::string::String;
+use std::vec::Vec;
+
+use log::Level;
+
+use crate::ring::{Ring, MAX_MESSAGE_LEN};
+
+fn messages<const N: usize>(ring: &Ring<N>) -> Vec<String> {
+    ring.records().map(|r| r.message().into()).collect()
+}
+
+#[test]
+fn test_ring_records() {
+    let ring = Ring::<4>::new();
+    assert_eq!(ring.records().count(), 0);
+
+    ring.push(Duration::from_micros(1_500), Level::Info, format_args!("hello {}", 1));
+    ring.push(Duration::from_secs(2), Level::Error, format_args!("bye"));
+
+    let records: Vec<_> = ring.records().collect();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0].seq(), 0);
+    assert_eq!(records[0].level(), Level::Info);
+    assert_eq!(records[0].time(), Duration::from_micros(1_500));
+    assert_eq!(records[0].message(), "hello 1");
+    assert_eq!(records[1].level(), Level::Error);
+    assert_eq!(std::format!("{}", records[0]), "[  0.001500 INFO ] hello 1");
+}
+
+#[test]
+fn test_ring_overwrite() {
+    let ring = Ring::<4>::new();
+    for i in 0..10 {
+        ring.push(Duration::ZERO, Level::Debug, format_args!("{}", i));
+    }
+    assert_eq!(messages(&ring), ["6", "7", "8", "9"]);
+    assert_eq!(ring.records().next().unwrap().seq(), 6);
+
+    let tail: Vec<_> = ring.tail(2).map(|r| r.seq()).collect();
+    assert_eq!(tail, [8, 9]);
+    assert_eq!(ring.tail(100).count(), 4);
+}
+
+#[test]
+fn test_ring_truncate() {
+    let ring = Ring::<2>::new();
+    let long = "x".repeat(MAX_MESSAGE_LEN - 1) + "é and more";
+    ring.push(Duration::ZERO, Level::Warn, format_args!("{}", long));
+    let record = ring.records().next().unwrap();
+    // the 2-byte char does not fit, and is not split
+    assert_eq!(record.message(), &long[..MAX_MESSAGE_LEN - 1]);
+
+    ring.push(Duration::ZERO, Level::Warn, format_args!("{}{}", long, "tail"));
+    assert_eq!(ring.records().nth(1).unwrap().message().len(), MAX_MESSAGE_LEN - 1);
+}
@@ -0,0 +1,48 @@
+//! Conversions between `core::net` and smoltcp addresses.
+
+use core::net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4};
+
+use smoltcp::wire::{IpAddress, IpEndpoint, IpListenEndpoint, Ipv4Address};
+
+use crate::{NetError, NetResult};
+
+pub fn from_core_ipv4(ip: Ipv4Addr) -> Ipv4Address {
+    Ipv4Address(ip.octets())
+}
+
+pub fn into_core_ipv4(ip: Ipv4Address) -> Ipv4Addr {
+    Ipv4Addr::from(ip.0)
+}
+
+pub fn from_core_sockaddr(addr: SocketAddr) -> NetResult<IpEndpoint> {
+    match addr.ip() {
+        IpAddr::V4(ip) => Ok(IpEndpoint::new(
+            IpAddress::Ipv4(from_core_ipv4(ip)),
+            addr.port(),
+        )),
+        IpAddr::V6(_) => Err(NetError::Unsupported),
+    }
+}
+
+pub fn into_core_sockaddr(endpoint: IpEndpoint) -> SocketAddr {
+    let IpAddress::Ipv4(ip) = endpoint.addr;
+    SocketAddr::V4(SocketAddrV4::new(into_core_ipv4(ip), endpoint.port))
+}
+
+/// Converts a local address to bind, where the unspecified address means
+/// any address.
+pub fn to_listen_endpoint(addr: SocketAddr) -> NetResult<IpListenEndpoint> {
+    let endpoint = from_core_sockaddr(addr)?;
+    Ok(IpListenEndpoint {
+        addr: (!endpoint.addr.is_unspecified()).then_some(endpoint.addr),
+        port: endpoint.port,
+    })
+}
+
+pub fn listen_endpoint_to_core(endpoint: IpListenEndpoint) -> SocketAddr {
+    let ip = match endpoint.addr {
+        Some(IpAddress::Ipv4(ip)) => into_core_ipv4(ip),
+        None => Ipv4Addr::UNSPECIFIED,
+    };
+    SocketAddr::V4(SocketAddrV4::new(ip, endpoint.port))
+}
@@ -0,0 +1,350 @@
+//! The network interface and the socket set, polled together.
+
+use alloc::collections::BTreeSet;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cell::{Cell, RefCell};
+use core::net::Ipv4Addr;
+
+use axdriver::{AxNetDevice, DevError};
+use axtask::WaitQueue;
+use smoltcp::iface::{Config, Interface, SocketHandle, SocketSet};
+use smoltcp::phy::{Device, DeviceCapabilities, Medium, RxToken, TxToken};
+use smoltcp::socket::{dhcpv4, tcp, AnySocket};
+use smoltcp::time::Instant;
+use smoltcp::wire::{EthernetAddress, HardwareAddress, IpCidr, Ipv4Address, Ipv4Cidr};
+use spin::Once;
+use spinlock::SpinNoIrq;
+
+use crate::addr::from_core_ipv4;
+use crate::{NetError, NetResult};
+
+const STANDARD_MTU: usize = 1500;
+const ETHERNET_HEADER_LEN: usize = 14;
+
+const RANDOM_SEED: u64 = 0xA2CE_05A2_CE05_A2CE;
+
+const EPHEMERAL_PORT_START: u16 = 0xC000;
+const EPHEMERAL_PORT_END: u16 = 0xFFFF;
+
+struct DeviceWrapper {
+    inner: AxNetDevice,
+}
+
+struct AxRxToken(Vec<u8>);
+
+struct AxTxToken<'a>(&'a mut AxNetDevice);
+
+struct InterfaceWrapper {
+    iface: Interface,
+    dev: DeviceWrapper,
+    sockets: SocketSet<'static>,
+    dhcp_handle: Option<SocketHandle>,
+    /// TCP sockets closed by the user, to be removed once fully closed.
+    closing: Vec<SocketHandle>,
+    /// Local ports in use, by TCP and UDP respectively.
+    tcp_ports: BTreeSet<u16>,
+    udp_ports: BTreeSet<u16>,
+    next_ephemeral_port: u16,
+}
+
+static ETH0: Once<SpinNoIrq<InterfaceWrapper>> = Once::new();
+
+/// Tasks waiting for sockets to become ready are woken up after each poll.
+static SOCKET_WQ: WaitQueue = WaitQueue::new();
+/// The polling task waits here for the next timer tick.
+static POLL_WQ: WaitQueue = WaitQueue::new();
+
+/// The protocol of a local port.
+#[derive(Clone, Copy)]
+pub(crate) enum Protocol {
+    Tcp,
+    Udp,
+}
+
+impl Device for DeviceWrapper {
+    type RxToken<'a> = AxRxToken;
+    type TxToken<'a> = AxTxToken<'a>;
+
+    fn receive(&mut self, _timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        if !self.inner.can_receive() {
+            return None;
+        }
+        let mut buf = vec![0; STANDARD_MTU + ETHERNET_HEADER_LEN];
+        match self.inner.receive(&mut buf) {
+            Ok(len) => {
+                buf.truncate(len);
+                Some((AxRxToken(buf), AxTxToken(&mut self.inner)))
+            }
+            Err(DevError::Again) => None,
+            Err(e) => {
+                warn!("failed to receive packet: {:?}", e);
+                None
+            }
+        }
+    }
+
+    fn transmit(&mut self, _timestamp: Instant) -> Option<Self::TxToken<'_>> {
+        if self.inner.can_transmit() {
+            Some(AxTxToken(&mut self.inner))
+        } else {
+            None
+        }
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        let mut caps = DeviceCapabilities::default();
+        caps.max_transmission_unit = STANDARD_MTU + ETHERNET_HEADER_LEN;
+        caps.max_burst_size = None;
+        caps.medium = Medium::Ethernet;
+        caps
+    }
+}
+
+impl RxToken for AxRxToken {
+    fn consume<R, F>(mut self, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        trace!("RECV {} bytes", self.0.len());
+        f(&mut self.0)
+    }
+}
+
+impl TxToken for AxTxToken<'_> {
+    fn consume<R, F>(self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        let mut buf = vec![0; len];
+        let ret = f(&mut buf);
+        trace!("SEND {} bytes", len);
+        if let Err(e) = self.0.transmit(&buf) {
+            warn!("failed to transmit packet: {:?}", e);
+        }
+        ret
+    }
+}
+
+fn current_instant() -> Instant {
+    Instant::from_micros_const(axhal::time::current_time().as_micros() as i64)
+}
+
+/// Parses the static configuration given at build time.
+fn static_config() -> Option<(Ipv4Cidr, Option<Ipv4Address>)> {
+    let ip = option_env!("AX_IP").filter(|s| !s.is_empty())?;
+    let (addr, prefix_len) = ip.split_once('/').unwrap_or((ip, "24"));
+    let addr: Ipv4Addr = addr.parse().expect("invalid AX_IP");
+    let prefix_len = prefix_len.parse().expect("invalid prefix length in AX_IP");
+    let gateway = option_env!("AX_GW")
+        .filter(|s| !s.is_empty())
+        .map(|gw| from_core_ipv4(gw.parse().expect("invalid AX_GW")));
+    Some((Ipv4Cidr::new(from_core_ipv4(addr), prefix_len), gateway))
+}
+
+fn set_ipv4_config(iface: &mut Interface, cidr: Option<Ipv4Cidr>, gateway: Option<Ipv4Address>) {
+    iface.update_ip_addrs(|addrs| {
+        addrs.clear();
+        if let Some(cidr) = cidr {
+            addrs.push(IpCidr::Ipv4(cidr)).unwrap();
+        }
+    });
+    match gateway {
+        Some(gw) => {
+            iface.routes_mut().add_default_ipv4_route(gw).unwrap();
+        }
+        None => {
+            iface.routes_mut().remove_default_ipv4_route();
+        }
+    }
+}
+
+pub(crate) fn init(dev: AxNetDevice) {
+    let mut dev = DeviceWrapper { inner: dev };
+    let mac = dev.inner.mac_address();
+    let mut config = Config::new(HardwareAddress::Ethernet(EthernetAddress(mac.0)));
+    config.random_seed = RANDOM_SEED;
+
+    let mut iface = Interface::new(config, &mut dev, current_instant());
+    let mut sockets = SocketSet::new(vec![]);
+    let dhcp_handle = match static_config() {
+        Some((cidr, gateway)) => {
+            set_ipv4_config(&mut iface, Some(cidr), gateway);
+            info!("  ip: {}, gateway: {:?}", cidr, gateway);
+            None
+        }
+        None => {
+            info!("  ip: waiting for DHCP");
+            Some(sockets.add(dhcpv4::Socket::new()))
+        }
+    };
+    info!("  use NIC: {}, mac: {}", dev.inner.device_name(), EthernetAddress(mac.0));
+
+    ETH0.call_once(|| {
+        SpinNoIrq::new(InterfaceWrapper {
+            iface,
+            dev,
+            sockets,
+            dhcp_handle,
+            closing: Vec::new(),
+            tcp_ports: BTreeSet::new(),
+            udp_ports: BTreeSet::new(),
+            next_ephemeral_port: EPHEMERAL_PORT_START,
+        })
+    });
+
+    axtask::spawn_raw(
+        || loop {
+            poll_interfaces();
+            POLL_WQ.wait();
+        },
+        "net-poll".into(),
+        axtask::default_stack_size(),
+    );
+}
+
+impl InterfaceWrapper {
+    fn poll(&mut self) {
+        self.iface
+            .poll(current_instant(), &mut self.dev, &mut self.sockets);
+
+        if let Some(handle) = self.dhcp_handle {
+            let event = self.sockets.get_mut::<dhcpv4::Socket>(handle).poll();
+            match event {
+                Some(dhcpv4::Event::Configured(config)) => {
+                    info!("DHCP: ip {}, gateway {:?}", config.address, config.router);
+                    set_ipv4_config(&mut self.iface, Some(config.address), config.router);
+                }
+                Some(dhcpv4::Event::Deconfigured) => {
+                    info!("DHCP: lost configuration");
+                    set_ipv4_config(&mut self.iface, None, None);
+                }
+                None => {}
+            }
+        }
+
+        let sockets = &mut self.sockets;
+        self.closing.retain(|&handle| {
+            let closed = matches!(
+                sockets.get::<tcp::Socket>(handle).state(),
+                tcp::State::Closed | tcp::State::TimeWait
+            );
+            if closed {
+                sockets.remove(handle);
+            }
+            !closed
+        });
+    }
+
+    fn ports(&mut self, proto: Protocol) -> &mut BTreeSet<u16> {
+        match proto {
+            Protocol::Tcp => &mut self.tcp_ports,
+            Protocol::Udp => &mut self.udp_ports,
+        }
+    }
+}
+
+/// Polls the interface and all sockets, then wakes up the tasks waiting for
+/// sockets.
+pub fn poll_interfaces() {
+    if let Some(eth0) = ETH0.get() {
+        eth0.lock().poll();
+        SOCKET_WQ.notify_all(false);
+    }
+}
+
+/// Wakes up the polling task, should be called on each timer tick.
+pub fn on_timer_tick() {
+    if ETH0.is_completed() {
+        POLL_WQ.notify_one(false);
+    }
+}
+
+fn eth0() -> NetResult<&'static SpinNoIrq<InterfaceWrapper>> {
+    ETH0.get().ok_or(NetError::BadState)
+}
+
+/// Repeats `f` until it does not return [`NetError::WouldBlock`], waiting
+/// for the next poll in between.
+pub(crate) fn block_on<T>(f: impl FnMut() -> NetResult<T>) -> NetResult<T> {
+    poll_interfaces();
+    wait_ready(&SOCKET_WQ, f)
+}
+
+/// Waits on `wq` until `f` does not return [`NetError::WouldBlock`].
+///
+/// `f` is tried with `wq` locked, so that a notification right after it
+/// would block is not lost.
+pub(crate) fn wait_ready<T>(wq: &WaitQueue, f: impl FnMut() -> NetResult<T>) -> NetResult<T> {
+    let f = RefCell::new(f);
+    let res = Cell::new(None);
+    wq.wait_until(|| match (f.borrow_mut())() {
+        Err(NetError::WouldBlock) => false,
+        r => {
+            res.set(Some(r));
+            true
+        }
+    });
+    res.take().unwrap()
+}
+
+/// Adds a socket to the socket set.
+pub(crate) fn add_socket<T: AnySocket<'static>>(socket: T) -> NetResult<SocketHandle> {
+    Ok(eth0()?.lock().sockets.add(socket))
+}
+
+/// Removes a socket from the socket set immediately.
+pub(crate) fn remove_socket(handle: SocketHandle) {
+    if let Ok(eth0) = eth0() {
+        eth0.lock().sockets.remove(handle);
+    }
+}
+
+/// Removes a closed TCP socket once the connection is fully closed.
+pub(crate) fn release_tcp_socket(handle: SocketHandle) {
+    if let Ok(eth0) = eth0() {
+        let mut eth0 = eth0.lock();
+        eth0.sockets.get_mut::<tcp::Socket>(handle).close();
+        eth0.closing.push(handle);
+    }
+}
+
+/// Calls `f` with the socket of `handle`, and the context of the interface.
+pub(crate) fn with_socket<T: AnySocket<'static>, R>(
+    handle: SocketHandle,
+    f: impl FnOnce(&mut T, &mut smoltcp::iface::Context) -> R,
+) -> R {
+    let mut eth0 = eth0().unwrap().lock();
+    let eth0 = &mut *eth0;
+    f(eth0.sockets.get_mut::<T>(handle), eth0.iface.context())
+}
+
+/// Reserves the local `port`, or an ephemeral port if it is 0.
+pub(crate) fn alloc_port(proto: Protocol, port: u16) -> NetResult<u16> {
+    let mut eth0 = eth0()?.lock();
+    if port != 0 {
+        return if eth0.ports(proto).insert(port) {
+            Ok(port)
+        } else {
+            Err(NetError::AddrInUse)
+        };
+    }
+    for _ in EPHEMERAL_PORT_START..=EPHEMERAL_PORT_END {
+        let port = eth0.next_ephemeral_port;
+        eth0.next_ephemeral_port = if port == EPHEMERAL_PORT_END {
+            EPHEMERAL_PORT_START
+        } else {
+            port + 1
+        };
+        if eth0.ports(proto).insert(port) {
+            return Ok(port);
+        }
+    }
+    Err(NetError::AddrInUse)
+}
+
+pub(crate) fn free_port(proto: Protocol, port: u16) {
+    if let Ok(eth0) = eth0() {
+        eth0.lock().ports(proto).remove(&port);
+    }
+}
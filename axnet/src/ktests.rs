@@ -0,0 +1,41 @@
+use alloc::string::String;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use axtask::WaitQueue;
+use axtest::ax_test;
+
+use crate::iface::wait_ready;
+use crate::NetError;
+
+#[ax_test]
+fn test_wait_ready_woken() {
+    static WQ: WaitQueue = WaitQueue::new();
+    static READY: AtomicUsize = AtomicUsize::new(0);
+    let task = axtask::spawn_raw(
+        || {
+            for _ in 0..4 {
+                axtask::yield_now();
+            }
+            READY.store(7, Ordering::Release);
+            WQ.notify_all(false);
+        },
+        String::from("ktest"),
+        axtask::default_stack_size(),
+    );
+    let res = wait_ready(&WQ, || {
+        match READY.load(Ordering::Acquire) {
+            0 => Err(NetError::WouldBlock),
+            n => Ok(n),
+        }
+    });
+    assert_eq!(res, Ok(7));
+    task.join();
+}
+
+#[ax_test]
+fn test_wait_ready_error() {
+    static WQ: WaitQueue = WaitQueue::new();
+    // Errors other than `WouldBlock` return at once.
+    let res: Result<(), _> = wait_ready(&WQ, || Err(NetError::ConnectionReset));
+    assert_eq!(res, Err(NetError::ConnectionReset));
+}
@@ -0,0 +1,70 @@
+//! Network module of [ArceOS](https://github.com/rcore-os/arceos).
+//!
+//! It drives a NIC with the [smoltcp] TCP/IP stack, and provides blocking
+//! TCP and UDP sockets on top of it.
+//!
+//! The interface is configured statically if the `AX_IP` environment
+//! variable (e.g. `10.0.2.15/24`, with the gateway in `AX_GW`) is given at
+//! build time, or by DHCP otherwise.
+//!
+//! Sockets are polled by a dedicated task on each timer tick. An operation
+//! that cannot complete yet blocks the calling task until the next poll.
+
+#![no_std]
+
+#[macro_use]
+extern crate log;
+extern crate alloc;
+
+mod addr;
+mod iface;
+mod tcp;
+mod udp;
+
+#[cfg(test)]
+mod tests;
+#[cfg(ktest)]
+mod ktests;
+
+use alloc::vec::Vec;
+use axdriver::AxNetDevice;
+
+pub use self::iface::{on_timer_tick, poll_interfaces};
+pub use self::tcp::{TcpListener, TcpStream};
+pub use self::udp::UdpSocket;
+
+/// The error type for socket operation failures.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum NetError {
+    /// The address or port is already in use.
+    AddrInUse,
+    /// The network is not initialized, or the socket is in a bad state.
+    BadState,
+    /// The remote host refused the connection.
+    ConnectionRefused,
+    /// The connection was reset by the remote host.
+    ConnectionReset,
+    /// Invalid parameter/argument.
+    InvalidInput,
+    /// The socket is not connected.
+    NotConnected,
+    /// The operation is not supported.
+    Unsupported,
+    /// The operation needs to block to complete.
+    WouldBlock,
+}
+
+/// A [`Result`] type with [`NetError`] as the error type.
+pub type NetResult<T = ()> = Result<T, NetError>;
+
+/// Initializes the network stack on the given NICs.
+///
+/// Only the first device is used currently.
+pub fn init_network(net_devs: Vec<AxNetDevice>) {
+    info!("Initialize network subsystem...");
+
+    match net_devs.into_iter().next() {
+        Some(dev) => iface::init(dev),
+        None => warn!("  no network device found"),
+    }
+}
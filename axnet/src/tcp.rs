@@ -0,0 +1,226 @@
+//! TCP sockets.
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::net::SocketAddr;
+
+use smoltcp::iface::SocketHandle;
+use smoltcp::socket::tcp::{self, ConnectError, State};
+use spinlock::SpinNoIrq;
+
+use crate::addr::{from_core_sockaddr, into_core_sockaddr, listen_endpoint_to_core, to_listen_endpoint};
+use crate::iface::{self, block_on, with_socket, Protocol};
+use crate::{NetError, NetResult};
+
+const TCP_RX_BUF_LEN: usize = 64 * 1024;
+const TCP_TX_BUF_LEN: usize = 64 * 1024;
+
+/// Number of connections that can be pending in a listener.
+const LISTEN_BACKLOG: usize = 4;
+
+fn new_tcp_socket() -> tcp::Socket<'static> {
+    tcp::Socket::new(
+        tcp::SocketBuffer::new(vec![0; TCP_RX_BUF_LEN]),
+        tcp::SocketBuffer::new(vec![0; TCP_TX_BUF_LEN]),
+    )
+}
+
+/// A TCP stream between a local and a remote socket.
+pub struct TcpStream {
+    handle: SocketHandle,
+    local_addr: SocketAddr,
+    peer_addr: SocketAddr,
+    /// The local port owned by this stream, if it is not accepted from a
+    /// listener.
+    own_port: Option<u16>,
+}
+
+/// A TCP socket server, listening for connections.
+///
+/// Several sockets listen on the port at the same time, so that up to
+/// [`LISTEN_BACKLOG`] connections can be established before they are
+/// accepted.
+pub struct TcpListener {
+    local_addr: SocketAddr,
+    backlog: SpinNoIrq<Vec<SocketHandle>>,
+}
+
+impl TcpStream {
+    /// Opens a TCP connection to a remote host, and blocks until the
+    /// connection is established.
+    pub fn connect(addr: SocketAddr) -> NetResult<Self> {
+        let remote = from_core_sockaddr(addr)?;
+        let handle = iface::add_socket(new_tcp_socket())?;
+        let port = match iface::alloc_port(Protocol::Tcp, 0) {
+            Ok(port) => port,
+            Err(e) => {
+                iface::remove_socket(handle);
+                return Err(e);
+            }
+        };
+        let mut stream = Self {
+            handle,
+            local_addr: SocketAddr::new(addr.ip(), port), // updated below
+            peer_addr: addr,
+            own_port: Some(port),
+        };
+
+        with_socket::<tcp::Socket, _>(handle, |socket, cx| {
+            socket.connect(cx, remote, port).map_err(|e| match e {
+                ConnectError::InvalidState => NetError::BadState,
+                ConnectError::Unaddressable => NetError::InvalidInput,
+            })
+        })?;
+        let local = block_on(|| {
+            with_socket::<tcp::Socket, _>(handle, |socket, _| match socket.state() {
+                State::SynSent => Err(NetError::WouldBlock),
+                State::Established => Ok(socket.local_endpoint()),
+                _ => Err(NetError::ConnectionRefused),
+            })
+        })?;
+        if let Some(local) = local {
+            stream.local_addr = into_core_sockaddr(local);
+        }
+        Ok(stream)
+    }
+
+    /// Returns the local address of this connection.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Returns the remote address of this connection.
+    pub fn peer_addr(&self) -> SocketAddr {
+        self.peer_addr
+    }
+
+    /// Receives data into `buf`, blocking until some data is available.
+    ///
+    /// Returns 0 once the remote host has closed the connection.
+    pub fn recv(&self, buf: &mut [u8]) -> NetResult<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        block_on(|| {
+            with_socket::<tcp::Socket, _>(self.handle, |socket, _| {
+                if socket.can_recv() {
+                    socket.recv_slice(buf).map_err(|_| NetError::BadState)
+                } else if !socket.may_recv() {
+                    Ok(0) // closed by the remote host
+                } else {
+                    Err(NetError::WouldBlock)
+                }
+            })
+        })
+    }
+
+    /// Sends data in `buf`, blocking until some of it has been queued.
+    pub fn send(&self, buf: &[u8]) -> NetResult<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        block_on(|| {
+            with_socket::<tcp::Socket, _>(self.handle, |socket, _| {
+                if !socket.is_active() {
+                    Err(NetError::ConnectionReset)
+                } else if !socket.may_send() {
+                    Err(NetError::NotConnected) // shut down
+                } else if socket.can_send() {
+                    socket.send_slice(buf).map_err(|_| NetError::BadState)
+                } else {
+                    Err(NetError::WouldBlock)
+                }
+            })
+        })
+    }
+
+    /// Shuts down the sending half of the connection.
+    pub fn shutdown(&self) -> NetResult {
+        with_socket::<tcp::Socket, _>(self.handle, |socket, _| socket.close());
+        iface::poll_interfaces();
+        Ok(())
+    }
+}
+
+impl Drop for TcpStream {
+    fn drop(&mut self) {
+        iface::release_tcp_socket(self.handle);
+        if let Some(port) = self.own_port {
+            iface::free_port(Protocol::Tcp, port);
+        }
+        iface::poll_interfaces();
+    }
+}
+
+impl TcpListener {
+    /// Creates a listener bound to `addr`.
+    ///
+    /// An ephemeral port is chosen if the port of `addr` is 0.
+    pub fn bind(addr: SocketAddr) -> NetResult<Self> {
+        let mut endpoint = to_listen_endpoint(addr)?;
+        endpoint.port = iface::alloc_port(Protocol::Tcp, endpoint.port)?;
+        let listener = Self {
+            local_addr: listen_endpoint_to_core(endpoint),
+            backlog: SpinNoIrq::new(Vec::new()),
+        };
+        for _ in 0..LISTEN_BACKLOG {
+            let handle = listener.new_listening_socket()?;
+            listener.backlog.lock().push(handle);
+        }
+        Ok(listener)
+    }
+
+    /// Returns the local address that this listener is bound to.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    fn new_listening_socket(&self) -> NetResult<SocketHandle> {
+        let endpoint = to_listen_endpoint(self.local_addr)?;
+        let handle = iface::add_socket(new_tcp_socket())?;
+        with_socket::<tcp::Socket, _>(handle, |socket, _| socket.listen(endpoint))
+            .map_err(|_| NetError::BadState)?;
+        Ok(handle)
+    }
+
+    /// Accepts a new incoming connection, blocking until one is established.
+    pub fn accept(&self) -> NetResult<(TcpStream, SocketAddr)> {
+        let (idx, handle, local, peer) = block_on(|| {
+            let backlog = self.backlog.lock();
+            for (idx, &handle) in backlog.iter().enumerate() {
+                let ready = with_socket::<tcp::Socket, _>(handle, |socket, _| {
+                    match socket.state() {
+                        State::Established | State::CloseWait => {
+                            Some((socket.local_endpoint(), socket.remote_endpoint()))
+                        }
+                        _ => None,
+                    }
+                });
+                if let Some((Some(local), Some(peer))) = ready {
+                    return Ok((idx, handle, local, peer));
+                }
+            }
+            Err(NetError::WouldBlock)
+        })?;
+
+        let peer_addr = into_core_sockaddr(peer);
+        let stream = TcpStream {
+            handle,
+            local_addr: into_core_sockaddr(local),
+            peer_addr,
+            own_port: None,
+        };
+        // listen on the port again with a new socket
+        self.backlog.lock()[idx] = self.new_listening_socket()?;
+        Ok((stream, peer_addr))
+    }
+}
+
+impl Drop for TcpListener {
+    fn drop(&mut self) {
+        for &handle in self.backlog.lock().iter() {
+            iface::remove_socket(handle);
+        }
+        iface::free_port(Protocol::Tcp, self.local_addr.port());
+    }
+}
@@ -0,0 +1,35 @@
+use core::net::{Ipv4Addr, SocketAddr};
+
+use smoltcp::wire::{IpAddress, IpEndpoint, IpListenEndpoint, Ipv4Address};
+
+use crate::addr::*;
+use crate::NetError;
+
+#[test]
+fn test_sockaddr_conversions() {
+    let addr: SocketAddr = "10.0.2.15:5555".parse().unwrap();
+    let endpoint = from_core_sockaddr(addr).unwrap();
+    assert_eq!(
+        endpoint,
+        IpEndpoint::new(IpAddress::Ipv4(Ipv4Address([10, 0, 2, 15])), 5555)
+    );
+    assert_eq!(into_core_sockaddr(endpoint), addr);
+    assert_eq!(into_core_ipv4(from_core_ipv4(Ipv4Addr::LOCALHOST)), Ipv4Addr::LOCALHOST);
+
+    let v6: SocketAddr = "[::1]:80".parse().unwrap();
+    assert_eq!(from_core_sockaddr(v6), Err(NetError::Unsupported));
+}
+
+#[test]
+fn test_listen_endpoint() {
+    // The unspecified address listens on any address.
+    let any: SocketAddr = "0.0.0.0:80".parse().unwrap();
+    let endpoint = to_listen_endpoint(any).unwrap();
+    assert_eq!(endpoint, IpListenEndpoint { addr: None, port: 80 });
+    assert_eq!(listen_endpoint_to_core(endpoint), any);
+
+    let addr: SocketAddr = "10.0.2.15:80".parse().unwrap();
+    let endpoint = to_listen_endpoint(addr).unwrap();
+    assert_eq!(endpoint.addr, Some(IpAddress::Ipv4(Ipv4Address([10, 0, 2, 15]))));
+    assert_eq!(listen_endpoint_to_core(endpoint), addr);
+}
@@ -0,0 +1,138 @@
+//! UDP sockets.
+
+use alloc::vec;
+use core::net::SocketAddr;
+
+use smoltcp::iface::SocketHandle;
+use smoltcp::socket::udp::{self, SendError};
+use spinlock::SpinNoIrq;
+
+use crate::addr::{from_core_sockaddr, into_core_sockaddr, listen_endpoint_to_core, to_listen_endpoint};
+use crate::iface::{self, block_on, with_socket, Protocol};
+use crate::{NetError, NetResult};
+
+const UDP_RX_BUF_LEN: usize = 64 * 1024;
+const UDP_TX_BUF_LEN: usize = 64 * 1024;
+const UDP_METADATA_LEN: usize = 256;
+
+/// A UDP socket.
+pub struct UdpSocket {
+    handle: SocketHandle,
+    local_addr: SocketAddr,
+    peer_addr: SpinNoIrq<Option<SocketAddr>>,
+}
+
+impl UdpSocket {
+    /// Creates a UDP socket bound to `addr`.
+    ///
+    /// An ephemeral port is chosen if the port of `addr` is 0.
+    pub fn bind(addr: SocketAddr) -> NetResult<Self> {
+        let mut endpoint = to_listen_endpoint(addr)?;
+        endpoint.port = iface::alloc_port(Protocol::Udp, endpoint.port)?;
+        let socket = udp::Socket::new(
+            udp::PacketBuffer::new(
+                vec![udp::PacketMetadata::EMPTY; UDP_METADATA_LEN],
+                vec![0; UDP_RX_BUF_LEN],
+            ),
+            udp::PacketBuffer::new(
+                vec![udp::PacketMetadata::EMPTY; UDP_METADATA_LEN],
+                vec![0; UDP_TX_BUF_LEN],
+            ),
+        );
+        let handle = match iface::add_socket(socket) {
+            Ok(handle) => handle,
+            Err(e) => {
+                iface::free_port(Protocol::Udp, endpoint.port);
+                return Err(e);
+            }
+        };
+        let socket = Self {
+            handle,
+            local_addr: listen_endpoint_to_core(endpoint),
+            peer_addr: SpinNoIrq::new(None),
+        };
+        with_socket::<udp::Socket, _>(handle, |s, _| s.bind(endpoint))
+            .map_err(|_| NetError::BadState)?;
+        Ok(socket)
+    }
+
+    /// Returns the local address that this socket is bound to.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Returns the remote address that this socket is connected to.
+    pub fn peer_addr(&self) -> NetResult<SocketAddr> {
+        self.peer_addr.lock().ok_or(NetError::NotConnected)
+    }
+
+    /// Sets the remote address for [`send`](Self::send) and
+    /// [`recv`](Self::recv).
+    pub fn connect(&self, addr: SocketAddr) -> NetResult {
+        from_core_sockaddr(addr)?;
+        *self.peer_addr.lock() = Some(addr);
+        Ok(())
+    }
+
+    /// Sends a datagram to `addr`.
+    pub fn send_to(&self, buf: &[u8], addr: SocketAddr) -> NetResult<usize> {
+        let remote = from_core_sockaddr(addr)?;
+        if remote.port == 0 || remote.addr.is_unspecified() {
+            return Err(NetError::InvalidInput);
+        }
+        let len = block_on(|| {
+            with_socket::<udp::Socket, _>(self.handle, |s, _| {
+                if !s.can_send() {
+                    return Err(NetError::WouldBlock);
+                }
+                match s.send_slice(buf, remote) {
+                    Ok(()) => Ok(buf.len()),
+                    Err(SendError::BufferFull) => Err(NetError::WouldBlock),
+                    Err(SendError::Unaddressable) => Err(NetError::InvalidInput),
+                }
+            })
+        })?;
+        iface::poll_interfaces();
+        Ok(len)
+    }
+
+    /// Receives a datagram, and returns its length and source address.
+    ///
+    /// The datagram is truncated if `buf` is too small.
+    pub fn recv_from(&self, buf: &mut [u8]) -> NetResult<(usize, SocketAddr)> {
+        block_on(|| {
+            with_socket::<udp::Socket, _>(self.handle, |s, _| match s.recv() {
+                Ok((data, meta)) => {
+                    let len = data.len().min(buf.len());
+                    buf[..len].copy_from_slice(&data[..len]);
+                    Ok((len, into_core_sockaddr(meta.endpoint)))
+                }
+                Err(_) => Err(NetError::WouldBlock),
+            })
+        })
+    }
+
+    /// Sends a datagram to the connected remote address.
+    pub fn send(&self, buf: &[u8]) -> NetResult<usize> {
+        self.send_to(buf, self.peer_addr()?)
+    }
+
+    /// Receives a datagram from the connected remote address, datagrams from
+    /// other addresses are dropped.
+    pub fn recv(&self, buf: &mut [u8]) -> NetResult<usize> {
+        let peer = self.peer_addr()?;
+        loop {
+            let (len, addr) = self.recv_from(buf)?;
+            if addr == peer {
+                return Ok(len);
+            }
+        }
+    }
+}
+
+impl Drop for UdpSocket {
+    fn drop(&mut self) {
+        iface::remove_socket(self.handle);
+        iface::free_port(Protocol::Udp, self.local_addr.port());
+    }
+}
@@ -1,15 +1,16 @@
 #![no_std]
 #![no_main]
 
-use axstd::{println, String, time, Vec, thread};
+use axstd::{env, println, String, time, Vec, thread};
 use axstd::sync::Mutex;
 
 #[no_mangle]
-pub fn main() {
+pub fn main() -> i32 {
     let now = time::Instant::now();
 
     let s = String::from("Hello, ArceOS!");
     println!("{s} Now axstd is okay!");
+    println!("Args: {:?}", env::args());
 
     try_alloc_bulk();
 
@@ -19,8 +20,19 @@ pub fn main() {
 
     test_mutex();
 
+    try_thread_local();
+
+    #[cfg(feature = "fs")]
+    try_filesystem();
+
+    #[cfg(feature = "net")]
+    try_network();
+
+    try_dmesg();
+
     let d = now.elapsed();
     println!("Elapsed: {}.{:06}", d.as_secs(), d.subsec_micros());
+    0
 }
 
 fn try_alloc_bulk() {
@@ -32,6 +44,16 @@ fn try_alloc_bulk() {
     println!("Alloc bulk memory ok!\n");
 }
 
+fn try_dmesg() {
+    use axstd::dmesg;
+
+    let count = dmesg::records().count();
+    println!("\nLast log records ({} in buffer):", count);
+    for record in dmesg::records().skip(count.saturating_sub(5)) {
+        println!("{record}");
+    }
+}
+
 fn try_multitask() {
     println!("Start task...");
 
@@ -79,3 +101,64 @@ fn test_mutex() {
     }
     println!("Mutex test run OK!");
 }
+
+fn try_thread_local() {
+    use core::cell::Cell;
+
+    axstd::thread_local! {
+        static COUNTER: Cell<u32> = Cell::new(1);
+    }
+
+    COUNTER.with(|c| c.set(c.get() + 1));
+    let handle = thread::spawn(|| {
+        COUNTER.with(|c| c.set(c.get() + 10));
+        COUNTER.with(|c| c.get())
+    });
+    let spawned = handle.join().unwrap();
+    let main = COUNTER.with(|c| c.get());
+    println!("Thread-local counters: main {main}, spawned {spawned}");
+    assert_eq!((main, spawned), (2, 11));
+}
+
+#[cfg(feature = "fs")]
+fn try_filesystem() {
+    use axstd::fs;
+
+    println!("\nTry filesystem ...");
+    fs::create_dir("/tmp").unwrap();
+    fs::write("/tmp/hello.txt", "Hello, filesystem!").unwrap();
+    let s = fs::read_to_string("/tmp/hello.txt").unwrap();
+    println!("Read back from /tmp/hello.txt: {s}");
+
+    for dir in ["/", "/mnt"] {
+        println!("List {dir}:");
+        for entry in fs::read_dir(dir).unwrap() {
+            let entry = entry.unwrap();
+            println!("  {} {}", entry.file_type().as_char(), entry.path());
+        }
+    }
+    fs::remove_file("/tmp/hello.txt").unwrap();
+    fs::remove_dir("/tmp").unwrap();
+    println!("Filesystem test run OK!");
+}
+
+#[cfg(feature = "net")]
+fn try_network() {
+    use axstd::io::{Read, Write};
+    use axstd::net::TcpListener;
+
+    println!("\nTry network ...");
+    let listener = TcpListener::bind("0.0.0.0:5555").unwrap();
+    println!("TCP echo server listening on {}", listener.local_addr().unwrap());
+    let (mut stream, peer) = listener.accept().unwrap();
+    println!("Accepted connection from {peer}");
+    let mut buf = [0; 1024];
+    loop {
+        let n = stream.read(&mut buf).unwrap();
+        if n == 0 {
+            break;
+        }
+        stream.write_all(&buf[..n]).unwrap();
+    }
+    println!("Network test run OK!");
+}
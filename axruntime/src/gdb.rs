@@ -0,0 +1,314 @@
+//! Debugging the kernel from GDB, over the console.
+//!
+//! Breakpoints stop the CPU that hits them in the stub of [`axgdb`], which
+//! talks to GDB with its remote protocol over the console. Unlike the stub of
+//! QEMU, it knows the tasks: `info threads` lists them, and `thread <id>`
+//! shows where a switched-out task resumes, with the registers its context
+//! switch saved.
+//!
+//! The kernel stops before `main` and waits for GDB. `make run GDB=y` connects
+//! the console to TCP port 1234 of QEMU, for GDB to attach:
+//!
+//! ```text
+//! $ gdb-multiarch target/riscv64gc-unknown-none-elf/release/axorigin
+//! (gdb) target remote :1234
+//! ```
+//!
+//! Only the CPU that stopped waits in the stub, the others keep running. The
+//! console output goes to GDB too, which ignores it, and as the console is
+//! only read when stopped, Ctrl-C can't interrupt the kernel. Compiled-in
+//! `ebreak`s stop the kernel as well, and wait for GDB to resume it.
+
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use core::arch::asm;
+
+use axconfig::{phys_to_virt, PHYS_MEMORY_BASE, PHYS_MEMORY_SIZE};
+use axgdb::riscv::{self, C_EBREAK, EBREAK};
+use axgdb::{Connection, GdbStub, Resume, Target, NUM_REGS};
+use axhal::trap::fetch_inst;
+use axhal::{TaskContext, TrapFrame};
+use axtask::{AxTaskRef, TaskId};
+use spinlock::SpinNoIrq;
+
+/// The signal of stops at breakpoints.
+const SIGTRAP: u8 = 5;
+
+static DEBUGGER: SpinNoIrq<Debugger> = SpinNoIrq::new(Debugger {
+    stub: GdbStub::new(Console),
+    breakpoints: BTreeMap::new(),
+    step: None,
+});
+
+struct Debugger {
+    stub: GdbStub<Console>,
+    /// The instructions replaced by breakpoints, by address.
+    breakpoints: BTreeMap<usize, u32>,
+    step: Option<Step>,
+}
+
+/// The temporary breakpoint of a single step, after the instruction stepped.
+#[derive(Clone, Copy)]
+struct Step {
+    addr: usize,
+    /// The instruction it replaced.
+    inst: u32,
+    /// The breakpoint stepped over, inserted again after the step.
+    reinsert: Option<usize>,
+    /// Whether GDB asked for the step, or it only steps over a breakpoint.
+    stop: bool,
+}
+
+/// The console, which GDB is connected to.
+struct Console;
+
+impl Connection for Console {
+    fn read_byte(&mut self) -> u8 {
+        loop {
+            if let Some(c) = axhal::console::getchar() {
+                return c;
+            }
+            core::hint::spin_loop();
+        }
+    }
+
+    fn write_byte(&mut self, byte: u8) {
+        axhal::console::putchar(byte);
+    }
+}
+
+/// The kernel stopped at a breakpoint by the current task, with the frame
+/// `tf` of the trap.
+struct Kernel<'a> {
+    tf: &'a mut TrapFrame,
+    breakpoints: &'a mut BTreeMap<usize, u32>,
+}
+
+impl Target for Kernel<'_> {
+    fn read_registers(&mut self, tid: u64) -> Option<[usize; NUM_REGS]> {
+        if tid == self.stopped_thread() {
+            return Some(trap_registers(self.tf));
+        }
+        find_task(tid)?.with_saved_context(saved_registers)
+    }
+
+    fn write_registers(&mut self, tid: u64, regs: &[usize; NUM_REGS]) -> bool {
+        // The contexts of switched-out tasks are left alone.
+        if tid != self.stopped_thread() {
+            return false;
+        }
+        let gprs = self.tf.regs.as_array_mut();
+        let (gp, tp) = (gprs[2], gprs[3]);
+        gprs.copy_from_slice(&regs[1..32]);
+        // Kernel traps restore neither.
+        (gprs[2], gprs[3]) = (gp, tp);
+        self.tf.sepc = regs[32];
+        true
+    }
+
+    fn read_memory(&mut self, addr: usize, buf: &mut [u8]) -> bool {
+        if !in_memory(addr, buf.len()) {
+            return false;
+        }
+        for (i, b) in buf.iter_mut().enumerate() {
+            *b = unsafe { ((addr + i) as *const u8).read_volatile() };
+        }
+        true
+    }
+
+    fn write_memory(&mut self, addr: usize, data: &[u8]) -> bool {
+        if !in_memory(addr, data.len()) {
+            return false;
+        }
+        for (i, &b) in data.iter().enumerate() {
+            unsafe { ((addr + i) as *mut u8).write_volatile(b) };
+        }
+        // It may be code.
+        unsafe { asm!("fence.i") };
+        true
+    }
+
+    fn insert_breakpoint(&mut self, addr: usize, _kind: usize) -> bool {
+        insert_breakpoint(self.breakpoints, addr)
+    }
+
+    fn remove_breakpoint(&mut self, addr: usize, _kind: usize) -> bool {
+        remove_breakpoint(self.breakpoints, addr).is_some()
+    }
+
+    fn for_each_thread(&mut self, f: &mut dyn FnMut(u64)) {
+        axtask::for_each_task(|task| f(task.id().as_u64()));
+    }
+
+    fn thread_info(&mut self, tid: u64) -> Option<String> {
+        let info = axtask::task_info(TaskId::from_u64(tid))?;
+        Some(format!("{} ({:?})", info.name, info.state))
+    }
+
+    fn stopped_thread(&self) -> u64 {
+        axtask::current_may_uninit().map_or(1, |curr| curr.id().as_u64())
+    }
+}
+
+/// Stops before `main`, to wait for GDB to attach.
+pub(crate) fn init() {
+    ax_println!("Waiting for GDB on the console...");
+    unsafe { asm!("ebreak") };
+}
+
+/// Handles the breakpoint trap with the frame `tf`: stops in the stub if it is
+/// a breakpoint, until GDB resumes the kernel.
+pub(crate) fn handle_breakpoint(tf: &mut TrapFrame) {
+    let mut debugger = DEBUGGER.lock();
+    let debugger = &mut *debugger;
+    let pc = tf.sepc;
+
+    let stepped = match debugger.step {
+        Some(step) if step.addr == pc => {
+            debugger.step = None;
+            unsafe { write_inst(pc, step.inst) };
+            if let Some(addr) = step.reinsert {
+                insert_breakpoint(&mut debugger.breakpoints, addr);
+            }
+            if !step.stop {
+                return;
+            }
+            true
+        }
+        _ => false,
+    };
+    // Removed while this CPU waited for the lock.
+    if !stepped && !debugger.breakpoints.contains_key(&pc) && !riscv::is_ebreak(fetch_inst(pc).0) {
+        return;
+    }
+
+    let mut kernel = Kernel {
+        tf: &mut *tf,
+        breakpoints: &mut debugger.breakpoints,
+    };
+    let resume = debugger.stub.handle_stop(&mut kernel, SIGTRAP);
+
+    // Compiled-in breakpoints are skipped, or they would stop again.
+    let pc = tf.sepc;
+    let (inst, len) = fetch_inst(pc);
+    if !debugger.breakpoints.contains_key(&pc) && riscv::is_ebreak(inst) {
+        tf.sepc += len;
+    }
+
+    match resume {
+        Resume::Detach => {
+            while let Some((&addr, _)) = debugger.breakpoints.first_key_value() {
+                remove_breakpoint(&mut debugger.breakpoints, addr);
+            }
+            if let Some(step) = debugger.step.take() {
+                unsafe { write_inst(step.addr, step.inst) };
+            }
+        }
+        Resume::Continue | Resume::Step => {
+            let pc = tf.sepc;
+            // Steps over the breakpoint the task resumes at.
+            let over = remove_breakpoint(&mut debugger.breakpoints, pc).map(|_| pc);
+            if resume == Resume::Step || over.is_some() {
+                let mut regs = [0; 32];
+                regs[1..].copy_from_slice(&trap_registers(tf)[1..32]);
+                let next = riscv::next_pc(fetch_inst(pc).0, pc, &regs);
+                if in_memory(next, 4) {
+                    let inst = fetch_inst(next).0;
+                    unsafe { write_inst(next, ebreak_for(inst)) };
+                    debugger.step = Some(Step {
+                        addr: next,
+                        inst,
+                        reinsert: over,
+                        stop: resume == Resume::Step,
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// Returns the registers of a trap in the kernel, in the order of GDB.
+fn trap_registers(tf: &TrapFrame) -> [usize; NUM_REGS] {
+    let mut regs = [0; NUM_REGS];
+    regs[1..32].copy_from_slice(tf.regs.as_array());
+    // Kernel traps save neither, as they don't change them.
+    unsafe {
+        asm!("mv {}, gp", out(reg) regs[3]);
+        asm!("mv {}, tp", out(reg) regs[4]);
+    }
+    regs[32] = tf.sepc;
+    regs
+}
+
+/// Returns the registers of a switched-out task, which resumes where it
+/// returns from the context switch. Those not saved are 0.
+fn saved_registers(ctx: &TaskContext) -> [usize; NUM_REGS] {
+    let mut regs = [0; NUM_REGS];
+    regs[1] = ctx.ra;
+    regs[2] = ctx.sp;
+    regs[4] = ctx.tp;
+    regs[8] = ctx.s0;
+    regs[9] = ctx.s1;
+    regs[18..28].copy_from_slice(&[
+        ctx.s2, ctx.s3, ctx.s4, ctx.s5, ctx.s6, ctx.s7, ctx.s8, ctx.s9, ctx.s10, ctx.s11,
+    ]);
+    regs[32] = ctx.ra;
+    regs
+}
+
+fn find_task(tid: u64) -> Option<AxTaskRef> {
+    let mut found = None;
+    axtask::for_each_task(|task| {
+        if task.id().as_u64() == tid {
+            found = Some(task.clone());
+        }
+    });
+    found
+}
+
+/// Returns whether `addr..addr + len` is in the linear mapping of the memory,
+/// where GDB can look without faulting.
+fn in_memory(addr: usize, len: usize) -> bool {
+    let base = phys_to_virt(PHYS_MEMORY_BASE);
+    addr >= base && addr.checked_add(len).is_some_and(|end| end <= base + PHYS_MEMORY_SIZE)
+}
+
+fn insert_breakpoint(breakpoints: &mut BTreeMap<usize, u32>, addr: usize) -> bool {
+    if addr % 2 != 0 || !in_memory(addr, 4) {
+        return false;
+    }
+    breakpoints.entry(addr).or_insert_with(|| {
+        let inst = fetch_inst(addr).0;
+        unsafe { write_inst(addr, ebreak_for(inst)) };
+        inst
+    });
+    true
+}
+
+/// Removes the breakpoint at `addr`, and returns the instruction it replaced.
+fn remove_breakpoint(breakpoints: &mut BTreeMap<usize, u32>, addr: usize) -> Option<u32> {
+    let inst = breakpoints.remove(&addr)?;
+    unsafe { write_inst(addr, inst) };
+    Some(inst)
+}
+
+/// Returns the breakpoint of the same length as `inst`.
+const fn ebreak_for(inst: u32) -> u32 {
+    if riscv::inst_len(inst as u16) == 2 {
+        C_EBREAK as u32
+    } else {
+        EBREAK
+    }
+}
+
+/// # Safety
+///
+/// `addr` must be code, with an instruction of the same length as `inst`.
+unsafe fn write_inst(addr: usize, inst: u32) {
+    (addr as *mut u16).write_volatile(inst as u16);
+    if riscv::inst_len(inst as u16) == 4 {
+        ((addr + 2) as *mut u16).write_volatile((inst >> 16) as u16);
+    }
+    asm!("fence.i");
+}
@@ -0,0 +1,51 @@
+//! The kernel symbol table, for symbolizing backtraces.
+//!
+//! With the `ksyms` feature, space for the table is reserved in the `.ksyms`
+//! section, `ksyms-size` in the platform config, and filled in after linking
+//! by `scripts/ksyms.sh`. Each line of the table is a hex address and a
+//! symbol name, sorted by address, and the rest of the space is zeros.
+//!
+//! A table cut short to fit ends with the address of the first symbol left
+//! out and no name, so that the addresses after it are not found in the last
+//! symbol kept.
+
+#[cfg(feature = "ksyms")]
+use axconfig::KSYMS_SIZE;
+
+#[cfg(feature = "ksyms")]
+#[used]
+#[link_section = ".ksyms"]
+static KSYMS: [u8; KSYMS_SIZE] = [0; KSYMS_SIZE];
+
+fn table() -> &'static [u8] {
+    #[cfg(feature = "ksyms")]
+    {
+        // The table is patched after linking, so don't let the compiler see
+        // the zeros.
+        let ptr = core::hint::black_box(KSYMS.as_ptr());
+        let bytes = unsafe { core::slice::from_raw_parts(ptr, KSYMS_SIZE) };
+        let len = bytes.iter().position(|&b| b == 0).unwrap_or(KSYMS_SIZE);
+        &bytes[..len]
+    }
+    #[cfg(not(feature = "ksyms"))]
+    &[]
+}
+
+fn parse_line(line: &[u8]) -> Option<(usize, &str)> {
+    let line = core::str::from_utf8(line).ok()?;
+    let (addr, name) = line.split_once(' ')?;
+    Some((usize::from_str_radix(addr, 16).ok()?, name))
+}
+
+/// Finds the symbol containing `addr`, and returns its name and the offset of
+/// `addr` in it.
+pub fn lookup(addr: usize) -> Option<(&'static str, usize)> {
+    let mut found = None;
+    for (sym_addr, name) in table().split(|&b| b == b'\n').filter_map(parse_line) {
+        if sym_addr > addr {
+            break;
+        }
+        found = (!name.is_empty()).then_some((name, addr - sym_addr));
+    }
+    found
+}
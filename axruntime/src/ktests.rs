@@ -0,0 +1,47 @@
+use core::arch::asm;
+
+use axtest::ax_test;
+
+#[ax_test]
+fn test_breakpoint_skipped() {
+    unsafe {
+        asm!("c.ebreak");
+        asm!(".option push", ".option norvc", "ebreak", ".option pop");
+    }
+}
+
+#[ax_test]
+fn test_misaligned_access() {
+    let mut buf = [0u64; 3];
+    let addr = buf.as_mut_ptr() as usize + 3;
+    let (dword, word): (usize, usize);
+    unsafe {
+        asm!("sd {}, 0({})", in(reg) 0x8877_6655_4433_2211_usize, in(reg) addr);
+        asm!("ld {}, 0({})", out(reg) dword, in(reg) addr);
+        asm!("sw {}, 8({})", in(reg) 0x8000_0001_usize, in(reg) addr);
+        asm!("lw {}, 8({})", out(reg) word, in(reg) addr);
+    }
+    assert_eq!(dword, 0x8877_6655_4433_2211);
+    assert_eq!(word, 0xffff_ffff_8000_0001);
+    assert_eq!(buf[0] >> 24, 0x55_4433_2211);
+}
+
+#[cfg(feature = "multitask")]
+#[ax_test]
+fn test_faulting_task_terminated() {
+    use alloc::string::String;
+
+    let illegal = axtask::spawn_raw(
+        || unsafe { asm!("unimp") },
+        String::from("ktest-fault"),
+        axtask::default_stack_size(),
+    );
+    assert_eq!(illegal.join(), Some(128 + 4));
+
+    let null_load = axtask::spawn_raw(
+        || unsafe { asm!("ld {}, 0(zero)", out(reg) _) },
+        String::from("ktest-fault"),
+        axtask::default_stack_size(),
+    );
+    assert_eq!(null_load.join(), Some(128 + 11));
+}
@@ -1,7 +1,45 @@
 use core::panic::PanicInfo;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// Number of recent log records printed on panic.
+const PANIC_LOG_TAIL: usize = 32;
+
+/// Exit code of a panic, the same as Rust programs on Linux.
+const PANIC_EXIT_CODE: i32 = 101;
 
 #[panic_handler]
 fn panic(info: &PanicInfo) -> ! {
+    static PANICKED: AtomicBool = AtomicBool::new(false);
+    if PANICKED.swap(true, Ordering::Relaxed) {
+        // panicked again while handling the panic
+        ax_println!("panicked while panicking: {}", info);
+        axhal::misc::terminate(PANIC_EXIT_CODE);
+    }
+
     error!("{}", info);
-    axhal::misc::terminate()
+    #[cfg(feature = "multitask")]
+    if let Some(curr) = axtask::current_may_uninit() {
+        ax_println!("in task {} ({})", curr.id().as_u64(), curr.name());
+    }
+    print_backtrace();
+    axlog::dump_tail(PANIC_LOG_TAIL);
+    axhal::misc::terminate(PANIC_EXIT_CODE)
+}
+
+fn print_backtrace() {
+    ax_println!("Backtrace:");
+    let mut depth = 0;
+    axhal::backtrace::walk(axhal::backtrace::current_fp(), |ra| {
+        // `ra` is the instruction after the call, which may be in the next
+        // symbol if the call is the last one.
+        match crate::ksyms::lookup(ra - 1) {
+            Some((name, offset)) => {
+                ax_println!("  {:>2}: {:#018x} - {}+{:#x}", depth, ra, name, offset + 1);
+            }
+            None => {
+                ax_println!("  {:>2}: {:#018x}", depth, ra);
+            }
+        }
+        depth += 1;
+    });
 }
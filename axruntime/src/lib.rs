@@ -2,11 +2,23 @@
 
 #[cfg(all(target_os = "none", not(test)))]
 mod lang_items;
+#[cfg(all(target_os = "none", not(test)))]
+mod ksyms;
 mod trap;
+#[cfg(all(feature = "irq", target_os = "none", not(test)))]
+mod timer;
+#[cfg(all(feature = "smp", target_os = "none", not(test)))]
+mod mp;
+#[cfg(all(feature = "gdbstub", target_os = "none", not(test)))]
+mod gdb;
+
+#[cfg(ktest)]
+mod ktests;
 
 #[allow(unused_imports)]
 #[macro_use]
 extern crate axlog;
+#[cfg(feature = "alloc")]
 extern crate alloc;
 
 use core::str;
@@ -22,104 +34,213 @@ impl axlog::LogIf for LogIfImpl {
     fn current_time() -> core::time::Duration {
         axhal::time::current_time()
     }
+
+    fn current_cpu_id() -> Option<usize> {
+        Some(axhal::cpu::this_cpu_id())
+    }
+
+    fn with_current_task(_f: &mut dyn FnMut(u64, &str)) {
+        #[cfg(feature = "multitask")]
+        if let Some(curr) = axtask::current_may_uninit() {
+            _f(curr.id().as_u64(), curr.name());
+        }
+    }
 }
 
+/// The log filter, `log=<level>[,<module>=<level>...]` in the kernel command
+/// line, e.g. `log=warn,axtask=debug`.
+///
+/// Each module can also have its own level with `log.<module>=<level>`.
+#[cfg(all(feature = "alloc", target_os = "none", not(test)))]
+static LOG_LEVEL: axcmdline::Param<&str> = axcmdline::Param::new(
+    "log",
+    match option_env!("AX_LOG") {
+        Some(level) => level,
+        None => "",
+    },
+);
+
+/// Whether log records are colored, `log_color=<bool>` in the kernel command
+/// line. Turn it off when the console output is captured to a file.
+#[cfg(all(feature = "alloc", target_os = "none", not(test)))]
+static LOG_COLOR: axcmdline::Param<bool> = axcmdline::Param::new("log_color", true);
+
+/// Whether log records show the CPU ID, `log_cpu=<bool>` in the kernel
+/// command line.
+#[cfg(all(feature = "alloc", target_os = "none", not(test)))]
+static LOG_CPU: axcmdline::Param<bool> = axcmdline::Param::new("log_cpu", false);
+
+/// Whether log records show the task ID and name, `log_task=<bool>` in the
+/// kernel command line.
+#[cfg(all(feature = "alloc", target_os = "none", not(test)))]
+static LOG_TASK: axcmdline::Param<bool> = axcmdline::Param::new("log_task", false);
+
+/// The log level of the in-memory log buffer, `log_ring=<level>` in the
+/// kernel command line. It keeps everything by default, so that the records
+/// around a crash can be read back; lower it if tracing everything is too
+/// slow.
+#[cfg(all(feature = "alloc", target_os = "none", not(test)))]
+static LOG_RING_LEVEL: axcmdline::Param<&str> = axcmdline::Param::new("log_ring", "trace");
+
+/// The timer frequency, `ticks=<n>` in the kernel command line.
+#[cfg(all(feature = "irq", feature = "alloc", target_os = "none", not(test)))]
+static TICKS_PER_SEC: axcmdline::Param<usize> =
+    axcmdline::Param::new("ticks", axconfig::TICKS_PER_SEC);
+
 #[no_mangle]
 #[cfg(all(target_os = "none", not(test)))]
 pub extern "C" fn rust_main(hartid: usize, dtb: usize) -> ! {
     extern "C" {
+        #[cfg(feature = "alloc")]
         fn _skernel();
-        #[cfg(not(test))]
-        fn main();
+        #[cfg(not(ktest))]
+        fn main() -> i32;
     }
 
     let log_level = option_env!("AX_LOG").unwrap_or("");
     ax_println!("\nArceOS is starting... [{}]\n", log_level);
 
     axlog::init();
-    axlog::set_max_level(log_level);
+    axlog::set_filter(log_level);
     info!("Logging is enabled.");
     info!("Primary CPU {} started, dtb = {:#x}.", hartid, dtb);
 
+    // Parse fdt for early memory info, before the heap is ready
+    #[cfg(feature = "alloc")]
+    let dt = {
+        let dtb_va = axconfig::phys_to_virt(dtb);
+        debug!("dtb: {:#x} => {:#x}", dtb, dtb_va);
+        match axdtb::DeviceTree::init(dtb_va) {
+            Ok(dt) => dt,
+            Err(err) => panic!("Bad dtb {:?}", err),
+        }
+    };
+    #[cfg(feature = "alloc")]
+    let dtb_info = parse_dtb(&dt);
+
     // We reserve 2M memory range [0x80000000, 0x80200000) for SBI,
     // but it only occupies ~194K. Split this range in half,
     // requisition the higher part(1M) for early heap.
+    #[cfg(feature = "alloc")]
     axalloc::early_init(_skernel as usize - 0x100000, 0x100000);
 
-    // Parse fdt for early memory info
-    let dtb_info = match parse_dtb(dtb.into()) {
-        Ok(info) => info,
-        Err(err) => panic!("Bad dtb {:?}", err),
-    };
+    #[cfg(feature = "alloc")]
+    {
+        axcmdline::init(dtb_info.bootargs);
+        init_log_levels();
+        info!("Command line: {}", axcmdline::cmdline().raw());
 
-    info!("Memory: {:#x}, size: {:#x}", dtb_info.memory_addr, dtb_info.memory_size);
-    info!("Virtio_mmio[{}]:", dtb_info.mmio_regions.len());
-    for r in &dtb_info.mmio_regions {
-        info!("\t{:#x}, size: {:#x}", r.0, r.1);
+        info!("Memory: {:#x}, size: {:#x}", dtb_info.memory_addr, dtb_info.memory_size);
+        info!("Virtio_mmio[{}]:", dtb_info.mmio_regions().len());
+        for r in dtb_info.mmio_regions() {
+            info!("\t{:#x}, size: {:#x}", r.0, r.1);
+        }
     }
 
-    info!("Initialize kernel page table...");
-    remap_kernel_memory(&dtb_info);
+    #[cfg(feature = "paging")]
+    {
+        info!("Initialize kernel page table...");
+        remap_kernel_memory(&dtb_info);
+    }
 
-    info!("Heap total: {}K, avail: {}K, used: {}K ({} pages)",
-          axalloc::total_bytes()/1024,
-          axalloc::available_bytes()/1024,
-          axalloc::used_bytes()/1024,
-          axalloc::used_pages());
+    #[cfg(feature = "alloc")]
+    {
+        info!("Heap total: {}K, avail: {}K, used: {}K ({} pages)",
+              axalloc::total_bytes()/1024,
+              axalloc::available_bytes()/1024,
+              axalloc::used_bytes()/1024,
+              axalloc::used_pages());
 
-    allocator_final_init(dtb_info.memory_addr + dtb_info.memory_size);
+        allocator_final_init(dtb_info.memory_addr + dtb_info.memory_size);
+    }
 
     info!("Initialize platform devices...");
     axhal::platform_init();
 
+    #[cfg(feature = "multitask")]
     axtask::init_scheduler();
 
-    info!("Initialize interrupt handlers...");
-    #[cfg(all(target_os = "none", not(test)))]
-    init_interrupt();
+    #[cfg(feature = "smp")]
+    mp::start_secondary_cpus(hartid);
 
-    #[cfg(not(test))]
-    unsafe {
-        main();
+    // Without `multitask`, there is no task to own the TLS block of main.
+    #[cfg(all(feature = "tls", not(feature = "multitask")))]
+    {
+        let main_tls = axhal::tls::TlsArea::alloc();
+        unsafe { axhal::tls::write_thread_pointer(main_tls.tls_ptr()) };
+        core::mem::forget(main_tls);
     }
 
-    debug!("main task exited: exit_code={}", 0);
-    axhal::misc::terminate();
-}
+    #[cfg(any(feature = "fs", feature = "net"))]
+    {
+        let all_devices = axdriver::init_drivers(dtb_info.mmio_regions());
+        #[cfg(feature = "fs")]
+        axfs::init_filesystems(all_devices.block);
+        #[cfg(feature = "net")]
+        axnet::init_network(all_devices.net);
+    }
 
-#[cfg(all(target_os = "none", not(test)))]
-fn init_interrupt() {
-    use axhal::irq::TIMER_IRQ_NUM;
+    #[cfg(feature = "irq")]
+    {
+        info!("Initialize interrupt handlers...");
+        init_interrupt();
+    }
 
-    // Setup timer interrupt handler
-    const PERIODIC_INTERVAL_NANOS: u64 =
-        axhal::time::NANOS_PER_SEC / axconfig::TICKS_PER_SEC as u64;
+    #[cfg(feature = "smp")]
+    {
+        mp::wait_for_all_cpus();
+        info!("All {} CPUs are up.", axconfig::SMP);
+    }
+
+    #[cfg(feature = "gdbstub")]
+    gdb::init();
 
-    static mut NEXT_DEADLINE: u64 = 0;
+    #[cfg(not(ktest))]
+    let exit_code = unsafe { main() };
+    #[cfg(ktest)]
+    let exit_code = {
+        axtest::run_all();
+        0
+    };
 
-    fn update_timer() {
-        let now_ns = axhal::time::current_time_nanos();
-        // Safety: we have disabled preemption in IRQ handler.
-        let mut deadline = unsafe { NEXT_DEADLINE };
-        if now_ns >= deadline {
-            deadline = now_ns + PERIODIC_INTERVAL_NANOS;
+    debug!("main task exited: exit_code={}", exit_code);
+    axhal::misc::terminate(exit_code);
+}
+
+#[cfg(all(feature = "alloc", target_os = "none", not(test)))]
+fn init_log_levels() {
+    let filter = LOG_LEVEL.get();
+    if !axlog::set_filter(filter) {
+        warn!("Invalid log filter {:?}", filter);
+    }
+    axlog::set_color(LOG_COLOR.get());
+    axlog::set_show_cpu(LOG_CPU.get());
+    axlog::set_show_task(LOG_TASK.get());
+    let ring_level = LOG_RING_LEVEL.get();
+    if !axlog::set_ring_level(ring_level) {
+        warn!("Invalid log level {:?} of the log buffer", ring_level);
+    }
+    for (module, level) in axcmdline::cmdline().with_prefix("log.") {
+        if !axlog::set_module_level(module, level) {
+            warn!("Failed to set log level of {} to {:?}", module, level);
         }
-        unsafe { NEXT_DEADLINE = deadline + PERIODIC_INTERVAL_NANOS };
-        trace!("now {} deadline {}", now_ns, deadline);
-        axhal::time::set_oneshot_timer(deadline);
     }
+}
 
-    axhal::irq::register_handler(TIMER_IRQ_NUM, || {
-        update_timer();
-        debug!("On timer tick!");
-        axtask::on_timer_tick();
-    });
+#[cfg(all(feature = "irq", target_os = "none", not(test)))]
+fn init_interrupt() {
+    // Setup timer interrupt handler
+    #[cfg(feature = "alloc")]
+    let ticks_per_sec = TICKS_PER_SEC.get().max(1) as u64;
+    #[cfg(not(feature = "alloc"))]
+    let ticks_per_sec = axconfig::TICKS_PER_SEC as u64;
+    timer::init(ticks_per_sec);
 
     // Enable IRQs before starting app
     axhal::irq::enable_irqs();
 }
 
-#[cfg(all(target_os = "none", not(test)))]
+#[cfg(all(feature = "alloc", target_os = "none", not(test)))]
 fn allocator_final_init(memory_size: usize) {
     use axhal::mem::free_regions;
     use axconfig::phys_to_virt;
@@ -130,14 +251,18 @@ fn allocator_final_init(memory_size: usize) {
     }
 }
 
-#[cfg(all(target_os = "none", not(test)))]
-fn remap_kernel_memory(dtb: &DtbInfo) {
-    use axhal::mem::{MemRegion, kernel_image_regions, free_regions};
-    use page_table::{PAGE_KERNEL_RW, PageTable};
+/// The kernel page table, shared by all CPUs.
+#[cfg(all(feature = "paging", target_os = "none", not(test)))]
+static KERNEL_PAGE_TABLE: axsync::BootOnceCell<page_table::PageTable> =
+    unsafe { axsync::BootOnceCell::new() };
+
+#[cfg(all(feature = "paging", target_os = "none", not(test)))]
+fn remap_kernel_memory(dtb: &DtbInfo<'_>) {
+    use axhal::mem::{MemRegion, kernel_image_regions, free_regions, mmio_regions};
+    use page_table::PAGE_KERNEL_RW;
     use axconfig::{phys_to_virt, SIZE_2M};
-    use axsync::BootOnceCell;
 
-    let mmio_regions = dtb.mmio_regions.iter().map(|reg| MemRegion {
+    let dtb_mmio_regions = dtb.mmio_regions().iter().map(|reg| MemRegion {
         paddr: reg.0.into(),
         size: reg.1,
         flags: PAGE_KERNEL_RW,
@@ -146,9 +271,10 @@ fn remap_kernel_memory(dtb: &DtbInfo) {
 
     let regions = kernel_image_regions()
         .chain(free_regions(dtb.memory_size))
-        .chain(mmio_regions);
+        .chain(mmio_regions())
+        .chain(dtb_mmio_regions);
 
-    let mut kernel_page_table = PageTable::alloc_table(0);
+    let mut kernel_page_table = page_table::PageTable::alloc_table(0);
     for r in regions {
         let _ = kernel_page_table.map(
             phys_to_virt(r.paddr),
@@ -159,9 +285,6 @@ fn remap_kernel_memory(dtb: &DtbInfo) {
         );
     }
 
-    static KERNEL_PAGE_TABLE: BootOnceCell<PageTable> =
-        unsafe { BootOnceCell::new() };
-
     KERNEL_PAGE_TABLE.init(kernel_page_table);
 
     unsafe {
@@ -169,73 +292,62 @@ fn remap_kernel_memory(dtb: &DtbInfo) {
     };
 }
 
-#[cfg(all(target_os = "none", not(test)))]
-struct DtbInfo {
+/// The most MMIO regions of devices taken from the device tree.
+#[cfg(all(feature = "alloc", target_os = "none", not(test)))]
+const MAX_MMIO_REGIONS: usize = 16;
+
+/// What the kernel needs from the device tree, borrowed from the blob.
+#[cfg(all(feature = "alloc", target_os = "none", not(test)))]
+struct DtbInfo<'a> {
     memory_addr: usize,
     memory_size: usize,
-    mmio_regions: alloc::vec::Vec<(usize, usize)>,
+    mmio_regions: [(usize, usize); MAX_MMIO_REGIONS],
+    num_mmio_regions: usize,
+    bootargs: &'a str,
 }
 
-#[cfg(all(target_os = "none", not(test)))]
-fn parse_dtb(dtb_pa: usize) -> axdtb::DeviceTreeResult<DtbInfo> {
-    use alloc::string::String;
-    use alloc::vec::Vec;
-    use axconfig::phys_to_virt;
-    use axdtb::util::SliceRead;
-
-    let dtb_va = phys_to_virt(dtb_pa);
-    debug!("dtb: {:#x} => {:#x}", dtb_pa, dtb_va);
-
-    let mut memory_addr = 0;
-    let mut memory_size = 0;
-    let mut mmio_regions = Vec::new();
-
-    let mut cb = |name: String, addr_cells: usize, size_cells: usize, props: Vec<(String, Vec<u8>)>| {
-        debug!("{}: cells {}, {}", name, addr_cells, size_cells);
-        let mut is_memory = false;
-        let mut is_mmio = false;
-        let mut reg = None;
-        for prop in props {
-            match prop.0.as_str() {
-                "device_type" => {
-                    is_memory = str::from_utf8(&(prop.1))
-                        .map_or_else(|_| false, |v| v == "memory\0");
-                },
-                "compatible" => {
-                    is_mmio = str::from_utf8(&(prop.1))
-                        .map_or_else(|_| false, |v| v == "virtio,mmio\0");
-                },
-                "reg" => {
-                    reg = Some(prop.1);
-                },
-                _ => (),
-            }
-        }
-        if is_memory {
-            assert!(addr_cells == 2);
-            assert!(size_cells == 2);
-            if let Some(ref reg) = reg {
-                memory_addr = reg.as_slice().read_be_u64(0).unwrap() as usize;
-                memory_size = reg.as_slice().read_be_u64(8).unwrap() as usize;
-            }
-        }
-        if is_mmio {
-            assert!(addr_cells == 2);
-            assert!(size_cells == 2);
-            if let Some(ref reg) = reg {
-                let addr = reg.as_slice().read_be_u64(0).unwrap() as usize;
-                let size = reg.as_slice().read_be_u64(8).unwrap() as usize;
-                mmio_regions.push((addr, size));
-            }
-        }
-    };
+#[cfg(all(feature = "alloc", target_os = "none", not(test)))]
+impl DtbInfo<'_> {
+    fn mmio_regions(&self) -> &[(usize, usize)] {
+        &self.mmio_regions[..self.num_mmio_regions]
+    }
+}
 
-    let dt = axdtb::DeviceTree::init(dtb_va.into())?;
-    dt.parse(dt.off_struct, 0, 0, &mut cb)?;
+/// Walks the device tree `dt` in place, as it runs before the heap is ready.
+#[cfg(all(feature = "alloc", target_os = "none", not(test)))]
+fn parse_dtb(dt: &axdtb::DeviceTree) -> DtbInfo<'_> {
+    for rsv in dt.memory_reservations() {
+        debug!("reserved: {:#x}, size: {:#x}", rsv.address, rsv.size);
+    }
+    let chosen = dt.chosen().unwrap_or_default();
+    debug!("chosen: {:?}", chosen);
+
+    let mut info = DtbInfo {
+        memory_addr: 0,
+        memory_size: 0,
+        mmio_regions: [(0, 0); MAX_MMIO_REGIONS],
+        num_mmio_regions: 0,
+        bootargs: chosen.bootargs.unwrap_or(""),
+    };
+    let memory = dt.nodes().find(|node| {
+        node.prop("device_type").and_then(|p| p.as_str().ok()) == Some("memory")
+    });
+    if let Some(reg) = memory.and_then(|node| node.reg().ok()?.next()) {
+        info.memory_addr = reg.address as usize;
+        info.memory_size = reg.size.unwrap_or(0) as usize;
+    }
 
-    Ok(DtbInfo {
-        memory_addr,
-        memory_size,
-        mmio_regions,
-    })
+    for node in dt.nodes().filter(|node| node.is_compatible("virtio,mmio")) {
+        if let Some(reg) = node.reg().ok().and_then(|mut reg| reg.next()) {
+            debug!("{}: {:#x}", node.name(), reg.address);
+            if info.num_mmio_regions == MAX_MMIO_REGIONS {
+                warn!("too many MMIO regions, {} ignored", node.name());
+                continue;
+            }
+            info.mmio_regions[info.num_mmio_regions] =
+                (reg.address as usize, reg.size.unwrap_or(0) as usize);
+            info.num_mmio_regions += 1;
+        }
+    }
+    info
 }
@@ -0,0 +1,74 @@
+//! Booting of the secondary CPUs.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use axconfig::{virt_to_phys, SMP, TASK_STACK_SIZE};
+
+/// Boot stacks of the secondary CPUs, which become the stacks of their idle
+/// tasks with `multitask`.
+#[link_section = ".bss.stack"]
+static mut SECONDARY_BOOT_STACK: [[u8; TASK_STACK_SIZE]; SMP - 1] = [[0; TASK_STACK_SIZE]; SMP - 1];
+
+/// Number of CPUs that have entered Rust code.
+static ENTERED_CPUS: AtomicUsize = AtomicUsize::new(1);
+
+/// Number of CPUs that have finished initialization.
+static INITED_CPUS: AtomicUsize = AtomicUsize::new(0);
+
+/// Starts the CPUs other than `primary_cpu_id` one by one.
+pub(crate) fn start_secondary_cpus(primary_cpu_id: usize) {
+    let mut logic_cpu_id = 0;
+    for cpu_id in (0..SMP).filter(|&cpu_id| cpu_id != primary_cpu_id) {
+        let stack_top = virt_to_phys(unsafe {
+            core::ptr::addr_of!(SECONDARY_BOOT_STACK[logic_cpu_id]) as usize + TASK_STACK_SIZE
+        });
+
+        debug!("starting CPU {}...", cpu_id);
+        axhal::mp::start_secondary_cpu(cpu_id, stack_top);
+        logic_cpu_id += 1;
+
+        while ENTERED_CPUS.load(Ordering::Acquire) <= logic_cpu_id {
+            core::hint::spin_loop();
+        }
+    }
+}
+
+/// The Rust entry of secondary CPUs, called by `axhal`.
+#[no_mangle]
+pub extern "C" fn rust_main_secondary(cpu_id: usize) -> ! {
+    ENTERED_CPUS.fetch_add(1, Ordering::Release);
+    info!("Secondary CPU {} started.", cpu_id);
+
+    #[cfg(feature = "paging")]
+    unsafe {
+        axhal::paging::write_page_table_root(crate::KERNEL_PAGE_TABLE.get().root_paddr())
+    };
+
+    axhal::platform_init_secondary();
+
+    #[cfg(feature = "multitask")]
+    axtask::init_scheduler_secondary();
+
+    info!("Secondary CPU {} init OK.", cpu_id);
+
+    // Wait until the primary CPU has set up the interrupt handlers.
+    wait_for_all_cpus();
+
+    #[cfg(feature = "irq")]
+    axhal::irq::enable_irqs();
+
+    #[cfg(feature = "multitask")]
+    axtask::run_idle();
+    #[cfg(not(feature = "multitask"))]
+    loop {
+        axhal::misc::wait_for_irqs();
+    }
+}
+
+/// Marks the current CPU as initialized, and waits for the others.
+pub(crate) fn wait_for_all_cpus() {
+    INITED_CPUS.fetch_add(1, Ordering::Release);
+    while INITED_CPUS.load(Ordering::Acquire) < SMP {
+        core::hint::spin_loop();
+    }
+}
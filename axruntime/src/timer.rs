@@ -0,0 +1,80 @@
+//! The timer interrupt.
+//!
+//! The timer fires `TICKS_PER_SEC` times per second to drive time slices and
+//! wake up sleeping tasks. With the `tickless` feature, the tick is stopped
+//! while the CPU is idle, and only the nearest sleeper deadline is programmed.
+//! It restarts on the way out of the interrupt that makes a task runnable.
+//!
+//! Each CPU has its own timer, and programs it from its own state.
+
+use core::sync::atomic::{AtomicU64, Ordering};
+#[cfg(feature = "tickless")]
+use core::sync::atomic::AtomicBool;
+
+use axconfig::SMP;
+use axhal::cpu::this_cpu_id;
+use axhal::irq::TIMER_IRQ_NUM;
+
+static PERIODIC_INTERVAL_NANOS: AtomicU64 = AtomicU64::new(0);
+static NEXT_DEADLINE: [AtomicU64; SMP] = [const { AtomicU64::new(0) }; SMP];
+
+#[cfg(feature = "tickless")]
+static TICK_STOPPED: [AtomicBool; SMP] = [const { AtomicBool::new(false) }; SMP];
+
+/// Programs the next periodic tick.
+fn update_timer() {
+    let interval = PERIODIC_INTERVAL_NANOS.load(Ordering::Relaxed);
+    let now_ns = axhal::time::current_time_nanos();
+    // IRQs and preemption are disabled in IRQ handlers, so only this CPU
+    // accesses its deadline.
+    let next_deadline = &NEXT_DEADLINE[this_cpu_id()];
+    let mut deadline = next_deadline.load(Ordering::Relaxed);
+    if now_ns >= deadline {
+        deadline = now_ns + interval;
+    }
+    next_deadline.store(deadline + interval, Ordering::Relaxed);
+    trace!("now {} deadline {}", now_ns, deadline);
+    axhal::time::set_oneshot_timer(deadline);
+}
+
+/// Stops the tick, and programs the timer for the nearest sleeper, if any.
+#[cfg(feature = "tickless")]
+fn stop_tick() {
+    TICK_STOPPED[this_cpu_id()].store(true, Ordering::Relaxed);
+    let deadline = axtask::next_sleeper_deadline().map_or(u64::MAX, |d| d.as_nanos() as u64);
+    trace!("tick stopped, next deadline {}", deadline);
+    axhal::time::set_oneshot_timer(deadline);
+}
+
+/// Restarts the tick if it is stopped and there are tasks to run.
+///
+/// Called on the way out of every interrupt, as any of them may wake up a
+/// task while the CPU is idle.
+#[cfg(feature = "tickless")]
+pub(crate) fn restart_tick_if_busy() {
+    let tick_stopped = &TICK_STOPPED[this_cpu_id()];
+    if tick_stopped.load(Ordering::Relaxed) && !axtask::is_idle() {
+        tick_stopped.store(false, Ordering::Relaxed);
+        trace!("tick restarted");
+        update_timer();
+    }
+}
+
+pub(crate) fn init(ticks_per_sec: u64) {
+    PERIODIC_INTERVAL_NANOS.store(axhal::time::NANOS_PER_SEC / ticks_per_sec, Ordering::Relaxed);
+
+    axhal::irq::register_handler(TIMER_IRQ_NUM, || {
+        trace!("On timer tick!");
+        #[cfg(feature = "multitask")]
+        axtask::on_timer_tick();
+        #[cfg(feature = "net")]
+        axnet::on_timer_tick();
+
+        #[cfg(feature = "tickless")]
+        if axtask::is_idle() {
+            stop_tick();
+            return;
+        }
+        update_timer();
+    });
+}
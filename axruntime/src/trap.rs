@@ -1,10 +1,74 @@
+#[cfg(all(target_os = "none", not(test)))]
+use axhal::trap::Exception;
+#[cfg(all(target_os = "none", not(test)))]
+use axhal::TrapFrame;
+
 #[cfg(all(target_os = "none", not(test)))]
 struct TrapHandlerImpl;
 
 #[cfg(all(target_os = "none", not(test)))]
 #[crate_interface::impl_interface]
 impl axhal::trap::TrapHandler for TrapHandlerImpl {
+    #[cfg_attr(not(feature = "irq"), allow(unused_variables))]
     fn handle_irq(irq_num: usize) {
+        #[cfg(feature = "multitask")]
+        axtask::irq_enter();
+        #[cfg(feature = "irq")]
         axhal::irq::dispatch_irq(irq_num);
     }
+
+    fn irq_exit() {
+        #[cfg(all(feature = "multitask", feature = "irq"))]
+        axtask::tasklet::run_pending();
+        #[cfg(feature = "tickless")]
+        crate::timer::restart_tick_if_busy();
+        #[cfg(feature = "multitask")]
+        axtask::irq_exit();
+    }
+
+    /// Terminates the faulting task, if it can be terminated where it is.
+    /// Otherwise the fault is in the kernel itself, and it panics.
+    #[cfg_attr(not(feature = "multitask"), allow(unused_variables))]
+    fn handle_exception(tf: &mut TrapFrame, exception: Exception) -> bool {
+        // They stop in the debugger, or are left to the default handling, which
+        // skips them.
+        if let Exception::Breakpoint { .. } = exception {
+            #[cfg(feature = "gdbstub")]
+            crate::gdb::handle_breakpoint(tf);
+            return cfg!(feature = "gdbstub");
+        }
+        #[cfg(feature = "multitask")]
+        if tf.irqs_enabled() && axtask::can_exit_current() {
+            let curr = axtask::current();
+            error!(
+                "task {} ({:?}) terminated: {} @ {:#x}",
+                curr.name(),
+                curr.id(),
+                exception,
+                tf.sepc
+            );
+            debug!("{:#x?}", tf);
+            axtask::exit(exit_code(exception));
+        }
+        false
+    }
+}
+
+/// Returns the exit code of a task terminated by `exception`, `128 + signal`
+/// like a shell reports.
+#[cfg(all(feature = "multitask", target_os = "none", not(test)))]
+fn exit_code(exception: Exception) -> i32 {
+    const SIGILL: i32 = 4;
+    const SIGTRAP: i32 = 5;
+    const SIGBUS: i32 = 7;
+    const SIGSEGV: i32 = 11;
+    let signal = match exception {
+        Exception::IllegalInstruction { .. } => SIGILL,
+        Exception::Breakpoint { .. } => SIGTRAP,
+        Exception::InstructionMisaligned { .. }
+        | Exception::LoadMisaligned { .. }
+        | Exception::StoreMisaligned { .. } => SIGBUS,
+        _ => SIGSEGV,
+    };
+    128 + signal
 }
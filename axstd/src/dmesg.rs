@@ -0,0 +1,17 @@
+//! The kernel log buffer, like `dmesg` on Linux.
+//!
+//! Recent log records are kept in memory, including those below the console
+//! log level, down to `log_ring=<level>` in the kernel command line (`info`
+//! by default).
+
+pub use axlog::RingRecord as LogRecord;
+
+/// Returns the log records in the buffer, oldest first.
+pub fn records() -> impl Iterator<Item = LogRecord> {
+    axlog::records()
+}
+
+/// Prints all log records in the buffer to the console.
+pub fn print() {
+    axlog::dump()
+}
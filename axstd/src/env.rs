@@ -0,0 +1,66 @@
+//! Inspection of the app's environment.
+
+use alloc::string::String;
+use alloc::vec::{self, Vec};
+use core::fmt;
+
+use axcmdline::Param;
+
+/// The name of the app, `init=<name>` in the kernel command line.
+static APP_NAME: Param<&str> = Param::new(
+    "init",
+    match option_env!("AX_APP") {
+        Some(name) => name,
+        None => "app",
+    },
+);
+
+/// An iterator over the arguments of the app, yielding a [`String`] value for
+/// each argument.
+pub struct Args {
+    inner: vec::IntoIter<String>,
+}
+
+/// Returns the arguments that this app was started with.
+///
+/// The first element is the name of the app, followed by the arguments in the
+/// kernel command line that are not kernel parameters, and all arguments after
+/// `--`.
+pub fn args() -> Args {
+    let mut args = Vec::new();
+    args.push(String::from(APP_NAME.get()));
+    args.extend(axcmdline::app_args().map(String::from));
+    Args {
+        inner: args.into_iter(),
+    }
+}
+
+impl Iterator for Args {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl ExactSizeIterator for Args {
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+}
+
+impl DoubleEndedIterator for Args {
+    fn next_back(&mut self) -> Option<String> {
+        self.inner.next_back()
+    }
+}
+
+impl fmt::Debug for Args {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.inner.as_slice()).finish()
+    }
+}
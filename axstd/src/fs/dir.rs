@@ -0,0 +1,101 @@
+use alloc::string::String;
+
+use axfs::fops::Directory;
+use axfs::VfsDirEntry;
+
+use super::FileType;
+use crate::io::{self, IoError};
+
+/// Iterator over the entries in a directory.
+///
+/// The `.` and `..` entries are skipped.
+pub struct ReadDir {
+    path: String,
+    inner: Directory,
+    buf: [VfsDirEntry; 31],
+    buf_pos: usize,
+    buf_end: usize,
+    end_of_stream: bool,
+}
+
+/// Entries returned by the [`ReadDir`] iterator.
+pub struct DirEntry {
+    dir_path: String,
+    entry_name: String,
+    entry_type: FileType,
+}
+
+impl ReadDir {
+    pub(super) fn new(path: &str) -> io::Result<Self> {
+        Ok(ReadDir {
+            path: String::from(path),
+            inner: Directory::open_dir(path)?,
+            buf: [const { VfsDirEntry::default() }; 31],
+            buf_pos: 0,
+            buf_end: 0,
+            end_of_stream: false,
+        })
+    }
+}
+
+impl Iterator for ReadDir {
+    type Item = io::Result<DirEntry>;
+
+    fn next(&mut self) -> Option<io::Result<DirEntry>> {
+        if self.end_of_stream {
+            return None;
+        }
+
+        loop {
+            if self.buf_pos >= self.buf_end {
+                match self.inner.read_dir(&mut self.buf) {
+                    Ok(0) => {
+                        self.end_of_stream = true;
+                        return None;
+                    }
+                    Ok(n) => {
+                        self.buf_pos = 0;
+                        self.buf_end = n;
+                    }
+                    Err(e) => {
+                        self.end_of_stream = true;
+                        return Some(Err(e.into()));
+                    }
+                }
+            }
+            let entry = &self.buf[self.buf_pos];
+            self.buf_pos += 1;
+            let name_bytes = entry.name_as_bytes();
+            if name_bytes == b"." || name_bytes == b".." {
+                continue;
+            }
+            let entry_name = match core::str::from_utf8(name_bytes) {
+                Ok(name) => String::from(name),
+                Err(_) => return Some(Err(IoError::InvalidData)),
+            };
+            return Some(Ok(DirEntry {
+                dir_path: self.path.clone(),
+                entry_name,
+                entry_type: entry.entry_type(),
+            }));
+        }
+    }
+}
+
+impl DirEntry {
+    /// Returns the full path to the file that this entry represents.
+    pub fn path(&self) -> String {
+        String::from(self.dir_path.trim_end_matches('/')) + "/" + &self.entry_name
+    }
+
+    /// Returns the bare file name of this directory entry without any other
+    /// leading path component.
+    pub fn file_name(&self) -> String {
+        self.entry_name.clone()
+    }
+
+    /// Returns the file type for the file that this entry points at.
+    pub fn file_type(&self) -> FileType {
+        self.entry_type
+    }
+}
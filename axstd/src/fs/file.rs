@@ -0,0 +1,168 @@
+use axfs::fops;
+
+use crate::io::{self, Read, Seek, SeekFrom, Write};
+
+/// A structure representing a type of file with accessors for each file type.
+pub use axfs::VfsNodeType as FileType;
+
+/// An object providing access to an open file on the filesystem.
+pub struct File {
+    inner: fops::File,
+}
+
+/// Metadata information about a file.
+pub struct Metadata(pub(super) axfs::VfsNodeAttr);
+
+/// Options and flags which can be used to configure how a file is opened.
+#[derive(Clone, Debug)]
+pub struct OpenOptions(fops::OpenOptions);
+
+impl OpenOptions {
+    /// Creates a blank new set of options ready for configuration.
+    pub const fn new() -> Self {
+        OpenOptions(fops::OpenOptions::new())
+    }
+
+    /// Sets the option for read access.
+    pub fn read(&mut self, read: bool) -> &mut Self {
+        self.0.read(read);
+        self
+    }
+
+    /// Sets the option for write access.
+    pub fn write(&mut self, write: bool) -> &mut Self {
+        self.0.write(write);
+        self
+    }
+
+    /// Sets the option for the append mode.
+    pub fn append(&mut self, append: bool) -> &mut Self {
+        self.0.append(append);
+        self
+    }
+
+    /// Sets the option for truncating a previous file.
+    pub fn truncate(&mut self, truncate: bool) -> &mut Self {
+        self.0.truncate(truncate);
+        self
+    }
+
+    /// Sets the option to create a new file, or open it if it already exists.
+    pub fn create(&mut self, create: bool) -> &mut Self {
+        self.0.create(create);
+        self
+    }
+
+    /// Sets the option to create a new file, failing if it already exists.
+    pub fn create_new(&mut self, create_new: bool) -> &mut Self {
+        self.0.create_new(create_new);
+        self
+    }
+
+    /// Opens a file at `path` with the options specified by `self`.
+    pub fn open(&self, path: &str) -> io::Result<File> {
+        Ok(File {
+            inner: fops::File::open(path, &self.0)?,
+        })
+    }
+}
+
+impl Default for OpenOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Metadata {
+    /// Returns the file type for this metadata.
+    pub const fn file_type(&self) -> FileType {
+        self.0.file_type()
+    }
+
+    /// Returns `true` if this metadata is for a directory.
+    pub const fn is_dir(&self) -> bool {
+        self.0.is_dir()
+    }
+
+    /// Returns `true` if this metadata is for a regular file.
+    pub const fn is_file(&self) -> bool {
+        self.0.is_file()
+    }
+
+    /// Returns the size of the file, in bytes, this metadata is for.
+    pub const fn len(&self) -> u64 {
+        self.0.size()
+    }
+
+    /// Returns `true` if the file is empty.
+    pub const fn is_empty(&self) -> bool {
+        self.0.size() == 0
+    }
+}
+
+impl File {
+    /// Attempts to open a file in read-only mode.
+    pub fn open(path: &str) -> io::Result<Self> {
+        OpenOptions::new().read(true).open(path)
+    }
+
+    /// Opens a file in write-only mode.
+    ///
+    /// This function will create a file if it does not exist, and will
+    /// truncate it if it does.
+    pub fn create(path: &str) -> io::Result<Self> {
+        OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)
+    }
+
+    /// Returns a new [`OpenOptions`] object.
+    pub fn options() -> OpenOptions {
+        OpenOptions::new()
+    }
+
+    /// Truncates or extends the underlying file, updating the size of this
+    /// file to become `size`.
+    pub fn set_len(&self, size: u64) -> io::Result<()> {
+        Ok(self.inner.truncate(size)?)
+    }
+
+    /// Attempts to sync all data to the underlying device.
+    pub fn sync_all(&self) -> io::Result<()> {
+        Ok(self.inner.flush()?)
+    }
+
+    /// Queries metadata about the underlying file.
+    pub fn metadata(&self) -> io::Result<Metadata> {
+        Ok(Metadata(self.inner.get_attr()?))
+    }
+}
+
+impl Read for File {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        Ok(self.inner.read(buf)?)
+    }
+}
+
+impl Write for File {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Ok(self.inner.write(buf)?)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(self.inner.flush()?)
+    }
+}
+
+impl Seek for File {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(pos) => fops::SeekFrom::Start(pos),
+            SeekFrom::End(off) => fops::SeekFrom::End(off),
+            SeekFrom::Current(off) => fops::SeekFrom::Current(off),
+        };
+        Ok(self.inner.seek(pos)?)
+    }
+}
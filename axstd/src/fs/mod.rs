@@ -0,0 +1,59 @@
+//! Filesystem manipulation operations.
+
+mod dir;
+mod file;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::io::{self, IoError, Read, Write};
+
+pub use self::dir::{DirEntry, ReadDir};
+pub use self::file::{File, FileType, Metadata, OpenOptions};
+
+/// Returns an iterator over the entries within a directory.
+pub fn read_dir(path: &str) -> io::Result<ReadDir> {
+    ReadDir::new(path)
+}
+
+/// Creates a new, empty directory at the provided path.
+pub fn create_dir(path: &str) -> io::Result<()> {
+    Ok(axfs::api::create_dir(path)?)
+}
+
+/// Removes an empty directory.
+pub fn remove_dir(path: &str) -> io::Result<()> {
+    Ok(axfs::api::remove_dir(path)?)
+}
+
+/// Removes a file from the filesystem.
+pub fn remove_file(path: &str) -> io::Result<()> {
+    Ok(axfs::api::remove_file(path)?)
+}
+
+/// Given a path, query the file system to get information about a file,
+/// directory, etc.
+pub fn metadata(path: &str) -> io::Result<Metadata> {
+    Ok(Metadata(axfs::api::metadata(path)?))
+}
+
+/// Read the entire contents of a file into a bytes vector.
+pub fn read(path: &str) -> io::Result<Vec<u8>> {
+    let mut file = File::open(path)?;
+    let mut bytes = Vec::with_capacity(file.metadata()?.len() as usize);
+    file.read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// Read the entire contents of a file into a string.
+pub fn read_to_string(path: &str) -> io::Result<String> {
+    String::from_utf8(read(path)?).map_err(|_| IoError::InvalidData)
+}
+
+/// Write a slice as the entire contents of a file.
+///
+/// This function will create a file if it does not exist, and will entirely
+/// replace its contents if it does.
+pub fn write<C: AsRef<[u8]>>(path: &str, contents: C) -> io::Result<()> {
+    File::create(path)?.write_all(contents.as_ref())
+}
@@ -1,18 +1,151 @@
 /// Constructs a new handle to the standard output of the current process.
 
-use core::fmt::{Write, Error};
+use core::fmt::{self, Error};
 use spinlock::SpinRaw;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub enum IoError {
     BadState = 1,
+    AddrInUse,
+    AlreadyExists,
+    ConnectionRefused,
+    ConnectionReset,
+    DirectoryNotEmpty,
+    InvalidData,
+    InvalidInput,
+    Io,
+    IsADirectory,
+    NotADirectory,
+    NotConnected,
+    NotFound,
+    PermissionDenied,
+    StorageFull,
+    UnexpectedEof,
+    Unsupported,
+    WouldBlock,
+    WriteZero,
 }
 
 pub type Result<T = ()> = core::result::Result<T, IoError>;
 
+#[cfg(feature = "fs")]
+impl From<axfs::VfsError> for IoError {
+    fn from(e: axfs::VfsError) -> Self {
+        use axfs::VfsError;
+        match e {
+            VfsError::AlreadyExists => IoError::AlreadyExists,
+            VfsError::BadState => IoError::BadState,
+            VfsError::DirectoryNotEmpty => IoError::DirectoryNotEmpty,
+            VfsError::InvalidData => IoError::InvalidData,
+            VfsError::InvalidInput => IoError::InvalidInput,
+            VfsError::Io => IoError::Io,
+            VfsError::IsADirectory => IoError::IsADirectory,
+            VfsError::NotADirectory => IoError::NotADirectory,
+            VfsError::NotFound => IoError::NotFound,
+            VfsError::PermissionDenied => IoError::PermissionDenied,
+            VfsError::StorageFull => IoError::StorageFull,
+            VfsError::UnexpectedEof => IoError::UnexpectedEof,
+            VfsError::Unsupported => IoError::Unsupported,
+        }
+    }
+}
+
+#[cfg(feature = "net")]
+impl From<axnet::NetError> for IoError {
+    fn from(e: axnet::NetError) -> Self {
+        use axnet::NetError;
+        match e {
+            NetError::AddrInUse => IoError::AddrInUse,
+            NetError::BadState => IoError::BadState,
+            NetError::ConnectionRefused => IoError::ConnectionRefused,
+            NetError::ConnectionReset => IoError::ConnectionReset,
+            NetError::InvalidInput => IoError::InvalidInput,
+            NetError::NotConnected => IoError::NotConnected,
+            NetError::Unsupported => IoError::Unsupported,
+            NetError::WouldBlock => IoError::WouldBlock,
+        }
+    }
+}
+
+/// Enumeration of possible methods to seek within an I/O object.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SeekFrom {
+    /// Sets the offset to the provided number of bytes.
+    Start(u64),
+    /// Sets the offset to the size of this object plus the specified number
+    /// of bytes.
+    End(i64),
+    /// Sets the offset to the current position plus the specified number of
+    /// bytes.
+    Current(i64),
+}
+
+/// The `Read` trait allows for reading bytes from a source.
+pub trait Read {
+    /// Pull some bytes from this source into the specified buffer, returning
+    /// how many bytes were read.
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize>;
+
+    /// Read all bytes until EOF in this source, placing them into `buf`.
+    #[cfg(feature = "alloc")]
+    fn read_to_end(&mut self, buf: &mut Vec<u8>) -> Result<usize> {
+        let start_len = buf.len();
+        let mut chunk = [0; 512];
+        loop {
+            match self.read(&mut chunk)? {
+                0 => return Ok(buf.len() - start_len),
+                n => buf.extend_from_slice(&chunk[..n]),
+            }
+        }
+    }
+
+    /// Read the exact number of bytes required to fill `buf`.
+    fn read_exact(&mut self, mut buf: &mut [u8]) -> Result {
+        while !buf.is_empty() {
+            match self.read(buf)? {
+                0 => return Err(IoError::UnexpectedEof),
+                n => buf = &mut buf[n..],
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A trait for objects which are byte-oriented sinks.
+pub trait Write {
+    /// Write a buffer into this writer, returning how many bytes were written.
+    fn write(&mut self, buf: &[u8]) -> Result<usize>;
+
+    /// Flush this output stream, ensuring that all intermediately buffered
+    /// contents reach their destination.
+    fn flush(&mut self) -> Result;
+
+    /// Attempts to write an entire buffer into this writer.
+    fn write_all(&mut self, mut buf: &[u8]) -> Result {
+        while !buf.is_empty() {
+            match self.write(buf)? {
+                0 => return Err(IoError::WriteZero),
+                n => buf = &buf[n..],
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The `Seek` trait provides a cursor which can be moved within a stream of
+/// bytes.
+pub trait Seek {
+    /// Seek to an offset, in bytes, in a stream.
+    ///
+    /// Returns the new position from the start of the stream.
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64>;
+}
+
 struct StdoutRaw;
 
-impl Write for StdoutRaw {
+impl fmt::Write for StdoutRaw {
     fn write_str(&mut self, s: &str) -> core::result::Result<(), Error> {
         axhal::console::write_bytes(s.as_bytes());
         Ok(())
@@ -22,5 +155,5 @@ impl Write for StdoutRaw {
 static STDOUT: SpinRaw<StdoutRaw> = SpinRaw::new(StdoutRaw);
 
 pub fn __print_impl(args: core::fmt::Arguments) {
-    STDOUT.lock().write_fmt(args).unwrap();
+    fmt::Write::write_fmt(&mut *STDOUT.lock(), args).unwrap();
 }
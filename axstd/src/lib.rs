@@ -1,16 +1,30 @@
 #![no_std]
+#![cfg_attr(feature = "tls", feature(allow_internal_unstable))]
+#![cfg_attr(feature = "tls", allow(internal_features))]
 
+#[cfg(feature = "alloc")]
 extern crate alloc;
 extern crate axruntime;
 
 #[macro_use]
 mod macros;
 
+#[cfg(feature = "alloc")]
+pub mod env;
 pub mod io;
+pub mod dmesg;
+pub mod process;
 pub mod time;
 pub mod thread;
 pub mod sync;
 
+#[cfg(feature = "fs")]
+pub mod fs;
+#[cfg(feature = "net")]
+pub mod net;
+
 // Re-export String
+#[cfg(feature = "alloc")]
 pub use alloc::string::String;
+#[cfg(feature = "alloc")]
 pub use alloc::vec::Vec;
@@ -0,0 +1,56 @@
+//! Networking primitives for TCP/UDP communication.
+
+mod tcp;
+mod udp;
+
+pub use core::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+
+pub use self::tcp::{TcpListener, TcpStream};
+pub use self::udp::UdpSocket;
+
+use crate::io::{self, IoError};
+
+/// A trait for objects which can be converted to a socket address.
+///
+/// There is no name resolution, so a string must be a literal socket address
+/// such as `"10.0.2.2:5555"`.
+pub trait ToSocketAddrs {
+    /// Converts this object to a socket address.
+    fn to_socket_addr(&self) -> io::Result<SocketAddr>;
+}
+
+impl ToSocketAddrs for SocketAddr {
+    fn to_socket_addr(&self) -> io::Result<SocketAddr> {
+        Ok(*self)
+    }
+}
+
+impl ToSocketAddrs for SocketAddrV4 {
+    fn to_socket_addr(&self) -> io::Result<SocketAddr> {
+        Ok(SocketAddr::V4(*self))
+    }
+}
+
+impl ToSocketAddrs for (IpAddr, u16) {
+    fn to_socket_addr(&self) -> io::Result<SocketAddr> {
+        Ok(SocketAddr::new(self.0, self.1))
+    }
+}
+
+impl ToSocketAddrs for (Ipv4Addr, u16) {
+    fn to_socket_addr(&self) -> io::Result<SocketAddr> {
+        Ok(SocketAddr::V4(SocketAddrV4::new(self.0, self.1)))
+    }
+}
+
+impl ToSocketAddrs for str {
+    fn to_socket_addr(&self) -> io::Result<SocketAddr> {
+        self.parse().map_err(|_| IoError::InvalidInput)
+    }
+}
+
+impl<T: ToSocketAddrs + ?Sized> ToSocketAddrs for &T {
+    fn to_socket_addr(&self) -> io::Result<SocketAddr> {
+        (**self).to_socket_addr()
+    }
+}
@@ -0,0 +1,68 @@
+use super::{SocketAddr, ToSocketAddrs};
+use crate::io::{self, Read, Write};
+
+/// A TCP stream between a local and a remote socket.
+pub struct TcpStream(axnet::TcpStream);
+
+/// A TCP socket server, listening for connections.
+pub struct TcpListener(axnet::TcpListener);
+
+impl TcpStream {
+    /// Opens a TCP connection to a remote host.
+    pub fn connect<A: ToSocketAddrs>(addr: A) -> io::Result<TcpStream> {
+        Ok(TcpStream(axnet::TcpStream::connect(addr.to_socket_addr()?)?))
+    }
+
+    /// Returns the socket address of the remote peer of this TCP connection.
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        Ok(self.0.peer_addr())
+    }
+
+    /// Returns the socket address of the local half of this TCP connection.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        Ok(self.0.local_addr())
+    }
+
+    /// Shuts down the write half of this connection.
+    pub fn shutdown(&self) -> io::Result<()> {
+        Ok(self.0.shutdown()?)
+    }
+}
+
+impl Read for TcpStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        Ok(self.0.recv(buf)?)
+    }
+}
+
+impl Write for TcpStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Ok(self.0.send(buf)?)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl TcpListener {
+    /// Creates a new `TcpListener` which will be bound to the specified
+    /// address.
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<TcpListener> {
+        Ok(TcpListener(axnet::TcpListener::bind(addr.to_socket_addr()?)?))
+    }
+
+    /// Returns the local socket address of this listener.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        Ok(self.0.local_addr())
+    }
+
+    /// Accept a new incoming connection from this listener.
+    ///
+    /// This function will block the calling thread until a new TCP connection
+    /// is established.
+    pub fn accept(&self) -> io::Result<(TcpStream, SocketAddr)> {
+        let (stream, addr) = self.0.accept()?;
+        Ok((TcpStream(stream), addr))
+    }
+}
@@ -0,0 +1,55 @@
+use super::{SocketAddr, ToSocketAddrs};
+use crate::io;
+
+/// A UDP socket.
+pub struct UdpSocket(axnet::UdpSocket);
+
+impl UdpSocket {
+    /// Creates a UDP socket from the given address.
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<UdpSocket> {
+        Ok(UdpSocket(axnet::UdpSocket::bind(addr.to_socket_addr()?)?))
+    }
+
+    /// Returns the socket address that this socket was created from.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        Ok(self.0.local_addr())
+    }
+
+    /// Returns the socket address of the remote peer this socket was
+    /// connected to.
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        Ok(self.0.peer_addr()?)
+    }
+
+    /// Receives a single datagram message on the socket. On success, returns
+    /// the number of bytes read and the origin.
+    ///
+    /// Bytes that do not fit in `buf` are discarded.
+    pub fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        Ok(self.0.recv_from(buf)?)
+    }
+
+    /// Sends data on the socket to the given address. On success, returns the
+    /// number of bytes written.
+    pub fn send_to<A: ToSocketAddrs>(&self, buf: &[u8], addr: A) -> io::Result<usize> {
+        Ok(self.0.send_to(buf, addr.to_socket_addr()?)?)
+    }
+
+    /// Connects this UDP socket to a remote address, allowing the `send` and
+    /// `recv` methods to be used.
+    pub fn connect<A: ToSocketAddrs>(&self, addr: A) -> io::Result<()> {
+        Ok(self.0.connect(addr.to_socket_addr()?)?)
+    }
+
+    /// Sends data on the socket to the remote address to which it is
+    /// connected.
+    pub fn send(&self, buf: &[u8]) -> io::Result<usize> {
+        Ok(self.0.send(buf)?)
+    }
+
+    /// Receives a single datagram message on the socket from the remote
+    /// address to which it is connected.
+    pub fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        Ok(self.0.recv(buf)?)
+    }
+}
@@ -0,0 +1,13 @@
+//! A module for working with processes.
+//!
+//! There is only one process, so exiting it shuts down the system. Returning
+//! from `main` does the same with its return value as the exit code.
+
+/// Terminates the current process with the specified exit code, even if it
+/// is called in a thread other than the main one.
+///
+/// A non-zero `code` is reported as a failure, and makes QEMU exit with a
+/// non-zero status.
+pub fn exit(code: i32) -> ! {
+    axhal::misc::terminate(code)
+}
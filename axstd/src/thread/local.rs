@@ -0,0 +1,70 @@
+//! Thread-local storage.
+
+use core::fmt;
+
+/// A thread-local key which owns its contents, declared by
+/// [`thread_local!`](crate::thread_local).
+///
+/// Each thread gets its own copy, lazily initialized on the first access.
+/// The values are not dropped when threads exit.
+pub struct LocalKey<T: 'static> {
+    inner: fn() -> *const T,
+}
+
+impl<T: 'static> LocalKey<T> {
+    #[doc(hidden)]
+    pub const unsafe fn new(inner: fn() -> *const T) -> Self {
+        Self { inner }
+    }
+
+    /// Acquires a reference to the value of this thread.
+    pub fn with<F, R>(&'static self, f: F) -> R
+    where
+        F: FnOnce(&T) -> R,
+    {
+        f(unsafe { &*(self.inner)() })
+    }
+}
+
+impl<T: 'static> fmt::Debug for LocalKey<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LocalKey").finish_non_exhaustive()
+    }
+}
+
+/// Declares new thread-local storage keys of type [`LocalKey`].
+///
+/// The syntax is the same as `std::thread_local!`, and `Cell` or `RefCell`
+/// give mutable access to the values:
+///
+/// ```ignore
+/// use core::cell::Cell;
+///
+/// axstd::thread_local! {
+///     static COUNTER: Cell<u32> = Cell::new(0);
+/// }
+///
+/// COUNTER.with(|c| c.set(c.get() + 1));
+/// ```
+#[macro_export]
+#[allow_internal_unstable(thread_local)]
+macro_rules! thread_local {
+    () => {};
+
+    ($(#[$attr:meta])* $vis:vis static $name:ident: $t:ty = $init:expr; $($rest:tt)*) => {
+        $crate::thread_local!($(#[$attr])* $vis static $name: $t = $init);
+        $crate::thread_local!($($rest)*);
+    };
+
+    ($(#[$attr:meta])* $vis:vis static $name:ident: $t:ty = $init:expr) => {
+        $(#[$attr])*
+        $vis const $name: $crate::thread::LocalKey<$t> = {
+            #[thread_local]
+            static VAL: ::core::cell::LazyCell<$t> = ::core::cell::LazyCell::new(|| $init);
+            fn get() -> *const $t {
+                &*VAL
+            }
+            unsafe { $crate::thread::LocalKey::new(get) }
+        };
+    };
+}
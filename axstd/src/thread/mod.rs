@@ -0,0 +1,44 @@
+//! Native threads.
+
+#[cfg(feature = "tls")]
+mod local;
+#[cfg(feature = "multitask")]
+mod multi;
+#[cfg(feature = "multitask")]
+pub use self::multi::*;
+#[cfg(feature = "tls")]
+pub use self::local::LocalKey;
+
+/// Current thread gives up the CPU time voluntarily, and switches to another
+/// ready thread.
+///
+/// For single-threaded configuration (`multitask` feature is disabled), we just
+/// relax the CPU and wait for incoming interrupts.
+pub fn yield_now() {
+    #[cfg(feature = "multitask")]
+    axtask::yield_now();
+    #[cfg(not(feature = "multitask"))]
+    core::hint::spin_loop();
+}
+
+/// Puts the current thread to sleep for at least the specified amount of time.
+///
+/// For single-threaded configuration (`multitask` feature is disabled), we just
+/// busy-wait until the time passes.
+pub fn sleep(dur: core::time::Duration) {
+    #[cfg(feature = "multitask")]
+    axtask::sleep(dur);
+    #[cfg(not(feature = "multitask"))]
+    {
+        let deadline = axhal::time::current_time() + dur;
+        while axhal::time::current_time() < deadline {
+            core::hint::spin_loop();
+        }
+    }
+}
+
+/// Returns the number of CPUs, `SMP` in make, which is how many threads can
+/// run in parallel.
+pub fn available_parallelism() -> crate::io::Result<core::num::NonZeroUsize> {
+    Ok(core::num::NonZeroUsize::new(axconfig::SMP).unwrap())
+}
@@ -1,9 +1,13 @@
+//! Native threads, backed by ArceOS tasks.
+
 use core::num::NonZeroU64;
 use core::cell::UnsafeCell;
 use alloc::sync::Arc;
 use crate::String;
 use crate::io::{self, Result, IoError};
 
+pub use axtask::CpuMask;
+
 /// A handle to a task.
 pub struct AxTaskHandle {
     inner: axtask::AxTaskRef,
@@ -14,15 +18,24 @@ pub struct AxTaskHandle {
 #[derive(Eq, PartialEq, Clone, Copy, Debug)]
 pub struct ThreadId(NonZeroU64);
 
+impl ThreadId {
+    /// Returns the ID as the underlying task ID.
+    pub fn as_u64(&self) -> NonZeroU64 {
+        self.0
+    }
+}
+
 /// A handle to a thread.
 pub struct Thread {
     id: ThreadId,
+    name: Option<String>,
 }
 
 impl Thread {
-    fn from_id(id: u64) -> Self {
+    fn new(id: u64, name: Option<String>) -> Self {
         Self {
             id: ThreadId(NonZeroU64::new(id).unwrap()),
+            name,
         }
     }
 
@@ -30,6 +43,18 @@ impl Thread {
     pub fn id(&self) -> ThreadId {
         self.id
     }
+
+    /// Gets the thread's name, `None` for unnamed threads.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+}
+
+/// Gets a handle to the thread that invokes it.
+pub fn current() -> Thread {
+    let curr = axtask::current();
+    let name = Some(curr.name()).filter(|name| !name.is_empty());
+    Thread::new(curr.id().as_u64(), name.map(String::from))
 }
 
 #[derive(Debug)]
@@ -38,6 +63,8 @@ pub struct Builder {
     name: Option<String>,
     // The size of the stack for the spawned thread in bytes
     stack_size: Option<usize>,
+    // The CPUs the spawned thread may run on
+    affinity: Option<CpuMask>,
 }
 
 impl Builder {
@@ -45,10 +72,9 @@ impl Builder {
         Builder {
             name: None,
             stack_size: None,
+            affinity: None,
         }
     }
-    /*
-
     /// Names the thread-to-be.
     pub fn name(mut self, name: String) -> Builder {
         self.name = Some(name);
@@ -60,7 +86,14 @@ impl Builder {
         self.stack_size = Some(size);
         self
     }
-    */
+
+    /// Sets the CPUs the new thread may run on, all of them by default.
+    ///
+    /// Spawning fails with [`IoError::InvalidInput`] if the set is empty.
+    pub fn affinity(mut self, affinity: CpuMask) -> Builder {
+        self.affinity = Some(affinity);
+        self
+    }
 
     /// Spawns a new thread by taking ownership of the `Builder`, and returns an
     /// [`Result`] to its [`JoinHandle`].
@@ -84,10 +117,14 @@ impl Builder {
         F: 'static,
         T: 'static,
     {
+        if self.affinity.is_some_and(|affinity| affinity.is_empty()) {
+            return Err(IoError::InvalidInput);
+        }
+        let thread_name = self.name.clone();
         let name = self.name.unwrap_or_default();
         let stack_size = self
             .stack_size
-            .unwrap_or(axconfig::TASK_STACK_SIZE);
+            .unwrap_or_else(axtask::default_stack_size);
 
         let my_packet = Arc::new(Packet {
             result: UnsafeCell::new(None),
@@ -100,13 +137,16 @@ impl Builder {
             drop(their_packet);
         };
 
-        let inner = axtask::spawn_raw(main, name, stack_size);
+        let inner = match self.affinity {
+            Some(affinity) => axtask::spawn_raw_with_affinity(main, name, stack_size, affinity),
+            None => axtask::spawn_raw(main, name, stack_size),
+        };
         let task = AxTaskHandle {
             id: inner.id().as_u64(),
             inner,
         };
         Ok(JoinHandle {
-            thread: Thread::from_id(task.id),
+            thread: Thread::new(task.id, thread_name),
             native: task,
             packet: my_packet,
         })
@@ -121,15 +161,6 @@ where
     Builder::new().spawn(f).expect("failed to spawn thread")
 }
 
-/// Current thread gives up the CPU time voluntarily, and switches to another
-/// ready thread.
-///
-/// For single-threaded configuration (`multitask` feature is disabled), we just
-/// relax the CPU and wait for incoming interrupts.
-pub fn yield_now() {
-    axtask::yield_now();
-}
-
 struct Packet<T> {
     result: UnsafeCell<Option<T>>,
 }
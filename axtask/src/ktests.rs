@@ -0,0 +1,411 @@
+use alloc::string::String;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use axtest::ax_test;
+
+use crate::task::TaskState;
+use crate::{AxTaskRef, WaitQueue};
+
+/// Spawns a task named `name` running `f`, with the default stack size.
+fn spawn_test<F>(name: &str, f: F) -> AxTaskRef
+where
+    F: FnOnce() + 'static,
+{
+    crate::spawn_raw(f, String::from(name), crate::default_stack_size())
+}
+
+#[ax_test]
+fn test_join_exit_code() {
+    let task = spawn_test("ktest", || crate::exit(42));
+    assert_eq!(task.join(), Some(42));
+}
+
+#[ax_test]
+fn test_join_blocks_until_exit() {
+    static RELEASE: AtomicUsize = AtomicUsize::new(0);
+    let task = spawn_test("ktest-joined", || {
+        while RELEASE.load(Ordering::Acquire) == 0 {
+            crate::yield_now();
+        }
+        crate::exit(7)
+    });
+    let main = crate::current().as_task_ref().clone();
+    let releaser = spawn_test("ktest-releaser", move || {
+        // Only once the main task really blocks in `join`.
+        while main.state() != TaskState::Blocked {
+            crate::yield_now();
+        }
+        RELEASE.store(1, Ordering::Release);
+    });
+    assert_eq!(task.join(), Some(7));
+    assert_eq!(releaser.join(), Some(0));
+}
+
+#[ax_test]
+fn test_yield_runs_others() {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let tasks: [_; 4] = core::array::from_fn(|_| {
+        spawn_test("ktest", || {
+            COUNTER.fetch_add(1, Ordering::Relaxed);
+        })
+    });
+    while COUNTER.load(Ordering::Relaxed) < tasks.len() {
+        crate::yield_now();
+    }
+    for task in tasks {
+        assert_eq!(task.join(), Some(0));
+    }
+}
+
+#[ax_test]
+fn test_wait_queue_notify() {
+    static WQ: WaitQueue = WaitQueue::new();
+    static READY: AtomicUsize = AtomicUsize::new(0);
+    let task = spawn_test("ktest", || {
+        READY.store(1, Ordering::Release);
+        WQ.notify_one(true);
+    });
+    WQ.wait_until(|| READY.load(Ordering::Acquire) == 1);
+    task.join();
+}
+
+#[ax_test]
+fn test_fp_preserved_across_switches() {
+    fn spin(seed: f64) -> f64 {
+        let mut x = core::hint::black_box(seed);
+        for _ in 0..100 {
+            x = x * 1.5 + 0.25;
+            crate::yield_now();
+            x = (x - 0.25) / 1.5;
+        }
+        x
+    }
+    let tasks: [_; 3] = core::array::from_fn(|i| {
+        spawn_test("ktest", move || assert_eq!(spin(i as f64 + 0.5), i as f64 + 0.5))
+    });
+    assert_eq!(spin(-1.0), -1.0);
+    for task in tasks {
+        assert_eq!(task.join(), Some(0));
+    }
+}
+
+#[ax_test]
+fn test_fp_restored_on_first_use() {
+    fn fpu_on() -> bool {
+        let sstatus: usize;
+        unsafe { core::arch::asm!("csrr {}, sstatus", out(reg) sstatus) };
+        sstatus & (0b11 << 13) != 0
+    }
+    let x = core::hint::black_box(1.5f64) * 2.0;
+    let task = spawn_test("ktest", || {});
+    crate::yield_now();
+    // Off after the switch back, until the first FP instruction.
+    assert!(!fpu_on());
+    assert_eq!(core::hint::black_box(x) + 1.0, 4.0);
+    assert!(fpu_on());
+    assert_eq!(task.join(), Some(0));
+}
+
+#[cfg(feature = "irq")]
+#[ax_test]
+fn test_fp_preserved_across_preemption() {
+    // Busy loops that keep their values in FP registers, and only switch
+    // when timer interrupts preempt them, so the trap path must keep them.
+    fn spin(seed: f64, ticks: u64) -> f64 {
+        let deadline = axhal::time::current_time_nanos()
+            + ticks * axhal::time::NANOS_PER_SEC / axconfig::TICKS_PER_SEC as u64;
+        let mut x = core::hint::black_box(seed);
+        while axhal::time::current_time_nanos() < deadline {
+            x = x * 1.5 + 0.25;
+            x = (x - 0.25) / 1.5;
+        }
+        x
+    }
+    let tasks: [_; 2] = core::array::from_fn(|i| {
+        spawn_test("ktest", move || assert_eq!(spin(i as f64 + 0.5, 5), i as f64 + 0.5))
+    });
+    assert_eq!(spin(-1.0, 5), -1.0);
+    for task in tasks {
+        assert_eq!(task.join(), Some(0));
+    }
+}
+
+#[cfg(feature = "tls")]
+#[ax_test]
+fn test_tls_per_task() {
+    #[thread_local]
+    static VALUE: core::cell::Cell<usize> = core::cell::Cell::new(7);
+
+    VALUE.set(1);
+    let task = spawn_test("ktest", || {
+        assert_eq!(VALUE.get(), 7);
+        VALUE.set(2);
+        crate::yield_now();
+        assert_eq!(VALUE.get(), 2);
+    });
+    crate::yield_now();
+    assert_eq!(VALUE.get(), 1);
+    task.join();
+    assert_eq!(VALUE.get(), 1);
+}
+
+#[cfg(feature = "irq")]
+#[ax_test]
+fn test_preempt_busy_loops() {
+    static COUNTERS: [AtomicUsize; 2] = [AtomicUsize::new(0), AtomicUsize::new(0)];
+    static STOP: core::sync::atomic::AtomicBool = core::sync::atomic::AtomicBool::new(false);
+
+    // Neither the loops nor the main task yield or take locks, so they can
+    // only make progress in turn if timer interrupts preempt them.
+    let tasks: [_; 2] = core::array::from_fn(|i| {
+        spawn_test("ktest", move || {
+            while !STOP.load(Ordering::Relaxed) {
+                COUNTERS[i].fetch_add(1, Ordering::Relaxed);
+            }
+        })
+    });
+    for _ in 0..3 {
+        let before = COUNTERS.each_ref().map(|c| c.load(Ordering::Relaxed));
+        while COUNTERS
+            .iter()
+            .zip(before)
+            .any(|(c, before)| c.load(Ordering::Relaxed) == before)
+        {
+            core::hint::spin_loop();
+        }
+    }
+    STOP.store(true, Ordering::Relaxed);
+    for task in tasks {
+        assert_eq!(task.join(), Some(0));
+    }
+}
+
+#[ax_test]
+fn test_sleep() {
+    use core::time::Duration;
+    let start = axhal::time::current_time();
+    crate::sleep(Duration::from_millis(20));
+    assert!(axhal::time::current_time() - start >= Duration::from_millis(20));
+
+    // sleepers wake up in order of their deadlines
+    static ORDER: AtomicUsize = AtomicUsize::new(0);
+    let tasks: [_; 3] = core::array::from_fn(|i| {
+        spawn_test("ktest", move || {
+            crate::sleep(Duration::from_millis(30 - 10 * i as u64));
+            assert_eq!(ORDER.fetch_add(1, Ordering::Relaxed), 2 - i);
+        })
+    });
+    for task in tasks {
+        assert_eq!(task.join(), Some(0));
+    }
+}
+
+#[ax_test]
+fn test_join_timeout_and_kill() {
+    use core::time::Duration;
+
+    static WQ: WaitQueue = WaitQueue::new();
+    static READY: AtomicUsize = AtomicUsize::new(0);
+    let task = spawn_test("ktest-stuck", || WQ.wait_until(|| false));
+    assert_eq!(task.join_timeout(Duration::from_millis(10)), None);
+    // Queued behind the stuck task.
+    let waiter = spawn_test("ktest-waiter", || {
+        WQ.wait_until(|| READY.load(Ordering::Acquire) == 1)
+    });
+    assert_eq!(waiter.join_timeout(Duration::from_millis(10)), None);
+
+    assert!(crate::kill(task.id()));
+    assert_eq!(task.join_timeout(Duration::from_millis(10)), Some(crate::KILLED_EXIT_CODE));
+    assert!(!crate::kill(task.id()));
+
+    // The killed task is out of the queue, so the waiter gets the wakeup.
+    READY.store(1, Ordering::Release);
+    assert!(WQ.notify_one(false));
+    assert_eq!(waiter.join_timeout(Duration::from_millis(10)), Some(0));
+}
+
+#[ax_test]
+fn test_task_registry() {
+    let task = spawn_test("ktest-info", crate::yield_now);
+    let info = crate::task_info(task.id()).unwrap();
+    assert_eq!(info.name, "ktest-info");
+    assert_eq!(info.state, crate::TaskState::Ready);
+    assert!(info.stack_size >= crate::default_stack_size());
+
+    let mut names = alloc::vec::Vec::new();
+    crate::for_each_task(|t| names.push(String::from(t.name())));
+    assert!(names.iter().any(|n| n == "main"));
+    assert!(names.iter().any(|n| n == "ktest-info"));
+
+    task.join();
+    assert!(crate::task_info(task.id()).is_none());
+    assert!(crate::current().cpu_time() > core::time::Duration::ZERO);
+}
+
+#[ax_test]
+fn test_sched_stats() {
+    let before = sum_cpu_stats();
+    let task = spawn_test("ktest-stats", || {
+        for _ in 0..3 {
+            crate::yield_now();
+        }
+    });
+    crate::yield_now();
+    assert_eq!(task.join(), Some(0));
+
+    let stats = task.stats();
+    assert!(stats.voluntary_switches >= 1);
+    assert!(stats.max_wait_time <= stats.wait_time);
+    let after = sum_cpu_stats();
+    assert!(after.0 > before.0);
+    assert!(after.1 > before.1);
+}
+
+/// Sums the context switches and the wait latencies counted on all CPUs.
+fn sum_cpu_stats() -> (u64, u64) {
+    (0..axconfig::SMP)
+        .filter_map(crate::cpu_stats)
+        .fold((0, 0), |(switches, latencies), stats| {
+            (switches + stats.context_switches, latencies + stats.wait_latency.count())
+        })
+}
+
+#[ax_test]
+fn test_latency_histogram() {
+    use crate::{LatencyHistogram, LATENCY_BUCKETS};
+    use core::time::Duration;
+
+    let mut hist = LatencyHistogram::new();
+    hist.record(Duration::from_nanos(500));
+    hist.record(Duration::from_micros(1));
+    hist.record(Duration::from_micros(3));
+    hist.record(Duration::from_secs(1));
+    assert_eq!(hist.buckets[..3], [1, 1, 1]);
+    assert_eq!(hist.buckets[LATENCY_BUCKETS - 1], 1);
+    assert_eq!(hist.count(), 4);
+    assert_eq!(LatencyHistogram::upper_bound(2), Some(Duration::from_micros(4)));
+    assert_eq!(LatencyHistogram::upper_bound(LATENCY_BUCKETS - 1), None);
+}
+
+#[ax_test]
+fn test_cpu_mask() {
+    use crate::CpuMask;
+
+    let full = CpuMask::full();
+    assert_eq!(full.iter().count(), axconfig::SMP);
+    assert!(full.contains(0));
+    assert!(!full.contains(axconfig::SMP));
+    assert!(CpuMask::one(axconfig::SMP).is_empty());
+    assert_eq!(CpuMask::one(0).iter().collect::<alloc::vec::Vec<_>>(), [0]);
+    assert_eq!(CpuMask::from_bits(u64::MAX), full);
+}
+
+#[ax_test]
+fn test_affinity() {
+    use crate::CpuMask;
+
+    let last = axconfig::SMP - 1;
+    let task = crate::spawn_raw_with_affinity(
+        || {
+            for _ in 0..10 {
+                assert_eq!(crate::current().cpu_id(), axconfig::SMP - 1);
+                crate::yield_now();
+            }
+        },
+        String::from("ktest-affinity"),
+        crate::default_stack_size(),
+        CpuMask::one(last),
+    );
+    assert_eq!(task.affinity(), CpuMask::one(last));
+    assert_eq!(task.join(), Some(0));
+}
+
+#[ax_test]
+fn test_migrate_current() {
+    use crate::CpuMask;
+
+    let curr = crate::current();
+    let affinity = curr.affinity();
+    for cpu_id in (0..axconfig::SMP).rev() {
+        curr.set_affinity(CpuMask::one(cpu_id));
+        assert_eq!(curr.cpu_id(), cpu_id);
+        assert_eq!(axhal::cpu::this_cpu_id(), cpu_id);
+    }
+    curr.set_affinity(affinity);
+}
+
+#[cfg(all(feature = "smp", feature = "irq"))]
+#[ax_test]
+fn test_smp_call_function() {
+    use crate::CpuMask;
+
+    static CALLED: AtomicUsize = AtomicUsize::new(0);
+    CALLED.store(0, Ordering::Relaxed);
+    axhal::ipi::smp_call_function(
+        CpuMask::full(),
+        || {
+            CALLED.fetch_or(1 << axhal::cpu::this_cpu_id(), Ordering::Relaxed);
+        },
+        true,
+    );
+    assert_eq!(CALLED.load(Ordering::Relaxed) as u64, CpuMask::full().bits());
+}
+
+#[ax_test]
+fn test_queue_work() {
+    static WQ: WaitQueue = WaitQueue::new();
+    static DONE: AtomicUsize = AtomicUsize::new(0);
+    for i in 1..=4 {
+        crate::workqueue::queue_work(move || {
+            DONE.fetch_add(i, Ordering::Release);
+            WQ.notify_one(true);
+        });
+    }
+    WQ.wait_until(|| DONE.load(Ordering::Acquire) == 10);
+    assert_eq!(crate::workqueue::pending_work(), 0);
+}
+
+#[cfg(feature = "irq")]
+#[ax_test]
+fn test_tasklet_runs_once() {
+    use crate::tasklet::Tasklet;
+
+    static RUNS: AtomicUsize = AtomicUsize::new(0);
+    static TASKLET: Tasklet = Tasklet::new(|| {
+        RUNS.fetch_add(1, Ordering::Relaxed);
+    });
+    // Scheduled twice before an interrupt comes, it runs once.
+    {
+        let _guard = kernel_guard::NoPreemptIrqSave::new();
+        TASKLET.schedule();
+        TASKLET.schedule();
+        assert!(TASKLET.is_scheduled());
+    }
+    crate::sleep(core::time::Duration::from_millis(20));
+    assert!(!TASKLET.is_scheduled());
+    assert_eq!(RUNS.load(Ordering::Relaxed), 1);
+}
+
+#[cfg(feature = "irq")]
+#[ax_test]
+fn test_irq_stats() {
+    use axhal::irq::TIMER_IRQ_NUM;
+
+    let before = axhal::irq::irq_stats(TIMER_IRQ_NUM).unwrap();
+    crate::sleep(core::time::Duration::from_millis(20));
+    let after = axhal::irq::irq_stats(TIMER_IRQ_NUM).unwrap();
+    assert!(after.count() > before.count());
+    assert!(after.max_latency >= before.max_latency);
+    assert!(after.total_time > before.total_time);
+
+    let mut timer_seen = false;
+    axhal::irq::for_each_irq_stats(|irq_num, stats| {
+        timer_seen |= irq_num == TIMER_IRQ_NUM && stats.count() > 0;
+    });
+    assert!(timer_seen);
+    let cpu_total: u64 = (0..axconfig::SMP)
+        .map(|cpu_id| axhal::irq::cpu_irq_stats(cpu_id).unwrap().count)
+        .sum();
+    assert!(cpu_total >= after.count());
+    assert!(axhal::irq::cpu_irq_stats(axconfig::SMP).is_none());
+}
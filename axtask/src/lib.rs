@@ -1,19 +1,43 @@
 #![no_std]
+#![cfg_attr(all(ktest, feature = "tls"), feature(thread_local))]
 
 #[macro_use]
 extern crate log;
 extern crate alloc;
 
 use alloc::string::String;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use axcmdline::Param;
 
 mod task;
 mod run_queue;
 mod wait_queue;
+mod timers;
+mod registry;
+mod stats;
+
+pub mod workqueue;
+#[cfg(feature = "irq")]
+pub mod tasklet;
+
+#[cfg(ktest)]
+mod ktests;
 
 use crate::task::CurrentTask;
 
-pub use task::{AxTaskRef, current};
+pub use task::{AxTaskRef, TaskId, TaskInfo, TaskState, current};
 pub use wait_queue::WaitQueue;
+pub use stats::{CpuStats, LatencyHistogram, TaskStats, LATENCY_BUCKETS};
+pub use axhal::cpumask::CpuMask;
+pub use run_queue::run_idle;
+
+/// Exit code of tasks killed by [`kill`], `128 + SIGKILL` like a shell
+/// reports.
+pub const KILLED_EXIT_CODE: i32 = 137;
+
+/// Stack size of tasks spawned without an explicit one.
+static STACK_SIZE: Param<usize> = Param::new("stack", axconfig::TASK_STACK_SIZE);
+static DEFAULT_STACK_SIZE: AtomicUsize = AtomicUsize::new(axconfig::TASK_STACK_SIZE);
 
 pub fn init_sched() {
     run_queue::init();
@@ -24,25 +48,159 @@ where
     F: FnOnce() + 'static,
 {
     let task = task::Task::new(f, name, stack_size);
-    run_queue::RUN_QUEUE.lock().add_task(task.clone());
+    run_queue::add_task(task.clone());
+    task
+}
+
+/// Spawns a task that only runs on the CPUs in `affinity`, which must not be
+/// empty.
+pub fn spawn_raw_with_affinity<F>(f: F, name: String, stack_size: usize, affinity: CpuMask) -> AxTaskRef
+where
+    F: FnOnce() + 'static,
+{
+    assert!(!affinity.is_empty(), "empty CPU affinity");
+    let task = task::Task::new(f, name, stack_size);
+    task.set_affinity(affinity);
+    run_queue::add_task(task.clone());
     task
 }
 
 pub fn init_scheduler() {
     info!("Initialize scheduling...");
+    DEFAULT_STACK_SIZE.store(STACK_SIZE.get(), Ordering::Relaxed);
     run_queue::init();
+    workqueue::init();
+}
+
+/// Initializes scheduling on a secondary CPU, which becomes its idle task.
+pub fn init_scheduler_secondary() {
+    run_queue::init_secondary();
+}
+
+/// Returns the stack size of tasks spawned without an explicit one, which is
+/// `stack=<size>` in the kernel command line.
+pub fn default_stack_size() -> usize {
+    DEFAULT_STACK_SIZE.load(Ordering::Relaxed)
 }
 
 pub fn exit(exit_code: i32) -> ! {
-    run_queue::RUN_QUEUE.lock().exit_current(exit_code)
+    run_queue::current_run_queue().exit_current(exit_code)
+}
+
+/// Returns whether the current task can exit where it is, such as after a
+/// fault: it is not the idle task, and preemption is enabled, so it holds no
+/// spinlocks and is not running an IRQ handler.
+pub fn can_exit_current() -> bool {
+    current_may_uninit().is_some_and(|curr| !curr.is_idle() && curr.can_preempt(0))
 }
 
 pub fn yield_now() {
-    run_queue::RUN_QUEUE.lock().yield_current();
+    run_queue::current_run_queue().yield_current();
+}
+
+/// Kills the task with the given ID, and returns whether it is found.
+///
+/// The cancellation is deferred: the task exits with [`KILLED_EXIT_CODE`]
+/// at its next safe point, when it yields, wakes up from blocking, or is
+/// preempted by an interrupt. A blocked task is woken up to do so. As with
+/// any cancellation, sleeping locks held by the task are not released.
+///
+/// The idle task can't be killed, and killing the main task shuts down the
+/// system.
+pub fn kill(id: TaskId) -> bool {
+    let Some(task) = registry::get(id) else {
+        return false;
+    };
+    if task.is_idle() {
+        return false;
+    }
+    task.set_killed();
+    if current().ptr_eq(&task) {
+        run_queue::current_run_queue().exit_if_killed();
+    } else {
+        run_queue::unblock_task(task, false);
+    }
+    true
+}
+
+/// Calls `f` with each live task, in the order of their IDs.
+pub fn for_each_task<F>(mut f: F)
+where
+    F: FnMut(&AxTaskRef),
+{
+    for task in registry::snapshot() {
+        f(&task);
+    }
+}
+
+/// Returns a snapshot of the information of the live task with the given ID.
+pub fn task_info(id: TaskId) -> Option<TaskInfo> {
+    registry::get(id).map(|task| task.info())
+}
+
+/// Returns the scheduler statistics of the live task with the given ID.
+pub fn task_stats(id: TaskId) -> Option<TaskStats> {
+    registry::get(id).map(|task| task.stats())
+}
+
+/// Returns the scheduler statistics of the CPU `cpu_id`, or `None` if there
+/// is no such CPU.
+pub fn cpu_stats(cpu_id: usize) -> Option<CpuStats> {
+    run_queue::cpu_stats(cpu_id)
+}
+
+/// Current task sleeps for the given duration.
+pub fn sleep(dur: core::time::Duration) {
+    sleep_until(axhal::time::current_time() + dur);
+}
+
+/// Current task sleeps until the given deadline.
+///
+/// Sleepers are woken up by timer ticks, or by the idle task without the
+/// `irq` feature.
+pub fn sleep_until(deadline: axhal::time::TimeValue) {
+    timers::sleep_until(deadline);
 }
 
 pub fn on_timer_tick() {
-    run_queue::RUN_QUEUE.lock().scheduler_timer_tick();
+    timers::check_events();
+    run_queue::current_run_queue().scheduler_timer_tick();
+}
+
+/// Returns whether there is nothing but the idle task to run.
+pub fn is_idle() -> bool {
+    let rq = run_queue::current_run_queue();
+    current().is_idle() && !rq.has_ready_tasks()
+}
+
+/// Returns the deadline of the first sleeping task to wake up.
+pub fn next_sleeper_deadline() -> Option<axhal::time::TimeValue> {
+    timers::next_deadline()
+}
+
+/// Disables preemption while an IRQ handler runs, so that a reschedule it
+/// requests is deferred to [`irq_exit`], and starts timing the handler.
+pub fn irq_enter() {
+    if let Some(curr) = current_may_uninit() {
+        curr.disable_preempt();
+    }
+    run_queue::current_run_queue().irq_enter();
+}
+
+/// Charges the time of the IRQ handler to the interrupted task, re-enables
+/// preemption, and reschedules if the handler requested it and the
+/// interrupted code allows it.
+///
+/// This is how CPU-bound tasks, which never enable preemption themselves,
+/// get preempted when their time slices run out.
+pub fn irq_exit() {
+    run_queue::current_run_queue().irq_exit();
+    if let Some(curr) = current_may_uninit() {
+        curr.enable_preempt(true);
+        if curr.is_killed() && curr.can_preempt(0) {
+            exit(KILLED_EXIT_CODE);
+        }
+    }
 }
 
 //
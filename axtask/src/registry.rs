@@ -0,0 +1,31 @@
+//! The registry of all live tasks.
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use spinlock::SpinNoIrq;
+
+use crate::task::TaskId;
+use crate::AxTaskRef;
+
+/// Tasks from their creation until they exit.
+static TASKS: SpinNoIrq<BTreeMap<u64, AxTaskRef>> = SpinNoIrq::new(BTreeMap::new());
+
+pub(crate) fn insert(task: AxTaskRef) {
+    TASKS.lock().insert(task.id().as_u64(), task);
+}
+
+pub(crate) fn remove(id: TaskId) {
+    TASKS.lock().remove(&id.as_u64());
+}
+
+pub(crate) fn get(id: TaskId) -> Option<AxTaskRef> {
+    TASKS.lock().get(&id.as_u64()).cloned()
+}
+
+/// Returns the live tasks in the order of their IDs.
+///
+/// It is a snapshot, so that callers can do anything with the tasks, even
+/// lock the run queue or spawn new tasks.
+pub(crate) fn snapshot() -> Vec<AxTaskRef> {
+    TASKS.lock().values().cloned().collect()
+}
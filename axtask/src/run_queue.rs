@@ -1,33 +1,275 @@
-use spinlock::SpinNoIrq;
+use core::ops::Deref;
+use core::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use spinlock::{SpinNoIrq, SpinRaw};
+use kernel_guard::{BaseGuard, NoPreemptIrqSave};
 use crate::{AxTaskRef, WaitQueue};
 use crate::task::{CurrentTask, TaskState, Task};
 use alloc::sync::Arc;
 use alloc::collections::VecDeque;
 use crate::task::current;
+use axconfig::SMP;
 use axsync::BootOnceCell;
+use axhal::cpu::this_cpu_id;
+use axhal::time::{current_time, TimeValue};
+use crate::stats::{CpuStats, LatencyHistogram};
 
 static EXITED_TASKS: SpinNoIrq<VecDeque<AxTaskRef>> = SpinNoIrq::new(VecDeque::new());
 static WAIT_FOR_EXIT: WaitQueue = WaitQueue::new();
 
-pub(crate) static RUN_QUEUE: SpinNoIrq<AxRunQueue> = SpinNoIrq::new(AxRunQueue::new());
-static IDLE_TASK: BootOnceCell<AxTaskRef> = unsafe { BootOnceCell::new() };
+/// The run queues of all CPUs, indexed by CPU ID.
+static RUN_QUEUES: [AxRunQueue; SMP] = {
+    let mut run_queues = [const { AxRunQueue::new() }; SMP];
+    let mut cpu_id = 0;
+    while cpu_id < SMP {
+        run_queues[cpu_id].cpu_id = cpu_id;
+        cpu_id += 1;
+    }
+    run_queues
+};
+
+/// How often a CPU pulls a task from the busiest CPU, in timer ticks.
+const BALANCE_INTERVAL_TICKS: u64 = 4;
 
+/// The run queue of a CPU.
+///
+/// Only the CPU itself switches tasks and updates the statistics. Other CPUs
+/// only add tasks to the ready queue, and steal tasks from it.
 pub(crate) struct AxRunQueue {
-    ready_queue: VecDeque<Arc<Task>>,
+    cpu_id: usize,
+    ready_queue: SpinNoIrq<VecDeque<AxTaskRef>>,
+    /// The length of `ready_queue`, for other CPUs to read without locking.
+    nr_ready: AtomicUsize,
+    idle_task: BootOnceCell<AxTaskRef>,
+    /// Whether the CPU is running its idle task, and needs an IPI to notice
+    /// new tasks.
+    is_idle: AtomicBool,
+    /// The task switched from, until its context is saved.
+    prev_task: AtomicUsize,
+    /// The task switched from, to be queued on a CPU it may run on after its
+    /// context is saved.
+    migrating_task: SpinRaw<Option<AxTaskRef>>,
+    ticks: AtomicU64,
+    stats: SpinNoIrq<CpuCounters>,
+}
+
+struct CpuCounters {
+    /// When the current task was switched to.
+    last_switch_time: TimeValue,
+    /// When the IRQ handler being run was entered.
+    irq_enter_time: TimeValue,
+    /// Number of IRQ handlers being run, more than one when they nest.
+    irq_depth: usize,
+    irq_time: TimeValue,
+    context_switches: u64,
+    wait_latency: LatencyHistogram,
+}
+
+/// The run queue of the current CPU, with IRQs and preemption disabled.
+///
+/// It dereferences to the run queue of the CPU the task is running on. That
+/// is a different one after a context switch if the task has migrated, so
+/// code after a switch must not keep references from before.
+pub(crate) struct CurrentRunQueue {
+    irq_state: usize,
+}
+
+pub(crate) fn current_run_queue() -> CurrentRunQueue {
+    CurrentRunQueue {
+        irq_state: NoPreemptIrqSave::acquire(),
+    }
+}
+
+impl Deref for CurrentRunQueue {
+    type Target = AxRunQueue;
+    #[inline]
+    fn deref(&self) -> &AxRunQueue {
+        &RUN_QUEUES[this_cpu_id()]
+    }
+}
+
+impl Drop for CurrentRunQueue {
+    #[inline]
+    fn drop(&mut self) {
+        NoPreemptIrqSave::release(self.irq_state);
+    }
 }
 
 impl AxRunQueue {
-    pub const fn new() -> Self {
-        Self { ready_queue: VecDeque::new() }
+    const fn new() -> Self {
+        Self {
+            cpu_id: 0,
+            ready_queue: SpinNoIrq::new(VecDeque::new()),
+            nr_ready: AtomicUsize::new(0),
+            idle_task: unsafe { BootOnceCell::new() },
+            is_idle: AtomicBool::new(false),
+            prev_task: AtomicUsize::new(0),
+            migrating_task: SpinRaw::new(None),
+            ticks: AtomicU64::new(0),
+            stats: SpinNoIrq::new(CpuCounters {
+                last_switch_time: TimeValue::ZERO,
+                irq_enter_time: TimeValue::ZERO,
+                irq_depth: 0,
+                irq_time: TimeValue::ZERO,
+                context_switches: 0,
+                wait_latency: LatencyHistogram::new(),
+            }),
+        }
+    }
+
+    /// Returns how long the current task has run since it was switched to.
+    pub fn running_time(&self) -> TimeValue {
+        current_time().saturating_sub(self.stats.lock().last_switch_time)
     }
 
-    pub fn scheduler_timer_tick(&mut self) {
+    pub fn irq_enter(&self) {
+        let mut stats = self.stats.lock();
+        if stats.irq_depth == 0 {
+            stats.irq_enter_time = current_time();
+        }
+        stats.irq_depth += 1;
+    }
+
+    /// Charges the time since [`irq_enter`](Self::irq_enter) to the IRQ time
+    /// of the CPU and the current task. Nested handlers are charged with the
+    /// outermost one.
+    pub fn irq_exit(&self) {
+        let mut stats = self.stats.lock();
+        stats.irq_depth -= 1;
+        if stats.irq_depth > 0 {
+            return;
+        }
+        let time = current_time().saturating_sub(stats.irq_enter_time);
+        stats.irq_time += time;
+        if let Some(curr) = crate::current_may_uninit() {
+            curr.sched().add_irq_time(time);
+        }
+    }
+
+    pub fn stats(&self) -> CpuStats {
+        let idle_time = self.idle_task.is_init().then(|| {
+            let idle = self.idle_task.get();
+            idle.sched().snapshot(running_time_of(idle)).kernel_time
+        });
+        let stats = self.stats.lock();
+        CpuStats {
+            idle_time: idle_time.unwrap_or_default(),
+            irq_time: stats.irq_time,
+            context_switches: stats.context_switches,
+            wait_latency: stats.wait_latency.clone(),
+        }
+    }
+
+    pub fn scheduler_timer_tick(&self) {
         let curr = current();
         if !curr.is_idle() && curr.task_tick() {
             curr.set_preempt_pending(true);
         }
+        if SMP > 1 && self.ticks.fetch_add(1, Ordering::Relaxed).is_multiple_of(BALANCE_INTERVAL_TICKS) {
+            self.balance();
+        }
     }
 
+    pub fn has_ready_tasks(&self) -> bool {
+        self.nr_ready.load(Ordering::SeqCst) > 0
+    }
+
+    /// Returns the number of tasks running or ready to run on the CPU.
+    fn load(&self) -> usize {
+        self.nr_ready.load(Ordering::SeqCst) + !self.is_idle.load(Ordering::SeqCst) as usize
+    }
+
+    fn push_task(&self, task: AxTaskRef, front: bool) {
+        task.sched().set_ready_since(current_time());
+        let mut ready_queue = self.ready_queue.lock();
+        if front {
+            ready_queue.push_front(task);
+        } else {
+            ready_queue.push_back(task);
+        }
+        self.nr_ready.store(ready_queue.len(), Ordering::SeqCst);
+    }
+
+    fn pop_task(&self) -> Option<AxTaskRef> {
+        let mut ready_queue = self.ready_queue.lock();
+        let task = ready_queue.pop_front();
+        self.nr_ready.store(ready_queue.len(), Ordering::SeqCst);
+        task
+    }
+
+    /// Takes the last queued task that may run on the CPU `cpu_id`. The task
+    /// just preempted is left alone, as it may be still switching out.
+    fn steal_task(&self, cpu_id: usize) -> Option<AxTaskRef> {
+        let mut ready_queue = self.ready_queue.lock();
+        let index = ready_queue
+            .iter()
+            .rposition(|task| task.affinity().contains(cpu_id) && !task.on_cpu())?;
+        let task = ready_queue.remove(index);
+        self.nr_ready.store(ready_queue.len(), Ordering::SeqCst);
+        task
+    }
+
+    /// Steals a task from the CPU with the most queued tasks, if any.
+    fn steal_from_busiest(&self) -> Option<AxTaskRef> {
+        let busiest = RUN_QUEUES
+            .iter()
+            .filter(|rq| rq.cpu_id != self.cpu_id)
+            .max_by_key(|rq| rq.nr_ready.load(Ordering::Relaxed))?;
+        if busiest.nr_ready.load(Ordering::Relaxed) == 0 {
+            return None;
+        }
+        let task = busiest.steal_task(self.cpu_id)?;
+        debug!("task {} stolen by CPU {}", task.name(), self.cpu_id);
+        Some(task)
+    }
+
+    /// Pulls a task from the busiest CPU if it has at least 2 more tasks than
+    /// this one. Or if this CPU has tasks waiting, kicks an idle CPU to steal
+    /// one of them, as idle CPUs may sleep until an interrupt.
+    fn balance(&self) {
+        let busiest = RUN_QUEUES.iter().max_by_key(|rq| rq.load()).unwrap();
+        if busiest.load() >= self.load() + 2 {
+            if let Some(task) = busiest.steal_task(self.cpu_id) {
+                debug!("task {} migrated to CPU {}", task.name(), self.cpu_id);
+                self.push_task(task, false);
+            }
+        } else if self.has_ready_tasks() {
+            #[cfg(all(feature = "smp", feature = "irq"))]
+            if let Some(idle) = RUN_QUEUES.iter().find(|rq| rq.load() == 0) {
+                axhal::ipi::send(idle.cpu_id, axhal::ipi::IpiMessage::Kick);
+            }
+        }
+    }
+
+    fn pick_next_task(&self) -> Option<AxTaskRef> {
+        while let Some(task) = self.pop_task() {
+            // The affinity has changed since the task was queued.
+            if !task.affinity().contains(self.cpu_id) && !task.on_cpu() {
+                add_task_to(select_cpu(&task), task, false);
+                continue;
+            }
+            return Some(task);
+        }
+        if SMP > 1 {
+            self.steal_from_busiest()
+        } else {
+            None
+        }
+    }
+
+    fn put_prev_task(&self, prev: AxTaskRef, preempt: bool) {
+        let front = prev.time_slice() > 0 && preempt;
+        if !front {
+            prev.reset_time_slice();
+        }
+        if prev.affinity().contains(self.cpu_id) {
+            self.push_task(prev, front);
+        } else {
+            *self.migrating_task.lock() = Some(prev);
+        }
+    }
+}
+
+impl CurrentRunQueue {
     pub fn preempt_resched(&mut self) {
         let curr = current();
         assert!(curr.is_running());
@@ -51,31 +293,58 @@ impl AxRunQueue {
         }
     }
 
-    pub fn add_task(&mut self, task: AxTaskRef) {
-        debug!("task spawn: {}", task.name());
-        //assert!(task.is_ready());
-        self.ready_queue.push_back(task);
+    pub fn yield_current(&mut self) {
+        self.resched(false);
+        self.exit_if_killed();
     }
 
-    pub fn pick_next_task(&mut self) -> Option<Arc<Task>> {
-        self.ready_queue.pop_front()
+    /// Exits the current task if it has been killed. It is called at the safe
+    /// points: yielding, waking up from blocking, and preemption.
+    pub fn exit_if_killed(&mut self) {
+        if current().is_killed() {
+            self.exit_current(crate::KILLED_EXIT_CODE);
+        }
     }
 
-    pub fn put_prev_task(&mut self, prev: Arc<Task>, preempt: bool) {
-        if prev.time_slice() > 0 && preempt {
-            self.ready_queue.push_front(prev)
+    pub fn exit_current(&mut self, exit_code: i32) -> ! {
+        let curr = current();
+        debug!("task exit: {}, exit_code={}", curr.name(), exit_code);
+        assert!(curr.is_running());
+        assert!(!curr.is_idle());
+        if curr.is_init() {
+            EXITED_TASKS.lock().clear();
+            axhal::misc::terminate(exit_code);
         } else {
-            prev.reset_time_slice();
-            self.ready_queue.push_back(prev)
+            crate::registry::remove(curr.id());
+            curr.notify_exit(exit_code);
+            EXITED_TASKS.lock().push_back(curr.clone());
+            WAIT_FOR_EXIT.notify_one(false);
+            self.resched(false);
         }
+        unreachable!("task exited!");
     }
 
-    pub fn yield_current(&mut self) {
+    /// Blocks the current task. `wait_queue_push` puts it where it is woken
+    /// up from, and may release the lock of that place, as the task is
+    /// already marked as blocked.
+    ///
+    /// A killed task is woken up too, and still where `wait_queue_push` put
+    /// it, so the caller must take it out from there before calling
+    /// [`exit_if_killed`](Self::exit_if_killed).
+    pub fn block_current<F>(&mut self, wait_queue_push: F)
+    where
+        F: FnOnce(AxTaskRef),
+    {
+        let curr = current();
+        debug!("task block: {}", curr.name());
+        assert!(curr.is_running());
+        assert!(!curr.is_idle());
+        assert!(curr.can_preempt(1));
+        curr.set_state(TaskState::Blocked);
+        wait_queue_push(curr.clone());
         self.resched(false);
     }
-}
 
-impl AxRunQueue {
     fn resched(&mut self, preempt: bool) {
         let prev = current();
         if prev.is_running() {
@@ -84,17 +353,38 @@ impl AxRunQueue {
                 self.put_prev_task(prev.clone(), preempt);
             }
         }
-        let next = self.pick_next_task().unwrap_or_else(|| IDLE_TASK.get().clone());
-        self.switch_to(prev, next);
+        let next = self
+            .pick_next_task()
+            .unwrap_or_else(|| self.idle_task.get().clone());
+        self.switch_to(prev, next, preempt);
     }
 
-    fn switch_to(&mut self, prev_task: CurrentTask, next_task: AxTaskRef) {
+    fn switch_to(&mut self, prev_task: CurrentTask, next_task: AxTaskRef, preempt: bool) {
         next_task.set_preempt_pending(false);
         next_task.set_state(TaskState::Running);
+        let now = current_time();
+        let mut stats = self.stats.lock();
+        if !next_task.is_idle() {
+            let wait = next_task.sched().end_wait(now);
+            stats.wait_latency.record(wait);
+        }
         if prev_task.ptr_eq(&next_task) {
             return;
         }
 
+        prev_task.sched().add_cpu_time(now.saturating_sub(stats.last_switch_time));
+        prev_task.sched().count_switch(preempt);
+        stats.last_switch_time = now;
+        stats.context_switches += 1;
+        drop(stats);
+
+        // Tasks are only queued on other CPUs after they are switched out.
+        debug_assert!(!next_task.on_cpu());
+        next_task.set_on_cpu(true);
+        next_task.set_cpu_id(self.cpu_id);
+        self.is_idle.store(next_task.is_idle(), Ordering::SeqCst);
+        self.prev_task.store(prev_task.as_task_ref().as_ref() as *const Task as usize, Ordering::Relaxed);
+
         unsafe {
             let prev_ctx_ptr = prev_task.ctx_mut_ptr();
             let next_ctx_ptr = next_task.ctx_mut_ptr();
@@ -105,59 +395,110 @@ impl AxRunQueue {
             CurrentTask::set_current(prev_task, next_task);
             (*prev_ctx_ptr).switch_to(&*next_ctx_ptr);
         }
+        finish_task_switch();
     }
+}
 
-    pub fn exit_current(&mut self, exit_code: i32) -> ! {
-        let curr = current();
-        debug!("task exit: {}, exit_code={}", curr.name(), exit_code);
-        assert!(curr.is_running());
-        assert!(!curr.is_idle());
-        if curr.is_init() {
-            EXITED_TASKS.lock().clear();
-            axhal::misc::terminate();
-        } else {
-            curr.set_state(TaskState::Exited);
-            curr.notify_exit(exit_code, self);
-            EXITED_TASKS.lock().push_back(curr.clone());
-            WAIT_FOR_EXIT.notify_one_locked(false, self);
-            self.resched(false);
-        }
-        unreachable!("task exited!");
+/// Releases the task switched from on this CPU, as its context is saved now.
+/// The task switched to calls it first, with IRQs disabled.
+pub(crate) fn finish_task_switch() {
+    let rq = &RUN_QUEUES[this_cpu_id()];
+    let prev = rq.prev_task.swap(0, Ordering::Relaxed) as *const Task;
+    if let Some(prev) = unsafe { prev.as_ref() } {
+        prev.set_on_cpu(false);
+    }
+    if let Some(task) = rq.migrating_task.lock().take() {
+        add_task_to(select_cpu(&task), task, false);
     }
+}
 
-    pub fn unblock_task(&mut self, task: AxTaskRef, resched: bool) {
+/// Chooses the CPU to run a ready task on: the CPU it ran on last if that is
+/// idle, or else any idle CPU, or else the CPU it ran on last, among the CPUs
+/// it may run on.
+fn select_cpu(task: &Task) -> usize {
+    let affinity = task.affinity();
+    let last = task.cpu_id();
+    if SMP == 1 {
+        return last;
+    }
+    let is_idle = |cpu_id: usize| RUN_QUEUES[cpu_id].load() == 0;
+    if affinity.contains(last) && is_idle(last) {
+        return last;
+    }
+    if let Some(cpu_id) = affinity.iter().find(|&cpu_id| is_idle(cpu_id)) {
+        return cpu_id;
+    }
+    if affinity.contains(last) {
+        last
+    } else {
+        affinity
+            .iter()
+            .min_by_key(|&cpu_id| RUN_QUEUES[cpu_id].load())
+            .unwrap_or(last)
+    }
+}
+
+/// Queues a ready task on the CPU `cpu_id`, and kicks the CPU if it is idle.
+fn add_task_to(cpu_id: usize, task: AxTaskRef, front: bool) {
+    let rq = &RUN_QUEUES[cpu_id];
+    rq.push_task(task, front);
+    #[cfg(all(feature = "smp", feature = "irq"))]
+    if cpu_id != this_cpu_id() && rq.is_idle.load(Ordering::SeqCst) {
+        axhal::ipi::send(cpu_id, axhal::ipi::IpiMessage::Kick);
+    }
+}
+
+/// Queues a new task on a CPU it may run on.
+pub(crate) fn add_task(task: AxTaskRef) {
+    debug!("task spawn: {}", task.name());
+    let _rq = current_run_queue();
+    add_task_to(select_cpu(&task), task, false);
+}
+
+/// Makes a blocked task ready to run, if it has not been woken up already.
+/// With `resched`, the current task is preempted if the task is queued on the
+/// current CPU. Returns whether the task was blocked.
+pub(crate) fn unblock_task(task: AxTaskRef, resched: bool) -> bool {
+    let unblocked = task.transition_state(TaskState::Blocked, TaskState::Ready);
+    if unblocked {
         debug!("task unblock: {}", task.name());
-        if task.is_blocked() {
-            task.set_state(TaskState::Ready);
-            self.add_task(task); // TODO: priority
-            if resched {
-                current().set_preempt_pending(true);
-            }
+        // It may have blocked on another CPU, and be still switching out.
+        while task.on_cpu() {
+            core::hint::spin_loop();
+        }
+        let _rq = current_run_queue();
+        let cpu_id = select_cpu(&task);
+        add_task_to(cpu_id, task, false);
+        if resched && cpu_id == this_cpu_id() {
+            current().set_preempt_pending(true);
         }
     }
+    unblocked
+}
 
-    pub fn block_current<F>(&mut self, wait_queue_push: F)
-    where
-        F: FnOnce(AxTaskRef),
-    {
-        let curr = current();
-        debug!("task block: {}", curr.name());
-        assert!(curr.is_running());
-        assert!(!curr.is_idle());
-        assert!(curr.can_preempt(1));
-        curr.set_state(TaskState::Blocked);
-        wait_queue_push(curr.clone());
-        self.resched(false);
+/// Returns how long `task` has run since it was switched to, if it is
+/// running.
+pub(crate) fn running_time_of(task: &Task) -> TimeValue {
+    if task.is_running() {
+        RUN_QUEUES[task.cpu_id()].running_time()
+    } else {
+        TimeValue::ZERO
     }
 }
 
+/// Returns the statistics of the CPU `cpu_id`.
+pub(crate) fn cpu_stats(cpu_id: usize) -> Option<CpuStats> {
+    RUN_QUEUES.get(cpu_id).map(|rq| rq.stats())
+}
+
 fn gc_entry() {
     loop {
         let n = EXITED_TASKS.lock().len();
         for _ in 0..n {
             let task = EXITED_TASKS.lock().pop_front();
             if let Some(task) = task {
-                if Arc::strong_count(&task) == 1 {
+                // The task may be still switching out on its CPU.
+                if Arc::strong_count(&task) == 1 && !task.on_cpu() {
                     drop(task);
                 } else {
                     EXITED_TASKS.lock().push_back(task);
@@ -168,26 +509,55 @@ fn gc_entry() {
     }
 }
 
+/// Makes `task` the current task of this CPU.
+fn init_current(task: AxTaskRef) {
+    let rq = &RUN_QUEUES[this_cpu_id()];
+    task.set_state(TaskState::Running);
+    task.set_on_cpu(true);
+    task.set_cpu_id(rq.cpu_id);
+    rq.is_idle.store(task.is_idle(), Ordering::SeqCst);
+    rq.stats.lock().last_switch_time = current_time();
+    unsafe { CurrentTask::init_current(task) }
+}
+
 pub(crate) fn init() {
     const IDLE_TASK_STACK_SIZE: usize = 4096;
     let idle_task = Task::new(|| run_idle(), "idle".into(), IDLE_TASK_STACK_SIZE);
-    IDLE_TASK.init(idle_task.clone());
-
-    let gc_task = Task::new(gc_entry, "gc".into(), axconfig::TASK_STACK_SIZE);
-    RUN_QUEUE.lock().add_task(gc_task);
+    RUN_QUEUES[this_cpu_id()].idle_task.init(idle_task);
 
     let main_task = Task::new_init("main".into());
-    main_task.set_state(TaskState::Running);
+    init_current(main_task);
 
-    unsafe { CurrentTask::init_current(main_task) }
+    let gc_task = Task::new(gc_entry, "gc".into(), axconfig::TASK_STACK_SIZE);
+    add_task(gc_task);
+}
+
+/// Makes the code running on a secondary CPU its idle task.
+pub(crate) fn init_secondary() {
+    let idle_task = Task::new_init("idle".into());
+    RUN_QUEUES[this_cpu_id()].idle_task.init(idle_task.clone());
+    init_current(idle_task);
 }
 
 pub fn yield_now() {
-    RUN_QUEUE.lock().yield_current();
+    current_run_queue().yield_current();
 }
 
 pub fn run_idle() -> ! {
     loop {
         yield_now();
+
+        // Check again with IRQs disabled, so that a task woken up by an
+        // interrupt right after the check is not missed. The pending
+        // interrupt still ends `wait_for_irqs`, and is taken on unlocking.
+        // Other CPUs see that this one is idle, and send an IPI after
+        // queuing a task.
+        let rq = current_run_queue();
+        #[cfg(not(feature = "irq"))]
+        crate::timers::check_events();
+        if !rq.has_ready_tasks() {
+            #[cfg(feature = "irq")]
+            axhal::misc::wait_for_irqs();
+        }
     }
 }
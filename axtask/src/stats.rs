@@ -0,0 +1,156 @@
+//! Scheduler statistics.
+//!
+//! All tasks run in supervisor mode, so the time of a task is split into the
+//! time running its own code and the time spent in IRQ handlers that
+//! interrupted it.
+
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::time::Duration;
+
+/// Number of buckets of [`LatencyHistogram`].
+pub const LATENCY_BUCKETS: usize = 16;
+
+/// Scheduler statistics of a task, see [`Task::stats`](crate::task::Task::stats).
+#[derive(Debug, Clone, Default)]
+pub struct TaskStats {
+    /// Time spent running the code of the task.
+    pub kernel_time: Duration,
+    /// Time spent in IRQ handlers that interrupted the task.
+    pub irq_time: Duration,
+    /// Switches away from the task because it yielded, blocked or exited.
+    pub voluntary_switches: u64,
+    /// Switches away from the task because it was preempted.
+    pub involuntary_switches: u64,
+    /// Total time spent ready in the run queue, waiting for a CPU.
+    pub wait_time: Duration,
+    /// The longest time spent ready in the run queue at once.
+    pub max_wait_time: Duration,
+}
+
+/// Scheduler statistics of a CPU, see [`cpu_stats`](crate::cpu_stats).
+#[derive(Debug, Clone, Default)]
+pub struct CpuStats {
+    /// Time spent in the idle task, not counting IRQ handlers.
+    pub idle_time: Duration,
+    /// Time spent in IRQ handlers.
+    pub irq_time: Duration,
+    /// Number of switches between different tasks.
+    pub context_switches: u64,
+    /// How long tasks waited in the run queue before they ran.
+    pub wait_latency: LatencyHistogram,
+}
+
+/// A histogram of latencies in power-of-two buckets of microseconds.
+///
+/// Bucket 0 counts latencies under 1us, bucket `i` counts those in
+/// `[2^(i-1), 2^i)` us, and the last bucket counts everything above.
+#[derive(Debug, Clone, Default)]
+pub struct LatencyHistogram {
+    pub buckets: [u64; LATENCY_BUCKETS],
+}
+
+impl LatencyHistogram {
+    pub const fn new() -> Self {
+        Self {
+            buckets: [0; LATENCY_BUCKETS],
+        }
+    }
+
+    /// Counts a latency in its bucket.
+    pub fn record(&mut self, latency: Duration) {
+        let us = latency.as_micros() as u64;
+        let index = (u64::BITS - us.leading_zeros()) as usize;
+        self.buckets[index.min(LATENCY_BUCKETS - 1)] += 1;
+    }
+
+    /// Returns the exclusive upper bound of the bucket `index`, or `None` for
+    /// the last one.
+    pub fn upper_bound(index: usize) -> Option<Duration> {
+        (index < LATENCY_BUCKETS - 1).then(|| Duration::from_micros(1 << index))
+    }
+
+    /// Returns the number of latencies counted.
+    pub fn count(&self) -> u64 {
+        self.buckets.iter().sum()
+    }
+}
+
+/// The counters behind [`TaskStats`], updated with the run queue locked.
+pub(crate) struct SchedCounters {
+    /// Time on a CPU, including IRQ handlers.
+    cpu_time_nanos: AtomicU64,
+    irq_time_nanos: AtomicU64,
+    voluntary_switches: AtomicU64,
+    involuntary_switches: AtomicU64,
+    wait_time_nanos: AtomicU64,
+    max_wait_time_nanos: AtomicU64,
+    /// When the task was last put in the run queue.
+    ready_since_nanos: AtomicU64,
+}
+
+impl SchedCounters {
+    pub const fn new() -> Self {
+        Self {
+            cpu_time_nanos: AtomicU64::new(0),
+            irq_time_nanos: AtomicU64::new(0),
+            voluntary_switches: AtomicU64::new(0),
+            involuntary_switches: AtomicU64::new(0),
+            wait_time_nanos: AtomicU64::new(0),
+            max_wait_time_nanos: AtomicU64::new(0),
+            ready_since_nanos: AtomicU64::new(0),
+        }
+    }
+
+    pub fn add_cpu_time(&self, time: Duration) {
+        add_nanos(&self.cpu_time_nanos, time);
+    }
+
+    pub fn add_irq_time(&self, time: Duration) {
+        add_nanos(&self.irq_time_nanos, time);
+    }
+
+    pub fn count_switch(&self, preempt: bool) {
+        let counter = if preempt {
+            &self.involuntary_switches
+        } else {
+            &self.voluntary_switches
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn set_ready_since(&self, now: Duration) {
+        self.ready_since_nanos
+            .store(now.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    /// Counts the time since [`set_ready_since`](Self::set_ready_since) as
+    /// waiting, and returns it.
+    pub fn end_wait(&self, now: Duration) -> Duration {
+        let since = Duration::from_nanos(self.ready_since_nanos.load(Ordering::Relaxed));
+        let wait = now.saturating_sub(since);
+        add_nanos(&self.wait_time_nanos, wait);
+        self.max_wait_time_nanos
+            .fetch_max(wait.as_nanos() as u64, Ordering::Relaxed);
+        wait
+    }
+
+    /// Returns the statistics, with `running` more time on a CPU than
+    /// counted so far.
+    pub fn snapshot(&self, running: Duration) -> TaskStats {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        let cpu_time = Duration::from_nanos(load(&self.cpu_time_nanos)) + running;
+        let irq_time = Duration::from_nanos(load(&self.irq_time_nanos));
+        TaskStats {
+            kernel_time: cpu_time.saturating_sub(irq_time),
+            irq_time,
+            voluntary_switches: load(&self.voluntary_switches),
+            involuntary_switches: load(&self.involuntary_switches),
+            wait_time: Duration::from_nanos(load(&self.wait_time_nanos)),
+            max_wait_time: Duration::from_nanos(load(&self.max_wait_time_nanos)),
+        }
+    }
+}
+
+fn add_nanos(counter: &AtomicU64, time: Duration) {
+    counter.fetch_add(time.as_nanos() as u64, Ordering::Relaxed);
+}
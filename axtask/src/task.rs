@@ -1,12 +1,17 @@
 use core::ops::Deref;
 use core::sync::atomic::{AtomicIsize, AtomicBool, AtomicI32, AtomicU64, AtomicU8, AtomicUsize, Ordering};
 use core::{alloc::Layout, cell::UnsafeCell, ptr::NonNull};
+use core::time::Duration;
 use crate::WaitQueue;
 use core::mem::ManuallyDrop;
 use alloc::{boxed::Box, string::String, sync::Arc};
 use axconfig::{PAGE_SIZE, align_up};
 use axhal::TaskContext;
-use crate::run_queue::{AxRunQueue, RUN_QUEUE};
+#[cfg(feature = "tls")]
+use axhal::tls::TlsArea;
+use crate::run_queue::current_run_queue;
+use crate::CpuMask;
+use crate::stats::{SchedCounters, TaskStats};
 
 pub type AxTaskRef = Arc<Task>;
 
@@ -21,6 +26,9 @@ impl TaskId {
     pub const fn as_u64(&self) -> u64 {
         self.0
     }
+    pub const fn from_u64(id: u64) -> Self {
+        Self(id)
+    }
 }
 
 pub struct Task {
@@ -34,10 +42,19 @@ pub struct Task {
     need_resched: AtomicBool,
     preempt_disable_count: AtomicUsize,
     exit_code: AtomicI32,
+    killed: AtomicBool,
+    /// Whether the task is running on a CPU, or its context is being saved.
+    on_cpu: AtomicBool,
+    /// The CPU the task runs or ran on last.
+    cpu_id: AtomicUsize,
+    affinity: AtomicU64,
+    sched: SchedCounters,
     wait_for_exit: WaitQueue,
     kstack: Option<TaskStack>,
     ctx: UnsafeCell<TaskContext>,
     time_slice: AtomicIsize,
+    #[cfg(feature = "tls")]
+    tls: TlsArea,
 }
 
 unsafe impl Send for Task {}
@@ -52,7 +69,7 @@ pub fn current() -> CurrentTask {
 /// The possible states of a task.
 #[repr(u8)]
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
-pub(crate) enum TaskState {
+pub enum TaskState {
     Running = 1,
     Ready = 2,
     Blocked = 3,
@@ -97,6 +114,21 @@ impl Drop for TaskStack {
     }
 }
 
+/// A snapshot of the information of a task, see [`Task::info`].
+#[derive(Debug, Clone)]
+pub struct TaskInfo {
+    pub id: TaskId,
+    pub name: String,
+    pub state: TaskState,
+    /// Scheduling priority. The round-robin scheduler runs all tasks with the
+    /// same priority, 0.
+    pub priority: isize,
+    /// Time spent running on a CPU.
+    pub cpu_time: Duration,
+    /// Size of the kernel stack, 0 for the main task running on the boot stack.
+    pub stack_size: usize,
+}
+
 impl Task {
     pub const fn id(&self) -> TaskId {
         self.id
@@ -104,10 +136,91 @@ impl Task {
     pub fn name(&self) -> &str {
         self.name.as_str()
     }
+
+    /// Waits for the task to exit, and returns its exit code.
     pub fn join(&self) -> Option<i32> {
         self.wait_for_exit.wait_until(|| self.state() == TaskState::Exited);
         Some(self.exit_code.load(Ordering::Acquire))
     }
+
+    /// Waits for the task to exit for at most `timeout`, and returns its exit
+    /// code, or `None` on timeout.
+    pub fn join_timeout(&self, timeout: Duration) -> Option<i32> {
+        let deadline = axhal::time::current_time() + timeout;
+        self.wait_for_exit
+            .wait_until_timeout(deadline, || self.state() == TaskState::Exited)
+            .then(|| self.exit_code.load(Ordering::Acquire))
+    }
+
+    /// Returns the current state of the task.
+    #[inline]
+    pub fn state(&self) -> TaskState {
+        self.state.load(Ordering::Acquire).into()
+    }
+
+    /// Returns whether the task has been killed by [`kill`](crate::kill), and
+    /// is to exit at its next safe point.
+    #[inline]
+    pub fn is_killed(&self) -> bool {
+        self.killed.load(Ordering::Acquire)
+    }
+
+    /// Returns the time the task has spent running on a CPU.
+    pub fn cpu_time(&self) -> Duration {
+        let stats = self.stats();
+        stats.kernel_time + stats.irq_time
+    }
+
+    /// Returns the scheduler statistics of the task.
+    pub fn stats(&self) -> TaskStats {
+        self.sched.snapshot(crate::run_queue::running_time_of(self))
+    }
+
+    /// Returns the CPUs the task may run on.
+    pub fn affinity(&self) -> CpuMask {
+        CpuMask::from_bits(self.affinity.load(Ordering::Acquire))
+    }
+
+    /// Sets the CPUs the task may run on, which must not be empty. It takes
+    /// effect the next time the task is scheduled, or right away for the
+    /// current task.
+    pub fn set_affinity(&self, affinity: CpuMask) {
+        assert!(!affinity.is_empty(), "empty CPU affinity");
+        self.affinity.store(affinity.bits(), Ordering::Release);
+        if current().id() == self.id && !affinity.contains(axhal::cpu::this_cpu_id()) {
+            crate::yield_now();
+        }
+    }
+
+    /// Returns the CPU the task runs or ran on last.
+    #[inline]
+    pub fn cpu_id(&self) -> usize {
+        self.cpu_id.load(Ordering::Acquire)
+    }
+
+    /// Calls `f` with the context saved when the task was last switched out,
+    /// or returns `None` if it runs on a CPU.
+    ///
+    /// The task may be switched in while `f` reads the context, unless the
+    /// other CPUs are stopped.
+    pub fn with_saved_context<R>(&self, f: impl FnOnce(&TaskContext) -> R) -> Option<R> {
+        if self.on_cpu() {
+            return None;
+        }
+        Some(f(unsafe { &*self.ctx.get() }))
+    }
+
+    /// Returns a snapshot of the information of the task.
+    pub fn info(&self) -> TaskInfo {
+        TaskInfo {
+            id: self.id,
+            name: self.name.clone(),
+            state: self.state(),
+            priority: 0,
+            cpu_time: self.cpu_time(),
+            stack_size: self.kstack.as_ref().map_or(0, |s| s.layout.size()),
+        }
+    }
 }
 
 impl Task {
@@ -125,10 +238,17 @@ impl Task {
             need_resched: AtomicBool::new(false),
             preempt_disable_count: AtomicUsize::new(0),
             exit_code: AtomicI32::new(0),
+            killed: AtomicBool::new(false),
+            on_cpu: AtomicBool::new(false),
+            cpu_id: AtomicUsize::new(axhal::cpu::this_cpu_id()),
+            affinity: AtomicU64::new(CpuMask::full().bits()),
+            sched: SchedCounters::new(),
             wait_for_exit: WaitQueue::new(),
             kstack: None,
             ctx: UnsafeCell::new(TaskContext::new()),
             time_slice: AtomicIsize::new(Self::MAX_TIME_SLICE),
+            #[cfg(feature = "tls")]
+            tls: TlsArea::alloc(),
         }
     }
 
@@ -141,32 +261,51 @@ impl Task {
         debug!("new task: {}", t.name());
         let kstack = TaskStack::alloc(align_up(stack_size, PAGE_SIZE));
 
+        #[cfg(feature = "tls")]
+        let tls = t.tls.tls_ptr();
+        #[cfg(not(feature = "tls"))]
+        let tls = 0;
+
         t.entry = Some(Box::into_raw(Box::new(entry)));
-        t.ctx.get_mut().init(task_entry as usize, kstack.top());
+        t.ctx.get_mut().init(task_entry as usize, kstack.top(), tls);
         t.kstack = Some(kstack);
         if t.name == "idle" {
             t.is_idle = true;
         }
-        Arc::new(t)
+        let task = Arc::new(t);
+        crate::registry::insert(task.clone());
+        task
     }
 
+    /// Creates a task for the code already running on this CPU. With the `tls`
+    /// feature, the CPU is switched to the TLS block of the new task.
     pub(crate) fn new_init(name: String) -> AxTaskRef {
         let mut t = Self::new_common(TaskId::new(), name);
         t.is_init = true;
         if t.name == "idle" {
             t.is_idle = true;
         }
-        Arc::new(t)
+        #[cfg(feature = "tls")]
+        unsafe {
+            axhal::tls::write_thread_pointer(t.tls.tls_ptr());
+        }
+        let task = Arc::new(t);
+        crate::registry::insert(task.clone());
+        task
     }
 
     #[inline]
-    pub(crate) fn state(&self) -> TaskState {
-        self.state.load(Ordering::Acquire).into()
+    pub(crate) fn set_state(&self, state: TaskState) {
+        self.state.store(state as u8, Ordering::Release)
     }
 
+    /// Changes the state from `from` to `to`, and returns whether it was
+    /// `from`.
     #[inline]
-    pub(crate) fn set_state(&self, state: TaskState) {
-        self.state.store(state as u8, Ordering::Release)
+    pub(crate) fn transition_state(&self, from: TaskState, to: TaskState) -> bool {
+        self.state
+            .compare_exchange(from as u8, to as u8, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok()
     }
 
     #[inline]
@@ -183,10 +322,6 @@ impl Task {
         self.is_init
     }
     #[inline]
-    pub(crate) fn is_blocked(&self) -> bool {
-        matches!(self.state(), TaskState::Blocked)
-    }
-    #[inline]
     pub(crate) fn in_wait_queue(&self) -> bool {
         self.in_wait_queue.load(Ordering::Acquire)
     }
@@ -220,16 +355,44 @@ impl Task {
     fn current_check_preempt_pending() {
         let curr = current();
         if curr.need_resched.load(Ordering::Acquire) && curr.can_preempt(0) {
-            let mut rq = RUN_QUEUE.lock();
+            let mut rq = current_run_queue();
             if curr.need_resched.load(Ordering::Acquire) {
                 rq.preempt_resched();
             }
         }
     }
 
-    pub(crate) fn notify_exit(&self, exit_code: i32, rq: &mut AxRunQueue) {
+    #[inline]
+    pub(crate) fn on_cpu(&self) -> bool {
+        self.on_cpu.load(Ordering::Acquire)
+    }
+
+    #[inline]
+    pub(crate) fn set_on_cpu(&self, on_cpu: bool) {
+        self.on_cpu.store(on_cpu, Ordering::Release);
+    }
+
+    #[inline]
+    pub(crate) fn set_cpu_id(&self, cpu_id: usize) {
+        self.cpu_id.store(cpu_id, Ordering::Release);
+    }
+
+    pub(crate) fn set_killed(&self) {
+        self.killed.store(true, Ordering::Release);
+    }
+
+    #[inline]
+    pub(crate) const fn sched(&self) -> &SchedCounters {
+        &self.sched
+    }
+
+    /// Marks the task as exited with `exit_code`, and wakes up the tasks
+    /// joining it.
+    pub(crate) fn notify_exit(&self, exit_code: i32) {
+        // Stored first, as joining tasks read it once they see the state.
         self.exit_code.store(exit_code, Ordering::Release);
-        self.wait_for_exit.notify_all_locked(false, rq);
+        self.set_state(TaskState::Exited);
+        self.wait_for_exit.notify_all(false);
     }
 
     #[inline]
@@ -298,6 +461,8 @@ impl Deref for CurrentTask {
 }
 
 extern "C" fn task_entry() -> ! {
+    crate::run_queue::finish_task_switch();
+    #[cfg(feature = "irq")]
     axhal::irq::enable_irqs();
     let task = current();
     if let Some(entry) = task.entry {
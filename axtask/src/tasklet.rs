@@ -0,0 +1,119 @@
+//! Tasklets, the bottom halves of IRQ handlers.
+//!
+//! An IRQ handler can leave the rest of its work to a tasklet, which runs on
+//! the way out of the interrupt with IRQs enabled, so other interrupts are
+//! not held up by it. Tasklets still run in interrupt context and must not
+//! block; work that does belongs in the [`workqueue`](crate::workqueue).
+
+use alloc::collections::VecDeque;
+use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use axconfig::SMP;
+use axhal::cpu::this_cpu_id;
+use kernel_guard::NoPreempt;
+use spinlock::SpinNoIrq;
+
+use crate::workqueue;
+
+const SCHEDULED: u8 = 1 << 0;
+const RUNNING: u8 = 1 << 1;
+
+/// Rounds of running the tasklets scheduled in the meantime, after which the
+/// rest are left to the worker threads, so that a busy IRQ line can't keep a
+/// CPU in interrupt context.
+const MAX_ROUNDS: usize = 10;
+
+/// Tasklets scheduled on each CPU.
+static PENDING: [SpinNoIrq<VecDeque<&'static Tasklet>>; SMP] =
+    [const { SpinNoIrq::new(VecDeque::new()) }; SMP];
+/// Whether each CPU is running its tasklets, so that an interrupt taken in
+/// the meantime leaves its tasklets to the outer run.
+static IN_TASKLETS: [AtomicBool; SMP] = [const { AtomicBool::new(false) }; SMP];
+
+/// A function deferred by IRQ handlers.
+///
+/// However many times it is scheduled before it starts, it runs once, and
+/// never on two CPUs at the same time.
+pub struct Tasklet {
+    func: fn(),
+    state: AtomicU8,
+}
+
+impl Tasklet {
+    pub const fn new(func: fn()) -> Self {
+        Self {
+            func,
+            state: AtomicU8::new(0),
+        }
+    }
+
+    /// Schedules the tasklet to run at the end of the current interrupt on
+    /// this CPU. Outside of IRQ handlers, it waits for the next interrupt.
+    pub fn schedule(&'static self) {
+        if self.state.fetch_or(SCHEDULED, Ordering::AcqRel) & SCHEDULED == 0 {
+            PENDING[this_cpu_id()].lock().push_back(self);
+        }
+    }
+
+    /// Returns whether the tasklet is scheduled and has not started yet.
+    pub fn is_scheduled(&self) -> bool {
+        self.state.load(Ordering::Acquire) & SCHEDULED != 0
+    }
+
+    /// Runs the tasklet unless it is running on another CPU, and returns
+    /// whether it ran.
+    fn try_run(&self) -> bool {
+        if self.state.fetch_or(RUNNING, Ordering::Acquire) & RUNNING != 0 {
+            return false;
+        }
+        // Scheduled again from now on, it runs again.
+        self.state.fetch_and(!SCHEDULED, Ordering::AcqRel);
+        (self.func)();
+        self.state.fetch_and(!RUNNING, Ordering::Release);
+        true
+    }
+
+    /// Runs the tasklet in a worker thread, with preemption disabled as in
+    /// interrupt context.
+    fn run_deferred(&'static self) {
+        let ran = {
+            let _guard = NoPreempt::new();
+            self.try_run()
+        };
+        if !ran {
+            workqueue::queue_work(move || self.run_deferred());
+        }
+    }
+}
+
+/// Runs the tasklets scheduled on this CPU, with IRQs enabled.
+///
+/// It is called on the way out of every interrupt, with IRQs and preemption
+/// disabled.
+pub fn run_pending() {
+    let cpu_id = this_cpu_id();
+    let pending = &PENDING[cpu_id];
+    if pending.lock().is_empty() || IN_TASKLETS[cpu_id].swap(true, Ordering::Relaxed) {
+        return;
+    }
+
+    axhal::irq::enable_irqs();
+    for _ in 0..MAX_ROUNDS {
+        let tasklets = core::mem::take(&mut *pending.lock());
+        if tasklets.is_empty() {
+            break;
+        }
+        for tasklet in tasklets {
+            // Tried again in the next round.
+            if !tasklet.try_run() {
+                pending.lock().push_back(tasklet);
+            }
+        }
+    }
+    axhal::irq::disable_irqs();
+
+    let rest = core::mem::take(&mut *pending.lock());
+    IN_TASKLETS[cpu_id].store(false, Ordering::Relaxed);
+    for tasklet in rest {
+        workqueue::queue_work(move || tasklet.run_deferred());
+    }
+}
@@ -0,0 +1,55 @@
+//! Tasks sleeping until a deadline.
+
+use alloc::collections::BTreeMap;
+use axhal::time::{current_time, TimeValue};
+use spinlock::SpinNoIrq;
+
+use crate::run_queue::{current_run_queue, unblock_task};
+use crate::{current, AxTaskRef};
+
+/// Sleeping tasks of all CPUs, ordered by deadline and then task ID.
+static SLEEPERS: SpinNoIrq<BTreeMap<(TimeValue, u64), AxTaskRef>> = SpinNoIrq::new(BTreeMap::new());
+
+/// Blocks the current task until `deadline`.
+pub(crate) fn sleep_until(deadline: TimeValue) {
+    let mut rq = current_run_queue();
+    if current_time() >= deadline {
+        return;
+    }
+    rq.block_current(|task| add(deadline, task));
+    let curr = current();
+    if curr.is_killed() {
+        cancel(deadline, curr.as_task_ref());
+        rq.exit_if_killed();
+    }
+}
+
+/// Wakes up `task` at `deadline` if it is still blocked by then.
+pub(crate) fn add(deadline: TimeValue, task: AxTaskRef) {
+    SLEEPERS.lock().insert((deadline, task.id().as_u64()), task);
+}
+
+/// Cancels the wakeup added by [`add`], if it has not happened.
+pub(crate) fn cancel(deadline: TimeValue, task: &AxTaskRef) {
+    SLEEPERS.lock().remove(&(deadline, task.id().as_u64()));
+}
+
+/// Wakes up the tasks whose deadlines have passed.
+pub(crate) fn check_events() {
+    let now = current_time();
+    loop {
+        let task = {
+            let mut sleepers = SLEEPERS.lock();
+            match sleepers.first_entry() {
+                Some(entry) if entry.key().0 <= now => entry.remove(),
+                _ => break,
+            }
+        };
+        unblock_task(task, true);
+    }
+}
+
+/// Returns the deadline of the first sleeper to wake up.
+pub(crate) fn next_deadline() -> Option<TimeValue> {
+    SLEEPERS.lock().first_key_value().map(|(key, _)| key.0)
+}
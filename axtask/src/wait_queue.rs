@@ -1,11 +1,16 @@
 use alloc::collections::VecDeque;
+use kernel_guard::NoPreemptIrqSave;
 use spinlock::SpinRaw;
-use crate::{AxTaskRef, run_queue::RUN_QUEUE};
+use crate::AxTaskRef;
 use crate::task::{current, CurrentTask};
-use crate::run_queue::AxRunQueue;
+use crate::run_queue::{current_run_queue, unblock_task};
+use crate::timers;
+use axhal::time::{current_time, TimeValue};
 
 pub struct WaitQueue {
-    queue: SpinRaw<VecDeque<AxTaskRef>>, // we already disabled IRQs when lock the `RUN_QUEUE`
+    // Only locked with IRQs and preemption disabled, by the run queue guard
+    // or one of its own, so that blocking can lock it under the former.
+    queue: SpinRaw<VecDeque<AxTaskRef>>,
 }
 
 impl WaitQueue {
@@ -19,70 +24,112 @@ impl WaitQueue {
     where
         F: Fn() -> bool,
     {
-        loop {
-            let mut rq = RUN_QUEUE.lock();
+        let killed = loop {
+            let mut rq = current_run_queue();
+            // Checked with the queue locked, so that a notification from
+            // another CPU right after the check is not missed.
+            let mut queue = self.queue.lock();
             if condition() {
-                break;
+                break false;
             }
             rq.block_current(|task| {
                 task.set_in_wait_queue(true);
-                self.queue.lock().push_back(task);
+                queue.push_back(task);
+                // Not held across the switch, for notifiers on other CPUs.
+                drop(queue);
             });
-        }
+            if current().is_killed() {
+                break true;
+            }
+        };
         self.cancel_events(current());
+        if killed {
+            current_run_queue().exit_if_killed();
+        }
+    }
+
+    /// Waits until `condition` becomes true or `deadline` passes, and returns
+    /// whether the condition is met.
+    pub fn wait_until_timeout<F>(&self, deadline: TimeValue, condition: F) -> bool
+    where
+        F: Fn() -> bool,
+    {
+        let curr = current();
+        let (met, killed) = loop {
+            let mut rq = current_run_queue();
+            let mut queue = self.queue.lock();
+            if condition() {
+                break (true, false);
+            }
+            if current_time() >= deadline {
+                break (false, false);
+            }
+            rq.block_current(|task| {
+                task.set_in_wait_queue(true);
+                queue.push_back(task.clone());
+                drop(queue);
+                timers::add(deadline, task);
+            });
+            timers::cancel(deadline, curr.as_task_ref());
+            if curr.is_killed() {
+                break (false, true);
+            }
+        };
+        self.cancel_events(curr);
+        if killed {
+            current_run_queue().exit_if_killed();
+        }
+        met
     }
 
     fn cancel_events(&self, curr: CurrentTask) {
         if curr.in_wait_queue() {
-            let _guard = kernel_guard::IrqSave::new();
+            let _guard = NoPreemptIrqSave::new();
             self.queue.lock().retain(|t| !curr.ptr_eq(t));
             curr.set_in_wait_queue(false);
         }
     }
 
-    pub(crate) fn notify_all_locked(&self, resched: bool, rq: &mut AxRunQueue) {
-        while let Some(task) = self.queue.lock().pop_front() {
-            task.set_in_wait_queue(false);
-            rq.unblock_task(task, resched);
-        }
-    }
-    pub(crate) fn notify_one_locked(&self, resched: bool, rq: &mut AxRunQueue) -> bool {
-        if let Some(task) = self.queue.lock().pop_front() {
-            task.set_in_wait_queue(false);
-            rq.unblock_task(task, resched);
-            true
-        } else {
-            false
-        }
-    }
-
     pub fn wait(&self) {
-        RUN_QUEUE.lock().block_current(|task| {
+        current_run_queue().block_current(|task| {
             task.set_in_wait_queue(true);
             self.queue.lock().push_back(task)
         });
         self.cancel_events(current());
+        current_run_queue().exit_if_killed();
     }
 
+    /// Wakes up the first task still blocked in the queue, and returns
+    /// whether there is one. Tasks woken up by other means, such as timeouts
+    /// or [`kill`](crate::kill), and not yet out of the queue, are skipped.
     pub fn notify_one(&self, resched: bool) -> bool {
-        let mut rq = RUN_QUEUE.lock();
-        if !self.queue.lock().is_empty() {
-            self.notify_one_locked(resched, &mut rq)
-        } else {
-            false
+        loop {
+            let task = {
+                let _guard = NoPreemptIrqSave::new();
+                self.queue.lock().pop_front()
+            };
+            let Some(task) = task else {
+                return false;
+            };
+            task.set_in_wait_queue(false);
+            if unblock_task(task, resched) {
+                return true;
+            }
         }
     }
 
     pub fn notify_all(&self, resched: bool) {
         loop {
-            let mut rq = RUN_QUEUE.lock();
-            if let Some(task) = self.queue.lock().pop_front() {
+            let task = {
+                let _guard = NoPreemptIrqSave::new();
+                self.queue.lock().pop_front()
+            };
+            if let Some(task) = task {
                 task.set_in_wait_queue(false);
-                rq.unblock_task(task, resched);
+                unblock_task(task, resched);
             } else {
                 break;
             }
-            drop(rq); // we must unlock `RUN_QUEUE` after unlocking `self.queue`.
         }
     }
 }
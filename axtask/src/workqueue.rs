@@ -0,0 +1,56 @@
+//! Deferred work, run by kernel worker threads.
+//!
+//! IRQ handlers run with IRQs disabled, so everything they do adds to the
+//! latency of every other interrupt. They can queue the heavy part of their
+//! processing here instead. It runs later in a worker thread, which is an
+//! ordinary task: IRQs are enabled, and the work may block.
+
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::format;
+use spinlock::SpinNoIrq;
+
+use crate::WaitQueue;
+
+type Work = Box<dyn FnOnce() + Send>;
+
+/// Work not yet picked up by a worker, in the order it is queued.
+static WORK_QUEUE: SpinNoIrq<VecDeque<Work>> = SpinNoIrq::new(VecDeque::new());
+/// Idle workers.
+static WORKERS_WQ: WaitQueue = WaitQueue::new();
+
+/// Queues `work` to run in a worker thread.
+///
+/// It can be called anywhere, including IRQ handlers. Work items are
+/// started in order, but with more than one worker, they may run
+/// concurrently.
+pub fn queue_work<F>(work: F)
+where
+    F: FnOnce() + Send + 'static,
+{
+    WORK_QUEUE.lock().push_back(Box::new(work));
+    WORKERS_WQ.notify_one(true);
+}
+
+/// Returns the number of queued work items no worker has started yet.
+pub fn pending_work() -> usize {
+    WORK_QUEUE.lock().len()
+}
+
+fn worker_entry() {
+    loop {
+        WORKERS_WQ.wait_until(|| !WORK_QUEUE.lock().is_empty());
+        // Another worker may have taken it first.
+        let work = WORK_QUEUE.lock().pop_front();
+        if let Some(work) = work {
+            work();
+        }
+    }
+}
+
+/// Starts a worker thread for each CPU.
+pub(crate) fn init() {
+    for i in 0..axconfig::SMP {
+        crate::spawn_raw(worker_entry, format!("kworker/{}", i), axconfig::TASK_STACK_SIZE);
+    }
+}
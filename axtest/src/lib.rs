@@ -0,0 +1,57 @@
+//! An in-kernel test framework.
+//!
+//! Functions marked with [`#[ax_test]`](ax_test) are collected into the
+//! `ax_tests` linker section, and run in the kernel by [`run_all`] instead of
+//! the application's `main`. Crates put their tests in a module compiled
+//! only with `--cfg ktest`, which `make ktest` passes to rustc:
+//!
+//! ```ignore
+//! #[cfg(ktest)]
+//! mod ktests;
+//! ```
+//!
+//! A failing test panics, and the panic stops the whole run with a non-zero
+//! exit code, so there is no need to grep the console output.
+
+#![no_std]
+
+#[macro_use]
+extern crate axlog;
+
+pub use axtest_macros::ax_test;
+
+/// A test registered by `#[ax_test]`.
+#[repr(C)]
+pub struct TestCase {
+    /// The path of the test function.
+    pub name: &'static str,
+    /// The test function.
+    pub func: fn(),
+}
+
+/// Returns all the tests linked into the kernel.
+pub fn tests() -> &'static [TestCase] {
+    extern "C" {
+        static __start_ax_tests: u8;
+        static __stop_ax_tests: u8;
+    }
+    let start = core::ptr::addr_of!(__start_ax_tests) as usize;
+    let end = core::ptr::addr_of!(__stop_ax_tests) as usize;
+    let count = (end - start) / core::mem::size_of::<TestCase>();
+    unsafe { core::slice::from_raw_parts(start as *const TestCase, count) }
+}
+
+/// Runs all the tests, and returns the number of them.
+///
+/// It only returns if all the tests pass.
+pub fn run_all() -> usize {
+    let tests = tests();
+    ax_println!("running {} kernel tests", tests.len());
+    for test in tests {
+        ax_println!("test {} ...", test.name);
+        (test.func)();
+        ax_println!("test {} ... ok", test.name);
+    }
+    ax_println!("test result: ok. {} passed", tests.len());
+    tests.len()
+}
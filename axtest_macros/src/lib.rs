@@ -0,0 +1,45 @@
+//! The `#[ax_test]` attribute, see the `axtest` crate.
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::{format_ident, quote};
+use syn::{Error, ItemFn, ReturnType};
+
+/// Registers a function as an in-kernel test.
+///
+/// The function takes no arguments and returns `()`. It fails by panicking.
+#[proc_macro_attribute]
+pub fn ax_test(attr: TokenStream, item: TokenStream) -> TokenStream {
+    if !attr.is_empty() {
+        return Error::new(Span::call_site(), "expect an empty attribute: `#[ax_test]`")
+            .to_compile_error()
+            .into();
+    }
+    let func = syn::parse_macro_input!(item as ItemFn);
+    let sig = &func.sig;
+    if !sig.inputs.is_empty()
+        || !matches!(sig.output, ReturnType::Default)
+        || sig.asyncness.is_some()
+        || !sig.generics.params.is_empty()
+    {
+        return Error::new_spanned(sig, "an `#[ax_test]` function should be `fn name()`")
+            .to_compile_error()
+            .into();
+    }
+
+    let name = &sig.ident;
+    let case_name = format_ident!("__AX_TEST_{}", name);
+    quote! {
+        #func
+
+        #[used]
+        #[doc(hidden)]
+        #[allow(non_upper_case_globals)]
+        #[link_section = "ax_tests"]
+        static #case_name: ::axtest::TestCase = ::axtest::TestCase {
+            name: concat!(module_path!(), "::", stringify!(#name)),
+            func: #name,
+        };
+    }
+    .into()
+}
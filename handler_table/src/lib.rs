@@ -1,36 +1,205 @@
 #![no_std]
 
-use core::sync::atomic::{AtomicUsize, Ordering};
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 
+/// A plain event handler.
 pub type Handler = fn();
 
+/// The maximum number of handlers of one event, which share it.
+pub const MAX_SHARED_HANDLERS: usize = 4;
+
+/// An event handler of any kind.
+///
+/// The ones with an argument are called with the index of the event, so that
+/// one handler can serve several events.
+#[derive(Clone, Copy)]
+pub enum HandlerFn {
+    /// A plain function.
+    Plain(Handler),
+    /// A closure, which can capture state.
+    Closure(&'static (dyn Fn(usize) + Sync)),
+    /// A function and the context it is called with, such as the state of
+    /// one device instance.
+    Context(fn(usize, *mut ()), *mut ()),
+}
+
+impl HandlerFn {
+    fn call(self, idx: usize) {
+        match self {
+            Self::Plain(f) => f(),
+            Self::Closure(f) => f(idx),
+            Self::Context(f, ctx) => f(idx, ctx),
+        }
+    }
+}
+
+impl From<Handler> for HandlerFn {
+    fn from(f: Handler) -> Self {
+        Self::Plain(f)
+    }
+}
+
+/// Handlers are the same if they call the same function, or closure, with
+/// the same context.
+impl PartialEq for HandlerFn {
+    fn eq(&self, other: &Self) -> bool {
+        match (*self, *other) {
+            (Self::Plain(a), Self::Plain(b)) => core::ptr::fn_addr_eq(a, b),
+            (Self::Closure(a), Self::Closure(b)) => core::ptr::addr_eq(a, b),
+            (Self::Context(a, ctx_a), Self::Context(b, ctx_b)) => {
+                core::ptr::fn_addr_eq(a, b) && ctx_a == ctx_b
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Eq for HandlerFn {}
+
+/// A handler of an event, if it is active.
+///
+/// The handler is only written while it is inactive and no one is calling
+/// it, so the dispatch path reads it without a lock.
+struct Slot {
+    active: AtomicBool,
+    /// Number of dispatchers that may be calling the handler.
+    users: AtomicUsize,
+    handler: UnsafeCell<Option<HandlerFn>>,
+}
+
+impl Slot {
+    const fn new() -> Self {
+        Self {
+            active: AtomicBool::new(false),
+            users: AtomicUsize::new(0),
+            handler: UnsafeCell::new(None),
+        }
+    }
+
+    /// Calls the handler if it is active, and returns whether it did.
+    fn call(&self, idx: usize) -> bool {
+        if !self.active.load(Ordering::Relaxed) {
+            return false;
+        }
+        // Either this sees the slot deactivated, or the deactivation waits
+        // for this call to return.
+        self.users.fetch_add(1, Ordering::SeqCst);
+        let active = self.active.load(Ordering::SeqCst);
+        if active {
+            // SAFETY: The handler is not written while the slot is active, or
+            // while it has users.
+            let handler = unsafe { *self.handler.get() };
+            if let Some(handler) = handler {
+                handler.call(idx);
+            }
+        }
+        self.users.fetch_sub(1, Ordering::Release);
+        active
+    }
+}
+
+/// A table of event handlers, several per event, dispatched without locks.
 pub struct HandlerTable<const N: usize> {
-    handlers: [AtomicUsize; N],
+    slots: [[Slot; MAX_SHARED_HANDLERS]; N],
+    counts: [AtomicU64; N],
+    /// Serializes registrations, which are rare.
+    updating: AtomicBool,
 }
 
+// SAFETY: Handlers are only written with `updating` held, while no one can
+// call them. Handlers with a context pointer are responsible for sharing the
+// context safely.
+unsafe impl<const N: usize> Sync for HandlerTable<N> {}
+
 impl<const N: usize> HandlerTable<N> {
-    #[allow(clippy::declare_interior_mutable_const)]
     pub const fn new() -> Self {
-        const EMPTY: AtomicUsize = AtomicUsize::new(0);
         Self {
-            handlers: [EMPTY; N],
+            slots: [const { [const { Slot::new() }; MAX_SHARED_HANDLERS] }; N],
+            counts: [const { AtomicU64::new(0) }; N],
+            updating: AtomicBool::new(false),
         }
     }
 
-    pub fn register_handler(&self, idx: usize, handler: Handler) -> bool {
-        self.handlers[idx]
-            .compare_exchange(0, handler as usize, Ordering::Acquire, Ordering::Relaxed)
-            .is_ok()
+    /// Adds `handler` to the handlers of event `idx`, and returns whether
+    /// there was room for it.
+    ///
+    /// It must not be called from a handler.
+    pub fn register_handler(&self, idx: usize, handler: impl Into<HandlerFn>) -> bool {
+        let handler = handler.into();
+        self.update(|| {
+            let Some(slot) = self.slots[idx]
+                .iter()
+                .find(|slot| !slot.active.load(Ordering::Relaxed))
+            else {
+                return false;
+            };
+            // SAFETY: The slot is inactive, and only called once activated.
+            unsafe { *slot.handler.get() = Some(handler) };
+            slot.active.store(true, Ordering::SeqCst);
+            true
+        })
     }
 
-    pub fn handle(&self, idx: usize) -> bool {
-        let handler = self.handlers[idx].load(Ordering::Acquire);
-        if handler != 0 {
-            let handler: Handler = unsafe { core::mem::transmute(handler) };
-            handler();
+    /// Removes `handler` from the handlers of event `idx`, and returns
+    /// whether it is found.
+    ///
+    /// Once it returns, the handler is not running on any CPU, so its context
+    /// can be freed. It must not be called from a handler.
+    pub fn unregister_handler(&self, idx: usize, handler: impl Into<HandlerFn>) -> bool {
+        let handler = Some(handler.into());
+        self.update(|| {
+            // SAFETY: Handlers are not written by others while updating.
+            let Some(slot) = self.slots[idx].iter().find(|slot| {
+                slot.active.load(Ordering::Relaxed) && unsafe { *slot.handler.get() } == handler
+            }) else {
+                return false;
+            };
+            slot.active.store(false, Ordering::SeqCst);
+            while slot.users.load(Ordering::Acquire) != 0 {
+                core::hint::spin_loop();
+            }
+            // SAFETY: The slot is inactive, and no one is calling it.
+            unsafe { *slot.handler.get() = None };
             true
-        } else {
-            false
+        })
+    }
+
+    /// Calls all handlers of event `idx`, and returns whether there is any.
+    pub fn handle(&self, idx: usize) -> bool {
+        self.counts[idx].fetch_add(1, Ordering::Relaxed);
+        let mut handled = false;
+        for slot in &self.slots[idx] {
+            handled |= slot.call(idx);
+        }
+        handled
+    }
+
+    /// Returns how many times event `idx` has been handled, including the
+    /// times it had no handlers.
+    pub fn count(&self, idx: usize) -> u64 {
+        self.counts[idx].load(Ordering::Relaxed)
+    }
+
+    fn update<R>(&self, f: impl FnOnce() -> R) -> R {
+        while self
+            .updating
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            core::hint::spin_loop();
         }
+        let ret = f();
+        self.updating.store(false, Ordering::Release);
+        ret
     }
 }
+
+impl<const N: usize> Default for HandlerTable<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests;
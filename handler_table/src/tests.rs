@@ -0,0 +1,100 @@
+extern crate std;
+
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use crate::{Handler, HandlerFn, HandlerTable, MAX_SHARED_HANDLERS};
+
+#[test]
+fn test_plain_handlers() {
+    static CALLS: AtomicUsize = AtomicUsize::new(0);
+    fn handler() {
+        CALLS.fetch_add(1, Ordering::Relaxed);
+    }
+
+    let table = HandlerTable::<4>::new();
+    assert!(!table.handle(0));
+    assert!(table.register_handler(1, handler as Handler));
+    assert!(!table.handle(0));
+    assert!(table.handle(1));
+    assert_eq!(CALLS.load(Ordering::Relaxed), 1);
+    assert_eq!(table.count(0), 2);
+    assert_eq!(table.count(1), 1);
+}
+
+#[test]
+fn test_closure_and_context_handlers() {
+    static SEEN: AtomicUsize = AtomicUsize::new(0);
+    static CLOSURE: &(dyn Fn(usize) + Sync) = &|idx| {
+        SEEN.fetch_add(idx, Ordering::Relaxed);
+    };
+    fn with_context(idx: usize, ctx: *mut ()) {
+        let counter = unsafe { &*(ctx as *const AtomicUsize) };
+        counter.fetch_add(idx * 100, Ordering::Relaxed);
+    }
+
+    let device = Arc::new(AtomicUsize::new(0));
+    let ctx = Arc::as_ptr(&device) as *mut ();
+    let table = HandlerTable::<4>::new();
+    assert!(table.register_handler(3, HandlerFn::Closure(CLOSURE)));
+    assert!(table.register_handler(3, HandlerFn::Context(with_context, ctx)));
+    assert!(table.handle(3));
+    assert_eq!(SEEN.load(Ordering::Relaxed), 3);
+    assert_eq!(device.load(Ordering::Relaxed), 300);
+}
+
+#[test]
+fn test_shared_and_unregister() {
+    fn count(idx: usize, ctx: *mut ()) {
+        let counter = unsafe { &*(ctx as *const AtomicUsize) };
+        counter.fetch_add(1, Ordering::Relaxed);
+        assert_eq!(idx, 2);
+    }
+
+    let counters: [AtomicUsize; MAX_SHARED_HANDLERS] = Default::default();
+    let handler = |i: usize| HandlerFn::Context(count, &counters[i] as *const _ as *mut ());
+    let table = HandlerTable::<4>::new();
+    for i in 0..MAX_SHARED_HANDLERS {
+        assert!(table.register_handler(2, handler(i)));
+    }
+    // The line is full.
+    assert!(!table.register_handler(2, handler(0)));
+    table.handle(2);
+
+    assert!(table.unregister_handler(2, handler(1)));
+    assert!(!table.unregister_handler(2, handler(1)));
+    assert!(!table.unregister_handler(1, handler(0)));
+    table.handle(2);
+    let calls: std::vec::Vec<_> = counters.iter().map(|c| c.load(Ordering::Relaxed)).collect();
+    assert_eq!(calls, [2, 1, 2, 2]);
+
+    // The freed slot is reused.
+    assert!(table.register_handler(2, handler(1)));
+    table.handle(2);
+    assert_eq!(counters[1].load(Ordering::Relaxed), 2);
+}
+
+#[test]
+fn test_unregister_waits_for_running_handler() {
+    static TABLE: HandlerTable<1> = HandlerTable::new();
+    static RUNNING: AtomicBool = AtomicBool::new(false);
+    static CALLS: AtomicUsize = AtomicUsize::new(0);
+    fn slow() {
+        RUNNING.store(true, Ordering::SeqCst);
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        CALLS.fetch_add(1, Ordering::SeqCst);
+        RUNNING.store(false, Ordering::SeqCst);
+    }
+
+    assert!(TABLE.register_handler(0, slow as Handler));
+    let dispatcher = std::thread::spawn(|| while TABLE.handle(0) {});
+    while !RUNNING.load(Ordering::SeqCst) {
+        std::thread::yield_now();
+    }
+    assert!(TABLE.unregister_handler(0, slow as Handler));
+    // Nothing runs the handler once it is unregistered.
+    assert!(!RUNNING.load(Ordering::SeqCst));
+    let calls = CALLS.load(Ordering::SeqCst);
+    dispatcher.join().unwrap();
+    assert_eq!(CALLS.load(Ordering::SeqCst), calls);
+}
@@ -1,9 +1,11 @@
 #![no_std]
 
+#[cfg(feature = "alloc")]
 extern crate alloc;
 extern crate log;
 
 use core::cmp::min;
+#[cfg(feature = "alloc")]
 use alloc::alloc::Layout;
 use axconfig::{PAGE_SHIFT, ASPACE_BITS, PAGE_SIZE};
 use axconfig::{virt_to_phys, phys_to_virt, is_aligned, align_offset};
@@ -25,6 +27,7 @@ const _PAGE_GLOBAL  : usize = 1 << 5;     /* Global */
 const _PAGE_ACCESSED: usize = 1 << 6;     /* Accessed (set by hardware) */
 const _PAGE_DIRTY   : usize = 1 << 7;     /* Dirty (set by hardware)*/
 
+#[cfg_attr(not(feature = "alloc"), allow(dead_code))]
 const PAGE_TABLE: usize = _PAGE_PRESENT;
 
 pub const PAGE_KERNEL_RO: usize =
@@ -48,7 +51,10 @@ pub const fn pfn_phys(pfn: usize) -> usize {
 }
 
 #[derive(Debug)]
-pub enum PagingError {}
+pub enum PagingError {
+    /// An intermediate table is needed but cannot be allocated.
+    NoMemory,
+}
 pub type PagingResult<T = ()> = Result<T, PagingError>;
 
 #[derive(Clone, Copy)]
@@ -94,6 +100,7 @@ impl PageTable<'_> {
         Self { level, table }
     }
 
+    #[cfg(feature = "alloc")]
     pub fn alloc_table(level: usize) -> Self {
         let layout = Layout::from_size_align(PAGE_SIZE, PAGE_SIZE).unwrap();
         let ptr = unsafe { alloc::alloc::alloc_zeroed(layout) };
@@ -167,9 +174,14 @@ impl PageTable<'_> {
 
     fn next_table_mut(&mut self, index: usize) -> PagingResult<PageTable> {
         if self.table[index].is_unused() {
-            let table = Self::alloc_table(self.level + 1);
-            self.table[index].set(table.root_paddr(), PAGE_TABLE);
-            Ok(table)
+            #[cfg(feature = "alloc")]
+            {
+                let table = Self::alloc_table(self.level + 1);
+                self.table[index].set(table.root_paddr(), PAGE_TABLE);
+                Ok(table)
+            }
+            #[cfg(not(feature = "alloc"))]
+            Err(PagingError::NoMemory)
         } else {
             self.next_table(index)
         }
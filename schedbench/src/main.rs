@@ -0,0 +1,84 @@
+//! Measures the throughput of the scheduler on all CPUs, e.g. with
+//! `make A=schedbench SMP=4 run`.
+//!
+//! Twice as many threads as CPUs run the same number of work chunks, yielding
+//! after each one. They first all run on CPU 0, and then on any CPU, so that
+//! the speedup shows how well the load is balanced.
+
+#![no_std]
+#![no_main]
+
+use axstd::thread::{self, Builder, CpuMask};
+use axstd::time::Instant;
+use core::time::Duration;
+use axstd::{env, println, String, Vec};
+
+/// Work chunks run by each thread, the first argument of the app.
+const DEFAULT_CHUNKS: usize = 2000;
+
+/// Iterations of busy work in a chunk.
+const CHUNK_ITERS: u64 = 2000;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let chunks = env::args()
+        .nth(1)
+        .and_then(|arg| arg.parse().ok())
+        .unwrap_or(DEFAULT_CHUNKS);
+    let cpus = thread::available_parallelism().unwrap().get();
+    let threads = cpus * 2;
+    println!("schedbench: {cpus} CPUs, {threads} threads, {chunks} chunks each");
+
+    let pinned = run(threads, chunks, Some(CpuMask::one(0)));
+    report("CPU 0", threads * chunks, pinned);
+    let balanced = run(threads, chunks, None);
+    report("all CPUs", threads * chunks, balanced);
+
+    let speedup = pinned.as_secs_f64() / balanced.as_secs_f64();
+    println!("speedup: {speedup:.2}x on {cpus} CPUs");
+    0
+}
+
+/// Runs `threads` threads of `chunks` work chunks each, and returns how long
+/// it takes them all.
+fn run(threads: usize, chunks: usize, affinity: Option<CpuMask>) -> Duration {
+    let start = Instant::now();
+    let handles: Vec<_> = (0..threads)
+        .map(|i| {
+            let mut builder = Builder::new().name(String::from("bench"));
+            if let Some(affinity) = affinity {
+                builder = builder.affinity(affinity);
+            }
+            builder
+                .spawn(move || {
+                    let mut state = i as u64;
+                    for _ in 0..chunks {
+                        state = busy_work(state);
+                        thread::yield_now();
+                    }
+                    state
+                })
+                .unwrap()
+        })
+        .collect();
+    for handle in handles {
+        core::hint::black_box(handle.join().unwrap());
+    }
+    start.elapsed()
+}
+
+fn busy_work(mut state: u64) -> u64 {
+    for _ in 0..CHUNK_ITERS {
+        state = core::hint::black_box(state.wrapping_mul(6364136223846793005).wrapping_add(1));
+    }
+    state
+}
+
+fn report(what: &str, total_chunks: usize, elapsed: Duration) {
+    let per_sec = total_chunks as f64 / elapsed.as_secs_f64();
+    println!(
+        "{what:>8}: {}.{:06}s, {per_sec:.0} chunks/s",
+        elapsed.as_secs(),
+        elapsed.subsec_micros()
+    );
+}
@@ -0,0 +1,46 @@
+#!/bin/sh
+# Fills the `.ksyms` section of a kernel ELF with its symbol table, which is
+# used to symbolize backtraces on panic.
+#
+# Each line of the table is a hex address and a symbol name, sorted by
+# address. The rest of the section is zeros. Does nothing if the kernel is
+# built without the section (the `ksyms` feature of axruntime).
+#
+# If the table does not fit in the section (`ksyms-size` in the platform
+# config), the symbols at the highest addresses are left out with a warning,
+# and the table ends with the address of the first of them and no name.
+#
+# Usage: scripts/ksyms.sh <elf>
+
+set -e
+
+ELF=$1
+NM=${NM:-rust-nm}
+OBJDUMP=${OBJDUMP:-rust-objdump}
+OBJCOPY=${OBJCOPY:-rust-objcopy}
+TABLE=$ELF.ksyms
+
+size_hex=$($OBJDUMP -h "$ELF" | awk '$2 == ".ksyms" { print $3 }')
+if [ -z "$size_hex" ] || [ $((0x$size_hex)) -eq 0 ]; then
+    exit 0
+fi
+size=$((0x$size_hex))
+
+# text symbols only, without local labels and the hash suffix of Rust symbols
+$NM -n --defined-only -C "$ELF" \
+    | awk '$2 ~ /^[tTwW]$/ && $3 !~ /^\.L/ { addr = $1; $1 = ""; $2 = ""; sub(/^ +/, ""); print addr, $0 }' \
+    | sed 's/::h[0-9a-f]\{16\}$//' > "$TABLE.all"
+
+# Keeps room for the end line and at least one zero after the table.
+if ! LC_ALL=C awk -v size="$size" '
+    { len = length($0) + 1 }
+    total + len + length($1) + 2 >= size { print $1 " "; exit 1 }
+    { total += len; print }
+' "$TABLE.all" > "$TABLE"; then
+    echo "ksyms: warning: the symbol table ($(wc -c < "$TABLE.all") bytes) does not fit" \
+        "in .ksyms ($size bytes), kept $(($(wc -l < "$TABLE") - 1)) of $(wc -l < "$TABLE.all")" \
+        "symbols; raise \`ksyms-size\` of the platform to keep all" >&2
+fi
+rm "$TABLE.all"
+truncate -s "$size" "$TABLE"
+$OBJCOPY --update-section .ksyms="$TABLE" "$ELF"
@@ -1,7 +1,23 @@
 #![no_std]
 
 mod raw;
-pub use self::raw::SpinRaw;
+pub use self::raw::{SpinRaw, SpinRawGuard};
 
 mod noirq;
 pub use self::noirq::SpinNoIrq;
+
+/// Spins until the lock word is acquired, reading it while it is held to
+/// keep the cache line shared.
+#[cfg(feature = "smp")]
+#[inline(always)]
+fn acquire(lock: &core::sync::atomic::AtomicBool) {
+    use core::sync::atomic::Ordering;
+    while lock
+        .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+        .is_err()
+    {
+        while lock.load(Ordering::Relaxed) {
+            core::hint::spin_loop();
+        }
+    }
+}
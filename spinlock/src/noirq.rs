@@ -1,13 +1,19 @@
 use core::cell::UnsafeCell;
 use core::ops::{Deref, DerefMut};
+#[cfg(feature = "smp")]
+use core::sync::atomic::{AtomicBool, Ordering};
 use kernel_guard::{BaseGuard, NoPreemptIrqSave};
 
 pub struct SpinNoIrq<T> {
+    #[cfg(feature = "smp")]
+    lock: AtomicBool,
     data: UnsafeCell<T>,
 }
 
 pub struct SpinNoIrqGuard<T> {
     irq_state: usize,
+    #[cfg(feature = "smp")]
+    lock: *const AtomicBool,
     data: *mut T,
 }
 
@@ -18,6 +24,8 @@ impl<T> SpinNoIrq<T> {
     #[inline(always)]
     pub const fn new(data: T) -> Self {
         Self {
+            #[cfg(feature = "smp")]
+            lock: AtomicBool::new(false),
             data: UnsafeCell::new(data),
         }
     }
@@ -33,8 +41,12 @@ impl<T> SpinNoIrq<T> {
     #[inline(always)]
     pub fn lock(&self) -> SpinNoIrqGuard<T> {
         let irq_state = NoPreemptIrqSave::acquire();
+        #[cfg(feature = "smp")]
+        crate::acquire(&self.lock);
         SpinNoIrqGuard {
             irq_state,
+            #[cfg(feature = "smp")]
+            lock: &self.lock,
             data: unsafe { &mut *self.data.get() },
         }
     }
@@ -58,6 +70,8 @@ impl<T> DerefMut for SpinNoIrqGuard<T> {
 impl<T> Drop for SpinNoIrqGuard<T> {
     #[inline(always)]
     fn drop(&mut self) {
+        #[cfg(feature = "smp")]
+        unsafe { &*self.lock }.store(false, Ordering::Release);
         NoPreemptIrqSave::release(self.irq_state);
     }
 }
@@ -1,11 +1,17 @@
 use core::cell::UnsafeCell;
 use core::ops::{Deref, DerefMut};
+#[cfg(feature = "smp")]
+use core::sync::atomic::{AtomicBool, Ordering};
 
 pub struct SpinRaw<T> {
+    #[cfg(feature = "smp")]
+    lock: AtomicBool,
     data: UnsafeCell<T>,
 }
 
 pub struct SpinRawGuard<T> {
+    #[cfg(feature = "smp")]
+    lock: *const AtomicBool,
     data: *mut T,
 }
 
@@ -16,6 +22,8 @@ impl<T> SpinRaw<T> {
     #[inline(always)]
     pub const fn new(data: T) -> Self {
         Self {
+            #[cfg(feature = "smp")]
+            lock: AtomicBool::new(false),
             data: UnsafeCell::new(data),
         }
     }
@@ -30,7 +38,11 @@ impl<T> SpinRaw<T> {
 impl<T> SpinRaw<T> {
     #[inline(always)]
     pub fn lock(&self) -> SpinRawGuard<T> {
+        #[cfg(feature = "smp")]
+        crate::acquire(&self.lock);
         SpinRawGuard {
+            #[cfg(feature = "smp")]
+            lock: &self.lock,
             data: unsafe { &mut *self.data.get() },
         }
     }
@@ -54,5 +66,7 @@ impl<T> DerefMut for SpinRawGuard<T> {
 impl<T> Drop for SpinRawGuard<T> {
     #[inline(always)]
     fn drop(&mut self) {
+        #[cfg(feature = "smp")]
+        unsafe { &*self.lock }.store(false, Ordering::Release);
     }
 }